@@ -0,0 +1,26 @@
+use ::dual_iso::dual_iso;
+use benchmarks::suite::{patterns, workloads};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+criterion_group!(benches, search_benchmarks);
+criterion_main!(benches);
+
+// The suite patterns on the suite workloads, dominated by the per-branch
+// re-simulation of the search and the candidate sets it allocates.
+fn search_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search");
+    group.sample_size(20);
+    for workload in workloads() {
+        let graph = (workload.generate)();
+        for (pattern_name, pattern) in patterns() {
+            group.bench_with_input(
+                BenchmarkId::new(pattern_name, workload.name),
+                &(&graph, &pattern),
+                |b, (graph, pattern)| {
+                    b.iter(|| dual_iso(black_box(graph), black_box(pattern)).len())
+                },
+            );
+        }
+    }
+    group.finish();
+}
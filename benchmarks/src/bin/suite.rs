@@ -0,0 +1,16 @@
+use benchmarks::suite::{engines, run, workloads};
+
+// Prints the matches and runtime per workload, pattern and engine as CSV.
+fn main() {
+    println!("workload,pattern,engine,matches,millis");
+    for measurement in run(&workloads(), &engines()) {
+        println!(
+            "{},{},{},{},{}",
+            measurement.workload,
+            measurement.pattern,
+            measurement.engine,
+            measurement.matches,
+            measurement.duration.as_millis()
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::hash::Hash;
+
+use ::dual_iso::{Graph, GraphBuilder};
+use rand::prelude::*;
+
+// Erdős–Rényi graph where each of the n * n relationships exists with probability p.
+pub fn random_graph(n: usize, p: f64, seed: u64) -> Graph<&'static str> {
+    let mut graph_builder = GraphBuilder::new();
+    let mut rng = SmallRng::seed_from_u64(seed);
+
+    // generate nodes
+    for node_id in 0..n {
+        graph_builder.add_node(node_id, "fixed");
+    }
+
+    // generate relationships
+    for source_id in 0..n {
+        for target_id in 0..n {
+            if rng.gen_range(0.0, 1.0) < p {
+                graph_builder.add_relationship(source_id, target_id);
+            }
+        }
+    }
+
+    graph_builder.build()
+}
+
+// R-MAT graph with 2^scale nodes and edge_factor * 2^scale relationships,
+// using the Graph500 quadrant probabilities. Its skewed, power-law like
+// degree distribution is closer to real data than the Erdős–Rényi case.
+pub fn rmat_graph(scale: u32, edge_factor: usize, seed: u64) -> Graph<&'static str> {
+    let (a, b, c) = (0.57, 0.19, 0.19);
+    let n = 1 << scale;
+    let mut graph_builder = GraphBuilder::new();
+    let mut rng = SmallRng::seed_from_u64(seed);
+
+    for node_id in 0..n {
+        graph_builder.add_node(node_id, "fixed");
+    }
+
+    for _ in 0..edge_factor * n {
+        // recursively pick one of the four quadrants of the adjacency matrix
+        let (mut source_id, mut target_id) = (0, 0);
+        for bit in (0..scale).rev() {
+            let r = rng.gen_range(0.0, 1.0);
+            let (is_lower, is_right) = if r < a {
+                (false, false)
+            } else if r < a + b {
+                (false, true)
+            } else if r < a + b + c {
+                (true, false)
+            } else {
+                (true, true)
+            };
+            source_id |= (is_lower as usize) << bit;
+            target_id |= (is_right as usize) << bit;
+        }
+        graph_builder.add_relationship(source_id, target_id);
+    }
+
+    graph_builder.build()
+}
+
+// Copies the graph, where the label of each node is drawn from a Zipf
+// distribution over the given labels, i.e. labels[k] is drawn with a
+// probability proportional to 1 / (k + 1)^exponent.
+pub fn zipf_labels<S, L>(graph: &Graph<S>, labels: &[L], exponent: f64, seed: u64) -> Graph<L>
+where
+    S: Eq + Hash,
+    L: Clone + Eq + Hash,
+{
+    assert!(!labels.is_empty(), "At least one label is required.");
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let weights = (1..=labels.len())
+        .map(|rank| 1.0 / (rank as f64).powf(exponent))
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+    let cumulative = weights
+        .iter()
+        .scan(0.0, |sum, weight| {
+            *sum += weight / total;
+            Some(*sum)
+        })
+        .collect::<Vec<_>>();
+
+    let mut graph_builder = GraphBuilder::new();
+    for node_id in 0..graph.node_count() {
+        let r = rng.gen_range(0.0, 1.0);
+        let idx = cumulative
+            .partition_point(|p| *p <= r)
+            .min(labels.len() - 1);
+        graph_builder.add_node(node_id, labels[idx].clone());
+    }
+    for source_id in 0..graph.node_count() {
+        for target_id in graph.neighbors(source_id) {
+            graph_builder.add_relationship(source_id, *target_id);
+        }
+    }
+
+    graph_builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rmat_graph() {
+        let graph = rmat_graph(6, 4, 42);
+
+        assert_eq!(64, graph.node_count());
+        assert_eq!(256, graph.relationship_count());
+        // the first quadrant is the most likely one, so low ids have high degrees
+        let degree = |nodes: std::ops::Range<usize>| nodes.map(|n| graph.degree(n)).sum::<usize>();
+        assert!(degree(0..32) > degree(32..64));
+    }
+
+    #[test]
+    fn test_zipf_labels() {
+        let graph = zipf_labels(&random_graph(1000, 0.001, 42), &["a", "b", "c"], 1.5, 42);
+
+        assert_eq!(1000, graph.node_count());
+        let count = |label| graph.nodes_by_label(&label).len();
+        assert!(count("a") > count("b"));
+        assert!(count("b") > count("c"));
+        assert_eq!(1000, count("a") + count("b") + count("c"));
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use ::dual_iso::{dual_iso, DistributedRuntime, Graph, GraphBuilder, MatchConfig, Partitioning};
+
+use crate::generators::{random_graph, rmat_graph, zipf_labels};
+
+pub type Label = &'static str;
+
+// A data graph to run the patterns against, generated on demand.
+pub struct Workload {
+    pub name: &'static str,
+    pub generate: fn() -> Graph<Label>,
+}
+
+// Counts the matches of a pattern in a graph.
+pub type Engine = fn(&Graph<Label>, &Graph<Label>) -> usize;
+
+const LABELS: [Label; 3] = ["a", "b", "c"];
+const PART_COUNT: usize = 4;
+
+pub fn workloads() -> Vec<Workload> {
+    vec![
+        Workload {
+            name: "random n=256 p=0.02",
+            generate: || zipf_labels(&random_graph(256, 0.02, 1337), &LABELS, 1.0, 1337),
+        },
+        Workload {
+            name: "rmat scale=10 edge_factor=8",
+            generate: || zipf_labels(&rmat_graph(10, 8, 1337), &LABELS, 1.0, 1337),
+        },
+    ]
+}
+
+// Paths, stars, triangles, diamonds and cliques over the workload labels.
+pub fn patterns() -> Vec<(&'static str, Graph<Label>)> {
+    vec![
+        ("path", pattern(&["a", "b", "c"], &[(0, 1), (1, 2)])),
+        (
+            "star",
+            pattern(&["a", "b", "b", "c"], &[(0, 1), (0, 2), (0, 3)]),
+        ),
+        (
+            "triangle",
+            pattern(&["a", "b", "c"], &[(0, 1), (1, 2), (2, 0)]),
+        ),
+        (
+            "diamond",
+            pattern(&["a", "b", "b", "c"], &[(0, 1), (0, 2), (1, 3), (2, 3)]),
+        ),
+        (
+            "clique",
+            pattern(
+                &["a", "a", "b", "c"],
+                &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)],
+            ),
+        ),
+    ]
+}
+
+pub fn engines() -> Vec<(&'static str, Engine)> {
+    vec![
+        ("dual_iso", |graph, pattern| dual_iso(graph, pattern).len()),
+        ("partitioned", |graph, pattern| {
+            Partitioning::hash(graph, PART_COUNT, pattern.node_count())
+                .dual_iso(pattern, &MatchConfig::default())
+                .len()
+        }),
+        ("distributed", |graph, pattern| {
+            let partitioning = Partitioning::hash(graph, PART_COUNT, pattern.node_count());
+            DistributedRuntime::new(&partitioning)
+                .run(pattern, &MatchConfig::default())
+                .unwrap()
+                .len()
+        }),
+    ]
+}
+
+pub struct Measurement {
+    pub workload: &'static str,
+    pub pattern: &'static str,
+    pub engine: &'static str,
+    pub matches: usize,
+    pub duration: Duration,
+}
+
+// Runs every pattern with every engine on every workload. Panics if the
+// engines disagree on the number of matches.
+pub fn run(workloads: &[Workload], engines: &[(&'static str, Engine)]) -> Vec<Measurement> {
+    let patterns = patterns();
+    let mut measurements: Vec<Measurement> = vec![];
+    for workload in workloads {
+        let graph = (workload.generate)();
+        for (pattern_name, pattern) in &patterns {
+            let first = measurements.len();
+            for (engine_name, engine) in engines {
+                let start = Instant::now();
+                let matches = engine(&graph, pattern);
+                let measurement = Measurement {
+                    workload: workload.name,
+                    pattern: pattern_name,
+                    engine: engine_name,
+                    matches,
+                    duration: start.elapsed(),
+                };
+                if let Some(expected) = measurements.get(first) {
+                    if expected.matches != matches {
+                        panic!(
+                            "Engine '{}' found {} matches of '{}', but '{}' found {}.",
+                            engine_name, matches, pattern_name, expected.engine, expected.matches
+                        );
+                    }
+                }
+                measurements.push(measurement);
+            }
+        }
+    }
+    measurements
+}
+
+fn pattern(labels: &[Label], relationships: &[(usize, usize)]) -> Graph<Label> {
+    let mut builder = GraphBuilder::new();
+    for (node_id, label) in labels.iter().enumerate() {
+        builder.add_node(node_id, *label);
+    }
+    for (start_node, end_node) in relationships {
+        builder.add_relationship(*start_node, *end_node);
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engines_agree() {
+        let workloads = [Workload {
+            name: "small",
+            generate: || zipf_labels(&rmat_graph(5, 4, 42), &LABELS, 0.5, 42),
+        }];
+        let measurements = run(&workloads, &engines());
+
+        assert_eq!(patterns().len() * engines().len(), measurements.len());
+        assert!(measurements
+            .iter()
+            .any(|measurement| measurement.matches > 0));
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::dual_iso::NestedVec;
+use crate::{dual_iso, Graph};
+
+// Matches that only differ by a symmetry of the pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchGroup {
+    representative: Vec<usize>,
+    members: Vec<usize>,
+}
+
+impl MatchGroup {
+    // the lexicographically smallest embedding of the group
+    pub fn representative(&self) -> &[usize] {
+        &self.representative
+    }
+
+    // indices of the grouped matches
+    pub fn members(&self) -> &[usize] {
+        &self.members
+    }
+
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+}
+
+// Every automorphism maps pattern node `u` to `automorphism[u]`.
+pub fn automorphisms<T: Eq + Hash>(pattern: &Graph<T>) -> NestedVec {
+    // an injective mapping of a graph into itself preserves all relationships
+    // if and only if it is an automorphism
+    dual_iso(pattern, pattern)
+}
+
+pub fn group_by_automorphism<T: Eq + Hash>(
+    pattern: &Graph<T>,
+    matches: &[Vec<usize>],
+) -> Vec<MatchGroup> {
+    let automorphisms = automorphisms(pattern);
+    let mut groups: Vec<MatchGroup> = vec![];
+    let mut group_idx = HashMap::new();
+
+    for (i, embedding) in matches.iter().enumerate() {
+        let key = automorphisms
+            .iter()
+            .map(|automorphism| {
+                automorphism
+                    .iter()
+                    .map(|u_p| embedding[*u_p])
+                    .collect::<Vec<_>>()
+            })
+            .min()
+            .unwrap_or_else(|| embedding.clone());
+        let idx = *group_idx.entry(key.clone()).or_insert_with(|| {
+            groups.push(MatchGroup {
+                representative: key,
+                members: vec![],
+            });
+            groups.len() - 1
+        });
+        groups[idx].members.push(i);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    fn triangle() -> Graph<&'static str> {
+        GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build()
+    }
+
+    #[test]
+    fn test_automorphisms() {
+        let mut automorphisms = automorphisms(&triangle());
+        automorphisms.sort();
+
+        assert_eq!(
+            vec![vec![0, 1, 2], vec![1, 2, 0], vec![2, 0, 1]],
+            automorphisms
+        );
+    }
+
+    #[test]
+    fn test_group_by_automorphism() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_node(3, "a")
+            .add_node(4, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .add_relationship(2, 3)
+            .add_relationship(3, 4)
+            .add_relationship(4, 2)
+            .build();
+        let pattern = triangle();
+
+        let matches = dual_iso(&graph, &pattern);
+        let groups = group_by_automorphism(&pattern, &matches);
+
+        assert_eq!(6, matches.len());
+        assert_eq!(2, groups.len());
+        assert!(groups.iter().all(|group| group.size() == 3));
+
+        let mut representatives = groups
+            .iter()
+            .map(|group| group.representative().to_vec())
+            .collect::<Vec<_>>();
+        representatives.sort();
+        assert_eq!(vec![vec![0, 1, 2], vec![2, 3, 4]], representatives);
+    }
+}
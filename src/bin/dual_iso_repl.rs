@@ -0,0 +1,9 @@
+use std::io;
+
+use dual_iso::Repl;
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    Repl::new().run(stdin.lock(), &mut stdout.lock())
+}
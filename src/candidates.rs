@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+use std::ops::Deref;
+
+use smallvec::SmallVec;
+
+// Most candidate sets shrink to a handful of nodes in the simulation of a
+// search branch, which then stores them without a heap allocation.
+pub(crate) const INLINE_CANDIDATES: usize = 4;
+
+// The candidates of a pattern node, either borrowed from a parent branch or
+// the label index, or owned by the branch that shrank them.
+#[derive(Clone, Debug)]
+pub(crate) enum Candidates<'a> {
+    Borrowed(&'a [usize]),
+    Owned(SmallVec<[usize; INLINE_CANDIDATES]>),
+}
+
+impl Candidates<'_> {
+    pub(crate) fn single(node_id: usize) -> Self {
+        Candidates::Owned(SmallVec::from_buf_and_len([node_id, 0, 0, 0], 1))
+    }
+
+    pub(crate) fn into_owned(self) -> Vec<usize> {
+        match self {
+            Candidates::Borrowed(candidates) => candidates.to_vec(),
+            Candidates::Owned(candidates) => candidates.into_vec(),
+        }
+    }
+}
+
+impl Deref for Candidates<'_> {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        match self {
+            Candidates::Borrowed(candidates) => candidates,
+            Candidates::Owned(candidates) => candidates,
+        }
+    }
+}
+
+impl From<Vec<usize>> for Candidates<'_> {
+    fn from(candidates: Vec<usize>) -> Self {
+        Candidates::Owned(SmallVec::from_vec(candidates))
+    }
+}
+
+impl<'a> From<Cow<'a, [usize]>> for Candidates<'a> {
+    fn from(candidates: Cow<'a, [usize]>) -> Self {
+        match candidates {
+            Cow::Borrowed(candidates) => Candidates::Borrowed(candidates),
+            Cow::Owned(candidates) => Candidates::from(candidates),
+        }
+    }
+}
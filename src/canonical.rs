@@ -0,0 +1,623 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::{Direction, Pattern};
+
+// A code that is equal for two patterns if and only if they are the same up
+// to renumbering their nodes, e.g. to use as a cache key. It covers the
+// labels, relationships with their directions and types, but not names, filters,
+// optional blocks, negative patterns or parameters.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CanonicalForm<T> {
+    labels: Vec<Rc<T>>,
+    // in canonical node order
+    relationships: Vec<Relationship>,
+}
+
+// (start, end, multiplicity, kind, types), where kind 0 is directed and kind 1
+// is undirected with start <= end
+type Relationship = (usize, usize, usize, u8, Vec<Rc<str>>);
+
+impl<T> CanonicalForm<T> {
+    // The canonical node order, i.e. node `i` of the code is `order[i]` of the pattern.
+    fn new(labels: &[Rc<T>], arcs: &[Arc], order: &[usize]) -> Self {
+        let mut position = vec![0; order.len()];
+        for (idx, u_p) in order.iter().enumerate() {
+            position[*u_p] = idx;
+        }
+        let mut relationships = arcs
+            .iter()
+            .map(|arc| {
+                let (start, end) = (position[arc.start], position[arc.end]);
+                let types = arc.types.to_vec();
+                if arc.kind == 1 {
+                    (
+                        start.min(end),
+                        start.max(end),
+                        arc.multiplicity,
+                        arc.kind,
+                        types,
+                    )
+                } else {
+                    (start, end, arc.multiplicity, arc.kind, types)
+                }
+            })
+            .collect::<Vec<_>>();
+        relationships.sort_unstable();
+        CanonicalForm {
+            labels: order.iter().map(|u_p| Rc::clone(&labels[*u_p])).collect(),
+            relationships,
+        }
+    }
+}
+
+// A pattern relationship with incoming directions turned around.
+struct Arc<'a> {
+    start: usize,
+    end: usize,
+    multiplicity: usize,
+    kind: u8,
+    types: &'a [Rc<str>],
+}
+
+impl<T> Pattern<T>
+where
+    T: Ord + Hash,
+{
+    // Nodes are first partitioned by label and then refined by the partition
+    // of their neighbors. While nodes remain indistinguishable, each node of
+    // the first smallest cell is singled out in turn and the partition refined
+    // again. Automorphisms found on the way prune nodes that would lead to the
+    // same forms, e.g. the interchangeable leaves of a star.
+    pub fn canonical_form(&self) -> CanonicalForm<T> {
+        let labels = self.shared_labels();
+        let arcs = self.arcs();
+
+        let mut search = Search {
+            labels: &labels,
+            arcs: &arcs,
+            first: None,
+            best: None,
+            automorphisms: vec![],
+        };
+        search.visit(&refine(label_colors(&labels), &arcs), &mut vec![]);
+        // there is at least one leaf, which is empty for an empty pattern
+        search.best.unwrap().form
+    }
+
+    fn shared_labels(&self) -> Vec<Rc<T>> {
+        let graph = self.graph();
+        (0..graph.node_count())
+            .map(|u_p| graph.shared_label(u_p))
+            .collect()
+    }
+
+    fn arcs(&self) -> Vec<Arc<'_>> {
+        let graph = self.graph();
+        (0..graph.node_count())
+            .flat_map(|u_p| {
+                graph
+                    .distinct_neighbors(u_p)
+                    .map(move |(v_p, multiplicity)| (u_p, v_p, multiplicity))
+            })
+            .map(|(u_p, v_p, multiplicity)| {
+                let types = graph.shared_relationship_types(u_p, v_p);
+                match self.direction(u_p, v_p) {
+                    Direction::Outgoing => Arc {
+                        start: u_p,
+                        end: v_p,
+                        multiplicity,
+                        kind: 0,
+                        types,
+                    },
+                    Direction::Incoming => Arc {
+                        start: v_p,
+                        end: u_p,
+                        multiplicity,
+                        kind: 0,
+                        types,
+                    },
+                    Direction::Either => Arc {
+                        start: u_p,
+                        end: v_p,
+                        multiplicity,
+                        kind: 1,
+                        types,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    // A hash of the labels and degrees, which is equal for patterns with
+    // equal canonical forms, but much cheaper to compute.
+    fn invariant_hash(&self) -> u64 {
+        let labels = self.shared_labels();
+        // (outgoing, incoming, undirected) relationships per node
+        let mut degrees = vec![(0, 0, 0); labels.len()];
+        for arc in self.arcs() {
+            if arc.kind == 0 {
+                degrees[arc.start].0 += arc.multiplicity;
+                degrees[arc.end].1 += arc.multiplicity;
+            } else {
+                degrees[arc.start].2 += arc.multiplicity;
+                degrees[arc.end].2 += arc.multiplicity;
+            }
+        }
+        let mut nodes = labels.iter().zip(degrees).collect::<Vec<_>>();
+        nodes.sort_unstable();
+        let mut hasher = DefaultHasher::new();
+        nodes.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+// Collects patterns that are pairwise different up to renumbering their
+// nodes, e.g. to drop duplicates while generating or mining candidate
+// patterns. Patterns are bucketed by a cheap invariant hash first, so
+// canonical forms are only computed for patterns that share a bucket.
+pub struct PatternSet<T> {
+    patterns: Vec<Pattern<T>>,
+    // lazily computed canonical form per pattern
+    forms: Vec<Option<CanonicalForm<T>>>,
+    buckets: HashMap<u64, Vec<usize>>,
+}
+
+impl<T> Default for PatternSet<T> {
+    fn default() -> Self {
+        PatternSet {
+            patterns: vec![],
+            forms: vec![],
+            buckets: HashMap::new(),
+        }
+    }
+}
+
+impl<T> PatternSet<T>
+where
+    T: Ord + Hash,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds the pattern unless an equal pattern up to renumbering has been
+    // added before and returns whether it has been added.
+    pub fn insert_unique(&mut self, pattern: Pattern<T>) -> bool {
+        let bucket = self.buckets.entry(pattern.invariant_hash()).or_default();
+        if !bucket.is_empty() {
+            let form = pattern.canonical_form();
+            let patterns = &self.patterns;
+            for idx in bucket.iter() {
+                let other = self.forms[*idx].get_or_insert_with(|| patterns[*idx].canonical_form());
+                if *other == form {
+                    return false;
+                }
+            }
+            self.forms.push(Some(form));
+        } else {
+            self.forms.push(None);
+        }
+        bucket.push(self.patterns.len());
+        self.patterns.push(pattern);
+        true
+    }
+
+    pub fn contains(&self, pattern: &Pattern<T>) -> bool {
+        let bucket = match self.buckets.get(&pattern.invariant_hash()) {
+            Some(bucket) => bucket,
+            None => return false,
+        };
+        let form = pattern.canonical_form();
+        bucket.iter().any(|idx| match &self.forms[*idx] {
+            Some(other) => *other == form,
+            None => self.patterns[*idx].canonical_form() == form,
+        })
+    }
+
+    // The added patterns in insertion order.
+    pub fn patterns(&self) -> &[Pattern<T>] {
+        &self.patterns
+    }
+
+    pub fn into_patterns(self) -> Vec<Pattern<T>> {
+        self.patterns
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+// The search tree of individualization and refinement, where each leaf is an
+// order of the pattern nodes.
+struct Search<'a, T> {
+    labels: &'a [Rc<T>],
+    arcs: &'a [Arc<'a>],
+    // leaves with the same form as the first or best leaf yield automorphisms
+    first: Option<Leaf<T>>,
+    best: Option<Leaf<T>>,
+    // each maps a node onto its image
+    automorphisms: Vec<Vec<usize>>,
+}
+
+struct Leaf<T> {
+    form: CanonicalForm<T>,
+    order: Vec<usize>,
+    // the nodes singled out on the way to the leaf
+    path: Vec<usize>,
+}
+
+impl<T> Search<'_, T>
+where
+    T: Ord,
+{
+    // `path` holds the nodes singled out on the way to the colors. Returns
+    // the depth to continue at if an automorphism showed that the rest of
+    // the subtree only repeats forms that have been seen.
+    fn visit(&mut self, colors: &[usize], path: &mut Vec<usize>) -> Option<usize> {
+        let cell_count = colors.iter().max().map_or(0, |c| c + 1);
+        if cell_count == colors.len() {
+            let mut order = vec![0; colors.len()];
+            for (u_p, color) in colors.iter().enumerate() {
+                order[*color] = u_p;
+            }
+            return self.leaf(order, path);
+        }
+
+        let mut sizes = vec![0; cell_count];
+        for color in colors {
+            sizes[*color] += 1;
+        }
+        let (_, cell) = sizes
+            .iter()
+            .enumerate()
+            .filter(|(_, size)| **size > 1)
+            .map(|(color, size)| (*size, color))
+            .min()
+            .unwrap();
+        let members = (0..colors.len())
+            .filter(|u_p| colors[*u_p] == cell)
+            .collect::<Vec<_>>();
+        let mut visited: Vec<usize> = vec![];
+        for u_p in members {
+            // an automorphism that keeps the path maps the subtrees onto each other
+            if !visited.is_empty() {
+                let orbits = self.orbits(path);
+                if visited.iter().any(|v_p| orbits[*v_p] == orbits[u_p]) {
+                    continue;
+                }
+            }
+            path.push(u_p);
+            let depth = self.visit(&refine(individualize(colors, u_p), self.arcs), path);
+            path.pop();
+            visited.push(u_p);
+            if depth.is_some_and(|depth| depth < path.len()) {
+                return depth;
+            }
+        }
+        None
+    }
+
+    fn leaf(&mut self, order: Vec<usize>, path: &[usize]) -> Option<usize> {
+        let form = CanonicalForm::new(self.labels, self.arcs, &order);
+        // A leaf with the same form maps each node onto the node at its
+        // position. The automorphism maps the subtree below the last common
+        // node of both paths, which has been searched, onto the current one.
+        let equal = self
+            .first
+            .iter()
+            .chain(self.best.iter())
+            .find(|leaf| leaf.form == form)
+            .map(|leaf| {
+                let mut automorphism = vec![0; order.len()];
+                for (u_p, v_p) in leaf.order.iter().zip(&order) {
+                    automorphism[*u_p] = *v_p;
+                }
+                let depth = leaf
+                    .path
+                    .iter()
+                    .zip(path)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                (automorphism, depth)
+            });
+        if let Some((automorphism, depth)) = equal {
+            self.automorphisms.push(automorphism);
+            return Some(depth);
+        }
+        if self.first.is_none() {
+            self.first = Some(Leaf {
+                form: CanonicalForm::new(self.labels, self.arcs, &order),
+                order: order.clone(),
+                path: path.to_vec(),
+            });
+        }
+        if self
+            .best
+            .as_ref()
+            .is_none_or(|best| form.relationships < best.form.relationships)
+        {
+            self.best = Some(Leaf {
+                form,
+                order,
+                path: path.to_vec(),
+            });
+        }
+        None
+    }
+
+    // The orbit of each node under the automorphisms that fix the path,
+    // identified by its smallest node.
+    fn orbits(&self, path: &[usize]) -> Vec<usize> {
+        let mut orbits = (0..self.labels.len()).collect::<Vec<_>>();
+        fn root(orbits: &mut [usize], mut u_p: usize) -> usize {
+            while orbits[u_p] != u_p {
+                orbits[u_p] = orbits[orbits[u_p]];
+                u_p = orbits[u_p];
+            }
+            u_p
+        }
+        for automorphism in &self.automorphisms {
+            if path.iter().any(|u_p| automorphism[*u_p] != *u_p) {
+                continue;
+            }
+            for (u_p, v_p) in automorphism.iter().enumerate() {
+                let (a, b) = (root(&mut orbits, u_p), root(&mut orbits, *v_p));
+                orbits[a.max(b)] = a.min(b);
+            }
+        }
+        (0..orbits.len())
+            .map(|u_p| root(&mut orbits, u_p))
+            .collect()
+    }
+}
+
+// Colors the nodes by label, ordered independently of the node ids.
+fn label_colors<T: Ord>(labels: &[Rc<T>]) -> Vec<usize> {
+    let mut sorted = labels.iter().collect::<Vec<_>>();
+    sorted.sort();
+    sorted.dedup();
+    labels
+        .iter()
+        .map(|label| sorted.binary_search(&label).unwrap())
+        .collect()
+}
+
+// Gives the node a color of its own, ordered before the other nodes of its
+// color.
+fn individualize(colors: &[usize], u_p: usize) -> Vec<usize> {
+    let cell = colors[u_p];
+    colors
+        .iter()
+        .enumerate()
+        .map(|(v_p, color)| {
+            if *color < cell || v_p == u_p {
+                *color
+            } else {
+                color + 1
+            }
+        })
+        .collect()
+}
+
+// Refines the colors such that nodes of the same color have the same number
+// of relationships to nodes of each color. Refined colors keep the order of
+// the colors they stem from.
+fn refine(mut colors: Vec<usize>, arcs: &[Arc]) -> Vec<usize> {
+    loop {
+        let mut signatures = colors
+            .iter()
+            .map(|color| (*color, vec![]))
+            .collect::<Vec<(usize, Vec<(u8, usize, usize)>)>>();
+        for arc in arcs {
+            // 0 and 1 mark both ends of directed, 2 both ends of undirected relationships
+            let (forward, backward) = if arc.kind == 0 { (0, 1) } else { (2, 2) };
+            signatures[arc.start]
+                .1
+                .push((forward, colors[arc.end], arc.multiplicity));
+            signatures[arc.end]
+                .1
+                .push((backward, colors[arc.start], arc.multiplicity));
+        }
+        for (_, neighbors) in signatures.iter_mut() {
+            neighbors.sort_unstable();
+        }
+        let mut distinct = signatures.iter().collect::<Vec<_>>();
+        distinct.sort();
+        distinct.dedup();
+        let refined = signatures
+            .iter()
+            .map(|signature| distinct.binary_search(&signature).unwrap())
+            .collect::<Vec<_>>();
+        if distinct.len() == colors.iter().max().map_or(0, |c| c + 1) {
+            return refined;
+        }
+        colors = refined;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    fn path(
+        labels: [&'static str; 3],
+        relationships: [(usize, usize); 2],
+    ) -> Pattern<&'static str> {
+        let mut builder = GraphBuilder::new();
+        for (node_id, label) in labels.iter().enumerate() {
+            builder.add_node(node_id, *label);
+        }
+        for (start_node, end_node) in relationships {
+            builder.add_relationship(start_node, end_node);
+        }
+        Pattern::new(builder.build())
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        let a = path(["a", "b", "a"], [(0, 1), (1, 2)]);
+        let b = path(["b", "a", "a"], [(2, 0), (0, 1)]);
+        let c = path(["a", "a", "b"], [(0, 2), (2, 1)]);
+        let star = path(["a", "b", "a"], [(1, 0), (1, 2)]);
+
+        assert_eq!(a.canonical_form(), b.canonical_form());
+        assert_eq!(a.canonical_form(), c.canonical_form());
+        assert_ne!(a.canonical_form(), star.canonical_form());
+        assert_ne!(
+            a.canonical_form(),
+            path(["a", "b", "b"], [(0, 1), (1, 2)]).canonical_form()
+        );
+    }
+
+    #[test]
+    fn test_canonical_form_with_directions() {
+        let mut a = path(["a", "b", "a"], [(0, 1), (1, 2)]);
+        let mut reversed = path(["a", "b", "a"], [(1, 0), (2, 1)]);
+        a.set_direction(1, 2, Direction::Incoming);
+        reversed.set_direction(1, 0, Direction::Incoming);
+
+        // incoming relationships are outgoing relationships turned around
+        assert_eq!(
+            path(["a", "b", "a"], [(0, 1), (2, 1)]).canonical_form(),
+            a.canonical_form()
+        );
+        assert_eq!(
+            path(["a", "b", "a"], [(0, 1), (2, 1)]).canonical_form(),
+            reversed.canonical_form()
+        );
+
+        a.set_direction(0, 1, Direction::Either);
+        reversed.set_direction(2, 1, Direction::Either);
+        assert_eq!(a.canonical_form(), reversed.canonical_form());
+    }
+
+    #[test]
+    fn test_canonical_form_with_types() {
+        let typed = |types: [&str; 2]| {
+            let graph = GraphBuilder::new()
+                .add_node(0, "a")
+                .add_node(1, "b")
+                .add_node(2, "a")
+                .add_relationship_with_type(0, 1, types[0])
+                .add_relationship_with_type(2, 1, types[1])
+                .build();
+            Pattern::new(graph)
+        };
+
+        assert_eq!(
+            typed(["KNOWS", "OWNS"]).canonical_form(),
+            typed(["OWNS", "KNOWS"]).canonical_form()
+        );
+        assert_ne!(
+            typed(["KNOWS", "OWNS"]).canonical_form(),
+            typed(["KNOWS", "KNOWS"]).canonical_form()
+        );
+        assert_ne!(
+            path(["a", "b", "a"], [(0, 1), (2, 1)]).canonical_form(),
+            typed(["KNOWS", "KNOWS"]).canonical_form()
+        );
+    }
+
+    #[test]
+    fn test_canonical_form_of_symmetric_pattern() {
+        // a cycle where refinement cannot tell the nodes apart
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..4 {
+            builder.add_node(node_id, "a");
+        }
+        for node_id in 0..4 {
+            builder.add_relationship(node_id, (node_id + 1) % 4);
+        }
+        let cycle = Pattern::new(builder.build());
+
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..4 {
+            builder.add_node(node_id, "a");
+        }
+        for (start_node, end_node) in [(0, 2), (2, 1), (1, 3), (3, 0)] {
+            builder.add_relationship(start_node, end_node);
+        }
+        let renumbered = Pattern::new(builder.build());
+
+        assert_eq!(cycle.canonical_form(), renumbered.canonical_form());
+    }
+
+    fn pattern(node_count: usize, relationships: &[(usize, usize)]) -> Pattern<&'static str> {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..node_count {
+            builder.add_node(node_id, "a");
+        }
+        for (start_node, end_node) in relationships {
+            builder.add_relationship(*start_node, *end_node);
+        }
+        Pattern::new(builder.build())
+    }
+
+    #[test]
+    fn test_canonical_form_of_regular_patterns() {
+        // every node has one incoming and one outgoing relationship
+        let hexagon = pattern(6, &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (5, 0)]);
+        let triangles = pattern(6, &[(0, 2), (2, 4), (4, 0), (1, 3), (3, 5), (5, 1)]);
+        let renumbered = pattern(6, &[(3, 0), (0, 5), (5, 1), (1, 4), (4, 2), (2, 3)]);
+
+        assert_ne!(hexagon.canonical_form(), triangles.canonical_form());
+        assert_eq!(hexagon.canonical_form(), renumbered.canonical_form());
+    }
+
+    #[test]
+    fn test_canonical_form_of_large_star() {
+        // the leaves are interchangeable, which automorphisms prune
+        let leaves = 40;
+        let star = pattern(
+            leaves + 1,
+            &(1..=leaves).map(|leaf| (0, leaf)).collect::<Vec<_>>(),
+        );
+        let renumbered = pattern(
+            leaves + 1,
+            &(0..leaves).map(|leaf| (leaves, leaf)).collect::<Vec<_>>(),
+        );
+
+        assert_eq!(star.canonical_form(), renumbered.canonical_form());
+
+        let complete = |order: &[usize]| {
+            let mut relationships = vec![];
+            for start_node in order {
+                for end_node in order {
+                    if start_node != end_node {
+                        relationships.push((*start_node, *end_node));
+                    }
+                }
+            }
+            pattern(order.len(), &relationships)
+        };
+        assert_eq!(
+            complete(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]).canonical_form(),
+            complete(&[9, 8, 7, 6, 5, 4, 3, 2, 1, 0]).canonical_form()
+        );
+    }
+
+    #[test]
+    fn test_insert_unique() {
+        let mut patterns = PatternSet::new();
+        assert!(patterns.insert_unique(path(["a", "b", "a"], [(0, 1), (1, 2)])));
+        assert!(!patterns.insert_unique(path(["b", "a", "a"], [(2, 0), (0, 1)])));
+        // same labels, but a different pattern
+        assert!(patterns.insert_unique(path(["a", "b", "a"], [(0, 1), (2, 1)])));
+        assert!(!patterns.insert_unique(path(["a", "a", "b"], [(0, 2), (1, 2)])));
+        assert!(patterns.insert_unique(path(["a", "b", "b"], [(0, 1), (1, 2)])));
+
+        assert_eq!(3, patterns.len());
+        assert!(patterns.contains(&path(["b", "a", "b"], [(1, 0), (0, 2)])));
+        assert!(!patterns.contains(&path(["b", "b", "b"], [(1, 0), (0, 2)])));
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::dual_iso::NestedVec;
+use crate::{dual_iso_with_config, match_pattern, Graph, MatchConfig, Pattern, PatternMatch};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CatalogError {
+    /// A graph with the name is already registered.
+    Duplicate { name: String },
+    /// No graph with the name is registered.
+    Unknown { name: String },
+    /// Registering the graph exceeds the memory limit in bytes.
+    MemoryLimit { name: String, limit: usize },
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::Duplicate { name } => {
+                write!(f, "Graph '{}' is already registered.", name)
+            }
+            CatalogError::Unknown { name } => write!(f, "Unknown graph '{}'.", name),
+            CatalogError::MemoryLimit { name, limit } => write!(
+                f,
+                "Registering graph '{}' exceeds the memory limit of {} bytes.",
+                name, limit
+            ),
+        }
+    }
+}
+
+impl Error for CatalogError {}
+
+// Holds named graphs and routes queries to them by name.
+pub struct GraphCatalog<T> {
+    graphs: HashMap<String, Graph<T>>,
+    memory_limit: Option<usize>,
+}
+
+impl<T> Default for GraphCatalog<T> {
+    fn default() -> Self {
+        GraphCatalog {
+            graphs: HashMap::new(),
+            memory_limit: None,
+        }
+    }
+}
+
+impl<T> GraphCatalog<T>
+where
+    T: Eq + Hash,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Limits the estimated memory usage of all registered graphs in bytes.
+    pub fn memory_limit(&mut self, bytes: usize) -> &mut Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    pub fn register(&mut self, name: &str, graph: Graph<T>) -> Result<&mut Self, CatalogError> {
+        if self.graphs.contains_key(name) {
+            return Err(CatalogError::Duplicate {
+                name: name.to_string(),
+            });
+        }
+        self.validate_memory(name, 0, graph.memory_usage())?;
+        self.graphs.insert(name.to_string(), graph);
+        Ok(self)
+    }
+
+    // Replaces a registered graph and returns the previous one.
+    pub fn replace(&mut self, name: &str, graph: Graph<T>) -> Result<Graph<T>, CatalogError> {
+        let previous_usage = self.graph(name)?.memory_usage();
+        self.validate_memory(name, previous_usage, graph.memory_usage())?;
+        Ok(self.graphs.insert(name.to_string(), graph).unwrap())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Graph<T>> {
+        self.graphs.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Graph<T>> {
+        self.graphs.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.graphs.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.graphs.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn len(&self) -> usize {
+        self.graphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graphs.is_empty()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.graphs.values().map(Graph::memory_usage).sum()
+    }
+
+    pub fn dual_iso(
+        &self,
+        name: &str,
+        pattern: &Graph<T>,
+        config: &MatchConfig,
+    ) -> Result<NestedVec, CatalogError> {
+        Ok(dual_iso_with_config(self.graph(name)?, pattern, config))
+    }
+
+    pub fn match_pattern(
+        &self,
+        name: &str,
+        pattern: &Pattern<T>,
+        config: &MatchConfig,
+    ) -> Result<Vec<PatternMatch>, CatalogError> {
+        Ok(match_pattern(self.graph(name)?, pattern, config))
+    }
+
+    fn graph(&self, name: &str) -> Result<&Graph<T>, CatalogError> {
+        self.graphs.get(name).ok_or_else(|| CatalogError::Unknown {
+            name: name.to_string(),
+        })
+    }
+
+    fn validate_memory(
+        &self,
+        name: &str,
+        released: usize,
+        additional: usize,
+    ) -> Result<(), CatalogError> {
+        match self.memory_limit {
+            Some(limit) if self.memory_usage() - released + additional > limit => {
+                Err(CatalogError::MemoryLimit {
+                    name: name.to_string(),
+                    limit,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    fn pair(label: &'static str) -> Graph<&'static str> {
+        GraphBuilder::new()
+            .add_node(0, label)
+            .add_node(1, label)
+            .add_relationship(0, 1)
+            .build()
+    }
+
+    #[test]
+    fn test_catalog_routes_by_name() {
+        let mut catalog = GraphCatalog::new();
+        catalog
+            .register("tenant_b", pair("b"))
+            .unwrap()
+            .register("tenant_a", pair("a"))
+            .unwrap();
+
+        assert_eq!(vec!["tenant_a", "tenant_b"], catalog.names());
+        assert_eq!(2, catalog.len());
+
+        let pattern = pair("a");
+        let config = MatchConfig::default();
+        assert_eq!(
+            Ok(vec![vec![0, 1]]),
+            catalog.dual_iso("tenant_a", &pattern, &config)
+        );
+        assert_eq!(Ok(vec![]), catalog.dual_iso("tenant_b", &pattern, &config));
+        assert_eq!(
+            1,
+            catalog
+                .match_pattern("tenant_a", &Pattern::new(pair("a")), &config)
+                .unwrap()
+                .len()
+        );
+
+        let previous = catalog.replace("tenant_b", pair("a")).unwrap();
+        assert_eq!(&"b", previous.node_label(0));
+        assert_eq!(
+            1,
+            catalog
+                .dual_iso("tenant_b", &pattern, &config)
+                .unwrap()
+                .len()
+        );
+
+        assert!(catalog.remove("tenant_a").is_some());
+        assert!(!catalog.contains("tenant_a"));
+    }
+
+    #[test]
+    fn test_catalog_memory_usage() {
+        let mut catalog = GraphCatalog::new();
+        catalog.register("a", pair("a")).unwrap();
+        assert!(catalog.memory_usage() > 0);
+        assert_eq!(
+            catalog.get("a").unwrap().memory_usage(),
+            catalog.memory_usage()
+        );
+    }
+
+    #[test]
+    fn test_catalog_duplicate_graph() {
+        let mut catalog = GraphCatalog::new();
+        catalog.register("a", pair("a")).unwrap();
+
+        let error = catalog.register("a", pair("b")).err().unwrap();
+        assert_eq!(
+            CatalogError::Duplicate {
+                name: "a".to_string()
+            },
+            error
+        );
+        assert_eq!("Graph 'a' is already registered.", error.to_string());
+        assert_eq!(&"a", catalog.get("a").unwrap().node_label(0));
+    }
+
+    #[test]
+    fn test_catalog_memory_limit() {
+        let mut catalog = GraphCatalog::new();
+        let limit = pair("a").memory_usage();
+        catalog
+            .memory_limit(limit)
+            .register("a", pair("a"))
+            .unwrap();
+
+        let error = catalog.register("b", pair("b")).err().unwrap();
+        assert_eq!(
+            CatalogError::MemoryLimit {
+                name: "b".to_string(),
+                limit
+            },
+            error
+        );
+        assert!(error
+            .to_string()
+            .starts_with("Registering graph 'b' exceeds the memory limit"));
+        assert!(!catalog.contains("b"));
+
+        // replacing releases the memory of the previous graph
+        assert!(catalog.replace("a", pair("b")).is_ok());
+    }
+
+    #[test]
+    fn test_catalog_unknown_graph() {
+        let mut catalog = GraphCatalog::<&str>::new();
+        let unknown = CatalogError::Unknown {
+            name: "missing".to_string(),
+        };
+
+        assert_eq!(
+            Err(unknown.clone()),
+            catalog.dual_iso("missing", &pair("a"), &MatchConfig::default())
+        );
+        assert_eq!(unknown, catalog.replace("missing", pair("a")).unwrap_err());
+        assert_eq!("Unknown graph 'missing'.", unknown.to_string());
+    }
+}
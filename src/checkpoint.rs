@@ -0,0 +1,470 @@
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{init_candidates, search, simple_simulation, NestedVec};
+use crate::query::Query;
+use crate::{Graph, MatchConfig};
+
+// The position of a resumable search, i.e. the last match it returned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    // node and relationship counts and a hash of the labels and relationships
+    // of graph and pattern, and a hash of the config settings that change the
+    // matches, to detect mismatches
+    fingerprint: [u64; 7],
+    match_count: usize,
+    last: Vec<usize>,
+}
+
+impl Checkpoint {
+    pub fn match_count(&self) -> usize {
+        self.match_count
+    }
+
+    pub fn last(&self) -> &[usize] {
+        &self.last
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fn join<V: ToString>(values: &[V]) -> String {
+            values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        let content = format!(
+            "dual-iso checkpoint 3\nfingerprint {}\nmatches {}\nlast {}\n",
+            join(&self.fingerprint),
+            self.match_count,
+            join(&self.last)
+        );
+        // write to a temporary file first so a crash never leaves a partial checkpoint
+        let temporary = path.as_ref().with_extension("tmp");
+        fs::write(&temporary, content)?;
+        fs::rename(temporary, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Checkpoint> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid checkpoint.");
+        let content = fs::read_to_string(path)?;
+        let mut lines = content.lines();
+        if lines.next() != Some("dual-iso checkpoint 3") {
+            return Err(invalid());
+        }
+        let mut field = |name: &str| -> io::Result<Vec<u64>> {
+            let line = lines.next().ok_or_else(invalid)?;
+            let values = line.strip_prefix(name).ok_or_else(invalid)?;
+            values
+                .split_whitespace()
+                .map(|value| value.parse().map_err(|_| invalid()))
+                .collect()
+        };
+        let fingerprint = field("fingerprint")?;
+        let match_count = field("matches")?;
+        let last = field("last")?
+            .into_iter()
+            .map(|node_id| usize::try_from(node_id).map_err(|_| invalid()))
+            .collect::<io::Result<Vec<_>>>()?;
+        match (<[u64; 7]>::try_from(fingerprint), match_count.as_slice()) {
+            (Ok(fingerprint), [match_count]) => Ok(Checkpoint {
+                fingerprint,
+                match_count: usize::try_from(*match_count).map_err(|_| invalid())?,
+                last,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// The checkpoint was taken for another graph or pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckpointMismatch;
+
+impl fmt::Display for CheckpointMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Checkpoint does not belong to this graph and pattern.")
+    }
+}
+
+impl Error for CheckpointMismatch {}
+
+// Enumerates matches in batches in lexicographic order of their graph nodes,
+// so the enumeration can continue from a checkpoint, e.g. after a restart.
+pub struct ResumableSearch<'g, T> {
+    graph: &'g Graph<T>,
+    query: Query<T>,
+    config: MatchConfig,
+    fingerprint: [u64; 7],
+    match_count: usize,
+    last: Option<Vec<usize>>,
+    is_done: bool,
+    is_exhausted: bool,
+}
+
+impl<'g, T> ResumableSearch<'g, T>
+where
+    T: Eq + Hash,
+{
+    // The limit and scorer of the config are ignored, batches are limited
+    // instead.
+    pub fn new(graph: &'g Graph<T>, pattern: &Graph<T>, config: &MatchConfig) -> Self {
+        let mut config = config.clone();
+        config.limit = None;
+        config.scorer = None;
+        let fingerprint = [
+            graph.node_count() as u64,
+            graph.relationship_count() as u64,
+            pattern.node_count() as u64,
+            pattern.relationship_count() as u64,
+            content_hash(graph),
+            content_hash(pattern),
+            settings_hash(&config),
+        ];
+        ResumableSearch {
+            graph,
+            query: Query::new(pattern),
+            config,
+            fingerprint,
+            match_count: 0,
+            last: None,
+            is_done: false,
+            is_exhausted: false,
+        }
+    }
+
+    pub fn resume(
+        graph: &'g Graph<T>,
+        pattern: &Graph<T>,
+        config: &MatchConfig,
+        checkpoint: &Checkpoint,
+    ) -> Result<Self, CheckpointMismatch> {
+        let mut search = ResumableSearch::new(graph, pattern, config);
+        if search.fingerprint != checkpoint.fingerprint
+            || checkpoint.last.len() != pattern.node_count()
+        {
+            return Err(CheckpointMismatch);
+        }
+        search.match_count = checkpoint.match_count;
+        search.last = Some(checkpoint.last.clone());
+        Ok(search)
+    }
+
+    // Returns up to `size` matches following the previous batch, an empty
+    // batch once all matches have been returned. Quotas apply per batch, a
+    // batch that a quota stopped may be short without the search being done,
+    // the next batch continues after its last match. A batch that a quota
+    // stopped before its first match ends the search, since the next batch
+    // would start at the same position, see `is_exhausted`.
+    pub fn next_batch(&mut self, size: usize) -> NestedVec {
+        if self.is_done || size == 0 {
+            return vec![];
+        }
+        let mut candidates = init_candidates(self.graph, &self.query)
+            .into_iter()
+            .map(|candidates| {
+                let mut candidates = candidates.into_owned();
+                candidates.sort_unstable();
+                Candidates::from(candidates)
+            })
+            .collect::<Vec<_>>();
+        let mut matches: NestedVec = vec![];
+        let config = self.config.accounted();
+        if simple_simulation(self.graph, &self.query, &config, &mut candidates) {
+            search(
+                self.graph,
+                &self.query,
+                &config,
+                &mut |embedding| {
+                    matches.push(embedding);
+                    matches.len() < size
+                },
+                &candidates,
+                0,
+                self.last.as_deref(),
+            );
+        }
+
+        self.is_exhausted = matches.is_empty() && config.is_quota_exceeded();
+        self.is_done = (matches.len() < size && !config.is_quota_exceeded()) || self.is_exhausted;
+        self.match_count += matches.len();
+        if let Some(last) = matches.last() {
+            self.last = Some(last.clone());
+        }
+        matches
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.is_done
+    }
+
+    // Whether the search ended because the quota does not suffice to reach
+    // the next match, rather than after the last match.
+    pub fn is_exhausted(&self) -> bool {
+        self.is_exhausted
+    }
+
+    // None until the first match has been returned.
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.last.as_ref().map(|last| Checkpoint {
+            fingerprint: self.fingerprint,
+            match_count: self.match_count,
+            last: last.clone(),
+        })
+    }
+}
+
+// a hash of the labels, relationships and relationship types, which depends
+// on the node ids
+fn content_hash<T: Eq + Hash>(graph: &Graph<T>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for node_id in graph.node_ids() {
+        graph.node_label(node_id).hash(&mut hasher);
+    }
+    for relationship in graph.edges() {
+        relationship.hash(&mut hasher);
+    }
+    // the types are stored in no particular order
+    let mut typed = graph.typed_relationships().collect::<Vec<_>>();
+    typed.sort_unstable();
+    typed.hash(&mut hasher);
+    hasher.finish()
+}
+
+// a hash of the settings that change which matches are enumerated, others,
+// such as quotas, only change how many are returned per batch
+fn settings_hash(config: &MatchConfig) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    config.semantics.hash(&mut hasher);
+    config.self_loops.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dual_iso, GraphBuilder, Semantics};
+
+    use super::*;
+
+    fn graphs() -> (Graph<&'static str>, Graph<&'static str>) {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..5 {
+            builder.add_node(node_id, "x");
+        }
+        for start_node in 0..5 {
+            for end_node in 0..5 {
+                if start_node != end_node {
+                    builder.add_relationship(start_node, end_node);
+                }
+            }
+        }
+        let pattern = GraphBuilder::new()
+            .add_node(0, "x")
+            .add_node(1, "x")
+            .add_node(2, "x")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+        (builder.build(), pattern)
+    }
+
+    #[test]
+    fn test_batches_cover_all_matches() {
+        let (graph, pattern) = graphs();
+        let mut search = ResumableSearch::new(&graph, &pattern, &MatchConfig::default());
+
+        let mut matches = vec![];
+        loop {
+            let batch = search.next_batch(7);
+            if batch.is_empty() {
+                break;
+            }
+            matches.extend(batch);
+        }
+        let mut expected = dual_iso(&graph, &pattern);
+        expected.sort();
+
+        assert!(search.is_done());
+        assert_eq!(60, matches.len());
+        assert_eq!(expected, matches);
+        assert_eq!(60, search.checkpoint().unwrap().match_count());
+    }
+
+    #[test]
+    fn test_batches_stopped_by_quota() {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..6 {
+            builder.add_node(node_id, "x");
+        }
+        for start_node in 0..6 {
+            for end_node in 0..6 {
+                if start_node != end_node {
+                    builder.add_relationship(start_node, end_node);
+                }
+            }
+        }
+        let graph = builder.build();
+        let (_, pattern) = graphs();
+        let mut config = MatchConfig::default();
+        config.max_branches(20);
+        let mut search = ResumableSearch::new(&graph, &pattern, &config);
+
+        let first = search.next_batch(200);
+        assert!(first.len() < 120);
+        assert!(!search.is_done());
+        let mut matches = first;
+        while !search.is_done() {
+            matches.extend(search.next_batch(200));
+        }
+        let mut expected = dual_iso(&graph, &pattern);
+        expected.sort();
+
+        assert_eq!(120, matches.len());
+        assert_eq!(expected, matches);
+        assert!(!search.is_exhausted());
+    }
+
+    #[test]
+    fn test_batches_exhausted_by_quota() {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..6 {
+            builder.add_node(node_id, "x");
+        }
+        for start_node in 0..6 {
+            for end_node in 0..6 {
+                if start_node != end_node {
+                    builder.add_relationship(start_node, end_node);
+                }
+            }
+        }
+        let graph = builder.build();
+        let (_, pattern) = graphs();
+        for max_branches in [1, 2] {
+            let mut config = MatchConfig::default();
+            config.max_branches(max_branches);
+            let mut search = ResumableSearch::new(&graph, &pattern, &config);
+
+            assert!(search.next_batch(10).is_empty());
+            assert!(search.is_done());
+            assert!(search.is_exhausted());
+            assert!(search.next_batch(10).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_resume_from_disk() {
+        let (graph, pattern) = graphs();
+        let config = MatchConfig::default();
+        let path = std::env::temp_dir().join(format!("dual_iso_checkpoint_{}", std::process::id()));
+
+        let mut search = ResumableSearch::new(&graph, &pattern, &config);
+        let first = search.next_batch(25);
+        search.checkpoint().unwrap().save(&path).unwrap();
+        drop(search);
+
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(25, checkpoint.match_count());
+        assert_eq!(first.last().unwrap().as_slice(), checkpoint.last());
+
+        let mut resumed = ResumableSearch::resume(&graph, &pattern, &config, &checkpoint).unwrap();
+        let rest = resumed.next_batch(100);
+        assert_eq!(35, rest.len());
+        assert!(first.last().unwrap() < &rest[0]);
+        assert!(resumed.is_done());
+    }
+
+    #[test]
+    fn test_resume_with_other_pattern() {
+        let (graph, pattern) = graphs();
+        let config = MatchConfig::default();
+        let mut search = ResumableSearch::new(&graph, &pattern, &config);
+        search.next_batch(1);
+        let checkpoint = search.checkpoint().unwrap();
+
+        let other = GraphBuilder::new().add_node(0, "x").build();
+        let error = ResumableSearch::resume(&graph, &other, &config, &checkpoint)
+            .err()
+            .unwrap();
+        assert_eq!(
+            "Checkpoint does not belong to this graph and pattern.",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_resume_with_other_graph_of_same_size() {
+        let (graph, pattern) = graphs();
+        let config = MatchConfig::default();
+        let mut search = ResumableSearch::new(&graph, &pattern, &config);
+        search.next_batch(1);
+        let checkpoint = search.checkpoint().unwrap();
+
+        // same counts, but one node has another label
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..5 {
+            builder.add_node(node_id, if node_id == 4 { "y" } else { "x" });
+        }
+        for start_node in 0..5 {
+            for end_node in 0..5 {
+                if start_node != end_node {
+                    builder.add_relationship(start_node, end_node);
+                }
+            }
+        }
+        let relabeled = builder.build();
+        assert!(ResumableSearch::resume(&relabeled, &pattern, &config, &checkpoint).is_err());
+
+        // same counts, but another relationship
+        let path = GraphBuilder::new()
+            .add_node(0, "x")
+            .add_node(1, "x")
+            .add_node(2, "x")
+            .add_relationship(0, 1)
+            .add_relationship(2, 1)
+            .build();
+        assert_eq!(
+            Err(CheckpointMismatch),
+            ResumableSearch::resume(&graph, &path, &config, &checkpoint).map(|_| ())
+        );
+
+        // same relationships, but another semantics
+        let mut homomorphism = MatchConfig::new();
+        homomorphism.semantics(Semantics::Homomorphism);
+        assert!(ResumableSearch::resume(&graph, &pattern, &homomorphism, &checkpoint).is_err());
+        assert!(ResumableSearch::resume(&graph, &pattern, &config, &checkpoint).is_ok());
+    }
+
+    #[test]
+    fn test_resume_with_other_relationship_types() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "x")
+            .add_node(1, "x")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship_with_type(1, 0, "LIKES")
+            .build();
+        let typed_pattern = |rel_type| {
+            GraphBuilder::new()
+                .add_node(0, "x")
+                .add_node(1, "x")
+                .add_relationship_with_type(0, 1, rel_type)
+                .build()
+        };
+        let config = MatchConfig::default();
+        let mut search = ResumableSearch::new(&graph, &typed_pattern("KNOWS"), &config);
+        assert_eq!(vec![vec![0, 1]], search.next_batch(1));
+        let checkpoint = search.checkpoint().unwrap();
+
+        let likes = typed_pattern("LIKES");
+        assert!(ResumableSearch::resume(&graph, &likes, &config, &checkpoint).is_err());
+        let knows = typed_pattern("KNOWS");
+        assert!(ResumableSearch::resume(&graph, &knows, &config, &checkpoint).is_ok());
+    }
+}
@@ -0,0 +1,230 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::scoring::Scorer;
+use crate::stats::Accounting;
+use crate::CandidateScorer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum SelfLoops {
+    /// A pattern self-loop requires a self-loop at the matched graph node.
+    #[default]
+    Match,
+    /// Self-loops in the pattern are dropped before matching.
+    Ignore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Semantics {
+    /// Distinct pattern nodes match distinct graph nodes.
+    #[default]
+    Isomorphism,
+    /// Distinct pattern nodes may match the same graph node.
+    Homomorphism,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EmptyCandidates {
+    /// There are no matches, as for any other pattern without matches.
+    #[default]
+    NoMatches,
+    /// `dual_iso_checked` returns the pattern node as an error, other functions
+    /// return no matches.
+    Error,
+    /// The relationships of the pattern node are dropped until it has candidates.
+    Relax,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MatchConfig {
+    pub(crate) self_loops: SelfLoops,
+    pub(crate) limit: Option<usize>,
+    // (u_P, limit) for the matches per graph node matched by u_P
+    pub(crate) anchor_limit: Option<(usize, usize)>,
+    pub(crate) max_simulation_rounds: Option<usize>,
+    pub(crate) all_different: bool,
+    pub(crate) hub_degree: Option<usize>,
+    pub(crate) semantics: Semantics,
+    pub(crate) minimize: bool,
+    pub(crate) max_duration: Option<Duration>,
+    pub(crate) max_branches: Option<usize>,
+    pub(crate) max_allocated_candidates: Option<usize>,
+    pub(crate) scorer: Option<Scorer>,
+    pub(crate) disable_fast_paths: bool,
+    pub(crate) empty_candidates: EmptyCandidates,
+    // shared by the branches of a single search
+    pub(crate) accounting: Option<Arc<Accounting>>,
+}
+
+impl MatchConfig {
+    pub fn new() -> Self {
+        MatchConfig::default()
+    }
+
+    // How a pattern node without candidates after the simulation is handled,
+    // which rules out any match of the pattern as given.
+    pub fn empty_candidates(&mut self, empty_candidates: EmptyCandidates) -> &mut Self {
+        self.empty_candidates = empty_candidates;
+        self
+    }
+
+    pub fn self_loops(&mut self, self_loops: SelfLoops) -> &mut Self {
+        self.self_loops = self_loops;
+        self
+    }
+
+    pub fn limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    // Limits the matches per graph node that the pattern node matches, e.g.
+    // per anchor when the pattern node is restricted to a set of graph nodes,
+    // so that one anchor with many matches does not use up the limit. The
+    // anchors are searched one after another.
+    pub fn limit_per_anchor(&mut self, node_id: usize, limit: usize) -> &mut Self {
+        self.anchor_limit = Some((node_id, limit));
+        self
+    }
+
+    // Bounds the rounds of the simulation before the search, the search
+    // itself still only returns exact matches.
+    pub fn max_simulation_rounds(&mut self, max_simulation_rounds: usize) -> &mut Self {
+        self.max_simulation_rounds = Some(max_simulation_rounds);
+        self
+    }
+
+    // Prunes branches in which the remaining candidates cannot be assigned to
+    // distinct graph nodes, at the cost of a bipartite matching per branch.
+    pub fn all_different(&mut self, all_different: bool) -> &mut Self {
+        self.all_different = all_different;
+        self
+    }
+
+    // Matches pattern nodes whose candidates include nodes with at least
+    // `hub_degree` relationships last, when their candidates are the most
+    // constrained.
+    pub fn defer_hubs(&mut self, hub_degree: usize) -> &mut Self {
+        self.hub_degree = Some(hub_degree);
+        self
+    }
+
+    pub fn semantics(&mut self, semantics: Semantics) -> &mut Self {
+        self.semantics = semantics;
+        self
+    }
+
+    // Matches the minimized pattern instead, see `minimize_pattern`. Under
+    // homomorphism semantics, the matches of the core are extended to the
+    // removed pattern nodes, so there is one match per match of the core
+    // instead of one per match of the pattern. Under isomorphism semantics,
+    // the pattern is matched as given: each pattern node needs a graph node
+    // of its own, so no smaller pattern has the same matches.
+    pub fn minimize(&mut self, minimize: bool) -> &mut Self {
+        self.minimize = minimize;
+        self
+    }
+
+    // Tries the candidates of each search step in the order of the scorer
+    // instead of by node id. Resumable searches ignore the scorer, since they
+    // rely on the node id order.
+    pub fn scorer<S: CandidateScorer + 'static>(&mut self, scorer: S) -> &mut Self {
+        self.scorer = Some(Scorer(Arc::new(scorer)));
+        self
+    }
+
+    // Matches patterns of common shapes, e.g. paths, with dedicated routines
+    // instead of the general search. Enabled by default, disabling them is
+    // mostly useful to compare both.
+    pub fn fast_paths(&mut self, fast_paths: bool) -> &mut Self {
+        self.disable_fast_paths = !fast_paths;
+        self
+    }
+
+    // Quotas stop the search once exceeded, its matches are then incomplete.
+    // `dual_iso_with_stats` reports which quota was exceeded.
+    pub fn max_duration(&mut self, max_duration: Duration) -> &mut Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn max_branches(&mut self, max_branches: usize) -> &mut Self {
+        self.max_branches = Some(max_branches);
+        self
+    }
+
+    pub fn max_allocated_candidates(&mut self, max_allocated_candidates: usize) -> &mut Self {
+        self.max_allocated_candidates = Some(max_allocated_candidates);
+        self
+    }
+
+    // The config with fresh accounting if it has quotas to enforce and is not
+    // accounted yet.
+    pub(crate) fn accounted(&self) -> Cow<'_, MatchConfig> {
+        let has_quotas = self.max_duration.is_some()
+            || self.max_branches.is_some()
+            || self.max_allocated_candidates.is_some();
+        if has_quotas && self.accounting.is_none() {
+            let mut config = self.clone();
+            config.accounting = Some(Accounting::new());
+            Cow::Owned(config)
+        } else {
+            Cow::Borrowed(self)
+        }
+    }
+
+    // Returns false if the search has to stop.
+    pub(crate) fn enter_branch(&self) -> bool {
+        self.accounting
+            .as_ref()
+            .is_none_or(|accounting| accounting.enter_branch(self))
+    }
+
+    // Whether a quota stopped the search, which is then incomplete.
+    pub(crate) fn is_quota_exceeded(&self) -> bool {
+        self.accounting
+            .as_ref()
+            .is_some_and(|accounting| accounting.is_exceeded())
+    }
+
+    // Records that the search step at `depth` stopped before its candidate at
+    // `idx` of `count` was completed.
+    pub(crate) fn stop_branch(&self, depth: usize, idx: usize, count: usize) {
+        if let Some(accounting) = &self.accounting {
+            accounting.stop_branch(depth, idx, count);
+        }
+    }
+
+    pub(crate) fn account_candidates(&self, count: usize) {
+        if let Some(accounting) = &self.accounting {
+            accounting.add_candidates(count);
+        }
+    }
+
+    pub(crate) fn account_allocation(&self, count: usize) {
+        if let Some(accounting) = &self.accounting {
+            accounting.add_allocated_candidates(count);
+        }
+    }
+
+    // Counts an intersection of candidates with an adjacency list if stats
+    // are requested, does nothing without the `perf-counters` feature.
+    #[inline]
+    pub(crate) fn account_intersection(&self, scanned_elements: usize, binary_searches: usize) {
+        #[cfg(feature = "perf-counters")]
+        if let Some(accounting) = &self.accounting {
+            accounting.add_intersection(scanned_elements, binary_searches);
+        }
+        #[cfg(not(feature = "perf-counters"))]
+        let _ = (scanned_elements, binary_searches);
+    }
+
+    pub(crate) fn is_injective(&self) -> bool {
+        self.semantics == Semantics::Isomorphism
+    }
+
+    pub(crate) fn is_limit_reached(&self, match_count: usize) -> bool {
+        self.limit.is_some_and(|limit| match_count >= limit)
+    }
+}
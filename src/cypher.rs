@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::{Direction, FilterError, GraphBuilder, Pattern};
+
+// Parses a pattern in a subset of Cypher, e.g.
+//
+//   (a:person)-->(c:car)<--(b:person), (a)--(b) WHERE a.age > 18
+//
+// Nodes are `(name:label)`, where either part may be omitted as long as every
+// node ends up with a label. Reusing a name refers to the same node.
+// Relationships are `-->`, `<--` or `--`, where the latter matches both
+// directions. Everything after `WHERE` is added as a filter.
+pub fn parse_pattern(source: &str) -> Result<Pattern<String>, FilterError> {
+    let (match_part, filter) = split_where(source);
+    let mut parser = PatternParser {
+        source: match_part,
+        position: 0,
+        labels: vec![],
+        names: HashMap::new(),
+        relationships: vec![],
+    };
+    parser.parse_paths()?;
+
+    let mut builder = GraphBuilder::new();
+    for (node_id, (label, position)) in parser.labels.iter().enumerate() {
+        match label {
+            Some(label) => builder.add_node(node_id, label.clone()),
+            None => return Err(FilterError::new("Node without label", *position)),
+        };
+    }
+    for (start_node, end_node, _) in &parser.relationships {
+        builder.add_relationship(*start_node, *end_node);
+    }
+
+    let mut pattern = Pattern::new(builder.build());
+    for (name, node_id) in &parser.names {
+        pattern.name_node(*node_id, name);
+    }
+    for (start_node, end_node, direction) in &parser.relationships {
+        if *direction != Direction::Outgoing {
+            pattern.set_direction(*start_node, *end_node, *direction);
+        }
+    }
+    if let Some((filter, offset)) = filter {
+        pattern
+            .add_filter(filter)
+            .map_err(|error| error.shifted(offset))?;
+    }
+    Ok(pattern)
+}
+
+// splits off the filter and returns it with its offset in the source
+fn split_where(source: &str) -> (&str, Option<(&str, usize)>) {
+    let upper = source.to_ascii_uppercase();
+    let mut from = 0;
+    while let Some(idx) = upper[from..].find("WHERE") {
+        let start = from + idx;
+        let end = start + "WHERE".len();
+        let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if !is_word(source[..start].chars().next_back()) && !is_word(source[end..].chars().next()) {
+            return (&source[..start], Some((&source[end..], end)));
+        }
+        from = end;
+    }
+    (source, None)
+}
+
+struct PatternParser<'a> {
+    source: &'a str,
+    position: usize,
+    // label per node and the position where the node first occurred
+    labels: Vec<(Option<String>, usize)>,
+    names: HashMap<String, usize>,
+    relationships: Vec<(usize, usize, Direction)>,
+}
+
+impl<'a> PatternParser<'a> {
+    fn parse_paths(&mut self) -> Result<(), FilterError> {
+        loop {
+            self.parse_path()?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                None => return Ok(()),
+                Some(_) => return Err(self.error("Expected ',' or end of pattern")),
+            }
+        }
+    }
+
+    fn parse_path(&mut self) -> Result<(), FilterError> {
+        let mut previous = self.parse_node()?;
+        loop {
+            self.skip_whitespace();
+            let rest = &self.source[self.position..];
+            let (is_reversed, direction, width) = if rest.starts_with("-->") {
+                (false, Direction::Outgoing, 3)
+            } else if rest.starts_with("<--") {
+                (true, Direction::Outgoing, 3)
+            } else if rest.starts_with("--") {
+                (false, Direction::Either, 2)
+            } else {
+                return Ok(());
+            };
+            self.position += width;
+            let next = self.parse_node()?;
+            let (start_node, end_node) = if is_reversed {
+                (next, previous)
+            } else {
+                (previous, next)
+            };
+            self.relationships.push((start_node, end_node, direction));
+            previous = next;
+        }
+    }
+
+    fn parse_node(&mut self) -> Result<usize, FilterError> {
+        self.skip_whitespace();
+        let start = self.position;
+        self.expect('(')?;
+        let name = self.parse_identifier();
+        self.skip_whitespace();
+        let label = if self.peek() == Some(':') {
+            self.position += 1;
+            let label = self.parse_identifier();
+            if label.is_empty() {
+                return Err(self.error("Expected label"));
+            }
+            Some(label.to_string())
+        } else {
+            None
+        };
+        self.expect(')')?;
+
+        if name.is_empty() {
+            self.labels.push((label, start));
+            return Ok(self.labels.len() - 1);
+        }
+        match self.names.get(name) {
+            Some(node_id) => {
+                let node_id = *node_id;
+                match (&self.labels[node_id].0, label) {
+                    (Some(existing), Some(label)) if *existing != label => {
+                        return Err(FilterError::new(
+                            format!("Variable '{}' has different labels", name),
+                            start,
+                        ))
+                    }
+                    (None, Some(label)) => self.labels[node_id].0 = Some(label),
+                    _ => {}
+                }
+                Ok(node_id)
+            }
+            None => {
+                self.labels.push((label, start));
+                self.names.insert(name.to_string(), self.labels.len() - 1);
+                Ok(self.labels.len() - 1)
+            }
+        }
+    }
+
+    fn parse_identifier(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let start = self.position;
+        let width = self.source[start..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .map(char::len_utf8)
+            .sum::<usize>();
+        self.position += width;
+        &self.source[start..start + width]
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), FilterError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", expected)))
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, message: &str) -> FilterError {
+        FilterError::new(message, self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{match_pattern, MatchConfig};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_pattern() {
+        let pattern = parse_pattern("(a:person)-->(c:car)<--(b:person), (a)--(b)").unwrap();
+        let graph = pattern.graph();
+
+        assert_eq!(3, graph.node_count());
+        assert_eq!(3, graph.relationship_count());
+        let (a, b, c) = (
+            pattern.node_by_name("a").unwrap(),
+            pattern.node_by_name("b").unwrap(),
+            pattern.node_by_name("c").unwrap(),
+        );
+        assert_eq!("car", graph.node_label(c));
+        assert_eq!(1, graph.multiplicity(a, c));
+        assert_eq!(1, graph.multiplicity(b, c));
+        assert_eq!(Direction::Either, pattern.direction(a, b));
+    }
+
+    #[test]
+    fn test_parse_pattern_with_filter() {
+        let graph = crate::parse_graph("node 0 a\nnode 1 a\nrel 0 1\n").unwrap();
+        let pattern = parse_pattern("(x:a)-->(y:a) where y.id = 1").unwrap();
+
+        let matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(0), Some(1)], matches[0].nodes());
+
+        assert!(parse_pattern("(nowhere:a)").is_ok());
+    }
+
+    #[test]
+    fn test_parse_pattern_errors() {
+        let error = |source: &str| parse_pattern(source).err().unwrap().to_string();
+
+        assert_eq!("Node without label at position 8.", error("(a:x)-->(b)"));
+        assert_eq!("Expected ')' at position 4.", error("(a:x"));
+        assert_eq!("Expected label at position 3.", error("(a:)"));
+        assert_eq!(
+            "Variable 'a' has different labels at position 7.",
+            error("(a:x), (a:y)")
+        );
+        assert_eq!(
+            "Expected ',' or end of pattern at position 6.",
+            error("(a:x) (b:x)")
+        );
+        assert_eq!(
+            "Unknown variable 'b' at position 12.",
+            error("(a:x) WHERE b.id = 1")
+        );
+    }
+}
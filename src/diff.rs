@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::dual_iso::NestedVec;
+use crate::{group_by_automorphism, Graph};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatchDiff {
+    added: NestedVec,
+    removed: NestedVec,
+    unchanged: NestedVec,
+}
+
+impl MatchDiff {
+    // Compares two match sets of the same pattern, e.g. from two graph versions.
+    pub fn new(before: &[Vec<usize>], after: &[Vec<usize>]) -> Self {
+        let before_keys = before.iter().collect::<HashSet<_>>();
+        let after_keys = after.iter().collect::<HashSet<_>>();
+        let mut diff = MatchDiff::default();
+        for embedding in after.iter().filter(dedup()) {
+            if before_keys.contains(embedding) {
+                diff.unchanged.push(embedding.clone());
+            } else {
+                diff.added.push(embedding.clone());
+            }
+        }
+        for embedding in before.iter().filter(dedup()) {
+            if !after_keys.contains(embedding) {
+                diff.removed.push(embedding.clone());
+            }
+        }
+        diff
+    }
+
+    // Like `new`, but matches that only differ by a pattern symmetry are considered equal.
+    // Each side is reduced to one representative embedding per automorphism group.
+    pub fn up_to_automorphism<T: Eq + Hash>(
+        pattern: &Graph<T>,
+        before: &[Vec<usize>],
+        after: &[Vec<usize>],
+    ) -> Self {
+        let representatives = |matches: &[Vec<usize>]| {
+            group_by_automorphism(pattern, matches)
+                .into_iter()
+                .map(|group| group.representative().to_vec())
+                .collect::<Vec<_>>()
+        };
+        MatchDiff::new(&representatives(before), &representatives(after))
+    }
+
+    pub fn added(&self) -> &[Vec<usize>] {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &[Vec<usize>] {
+        &self.removed
+    }
+
+    pub fn unchanged(&self) -> &[Vec<usize>] {
+        &self.unchanged
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+// Differences between two graphs, where nodes are matched by their id.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphDiff {
+    added_nodes: Vec<usize>,
+    removed_nodes: Vec<usize>,
+    changed_labels: Vec<usize>,
+    added_relationships: Vec<(usize, usize)>,
+    removed_relationships: Vec<(usize, usize)>,
+}
+
+impl GraphDiff {
+    pub fn new<T: Eq + Hash>(before: &Graph<T>, after: &Graph<T>) -> Self {
+        let common = before.node_count().min(after.node_count());
+        let mut diff = GraphDiff {
+            added_nodes: (common..after.node_count()).collect(),
+            removed_nodes: (common..before.node_count()).collect(),
+            ..GraphDiff::default()
+        };
+        for node_id in 0..common {
+            if before.node_label(node_id) != after.node_label(node_id) {
+                diff.changed_labels.push(node_id);
+            }
+        }
+
+        let no_neighbors: &[usize] = &[];
+        for node_id in 0..before.node_count().max(after.node_count()) {
+            let old = if node_id < before.node_count() {
+                before.neighbors(node_id)
+            } else {
+                no_neighbors
+            };
+            let new = if node_id < after.node_count() {
+                after.neighbors(node_id)
+            } else {
+                no_neighbors
+            };
+            // merge both sorted lists, parallel relationships are compared by count
+            let (mut i, mut j) = (0, 0);
+            while i < old.len() || j < new.len() {
+                if j == new.len() || (i < old.len() && old[i] < new[j]) {
+                    diff.removed_relationships.push((node_id, old[i]));
+                    i += 1;
+                } else if i == old.len() || new[j] < old[i] {
+                    diff.added_relationships.push((node_id, new[j]));
+                    j += 1;
+                } else {
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        diff
+    }
+
+    pub fn added_nodes(&self) -> &[usize] {
+        &self.added_nodes
+    }
+
+    pub fn removed_nodes(&self) -> &[usize] {
+        &self.removed_nodes
+    }
+
+    pub fn changed_labels(&self) -> &[usize] {
+        &self.changed_labels
+    }
+
+    pub fn added_relationships(&self) -> &[(usize, usize)] {
+        &self.added_relationships
+    }
+
+    pub fn removed_relationships(&self) -> &[(usize, usize)] {
+        &self.removed_relationships
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_labels.is_empty()
+            && self.added_relationships.is_empty()
+            && self.removed_relationships.is_empty()
+    }
+}
+
+fn dedup<'a>() -> impl FnMut(&&'a Vec<usize>) -> bool {
+    let mut seen = HashSet::new();
+    move |embedding| seen.insert(*embedding)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_match_diff() {
+        let before = vec![vec![0, 1], vec![1, 2], vec![2, 3]];
+        let after = vec![vec![1, 2], vec![3, 4], vec![2, 3], vec![3, 4]];
+
+        let diff = MatchDiff::new(&before, &after);
+
+        assert_eq!(&[vec![3, 4]], diff.added());
+        assert_eq!(&[vec![0, 1]], diff.removed());
+        assert_eq!(&[vec![1, 2], vec![2, 3]], diff.unchanged());
+        assert!(!diff.is_empty());
+        assert!(MatchDiff::new(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_graph_diff() {
+        let before = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 1)
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+        let after = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "x")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .build();
+
+        let diff = before.diff(&after);
+
+        assert_eq!(&[] as &[usize], diff.added_nodes());
+        assert_eq!(&[2], diff.removed_nodes());
+        assert_eq!(&[1], diff.changed_labels());
+        assert_eq!(&[(1, 0)], diff.added_relationships());
+        assert_eq!(&[(0, 1), (1, 2), (2, 0)], diff.removed_relationships());
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn test_match_diff_up_to_automorphism() {
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .build();
+
+        let diff = MatchDiff::up_to_automorphism(&pattern, &[vec![0, 1]], &[vec![1, 0]]);
+
+        assert!(diff.is_empty());
+        assert_eq!(&[vec![0, 1]], diff.unchanged());
+    }
+}
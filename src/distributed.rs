@@ -0,0 +1,535 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{
+    combine_components, dual_iso_with_candidates, is_supported, panic_message, NestedVec,
+};
+use crate::query::Query;
+use crate::{Graph, MatchConfig, MatchPanic, Partition, Partitioning, SelfLoops};
+
+pub enum Message {
+    // owned graph nodes (original ids) that are no longer candidates of the pattern node
+    Removed {
+        pattern_node: usize,
+        nodes: Vec<usize>,
+    },
+    // a worker finished a simulation round, with the number of `Removed`
+    // messages it sent to each part
+    Refined {
+        updated: bool,
+        sent: Vec<usize>,
+    },
+    // starts the next round once the receiver got `expected` more `Removed` messages
+    Continue {
+        expected: usize,
+    },
+    Search,
+    // a match of a pattern component in original ids
+    Match {
+        component: usize,
+        nodes: Vec<usize>,
+    },
+    Done,
+    Failed {
+        message: String,
+    },
+    Stop,
+}
+
+// One endpoint of the message passing between workers and the coordinator.
+// Workers are addressed by their part index, the coordinator by the number
+// of parts. Only the in-process `LocalTransport` is provided, a transport
+// across processes would also have to encode the messages.
+pub trait Transport {
+    fn send(&mut self, to: usize, message: Message);
+
+    // Blocks until a message for this endpoint arrives. Returns `None` once
+    // no other endpoint can send to it anymore.
+    fn receive(&mut self) -> Option<Message>;
+}
+
+// In-process transport backed by one channel per endpoint.
+pub struct LocalTransport {
+    senders: Vec<Sender<Message>>,
+    receiver: Receiver<Message>,
+}
+
+impl LocalTransport {
+    pub fn endpoints(endpoint_count: usize) -> Vec<Self> {
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..endpoint_count).map(|_| mpsc::channel()).unzip();
+        receivers
+            .into_iter()
+            .map(|receiver| LocalTransport {
+                senders: senders.clone(),
+                receiver,
+            })
+            .collect()
+    }
+}
+
+impl Transport for LocalTransport {
+    fn send(&mut self, to: usize, message: Message) {
+        // endpoints that stopped don't receive anymore
+        let _ = self.senders[to].send(message);
+    }
+
+    fn receive(&mut self) -> Option<Message> {
+        self.receiver.recv().ok()
+    }
+}
+
+// Holds a single part and its view of the candidate sets, where only the
+// candidates owned by the part are decided locally.
+struct Worker<'a, T> {
+    part: &'a Partition<T>,
+    candidates: Vec<Vec<usize>>,
+}
+
+impl<'a, T> Worker<'a, T>
+where
+    T: Eq + Hash,
+{
+    fn new(part: &'a Partition<T>, query: &Query<T>) -> Self {
+        let candidates = (0..query.node_count())
+            .map(|u_p| part.graph().nodes_by_label(query.label(u_p)).to_vec())
+            .collect();
+        Worker { part, candidates }
+    }
+
+    fn local_id(&self, node_id: usize) -> Option<usize> {
+        self.part
+            .subgraph()
+            .original_ids()
+            .binary_search(&node_id)
+            .ok()
+    }
+
+    // Removes owned candidates without support until nothing changes and
+    // returns them per pattern node.
+    fn refine(&mut self, query: &Query<T>, config: &MatchConfig) -> HashMap<usize, Vec<usize>> {
+        let graph = self.part.graph();
+        let mut removed: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut is_updated = true;
+        while is_updated {
+            is_updated = false;
+            for u_p in 0..query.node_count() {
+                for constraint in query.constraints(u_p) {
+                    if constraint.target == u_p && config.self_loops == SelfLoops::Ignore {
+                        continue;
+                    }
+                    let v_p_candidates = &self.candidates[constraint.target];
+                    let (kept, dropped): (Vec<usize>, Vec<usize>) =
+                        self.candidates[u_p].iter().partition(|u_g| {
+                            !self.part.is_owned(**u_g)
+                                || is_supported(
+                                    graph,
+                                    config,
+                                    u_p,
+                                    constraint,
+                                    **u_g,
+                                    v_p_candidates,
+                                )
+                        });
+                    if !dropped.is_empty() {
+                        is_updated = true;
+                        removed.entry(u_p).or_default().extend(
+                            dropped
+                                .iter()
+                                .map(|u_g| self.part.subgraph().original_id(*u_g)),
+                        );
+                        self.candidates[u_p] = kept;
+                    }
+                }
+            }
+        }
+        removed
+    }
+
+    fn remove(&mut self, pattern_node: usize, nodes: &[usize]) {
+        let local = nodes
+            .iter()
+            .filter_map(|node_id| self.local_id(*node_id))
+            .collect::<HashSet<_>>();
+        self.candidates[pattern_node].retain(|u_g| !local.contains(u_g));
+    }
+
+    // matches of the component whose first node is owned by this part
+    fn search(&self, query: &Query<T>, component: &[usize], config: &MatchConfig) -> NestedVec {
+        let candidates = component
+            .iter()
+            .enumerate()
+            .map(|(idx, u_p)| {
+                if idx == 0 {
+                    Candidates::Owned(
+                        self.candidates[*u_p]
+                            .iter()
+                            .copied()
+                            .filter(|u_g| self.part.is_owned(*u_g))
+                            .collect(),
+                    )
+                } else {
+                    Candidates::Borrowed(self.candidates[*u_p].as_slice())
+                }
+            })
+            .collect();
+        dual_iso_with_candidates(
+            self.part.graph(),
+            &query.restrict(component),
+            config,
+            candidates,
+        )
+        .iter()
+        .map(|embedding| self.part.subgraph().to_original(embedding))
+        .collect()
+    }
+}
+
+// Runs the matcher on a partitioned graph, where each part is held by a
+// worker on its own thread. Workers exchange removed boundary candidates
+// during the simulation until no worker changes anymore, then search their
+// part and send the matches to the coordinator. The halo of the partitioning
+// must be at least 1 and cover each pattern component as required by
+// `Partitioning::dual_iso`.
+pub struct DistributedRuntime<'a, T> {
+    partitioning: &'a Partitioning<T>,
+}
+
+impl<'a, T> DistributedRuntime<'a, T>
+where
+    T: Eq + Hash + Clone + Send,
+{
+    pub fn new(partitioning: &'a Partitioning<T>) -> Self {
+        if partitioning.halo() == 0 {
+            panic!("Distributed matching requires a halo of at least 1.");
+        }
+        DistributedRuntime { partitioning }
+    }
+
+    pub fn run(&self, pattern: &Graph<T>, config: &MatchConfig) -> Result<NestedVec, MatchPanic> {
+        let mut matches = vec![];
+        self.run_streaming(pattern, config, &mut |nodes| matches.push(nodes.to_vec()))?;
+        Ok(matches)
+    }
+
+    // Matches are passed to `on_match` as soon as the coordinator receives
+    // them, matches of disconnected patterns once all components are known.
+    // A panicking worker stops all workers and is returned as an error.
+    pub fn run_streaming(
+        &self,
+        pattern: &Graph<T>,
+        config: &MatchConfig,
+        on_match: &mut dyn FnMut(&[usize]),
+    ) -> Result<(), MatchPanic> {
+        let parts = self.partitioning.parts();
+        let part_count = parts.len();
+        for component in pattern.weakly_connected_components() {
+            self.partitioning
+                .validate_halo(&pattern.induced_subgraph(&component));
+        }
+
+        let mut endpoints = LocalTransport::endpoints(part_count + 1);
+        let mut coordinator = endpoints.pop().unwrap();
+        thread::scope(|scope| {
+            for (part, mut transport) in parts.iter().zip(endpoints) {
+                // graphs share their labels, so each worker gets its own copy
+                let part = part.detach();
+                let pattern = pattern.detach();
+                let config = config.clone();
+                scope.spawn(move || {
+                    let part = part.attach();
+                    let pattern = pattern.attach();
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        run_worker(&part, &pattern, &config, part_count, &mut transport)
+                    }));
+                    if let Err(payload) = result {
+                        transport.send(
+                            part_count,
+                            Message::Failed {
+                                message: panic_message(payload),
+                            },
+                        );
+                    }
+                });
+            }
+            run_coordinator(part_count, pattern, config, &mut coordinator, on_match)
+        })
+    }
+}
+
+// Holds the part with the index of the transport endpoint until the
+// coordinator is done or stops it. The coordinator is the endpoint
+// `part_count`.
+pub fn run_worker<T, R>(
+    part: &Partition<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    part_count: usize,
+    transport: &mut R,
+) where
+    T: Eq + Hash,
+    R: Transport,
+{
+    let query = Query::new(pattern);
+    let mut worker = Worker::new(part, &query);
+
+    // simulation rounds
+    let mut received = 0;
+    let mut expected = 0;
+    loop {
+        let removed = worker.refine(&query, config);
+        let mut sent = vec![0; part_count];
+        let updated = !removed.is_empty();
+        for (pattern_node, nodes) in removed {
+            // parts holding a copy of a node need to know when it is removed
+            let mut by_part: HashMap<usize, Vec<usize>> = HashMap::new();
+            for node_id in nodes {
+                for part_id in part.copies(node_id) {
+                    by_part.entry(*part_id).or_default().push(node_id);
+                }
+            }
+            for (part_id, nodes) in by_part {
+                transport.send(
+                    part_id,
+                    Message::Removed {
+                        pattern_node,
+                        nodes,
+                    },
+                );
+                sent[part_id] += 1;
+            }
+        }
+        transport.send(part_count, Message::Refined { updated, sent });
+
+        let mut is_continued = None;
+        while is_continued.is_none() || received < expected {
+            match transport.receive() {
+                Some(Message::Removed {
+                    pattern_node,
+                    nodes,
+                }) => {
+                    worker.remove(pattern_node, &nodes);
+                    received += 1;
+                }
+                Some(Message::Continue { expected: more }) => {
+                    expected += more;
+                    is_continued = Some(true);
+                }
+                Some(Message::Search) => is_continued = Some(false),
+                Some(Message::Stop) | None => return,
+                Some(_) => {}
+            }
+        }
+        if is_continued == Some(false) {
+            break;
+        }
+    }
+
+    // search phase, components are combined by the coordinator
+    let mut component_config = config.clone();
+    component_config.limit = None;
+    for (component_id, component) in pattern.weakly_connected_components().iter().enumerate() {
+        for nodes in worker.search(&query, component, &component_config) {
+            transport.send(
+                part_count,
+                Message::Match {
+                    component: component_id,
+                    nodes,
+                },
+            );
+        }
+    }
+    transport.send(part_count, Message::Done);
+}
+
+// Drives the simulation rounds of `part_count` workers and collects their
+// matches. A failed worker stops all workers.
+pub fn run_coordinator<T, R>(
+    part_count: usize,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    transport: &mut R,
+    on_match: &mut dyn FnMut(&[usize]),
+) -> Result<(), MatchPanic>
+where
+    T: Eq + Hash,
+    R: Transport,
+{
+    let components = pattern.weakly_connected_components();
+
+    // simulation rounds
+    loop {
+        let mut is_updated = false;
+        let mut expected = vec![0; part_count];
+        let mut refined = 0;
+        while refined < part_count {
+            if let Message::Refined { updated, sent } = receive(part_count, transport)? {
+                is_updated |= updated;
+                for (part_id, count) in sent.into_iter().enumerate() {
+                    expected[part_id] += count;
+                }
+                refined += 1;
+            }
+        }
+        if !is_updated {
+            break;
+        }
+        for (part_id, expected) in expected.into_iter().enumerate() {
+            transport.send(part_id, Message::Continue { expected });
+        }
+    }
+    for part_id in 0..part_count {
+        transport.send(part_id, Message::Search);
+    }
+
+    // search phase
+    let mut match_count = 0;
+    let mut component_matches: Vec<NestedVec> = vec![vec![]; components.len()];
+    let mut done = 0;
+    while done < part_count {
+        match receive(part_count, transport)? {
+            Message::Match { component, nodes } => {
+                if components.len() > 1 {
+                    component_matches[component].push(nodes);
+                } else if !config.is_limit_reached(match_count) {
+                    match_count += 1;
+                    on_match(&nodes);
+                }
+            }
+            Message::Done => done += 1,
+            _ => {}
+        }
+    }
+
+    if components.len() > 1 {
+        for nodes in combine_components(
+            config,
+            pattern.node_count(),
+            &components,
+            &component_matches,
+        ) {
+            on_match(&nodes);
+        }
+    }
+    Ok(())
+}
+
+// the next message at the coordinator, where a failure stops all workers
+fn receive<R: Transport>(part_count: usize, transport: &mut R) -> Result<Message, MatchPanic> {
+    let message = match transport.receive() {
+        Some(Message::Failed { message }) => message,
+        Some(message) => return Ok(message),
+        None => "Workers disconnected.".to_string(),
+    };
+    for part_id in 0..part_count {
+        transport.send(part_id, Message::Stop);
+    }
+    Err(MatchPanic { message })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dual_iso, GraphBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_local_transport() {
+        let mut endpoints = LocalTransport::endpoints(2);
+        endpoints[0].send(1, Message::Done);
+        endpoints[0].send(1, Message::Stop);
+
+        assert!(matches!(endpoints[1].receive(), Some(Message::Done)));
+        assert!(matches!(endpoints[1].receive(), Some(Message::Stop)));
+    }
+
+    #[test]
+    fn test_distributed_dual_iso() {
+        // node 4 has no successor labeled c, which prunes node 3 in another part
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_node(3, "a")
+            .add_node(4, "b")
+            .add_node(5, "d")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 3)
+            .add_relationship(3, 4)
+            .add_relationship(4, 5)
+            .add_relationship(5, 0)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let partitioning = Partitioning::hash(&graph, 3, 2);
+        let runtime = DistributedRuntime::new(&partitioning);
+        let mut matches = runtime.run(&pattern, &MatchConfig::default()).unwrap();
+        matches.sort();
+
+        assert_eq!(vec![vec![0, 1, 2]], matches);
+    }
+
+    #[test]
+    fn test_distributed_dual_iso_disconnected() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_node(4, "c")
+            .add_relationship(0, 1)
+            .add_relationship(2, 3)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 1)
+            .build();
+
+        let partitioning = Partitioning::hash(&graph, 2, 1);
+        let runtime = DistributedRuntime::new(&partitioning);
+        let mut matches = runtime.run(&pattern, &MatchConfig::default()).unwrap();
+        matches.sort();
+        let mut expected = dual_iso(&graph, &pattern);
+        expected.sort();
+
+        assert_eq!(expected, matches);
+    }
+
+    #[test]
+    fn test_distributed_dual_iso_panic() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let partitioning = Partitioning::hash(&graph, 2, 1);
+        let runtime = DistributedRuntime::new(&partitioning);
+
+        assert_eq!(
+            "Node id 2 must be within range [0..2).",
+            runtime
+                .run(&pattern, MatchConfig::new().limit_per_anchor(2, 1))
+                .unwrap_err()
+                .message()
+        );
+    }
+}
@@ -1,106 +1,1456 @@
+use std::any::Any;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
 use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::Graph;
+use smallvec::SmallVec;
+
+use crate::candidates::{Candidates, INLINE_CANDIDATES};
+use crate::minimize::retract;
+use crate::query::{Constraint, Direction, Query};
+use crate::shapes::{count_shape, visit_shape};
+use crate::stats::{Accounting, MatchStats};
+use crate::{EmptyCandidates, Graph, MatchConfig, SelfLoops};
 
 pub type NestedVec = Vec<Vec<usize>>;
 
+// Flattens matches into a row-major (match count, pattern size) matrix, e.g.
+// for `ndarray::Array2::from_shape_vec`.
+pub fn to_row_major(matches: &[Vec<usize>], pattern_size: usize) -> Vec<usize> {
+    let mut values = Vec::with_capacity(matches.len() * pattern_size);
+    for (idx, embedding) in matches.iter().enumerate() {
+        if embedding.len() != pattern_size {
+            panic!(
+                "Match {} has {} nodes, but the pattern has {}.",
+                idx,
+                embedding.len(),
+                pattern_size
+            );
+        }
+        values.extend_from_slice(embedding);
+    }
+    values
+}
+
+// The matches as a (match count, pattern size) array, e.g. to extract
+// features of the matched nodes.
+#[cfg(feature = "ndarray")]
+pub fn to_ndarray(matches: &[Vec<usize>], pattern_size: usize) -> ndarray::Array2<usize> {
+    ndarray::Array2::from_shape_vec(
+        (matches.len(), pattern_size),
+        to_row_major(matches, pattern_size),
+    )
+    .unwrap()
+}
+
+// Number of candidates whose adjacency lists are prefetched at once.
+const PREFETCH_BLOCK_SIZE: usize = 16;
+
 pub fn dual_iso<T: Eq + Hash>(graph: &Graph<T>, pattern: &Graph<T>) -> NestedVec {
+    dual_iso_with_config(graph, pattern, &MatchConfig::default())
+}
+
+pub fn dual_iso_with_config<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> NestedVec {
+    Resolved::new(graph, pattern, config).matches(graph, config)
+}
+
+// The query the entry points search for the pattern. Under
+// `EmptyCandidates::Relax` pattern nodes that the simulation leaves without
+// candidates lose their relationships, and with `minimize` the pattern is
+// reduced to its core.
+#[derive(PartialEq, Eq)]
+pub(crate) struct Resolved<T> {
+    pub(crate) query: Query<T>,
+    // for each pattern node, the query node whose match it takes, None if the
+    // query has the nodes of the pattern
+    retraction: Option<Vec<usize>>,
+}
+
+impl<T: Eq + Hash> Resolved<T> {
+    // Only `dual_iso_checked` reports empty candidates as an error, so
+    // `EmptyCandidates::Error` searches the pattern as given.
+    pub(crate) fn new(graph: &Graph<T>, pattern: &Graph<T>, config: &MatchConfig) -> Self {
+        let policy = match config.empty_candidates {
+            EmptyCandidates::Relax => EmptyCandidates::Relax,
+            _ => EmptyCandidates::NoMatches,
+        };
+        match relax(graph, pattern, config, policy) {
+            Ok(pattern) => Resolved::minimized(&pattern, config),
+            Err(_) => unreachable!("only the error policy reports empty candidates"),
+        }
+    }
+
+    fn checked(
+        graph: &Graph<T>,
+        pattern: &Graph<T>,
+        config: &MatchConfig,
+    ) -> Result<Self, EmptyCandidatesError> {
+        let pattern = relax(graph, pattern, config, config.empty_candidates)?;
+        Ok(Resolved::minimized(&pattern, config))
+    }
+
+    fn minimized(pattern: &Graph<T>, config: &MatchConfig) -> Self {
+        if config.minimize && !config.is_injective() {
+            let (core, retraction) = retract(pattern, config);
+            return Resolved {
+                query: Query::new(core.graph()),
+                retraction: Some(retraction),
+            };
+        }
+        Resolved {
+            query: Query::new(pattern),
+            retraction: None,
+        }
+    }
+
+    // maps a match of the query onto the nodes of the pattern
+    pub(crate) fn expand(&self, embedding: Vec<usize>) -> Vec<usize> {
+        match &self.retraction {
+            Some(retraction) => retraction.iter().map(|u_q| embedding[*u_q]).collect(),
+            None => embedding,
+        }
+    }
+
+    // Passes each match of the pattern to `emit`, see `visit_matches`.
+    pub(crate) fn visit(
+        &self,
+        graph: &Graph<T>,
+        config: &MatchConfig,
+        emit: &mut dyn FnMut(Vec<usize>) -> bool,
+    ) {
+        let candidates = init_candidates(graph, &self.query);
+        visit_matches(graph, &self.query, config, candidates, &mut |embedding| {
+            emit(self.expand(embedding))
+        });
+    }
+
+    // Like `visit`, with some pattern nodes fixed to the given graph nodes,
+    // which have to be candidates of their pattern nodes.
+    pub(crate) fn visit_anchored(
+        &self,
+        graph: &Graph<T>,
+        config: &MatchConfig,
+        anchors: &[(usize, usize)],
+        emit: &mut dyn FnMut(Vec<usize>) -> bool,
+    ) {
+        let mut candidates = init_candidates(graph, &self.query);
+        for (u_p, v_g) in anchors {
+            // pattern nodes folded onto the same node need the same anchor
+            let u_q = self
+                .retraction
+                .as_ref()
+                .map_or(*u_p, |retraction| retraction[*u_p]);
+            candidates[u_q] = anchor_candidates(&candidates[u_q], *v_g);
+        }
+        visit_matches(graph, &self.query, config, candidates, &mut |embedding| {
+            emit(self.expand(embedding))
+        });
+    }
+
+    pub(crate) fn matches(&self, graph: &Graph<T>, config: &MatchConfig) -> NestedVec {
+        let mut matches: NestedVec = vec![];
+        self.visit(graph, config, &mut |embedding| {
+            matches.push(embedding);
+            true
+        });
+        matches
+    }
+}
+
+// A pattern node without candidates after the simulation, see
+// `EmptyCandidates::Error`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmptyCandidatesError {
+    node_id: usize,
+    labeled_nodes: usize,
+}
+
+impl EmptyCandidatesError {
+    pub fn node_id(&self) -> usize {
+        self.node_id
+    }
+
+    // number of graph nodes with the label of the pattern node, 0 if the
+    // label alone rules out any match
+    pub fn labeled_nodes(&self) -> usize {
+        self.labeled_nodes
+    }
+}
+
+impl fmt::Display for EmptyCandidatesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.labeled_nodes == 0 {
+            write!(
+                f,
+                "No graph node has the label of pattern node {}.",
+                self.node_id
+            )
+        } else {
+            write!(
+                f,
+                "None of the {} graph nodes with the label of pattern node {} has its relationships.",
+                self.labeled_nodes, self.node_id
+            )
+        }
+    }
+}
+
+impl Error for EmptyCandidatesError {}
+
+// Like `dual_iso_with_config`, but applies the `EmptyCandidates` policy of the
+// config to the pattern node whose candidates the simulation empties first.
+// Returns an error for it unless the policy is `NoMatches` or `Relax`. A
+// relaxed pattern drops all relationships of the pattern node, so its matches
+// only approximate the pattern as given.
+pub fn dual_iso_checked<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> Result<NestedVec, EmptyCandidatesError> {
+    Ok(Resolved::checked(graph, pattern, config)?.matches(graph, config))
+}
+
+// Applies the policy to the pattern node whose candidates the simulation
+// empties first. Returns the pattern as given under `NoMatches`, or once no
+// pattern node is left without candidates or the node cannot be relaxed.
+fn relax<'a, T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &'a Graph<T>,
+    config: &MatchConfig,
+    policy: EmptyCandidates,
+) -> Result<Cow<'a, Graph<T>>, EmptyCandidatesError> {
+    let mut pattern = Cow::Borrowed(pattern);
+    if policy == EmptyCandidates::NoMatches {
+        return Ok(pattern);
+    }
+    loop {
+        let query = Query::new(&pattern);
+        let Some(error) = empty_candidates(graph, &query, config) else {
+            return Ok(pattern);
+        };
+        let node_id = error.node_id;
+        let is_relaxed = policy == EmptyCandidates::Relax
+            && error.labeled_nodes > 0
+            && (pattern.degree(node_id) > 0 || pattern.in_degree(node_id) > 0);
+        match policy {
+            EmptyCandidates::Error => return Err(error),
+            // nothing matches the pattern node, as given or relaxed
+            _ if !is_relaxed => return Ok(pattern),
+            _ => {}
+        }
+        let relaxed = pattern.to_mut();
+        let neighbors = relaxed.neighbors(node_id).to_vec();
+        let in_neighbors = relaxed.in_neighbors(node_id).to_vec();
+        for end_node in neighbors {
+            while relaxed.remove_relationship(node_id, end_node) {}
+        }
+        for start_node in in_neighbors {
+            while relaxed.remove_relationship(start_node, node_id) {}
+        }
+    }
+}
+
+// The first pattern node without candidates, either by label or after the
+// simulation to its fixpoint.
+fn empty_candidates<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+) -> Option<EmptyCandidatesError> {
+    let mut candidates = init_candidates(graph, query);
+    let labeled_nodes = candidates.iter().map(|c| c.len()).collect::<Vec<_>>();
+    let unsupported = candidates.iter().position(|c| c.is_empty()).or_else(|| {
+        let fixpoint = simulation_rounds(graph, query, config, &mut candidates, None, &mut |_| {});
+        (fixpoint == Fixpoint::Empty)
+            .then(|| candidates.iter().position(|c| c.is_empty()))
+            .flatten()
+    })?;
+    Some(EmptyCandidatesError {
+        node_id: query.original_id(unsupported),
+        labeled_nodes: labeled_nodes[unsupported],
+    })
+}
+
+// A panic raised while matching, e.g. due to an invalid node id.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchPanic {
+    pub(crate) message: String,
+}
+
+impl MatchPanic {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for MatchPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Matching panicked: {}", self.message)
+    }
+}
+
+impl Error for MatchPanic {}
+
+// Same as `dual_iso_with_config`, but returns panics as errors instead of
+// unwinding into the caller, e.g. to fuzz inputs from untrusted sources. The
+// panic message is still printed by the panic hook.
+pub fn try_dual_iso_with_config<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> Result<NestedVec, MatchPanic> {
+    // the inputs are only read, so they cannot be left in a broken state
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        dual_iso_with_config(graph, pattern, config)
+    }))
+    .map_err(|payload| MatchPanic {
+        message: panic_message(payload),
+    })
+}
+
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+            || "unknown panic".to_string(),
+            |message| message.to_string(),
+        ),
+    }
+}
+
+// Matches the pattern starting from the given candidates per pattern node
+// instead of the label buckets.
+pub(crate) fn dual_iso_with_candidates<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    candidates: Vec<Candidates>,
+) -> NestedVec {
+    let mut matches: NestedVec = vec![];
+    visit_matches(graph, query, config, candidates, &mut |embedding| {
+        matches.push(embedding);
+        true
+    });
+    matches
+}
+
+// Counts the matches as `dual_iso_with_config` would return them. Matches of
+// star patterns are counted without enumerating them.
+pub fn count_matches<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> usize {
+    // the pattern has one match per match of its core
+    let query = Resolved::new(graph, pattern, config).query;
+    if config.anchor_limit.is_none() && query.weakly_connected_components().len() == 1 {
+        let accounted = config.accounted();
+        if let Some(count) = count_shape(graph, &query, &accounted, &init_candidates(graph, &query))
+        {
+            return config.limit.map_or(count, |limit| count.min(limit));
+        }
+    }
+    let mut count = 0;
+    visit_matches(
+        graph,
+        &query,
+        config,
+        init_candidates(graph, &query),
+        &mut |_| {
+            count += 1;
+            true
+        },
+    );
+    count
+}
+
+// Returns the matches together with the resources the search used and the
+// quota that stopped it, if any.
+pub fn dual_iso_with_stats<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> (NestedVec, MatchStats) {
+    let accounting = Accounting::new();
+    let mut config = config.clone();
+    config.accounting = Some(Arc::clone(&accounting));
+    let matches = dual_iso_with_config(graph, pattern, &config);
+    (matches, accounting.stats())
+}
+
+// Returns the matches found within the time budget, e.g. to show partial
+// results, and the stats whose `explored` estimates how much of the search
+// they cover. Fast paths for special pattern shapes are disabled, as they do
+// not track their progress.
+pub fn dual_iso_anytime<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    budget: Duration,
+) -> (NestedVec, MatchStats) {
+    let mut config = config.clone();
+    config.max_duration(budget).fast_paths(false);
+    dual_iso_with_stats(graph, pattern, &config)
+}
+
+// Sends the matches into the channel as soon as they are found. Sending blocks
+// while the channel is full, which pauses the search until the receiver caught
+// up, so at most the channel capacity of matches is buffered. The search stops
+// once the receiver is dropped. Returns the number of sent matches.
+//
+// For disconnected patterns, the matches of each component are collected
+// before their combinations are sent.
+pub fn dual_iso_to_channel<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    sender: &SyncSender<Vec<usize>>,
+) -> usize {
+    let mut sent = 0;
+    Resolved::new(graph, pattern, config).visit(graph, config, &mut |embedding| {
+        if sender.send(embedding).is_err() {
+            return false;
+        }
+        sent += 1;
+        true
+    });
+    sent
+}
+
+// For each graph node, the indices of the matches it participates in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParticipationIndex {
+    matches: HashMap<usize, Vec<usize>>,
+}
+
+impl ParticipationIndex {
+    // ascending match indices, empty if the node is in no match
+    pub fn matches(&self, node_id: usize) -> &[usize] {
+        self.matches
+            .get(&node_id)
+            .map_or(&[], |matches| matches.as_slice())
+    }
+
+    // number of graph nodes in at least one match
+    pub fn node_count(&self) -> usize {
+        self.matches.len()
+    }
+}
+
+// Returns the matches together with an index from graph nodes to the matches
+// they participate in, built while the matches are enumerated.
+pub fn dual_iso_with_participation<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> (NestedVec, ParticipationIndex) {
     let mut matches: NestedVec = vec![];
-    let mut initial_candidates = init_candidates(graph, pattern);
+    let mut index = ParticipationIndex::default();
+    Resolved::new(graph, pattern, config).visit(graph, config, &mut |embedding| {
+        for v_g in &embedding {
+            let participations = index.matches.entry(*v_g).or_default();
+            // under homomorphism semantics a node may occur repeatedly
+            if participations.last() != Some(&matches.len()) {
+                participations.push(matches.len());
+            }
+        }
+        matches.push(embedding);
+        true
+    });
+    (matches, index)
+}
+
+// Passes each match to `emit` until it returns false or the limit is reached.
+pub(crate) fn visit_matches<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    candidates: Vec<Candidates>,
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
+) {
+    if config.is_limit_reached(0) {
+        return;
+    }
+    // an injective match needs a distinct graph node per pattern node
+    if config.is_injective() && query.node_count() > graph.node_count() {
+        return;
+    }
+    let config = config.accounted();
+    let config = &*config;
+    let mut count = 0;
+    let mut emit = |embedding| {
+        count += 1;
+        emit(embedding) && !config.is_limit_reached(count)
+    };
+
+    if let Some((u_p, limit)) = config.anchor_limit {
+        if u_p >= query.node_count() {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                u_p,
+                query.node_count()
+            );
+        }
+        // search each anchor on its own with the limit per anchor, the
+        // overall limit applies across anchors
+        let mut anchor_config = config.clone();
+        anchor_config.anchor_limit = None;
+        anchor_config.limit = Some(limit);
+        for v_g in candidates[u_p].iter() {
+            let mut anchored = candidates.clone();
+            anchored[u_p] = Candidates::single(*v_g);
+            let mut is_stopped = false;
+            visit_matches(graph, query, &anchor_config, anchored, &mut |embedding| {
+                is_stopped = !emit(embedding);
+                !is_stopped
+            });
+            if is_stopped {
+                return;
+            }
+        }
+        return;
+    }
 
-    simple_simulation(graph, pattern, &mut initial_candidates);
-    search(graph, pattern, &mut matches, &initial_candidates, 0);
+    let components = query.weakly_connected_components();
+    if components.len() <= 1 {
+        visit_candidates(graph, query, config, candidates, &mut emit);
+        return;
+    }
+
+    // match each component on its own and combine the results afterwards,
+    // the limit only applies to the combined matches
+    let component_matches = components
+        .iter()
+        .map(|component| {
+            let mut matches: NestedVec = vec![];
+            visit_candidates(
+                graph,
+                &query.restrict(component),
+                config,
+                component
+                    .iter()
+                    .map(|u_p| candidates[*u_p].clone())
+                    .collect(),
+                &mut |embedding| {
+                    matches.push(embedding);
+                    true
+                },
+            );
+            matches
+        })
+        .collect::<Vec<_>>();
+
+    // order constraints between components are only checked on the combined matches
+    let mut emit = |embedding: Vec<usize>| {
+        if query
+            .order()
+            .iter()
+            .all(|(u_p, v_p)| embedding[*u_p] < embedding[*v_p])
+        {
+            emit(embedding)
+        } else {
+            true
+        }
+    };
+    let mut embedding = vec![0; query.node_count()];
+    cross_product(
+        config,
+        &components,
+        &component_matches,
+        &mut emit,
+        &mut embedding,
+        &mut HashSet::new(),
+        0,
+    );
+}
 
+// Combines the matches of the pattern components into matches of the whole
+// pattern, where no graph node is used by more than one component.
+pub(crate) fn combine_components(
+    config: &MatchConfig,
+    node_count: usize,
+    components: &[Vec<usize>],
+    component_matches: &[NestedVec],
+) -> NestedVec {
+    let mut matches: NestedVec = vec![];
+    if config.is_limit_reached(0) {
+        return matches;
+    }
+    let mut embedding = vec![0; node_count];
+    cross_product(
+        config,
+        components,
+        component_matches,
+        &mut |embedding| {
+            matches.push(embedding);
+            !config.is_limit_reached(matches.len())
+        },
+        &mut embedding,
+        &mut HashSet::new(),
+        0,
+    );
     matches
 }
 
-fn search<T: Eq + Hash>(
+// Matches the pattern with each `(u_P, count)` pattern node repeated `count`
+// times, e.g. to match three distinct neighbors with the same label. The
+// copies of u_P are appended to the pattern nodes. They match ascending graph
+// nodes, so each set of graph nodes is matched once instead of once per
+// permutation.
+pub fn dual_iso_with_repetitions<T: Eq + Hash>(
     graph: &Graph<T>,
     pattern: &Graph<T>,
-    matches: &mut NestedVec,
-    candidates: &[Cow<Vec<usize>>],
-    depth: usize,
+    config: &MatchConfig,
+    repetitions: &[(usize, usize)],
+) -> NestedVec {
+    let mut query = Query::new(pattern);
+    for (u_p, count) in repetitions {
+        if *u_p >= pattern.node_count() {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                u_p,
+                pattern.node_count()
+            );
+        }
+        if *count == 0 {
+            panic!("Node {} must be repeated at least once.", u_p);
+        }
+        query.repeat(*u_p, *count);
+    }
+    let candidates = init_candidates(graph, &query);
+    dual_iso_with_candidates(graph, &query, config, candidates)
+}
+
+// Matches the pattern with some pattern nodes fixed to the given graph nodes.
+// An anchor has to be a candidate of its pattern node, i.e. have its label,
+// unless the pattern node is one of the `wildcards`, which match graph nodes
+// regardless of their label.
+pub(crate) fn dual_iso_anchored<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    anchors: &[(usize, usize)],
+    wildcards: &[usize],
+) -> NestedVec {
+    let query = Query::new(pattern);
+    let mut candidates = init_candidates(graph, &query);
+    for u_p in wildcards {
+        candidates[*u_p] = Candidates::Owned((0..graph.node_count()).collect());
+    }
+    for (u_p, v_g) in anchors {
+        candidates[*u_p] = anchor_candidates(&candidates[*u_p], *v_g);
+    }
+    dual_iso_with_candidates(graph, &query, config, candidates)
+}
+
+// The anchor if it is one of the initial candidates, which are sorted.
+fn anchor_candidates(candidates: &[usize], v_g: usize) -> Candidates<'static> {
+    if candidates.binary_search(&v_g).is_ok() {
+        Candidates::single(v_g)
+    } else {
+        Candidates::from(vec![])
+    }
+}
+
+pub fn dual_iso_for_anchors<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    node_id: usize,
+    anchors: &[usize],
+) -> Vec<NestedVec> {
+    dual_iso_for_anchors_with_config(graph, pattern, &MatchConfig::default(), node_id, anchors)
+}
+
+// Matches the pattern with the pattern node fixed to each of the anchors and
+// returns the matches per anchor. The simulation runs once for all anchors
+// together, so anchors without matches are mostly ruled out before the search
+// and the searches per anchor start from the shrunk candidates.
+pub fn dual_iso_for_anchors_with_config<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    node_id: usize,
+    anchors: &[usize],
+) -> Vec<NestedVec> {
+    let query = Query::new(pattern);
+    if node_id >= query.node_count() {
+        panic!(
+            "Node id {} must be within range [0..{}).",
+            node_id,
+            query.node_count()
+        );
+    }
+    let mut matches = vec![vec![]; anchors.len()];
+    let mut candidates = init_candidates(graph, &query);
+    // the initial candidates are sorted
+    candidates[node_id] = Candidates::Owned(
+        anchors
+            .iter()
+            .copied()
+            .filter(|v_g| candidates[node_id].binary_search(v_g).is_ok())
+            .collect(),
+    );
+    let fixpoint = simulation_rounds(
+        graph,
+        &query,
+        config,
+        &mut candidates,
+        config.max_simulation_rounds,
+        &mut |_| {},
+    );
+    if fixpoint == Fixpoint::Empty {
+        return matches;
+    }
+
+    let remaining = candidates[node_id].iter().copied().collect::<HashSet<_>>();
+    for (v_g, anchor_matches) in anchors.iter().zip(matches.iter_mut()) {
+        if !remaining.contains(v_g) {
+            continue;
+        }
+        let mut anchored = candidates.clone();
+        anchored[node_id] = Candidates::single(*v_g);
+        visit_matches(graph, &query, config, anchored, &mut |embedding| {
+            anchor_matches.push(embedding);
+            true
+        });
+    }
+    matches
+}
+
+fn visit_candidates<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    mut initial_candidates: Vec<Candidates>,
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
 ) {
-    if depth == pattern.node_count() {
-        // found a match
-        matches.push(candidates.iter().map(|c| c[0]).collect::<Vec<_>>());
+    if visit_shape(graph, query, config, &initial_candidates, emit) {
+        return;
+    }
+    // the simulation in each branch runs to the fixpoint, so capping the
+    // rounds before the search only trades pruning for time
+    let fixpoint = simulation_rounds(
+        graph,
+        query,
+        config,
+        &mut initial_candidates,
+        config.max_simulation_rounds,
+        &mut |_| {},
+    );
+    config.account_candidates(initial_candidates.iter().map(|c| c.len()).sum());
+    if fixpoint == Fixpoint::Empty
+        || (config.all_different
+            && config.is_injective()
+            && !has_injective_assignment(&initial_candidates))
+    {
         return;
     }
-    for v_g in &*candidates[depth] {
+
+    let order = match config.hub_degree {
+        Some(hub_degree) => hub_order(graph, &initial_candidates, hub_degree),
+        None => (0..query.node_count()).collect(),
+    };
+    if order.iter().enumerate().all(|(idx, u_p)| idx == *u_p) {
+        search(graph, query, config, emit, &initial_candidates, 0, None);
+        return;
+    }
+    // search the pattern with its nodes in the given order and map the
+    // matches back to the original order
+    let ordered_query = query.restrict(&order);
+    let ordered_candidates = order
+        .iter()
+        .map(|u_p| initial_candidates[*u_p].clone())
+        .collect::<Vec<_>>();
+    search(
+        graph,
+        &ordered_query,
+        config,
+        &mut |embedding| {
+            let mut original = vec![0; embedding.len()];
+            for (v_g, u_p) in embedding.into_iter().zip(&order) {
+                original[*u_p] = v_g;
+            }
+            emit(original)
+        },
+        &ordered_candidates,
+        0,
+        None,
+    );
+}
+
+// Orders the pattern nodes whose candidates include a hub, i.e. a node with at
+// least `hub_degree` relationships, after all other pattern nodes. By the time
+// they are matched, their candidate sets are as small as the assignments of
+// their neighbors make them.
+pub(crate) fn hub_order<T: Eq + Hash>(
+    graph: &Graph<T>,
+    candidates: &[Candidates],
+    hub_degree: usize,
+) -> Vec<usize> {
+    let (mut order, hubs): (Vec<_>, Vec<_>) = (0..candidates.len()).partition(|u_p| {
+        !candidates[*u_p]
+            .iter()
+            .any(|v_g| graph.degree(*v_g) >= hub_degree)
+    });
+    order.extend(hubs);
+    order
+}
+
+// Returns false once `emit` asked to stop.
+fn cross_product(
+    config: &MatchConfig,
+    components: &[Vec<usize>],
+    component_matches: &[NestedVec],
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
+    embedding: &mut Vec<usize>,
+    used: &mut HashSet<usize>,
+    depth: usize,
+) -> bool {
+    if depth == components.len() {
+        return emit(embedding.clone());
+    }
+    for component_match in &component_matches[depth] {
+        if !config.enter_branch() {
+            return false;
+        }
+        // graph nodes must not be shared between components
+        if config.is_injective() && component_match.iter().any(|v_g| used.contains(v_g)) {
+            continue;
+        }
+        for (u_p, v_g) in components[depth].iter().zip(component_match) {
+            embedding[*u_p] = *v_g;
+            used.insert(*v_g);
+        }
+        let is_continued = cross_product(
+            config,
+            components,
+            component_matches,
+            emit,
+            embedding,
+            used,
+            depth + 1,
+        );
+        for v_g in component_match {
+            used.remove(v_g);
+        }
+        if !is_continued {
+            return false;
+        }
+    }
+    true
+}
+
+// If `after` is given, only matches that are lexicographically greater than it
+// are searched, which requires candidate sets sorted by node id. Returns false
+// once `emit` asked to stop.
+pub(crate) fn search<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
+    candidates: &[Candidates],
+    depth: usize,
+    after: Option<&[usize]>,
+) -> bool {
+    if depth == query.node_count() {
+        // found a match, unless it is the one to continue after
+        if after.is_none() {
+            return emit(candidates.iter().map(|c| c[0]).collect::<Vec<_>>());
+        }
+        return true;
+    }
+    let scored;
+    let ordered = match &config.scorer {
+        Some(scorer) if after.is_none() => {
+            scored = scorer.order(query, candidates, depth);
+            &scored
+        }
+        _ => &*candidates[depth],
+    };
+    for (idx, v_g) in ordered.iter().enumerate() {
+        // skip branches before the one to continue after
+        let after = match after {
+            Some(after) if *v_g < after[depth] => continue,
+            Some(after) if *v_g == after[depth] => Some(after),
+            _ => None,
+        };
         // check if v_G has matched a previous candidate
-        if !candidates[..depth].iter().any(|x| x[0] == *v_g) {
-            let mut new_candidates = candidates.to_owned();
-            new_candidates[depth] = Cow::Owned(vec![*v_g]);
-            if simple_simulation(graph, pattern, &mut new_candidates) {
-                search(graph, pattern, matches, &new_candidates, depth + 1);
+        if (!config.is_injective() || !candidates[..depth].iter().any(|x| x[0] == *v_g))
+            && is_ordered(query, depth, *v_g, |u_p| candidates[u_p][0])
+        {
+            if !config.enter_branch() {
+                config.stop_branch(depth, idx, ordered.len());
+                return false;
+            }
+            // the branch borrows the candidate sets of its parent and only
+            // owns the sets that the simulation shrinks, the assigned
+            // candidate is borrowed from the parent's set as well
+            let mut new_candidates = candidates
+                .iter()
+                .map(|c| Candidates::Borrowed(c))
+                .collect::<Vec<_>>();
+            new_candidates[depth] = Candidates::Borrowed(std::slice::from_ref(v_g));
+            if simple_simulation(graph, query, config, &mut new_candidates)
+                && (!config.all_different
+                    || !config.is_injective()
+                    || has_injective_assignment(&new_candidates))
+                && !search(
+                    graph,
+                    query,
+                    config,
+                    emit,
+                    &new_candidates,
+                    depth + 1,
+                    after,
+                )
+            {
+                config.stop_branch(depth, idx, ordered.len());
+                return false;
             }
         }
     }
+    true
 }
 
-fn init_candidates<'graph, T: Eq + Hash>(
-    graph: &'graph Graph<T>,
+// Checks the order of the candidate for the pattern node at `depth` against
+// the graph nodes `assigned` to the pattern nodes before it.
+fn is_ordered<T: Eq + Hash>(
+    query: &Query<T>,
+    depth: usize,
+    v_g: usize,
+    assigned: impl Fn(usize) -> usize,
+) -> bool {
+    query.order().iter().all(|(u_p, v_p)| {
+        if *u_p == depth && *v_p < depth {
+            v_g < assigned(*v_p)
+        } else if *v_p == depth && *u_p < depth {
+            assigned(*u_p) < v_g
+        } else {
+            true
+        }
+    })
+}
+
+// Returns the first match found, e.g. to test whether the pattern occurs at
+// all. Instead of copying candidate sets per branch, a single set of
+// candidates is shrunk in place and restored on backtracking, so apart from
+// the initial candidates, the result and the scoring of candidates nothing is
+// allocated.
+pub fn find_first_match<T: Eq + Hash>(
+    graph: &Graph<T>,
     pattern: &Graph<T>,
-) -> Vec<Cow<'graph, Vec<usize>>> {
-    let mut candidates = Vec::with_capacity(pattern.node_count());
-    for pattern_node_id in 0..pattern.node_count() {
-        candidates.push(Cow::Borrowed(
-            graph.nodes_by_label(pattern.node_label(pattern_node_id)),
-        ))
+    config: &MatchConfig,
+) -> Option<Vec<usize>> {
+    let config = &*config.accounted();
+    let resolved = Resolved::new(graph, pattern, config);
+    let query = &resolved.query;
+    let mut workspace = Workspace::new(graph, query);
+    if simulate_in_place(graph, query, config, &mut workspace)
+        && search_first(graph, query, config, &mut workspace, 0)
+    {
+        Some(resolved.expand(workspace.sets.iter().map(|set| set[0]).collect()))
+    } else {
+        None
     }
-    candidates
 }
 
-fn simple_simulation<T: Eq + Hash>(
+// Candidate sets where only the first `lens[u_p]` entries of `sets[u_p]` are
+// live. Removed candidates are swapped behind the live ones, so restoring a
+// length from the trail restores the set.
+struct Workspace {
+    sets: Vec<Vec<usize>>,
+    lens: Vec<usize>,
+    // pattern node and its length before each change
+    trail: Vec<(usize, usize)>,
+}
+
+impl Workspace {
+    fn new<T: Eq + Hash>(graph: &Graph<T>, query: &Query<T>) -> Self {
+        let sets = init_candidates(graph, query)
+            .into_iter()
+            .map(Candidates::into_owned)
+            .collect::<Vec<_>>();
+        Workspace {
+            lens: sets.iter().map(Vec::len).collect(),
+            sets,
+            trail: vec![],
+        }
+    }
+
+    fn live(&self, u_p: usize) -> &[usize] {
+        &self.sets[u_p][..self.lens[u_p]]
+    }
+
+    fn remove(&mut self, u_p: usize, idx: usize) {
+        self.trail.push((u_p, self.lens[u_p]));
+        self.lens[u_p] -= 1;
+        self.sets[u_p].swap(idx, self.lens[u_p]);
+    }
+
+    // reduces the set to the candidate at the given index
+    fn assign(&mut self, u_p: usize, idx: usize) {
+        self.trail.push((u_p, self.lens[u_p]));
+        self.lens[u_p] = 1;
+        self.sets[u_p].swap(0, idx);
+    }
+
+    // reverts all changes made since the trail had the given length
+    fn undo(&mut self, mark: usize) {
+        while self.trail.len() > mark {
+            let (u_p, len) = self.trail.pop().unwrap();
+            self.lens[u_p] = len;
+        }
+    }
+}
+
+fn search_first<T: Eq + Hash>(
     graph: &Graph<T>,
-    pattern: &Graph<T>,
-    candidates: &mut Vec<Cow<Vec<usize>>>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    workspace: &mut Workspace,
+    depth: usize,
+) -> bool {
+    if depth == query.node_count() {
+        return true;
+    }
+    let scored = config.scorer.as_ref().map(|scorer| {
+        let candidates = (0..query.node_count())
+            .map(|u_p| Candidates::Borrowed(workspace.live(u_p)))
+            .collect::<Vec<_>>();
+        scorer.order(query, &candidates, depth)
+    });
+    // assigning a candidate swaps it to the front, which keeps all candidates
+    // after the current index in place
+    for step in 0..workspace.lens[depth] {
+        let (idx, v_g) = match &scored {
+            Some(scored) => {
+                let v_g = scored[step];
+                (
+                    workspace
+                        .live(depth)
+                        .iter()
+                        .position(|x| *x == v_g)
+                        .unwrap(),
+                    v_g,
+                )
+            }
+            None => (step, workspace.sets[depth][step]),
+        };
+        // check if v_G has matched a previous candidate
+        if config.is_injective() && (0..depth).any(|u_p| workspace.sets[u_p][0] == v_g) {
+            continue;
+        }
+        if !is_ordered(query, depth, v_g, |u_p| workspace.sets[u_p][0]) {
+            continue;
+        }
+        if !config.enter_branch() {
+            return false;
+        }
+        let mark = workspace.trail.len();
+        workspace.assign(depth, idx);
+        if simulate_in_place(graph, query, config, workspace)
+            && search_first(graph, query, config, workspace, depth + 1)
+        {
+            return true;
+        }
+        workspace.undo(mark);
+    }
+    false
+}
+
+// Same as `simple_simulation`, but on the workspace. Its live candidates are
+// not sorted, so parents are found by scanning them.
+fn simulate_in_place<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    workspace: &mut Workspace,
 ) -> bool {
     let mut is_updated = true;
 
     while is_updated {
         is_updated = false;
-        // for each node u_P in the pattern
-        for u_p in 0..pattern.node_count() {
-            // for each neighbor of u_P (v_P)
-            for v_p in pattern.neighbors(u_p) {
-                // updated candidate set for u_P
-                let mut u_g_new: Vec<usize> = vec![];
-                // for each candidate of u_P (u_G)
-                for u_g in &*candidates[u_p] {
-                    let mut found_relationship = false;
-                    // for each candidate of v_P (v_G)
-                    // TODO: efficient intersect between graph.neighbors(u_g) and candidates(v_p)
-                    for v_g in &*candidates[*v_p] {
-                        if graph.neighbors(*u_g).binary_search(v_g).is_ok() {
-                            found_relationship = true;
-                            break;
-                        }
+        for u_p in 0..query.node_count() {
+            for constraint in query.constraints(u_p) {
+                let v_p = constraint.target;
+                if v_p == u_p && config.self_loops == SelfLoops::Ignore {
+                    continue;
+                }
+                let mut idx = 0;
+                while idx < workspace.lens[u_p] {
+                    let u_g = workspace.sets[u_p][idx];
+                    if is_supported(graph, config, u_p, constraint, u_g, workspace.live(v_p)) {
+                        idx += 1;
+                    } else {
+                        // the last live candidate moves to idx and is checked next
+                        workspace.remove(u_p, idx);
+                        is_updated = true;
                     }
-                    if found_relationship {
-                        u_g_new.push(*u_g);
+                }
+                if workspace.lens[u_p] == 0 {
+                    return false;
+                }
+                if v_p == u_p {
+                    continue;
+                }
+                let mut idx = 0;
+                while idx < workspace.lens[v_p] {
+                    let v_g = workspace.sets[v_p][idx];
+                    if has_parent(graph, config, constraint, v_g, workspace.live(u_p), false) {
+                        idx += 1;
                     } else {
+                        workspace.remove(v_p, idx);
                         is_updated = true;
                     }
                 }
-                if u_g_new.is_empty() {
+                if workspace.lens[v_p] == 0 {
                     return false;
                 }
-                candidates[u_p] = Cow::Owned(u_g_new);
             }
         }
     }
     true
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::GraphBuilder;
+pub(crate) fn init_candidates<'graph, T: Eq + Hash>(
+    graph: &'graph Graph<T>,
+    query: &Query<T>,
+) -> Vec<Candidates<'graph>> {
+    let mut candidates = Vec::with_capacity(query.node_count());
+    for pattern_node_id in 0..query.node_count() {
+        candidates.push(Candidates::Borrowed(
+            graph.nodes_by_label(query.label(pattern_node_id)),
+        ))
+    }
+    candidates
+}
 
-    use super::*;
+pub(crate) fn simple_simulation<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    candidates: &mut [Candidates],
+) -> bool {
+    simulation_rounds(graph, query, config, candidates, None, &mut |_| {}) != Fixpoint::Empty
+}
 
-    #[test]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Fixpoint {
+    Reached,
+    // stopped after the maximum number of rounds
+    Capped,
+    // a candidate set became empty, so there is no match
+    Empty,
+}
+
+// Removes unsupported candidates in rounds over all constraints until no
+// candidate is removed or `max_rounds` rounds ran. Reports the number of
+// removed candidates per round.
+pub(crate) fn simulation_rounds<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    candidates: &mut [Candidates],
+    max_rounds: Option<usize>,
+    on_round: &mut dyn FnMut(usize),
+) -> Fixpoint {
+    let mut is_updated = true;
+    let mut rounds = 0;
+
+    while is_updated {
+        if max_rounds.is_some_and(|max_rounds| rounds >= max_rounds) {
+            return Fixpoint::Capped;
+        }
+        rounds += 1;
+        is_updated = false;
+        let mut removed = 0;
+        // for each node u_P in the pattern
+        for u_p in 0..query.node_count() {
+            // for each relationship between u_P and a neighbor v_P
+            for constraint in query.constraints(u_p) {
+                let v_p = constraint.target;
+                let is_self_loop = v_p == u_p;
+                if is_self_loop && config.self_loops == SelfLoops::Ignore {
+                    continue;
+                }
+                // updated candidate set for u_P, only allocated once the
+                // first candidate is removed
+                let mut u_g_new: Option<SmallVec<[usize; INLINE_CANDIDATES]>> = None;
+                let mut offset = 0;
+                // for each candidate of u_P (u_G), in blocks whose adjacency
+                // lists are fetched while the previous block is checked
+                let mut blocks = candidates[u_p].chunks(PREFETCH_BLOCK_SIZE).peekable();
+                while let Some(block) = blocks.next() {
+                    if let Some(next_block) = blocks.peek() {
+                        for u_g in *next_block {
+                            graph.prefetch_neighbors(*u_g);
+                        }
+                    }
+                    for (idx, u_g) in block.iter().enumerate() {
+                        let is_kept =
+                            is_supported(graph, config, u_p, constraint, *u_g, &candidates[v_p]);
+                        match &mut u_g_new {
+                            Some(u_g_new) if is_kept => u_g_new.push(*u_g),
+                            None if !is_kept => {
+                                u_g_new =
+                                    Some(SmallVec::from_slice(&candidates[u_p][..offset + idx]))
+                            }
+                            _ => {}
+                        }
+                    }
+                    offset += block.len();
+                }
+                // unchanged sets stay shared with the parent branch
+                if let Some(u_g_new) = u_g_new {
+                    config.account_allocation(u_g_new.len());
+                    is_updated = true;
+                    removed += candidates[u_p].len() - u_g_new.len();
+                    candidates[u_p] = Candidates::Owned(u_g_new);
+                }
+                if candidates[u_p].is_empty() {
+                    on_round(removed);
+                    return Fixpoint::Empty;
+                }
+                if is_self_loop {
+                    continue;
+                }
+                // dual simulation: each candidate of v_P (v_G) needs a parent
+                // among the candidates of u_P as well
+                let is_sorted = candidates[u_p].is_sorted();
+                let mut v_g_new: Option<SmallVec<[usize; INLINE_CANDIDATES]>> = None;
+                for (idx, v_g) in candidates[v_p].iter().enumerate() {
+                    let is_kept =
+                        has_parent(graph, config, constraint, *v_g, &candidates[u_p], is_sorted);
+                    match &mut v_g_new {
+                        Some(v_g_new) if is_kept => v_g_new.push(*v_g),
+                        None if !is_kept => {
+                            v_g_new = Some(SmallVec::from_slice(&candidates[v_p][..idx]))
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(v_g_new) = v_g_new {
+                    config.account_allocation(v_g_new.len());
+                    is_updated = true;
+                    removed += candidates[v_p].len() - v_g_new.len();
+                    candidates[v_p] = Candidates::Owned(v_g_new);
+                }
+                if candidates[v_p].is_empty() {
+                    on_round(removed);
+                    return Fixpoint::Empty;
+                }
+            }
+        }
+        on_round(removed);
+    }
+    Fixpoint::Reached
+}
+
+// The outcome of the simulation that runs before the search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulationReport {
+    is_converged: bool,
+    removed: Vec<usize>,
+    candidates: NestedVec,
+}
+
+impl SimulationReport {
+    // False if the simulation stopped at the maximum number of rounds.
+    pub fn is_converged(&self) -> bool {
+        self.is_converged
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.removed.len()
+    }
+
+    // number of candidates removed per round
+    pub fn removed(&self) -> &[usize] {
+        &self.removed
+    }
+
+    // remaining candidates per pattern node, a superset of the candidates of
+    // any match, which are all empty if there is no match
+    pub fn candidates(&self) -> &NestedVec {
+        &self.candidates
+    }
+
+    // The graph nodes that may match the pattern node, e.g. to show which
+    // nodes could play its role without enumerating the matches.
+    pub fn node_candidates(&self, node_id: usize) -> &[usize] {
+        if node_id >= self.candidates.len() {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                node_id,
+                self.candidates.len()
+            );
+        }
+        &self.candidates[node_id]
+    }
+}
+
+// Runs the simulation that precedes the search, bounded by the maximum
+// number of simulation rounds of the config, and reports how it went.
+pub fn simulate<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> SimulationReport {
+    let query = Query::new(pattern);
+    let mut candidates = init_candidates(graph, &query);
+    let mut removed = vec![];
+    let fixpoint = simulation_rounds(
+        graph,
+        &query,
+        config,
+        &mut candidates,
+        config.max_simulation_rounds,
+        &mut |count| removed.push(count),
+    );
+    SimulationReport {
+        is_converged: fixpoint != Fixpoint::Capped,
+        removed,
+        candidates: if fixpoint == Fixpoint::Empty {
+            vec![vec![]; query.node_count()]
+        } else {
+            candidates.into_iter().map(Candidates::into_owned).collect()
+        },
+    }
+}
+
+// Whether every pattern node can get its own candidate, i.e. the bipartite
+// graph between pattern nodes and their candidates has a matching covering
+// all pattern nodes. Found with augmenting paths (Kuhn's algorithm).
+pub(crate) fn has_injective_assignment(candidates: &[Candidates]) -> bool {
+    fn augment(
+        candidates: &[Candidates],
+        u_p: usize,
+        assigned: &mut HashMap<usize, usize>,
+        visited: &mut HashSet<usize>,
+    ) -> bool {
+        for v_g in &*candidates[u_p] {
+            if visited.insert(*v_g) {
+                let is_free = match assigned.get(v_g) {
+                    Some(other) => augment(candidates, *other, assigned, visited),
+                    None => true,
+                };
+                if is_free {
+                    assigned.insert(*v_g, u_p);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    let mut assigned = HashMap::new();
+    let mut visited = HashSet::new();
+    (0..candidates.len()).all(|u_p| {
+        visited.clear();
+        augment(candidates, u_p, &mut assigned, &mut visited)
+    })
+}
+
+// Whether u_G has the relationships to candidates of v_P required by the
+// constraint of u_P.
+pub(crate) fn is_supported<T: Eq + Hash>(
+    graph: &Graph<T>,
+    config: &MatchConfig,
+    u_p: usize,
+    constraint: &Constraint,
+    u_g: usize,
+    v_p_candidates: &[usize],
+) -> bool {
+    if constraint.target == u_p {
+        // a pattern self-loop can only be matched by a self-loop
+        constraint.relationships(graph, u_g, u_g) >= constraint.multiplicity
+    } else {
+        // for each candidate of v_P (v_G), where v_G != u_G unless nodes may repeat
+        // TODO: efficient intersect between graph.neighbors(u_g) and candidates(v_p)
+        let mut scanned = 0;
+        let is_supported = v_p_candidates.iter().inspect(|_| scanned += 1).any(|v_g| {
+            (*v_g != u_g || !config.is_injective()) && constraint.is_satisfied_by(graph, u_g, *v_g)
+        });
+        config.account_intersection(scanned, scanned * lookups(constraint));
+        is_supported
+    }
+}
+
+// Whether v_G has the relationships from a candidate of u_P required by the
+// constraint of u_P, i.e. a parent among the candidates of u_P. The relevant
+// adjacency list of v_G is searched if the candidates are sorted and more
+// than its entries.
+pub(crate) fn has_parent<T: Eq + Hash>(
+    graph: &Graph<T>,
+    config: &MatchConfig,
+    constraint: &Constraint,
+    v_g: usize,
+    u_p_candidates: &[usize],
+    is_sorted: bool,
+) -> bool {
+    let parents = match constraint.direction {
+        Direction::Outgoing => Some(graph.in_neighbors(v_g)),
+        Direction::Incoming => Some(graph.neighbors(v_g)),
+        Direction::Either => None,
+    };
+    match parents {
+        Some(parents) if is_sorted && parents.len() < u_p_candidates.len() => {
+            let (mut scanned, mut searches) = (0, 0);
+            let has_parent = parents.chunk_by(|a, b| a == b).any(|run| {
+                scanned += run.len();
+                if (run[0] == v_g && config.is_injective()) || run.len() < constraint.multiplicity {
+                    return false;
+                }
+                searches += 1;
+                if u_p_candidates.binary_search(&run[0]).is_err() {
+                    return false;
+                }
+                // the run counts relationships of all types
+                constraint.rel_type.is_none() || {
+                    searches += 1;
+                    constraint.is_satisfied_by(graph, run[0], v_g)
+                }
+            });
+            config.account_intersection(scanned, searches);
+            has_parent
+        }
+        _ => {
+            let mut scanned = 0;
+            let has_parent = u_p_candidates.iter().inspect(|_| scanned += 1).any(|u_g| {
+                (*u_g != v_g || !config.is_injective())
+                    && constraint.is_satisfied_by(graph, *u_g, v_g)
+            });
+            config.account_intersection(scanned, scanned * lookups(constraint));
+            has_parent
+        }
+    }
+}
+
+// number of adjacency list lookups per check of the constraint
+fn lookups(constraint: &Constraint) -> usize {
+    match constraint.direction {
+        Direction::Either => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GraphBuilder, Quota, Semantics};
+
+    use super::*;
+
+    #[test]
     fn match1() {
         let graph = GraphBuilder::new()
             .add_node(0, "b")
@@ -138,4 +1488,1098 @@ mod tests {
 
         assert_eq!(vec![vec![2, 6, 7]], matches)
     }
+
+    #[test]
+    fn match_self_loop() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_relationship(0, 0)
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 0)
+            .add_relationship(0, 1)
+            .build();
+
+        assert_eq!(vec![vec![0, 2]], dual_iso(&graph, &pattern));
+
+        let mut matches = dual_iso_with_config(
+            &graph,
+            &pattern,
+            MatchConfig::new().self_loops(SelfLoops::Ignore),
+        );
+        matches.sort();
+
+        assert_eq!(vec![vec![0, 2], vec![1, 2]], matches)
+    }
+
+    #[test]
+    fn match_graph_self_loop_is_not_a_relationship() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 0)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        assert!(dual_iso(&graph, &pattern).is_empty())
+    }
+
+    #[test]
+    fn match_multiplicity() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(0, 2)
+            .add_relationship(0, 2)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 1)
+            .build();
+
+        assert_eq!(vec![vec![0, 2]], dual_iso(&graph, &pattern))
+    }
+
+    #[test]
+    fn match_relationship_types() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "company")
+            .add_node(3, "person")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship_with_type(0, 2, "WORKS_AT")
+            .add_relationship_with_type(3, 2, "OWNS")
+            .add_relationship_with_type(3, 1, "KNOWS")
+            .add_relationship_with_type(3, 1, "KNOWS")
+            .add_relationship(1, 2)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "company")
+            .add_relationship_with_type(0, 1, "WORKS_AT")
+            .build();
+        assert_eq!(vec![vec![0, 2]], dual_iso(&graph, &pattern));
+
+        // untyped pattern relationships match relationships of any type
+        let pattern = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "company")
+            .add_relationship(0, 1)
+            .build();
+        let mut embeddings = dual_iso(&graph, &pattern);
+        embeddings.sort();
+        assert_eq!(vec![vec![0, 2], vec![1, 2], vec![3, 2]], embeddings);
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship(0, 1)
+            .build();
+        assert_eq!(vec![vec![3, 1]], dual_iso(&graph, &pattern));
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "company")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship_with_type(0, 2, "OWNS")
+            .build();
+        assert_eq!(vec![vec![3, 1, 2]], dual_iso(&graph, &pattern));
+    }
+
+    #[test]
+    fn match_order_is_reproducible() {
+        let build = || {
+            let mut builder = GraphBuilder::new();
+            for node_id in 0..20 {
+                builder.add_node(node_id, node_id % 3);
+            }
+            for node_id in 0..20 {
+                builder.add_relationship(node_id, (node_id * 7 + 1) % 20);
+                builder.add_relationship(node_id, (node_id * 3 + 2) % 20);
+            }
+            builder.build()
+        };
+        let pattern = GraphBuilder::new()
+            .add_node(0, 0)
+            .add_node(1, 1)
+            .add_node(2, 2)
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        // each graph hashes its labels with other keys
+        let matches = dual_iso(&build(), &pattern);
+        assert!(!matches.is_empty());
+        for _ in 0..5 {
+            assert_eq!(matches, dual_iso(&build(), &pattern));
+        }
+    }
+
+    #[test]
+    fn match_limit() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        assert_eq!(3, dual_iso(&graph, &pattern).len());
+        assert_eq!(
+            2,
+            dual_iso_with_config(&graph, &pattern, MatchConfig::new().limit(2)).len()
+        );
+    }
+
+    #[test]
+    fn match_limit_per_anchor() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_node(3, "b")
+            .add_node(4, "b")
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .add_relationship(0, 4)
+            .add_relationship(1, 4)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut matches =
+            dual_iso_with_config(&graph, &pattern, MatchConfig::new().limit_per_anchor(0, 2));
+        matches.sort();
+        assert_eq!(3, matches.len());
+        assert_eq!(2, matches.iter().filter(|m| m[0] == 0).count());
+        assert_eq!(vec![1, 4], matches[2]);
+
+        assert_eq!(
+            2,
+            dual_iso_with_config(
+                &graph,
+                &pattern,
+                MatchConfig::new().limit_per_anchor(0, 2).limit(2)
+            )
+            .len()
+        );
+    }
+
+    #[test]
+    fn match_for_anchors() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_node(3, "b")
+            .add_node(4, "a")
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .add_relationship(1, 3)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut matches = dual_iso_for_anchors(&graph, &pattern, 0, &[1, 4, 2, 0]);
+        matches[3].sort();
+        assert_eq!(
+            vec![
+                vec![vec![1, 3]],
+                vec![],
+                vec![],
+                vec![vec![0, 2], vec![0, 3]]
+            ],
+            matches
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id 2 must be within range [0..2).")]
+    fn match_for_anchors_with_invalid_node() {
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .build();
+        dual_iso_for_anchors(&pattern, &pattern, 2, &[0]);
+    }
+
+    #[test]
+    fn match_disconnected_pattern_with_quota() {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..10 {
+            builder.add_node(node_id, "a");
+        }
+        let graph = builder.build();
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..5 {
+            builder.add_node(node_id, "a");
+        }
+        let pattern = builder.build();
+
+        // the combinations of the components count as branches
+        let mut config = MatchConfig::new();
+        config.max_branches(100);
+        let (matches, stats) = dual_iso_with_stats(&graph, &pattern, &config);
+        assert_eq!(Some(Quota::Branches), stats.exceeded());
+        assert!(matches.len() < 30240);
+
+        // more pattern nodes than graph nodes have no injective match
+        assert!(dual_iso(&pattern, &graph).is_empty());
+    }
+
+    #[test]
+    fn match_catching_panics() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        assert_eq!(
+            Ok(vec![vec![0, 1]]),
+            try_dual_iso_with_config(&graph, &pattern, &MatchConfig::default())
+        );
+        assert_eq!(
+            "Node id 2 must be within range [0..2).",
+            try_dual_iso_with_config(&graph, &pattern, MatchConfig::new().limit_per_anchor(2, 1))
+                .unwrap_err()
+                .message()
+        );
+    }
+
+    #[test]
+    fn match_with_scorer() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_node(3, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+
+        // prefer high node ids for pattern node 1 and low ones for pattern node 2
+        let mut config = MatchConfig::new();
+        config.scorer(|u_p: usize, v_g: usize, assigned: &[(usize, usize)]| {
+            assert_eq!(u_p, assigned.len());
+            if u_p == 1 {
+                v_g as f64
+            } else {
+                -(v_g as f64)
+            }
+        });
+        assert_eq!(
+            vec![vec![0, 3, 1], vec![0, 3, 2], vec![0, 2, 1]],
+            dual_iso_with_config(&graph, &pattern, config.limit(3))
+        );
+        assert_eq!(
+            Some(vec![0, 3, 1]),
+            find_first_match(&graph, &pattern, &config)
+        );
+    }
+
+    #[test]
+    fn match_with_participation() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_node(3, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let (matches, index) =
+            dual_iso_with_participation(&graph, &pattern, &MatchConfig::default());
+        assert_eq!(2, matches.len());
+        assert_eq!(3, index.node_count());
+        assert_eq!(&[0, 1], index.matches(0));
+        for (idx, embedding) in matches.iter().enumerate() {
+            assert_eq!(&[idx], index.matches(embedding[1]));
+        }
+        assert!(index.matches(3).is_empty());
+    }
+
+    #[test]
+    fn match_with_repetitions() {
+        let mut builder = GraphBuilder::new();
+        builder.add_node(0, "a");
+        for node_id in 1..5 {
+            builder.add_node(node_id, "b").add_relationship(0, node_id);
+        }
+        let graph = builder.add_node(5, "a").add_relationship(5, 1).build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let config = MatchConfig::default();
+        let mut matches = dual_iso_with_repetitions(&graph, &pattern, &config, &[(1, 3)]);
+        matches.sort();
+        assert_eq!(
+            vec![
+                vec![0, 1, 2, 3],
+                vec![0, 1, 2, 4],
+                vec![0, 1, 3, 4],
+                vec![0, 2, 3, 4]
+            ],
+            matches
+        );
+
+        // two distinct nodes a that share a neighbor b
+        let mut matches = dual_iso_with_repetitions(&graph, &pattern, &config, &[(0, 2)]);
+        matches.sort();
+        assert_eq!(vec![vec![0, 1, 5]], matches);
+
+        // isolated copies match in different components
+        let pattern = GraphBuilder::new().add_node(0, "a").build();
+        assert_eq!(
+            vec![vec![0, 5]],
+            dual_iso_with_repetitions(&graph, &pattern, &config, &[(0, 2)])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 0 must be repeated at least once.")]
+    fn match_with_zero_repetitions() {
+        let pattern = GraphBuilder::new().add_node(0, "a").build();
+        dual_iso_with_repetitions(&pattern, &pattern, &MatchConfig::default(), &[(0, 0)]);
+    }
+
+    #[test]
+    fn match_with_stats() {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..6 {
+            builder.add_node(node_id, "a");
+        }
+        for start_node in 0..6 {
+            for end_node in 0..6 {
+                if start_node != end_node {
+                    builder.add_relationship(start_node, end_node);
+                }
+            }
+        }
+        let graph = builder.build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let (matches, stats) = dual_iso_with_stats(&graph, &pattern, &MatchConfig::default());
+        assert_eq!(120, matches.len());
+        assert_eq!(18, stats.candidates());
+        assert!(stats.branches() >= 120);
+        assert_eq!(None, stats.exceeded());
+
+        let mut config = MatchConfig::new();
+        config.max_branches(10);
+        let (matches, stats) = dual_iso_with_stats(&graph, &pattern, &config);
+        assert_eq!(10, stats.branches());
+        assert_eq!(Some(Quota::Branches), stats.exceeded());
+        assert!(matches.len() < 120);
+        // quotas work without asking for stats
+        assert_eq!(matches, dual_iso_with_config(&graph, &pattern, &config));
+
+        // the simulation of the general search copies the two candidates with
+        // an outgoing and the two with an incoming relationship
+        let path = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+        let relationship = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+        let mut config = MatchConfig::new();
+        config.max_allocated_candidates(1).fast_paths(false);
+        let (matches, stats) = dual_iso_with_stats(&path, &relationship, &config);
+        assert_eq!(4, stats.allocated_candidates());
+        assert_eq!(Some(Quota::AllocatedCandidates), stats.exceeded());
+        assert!(matches.is_empty());
+
+        let mut config = MatchConfig::new();
+        config.max_duration(std::time::Duration::from_secs(0));
+        assert_eq!(None, find_first_match(&graph, &pattern, &config));
+    }
+
+    #[test]
+    fn match_empty_candidates() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 1)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let mut config = MatchConfig::new();
+        assert_eq!(Ok(vec![]), dual_iso_checked(&graph, &pattern, &config));
+
+        config.empty_candidates(EmptyCandidates::Error);
+        let error = dual_iso_checked(&graph, &pattern, &config).unwrap_err();
+        assert_eq!(1, error.node_id());
+        assert_eq!(1, error.labeled_nodes());
+        assert_eq!(
+            "None of the 1 graph nodes with the label of pattern node 1 has its relationships.",
+            error.to_string()
+        );
+        assert!(dual_iso_with_config(&graph, &pattern, &config).is_empty());
+        assert_eq!(
+            Ok(vec![]),
+            try_dual_iso_with_config(&graph, &pattern, &config)
+        );
+
+        // node 1 loses both of its relationships
+        config.empty_candidates(EmptyCandidates::Relax);
+        assert_eq!(
+            Ok(vec![vec![0, 1, 2]]),
+            dual_iso_checked(&graph, &pattern, &config)
+        );
+        assert_eq!(
+            vec![vec![0, 1, 2]],
+            dual_iso_with_config(&graph, &pattern, &config)
+        );
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "d")
+            .add_relationship(0, 1)
+            .build();
+        assert_eq!(Ok(vec![]), dual_iso_checked(&graph, &pattern, &config));
+        config.empty_candidates(EmptyCandidates::Error);
+        assert_eq!(
+            "No graph node has the label of pattern node 1.",
+            dual_iso_checked(&graph, &pattern, &config)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn match_resolved_pattern_in_every_entry_point() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+        let mut relaxed = MatchConfig::new();
+        relaxed.empty_candidates(EmptyCandidates::Relax);
+
+        // homomorphic matches of the two `a` nodes are folded onto one
+        let folded = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .build();
+        let graph_with_relationship = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(2, 1)
+            .build();
+        let mut minimized = MatchConfig::new();
+        minimized.semantics(Semantics::Homomorphism).minimize(true);
+
+        for (graph, pattern, config) in [
+            (&graph, &pattern, &relaxed),
+            (&graph_with_relationship, &folded, &minimized),
+        ] {
+            let expected = dual_iso_with_config(graph, pattern, config);
+            assert_eq!(2, expected.len());
+            assert_eq!(expected.len(), count_matches(graph, pattern, config));
+            assert!(expected.contains(&find_first_match(graph, pattern, config).unwrap()));
+
+            let (sender, receiver) = std::sync::mpsc::sync_channel(expected.len());
+            assert_eq!(2, dual_iso_to_channel(graph, pattern, config, &sender));
+            assert_eq!(expected, receiver.try_iter().collect::<Vec<_>>());
+
+            let (matches, index) = dual_iso_with_participation(graph, pattern, config);
+            assert_eq!(expected, matches);
+            assert_eq!(3, index.node_count());
+        }
+    }
+
+    #[test]
+    fn match_anytime() {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..6 {
+            builder.add_node(node_id, "a");
+        }
+        for start_node in 0..6 {
+            for end_node in 0..6 {
+                if start_node != end_node {
+                    builder.add_relationship(start_node, end_node);
+                }
+            }
+        }
+        let graph = builder.build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let config = MatchConfig::default();
+        let (matches, stats) = dual_iso_anytime(&graph, &pattern, &config, Duration::from_secs(60));
+        assert_eq!(120, matches.len());
+        assert_eq!(1.0, stats.explored());
+
+        let (matches, stats) = dual_iso_anytime(&graph, &pattern, &config, Duration::ZERO);
+        assert!(matches.is_empty());
+        assert_eq!(0.0, stats.explored());
+
+        // within the first of the six candidates of pattern node 0, two of
+        // the five candidates of node 1 are completed and one of the five
+        // candidates of node 2 within the third
+        let mut config = MatchConfig::new();
+        config.max_branches(12).fast_paths(false);
+        let (matches, stats) = dual_iso_with_stats(&graph, &pattern, &config);
+        assert_eq!(8, matches.len());
+        assert_eq!((2.0 + 1.0 / 5.0) / 30.0, stats.explored());
+    }
+
+    #[cfg(feature = "perf-counters")]
+    #[test]
+    fn match_with_perf_counters() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut config = MatchConfig::new();
+        config.fast_paths(false);
+        let (matches, stats) = dual_iso_with_stats(&graph, &pattern, &config);
+        assert_eq!(2, matches.len());
+        assert!(stats.intersections() > 0);
+        assert!(stats.scanned_elements() >= stats.intersections());
+        assert!(stats.binary_searches() > 0);
+    }
+
+    #[test]
+    fn match_to_channel() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        let config = MatchConfig::default();
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        let consumer = std::thread::spawn(move || receiver.iter().collect::<Vec<_>>());
+        assert_eq!(3, dual_iso_to_channel(&graph, &pattern, &config, &sender));
+        drop(sender);
+        let mut matches = consumer.join().unwrap();
+        matches.sort();
+        assert_eq!(vec![vec![0, 1], vec![1, 2], vec![2, 0]], matches);
+
+        // without capacity every send waits for the receiver, which stops
+        // the search once it hangs up
+        let (sender, receiver) = std::sync::mpsc::sync_channel(0);
+        let consumer = std::thread::spawn(move || receiver.iter().take(2).count());
+        assert_eq!(2, dual_iso_to_channel(&graph, &pattern, &config, &sender));
+        assert_eq!(2, consumer.join().unwrap());
+    }
+
+    #[test]
+    fn match_anchored() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        let config = MatchConfig::default();
+        assert_eq!(
+            vec![vec![1, 2]],
+            dual_iso_anchored(&graph, &pattern, &config, &[(0, 1)], &[])
+        );
+        assert!(dual_iso_anchored(&graph, &pattern, &config, &[(0, 1), (1, 0)], &[]).is_empty());
+    }
+
+    #[test]
+    fn match_disconnected() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_node(4, "c")
+            .add_relationship(0, 1)
+            .add_relationship(2, 3)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "c")
+            .add_node(2, "b")
+            .add_relationship(0, 2)
+            .build();
+
+        let mut matches = dual_iso(&graph, &pattern);
+        matches.sort();
+
+        assert_eq!(vec![vec![0, 4, 1], vec![2, 4, 3]], matches)
+    }
+
+    #[test]
+    fn match_disconnected_is_injective() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        let matches = dual_iso(&graph, &pattern);
+
+        assert_eq!(vec![vec![0, 1, 2]], matches)
+    }
+
+    #[test]
+    fn match_homomorphism_onto_self_loop() {
+        // nodes 1 and 2 make the candidates outnumber the parents of node 0
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 0)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut config = MatchConfig::new();
+        config.semantics(Semantics::Homomorphism);
+
+        assert_eq!(
+            vec![vec![0, 0]],
+            dual_iso_with_config(&graph, &pattern, &config)
+        );
+        config.fast_paths(false);
+        assert_eq!(
+            vec![vec![0, 0]],
+            dual_iso_with_config(&graph, &pattern, &config)
+        );
+        assert!(dual_iso(&graph, &pattern).is_empty());
+    }
+
+    #[test]
+    fn match_candidates_across_prefetch_blocks() {
+        // more candidates than fit into a single prefetch block, every other
+        // one is removed by the simulation
+        let node_count = 5 * PREFETCH_BLOCK_SIZE;
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..node_count {
+            builder.add_node(node_id, if node_id % 2 == 0 { "a" } else { "b" });
+        }
+        for node_id in (0..node_count).step_by(4) {
+            builder.add_relationship(node_id, node_id + 1);
+        }
+        let graph = builder.build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut matches = dual_iso(&graph, &pattern);
+        matches.sort();
+        let expected = (0..node_count)
+            .step_by(4)
+            .map(|node_id| vec![node_id, node_id + 1])
+            .collect::<Vec<_>>();
+        assert_eq!(expected, matches);
+    }
+
+    #[test]
+    fn match_first() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_node(4, "b")
+            .add_relationship(0, 1)
+            .add_relationship(2, 3)
+            .add_relationship(2, 4)
+            .add_relationship(3, 4)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .build();
+
+        let config = MatchConfig::default();
+        assert_eq!(
+            Some(vec![2, 3, 4]),
+            find_first_match(&graph, &pattern, &config)
+        );
+
+        // disconnected pattern nodes still need distinct graph nodes
+        let disconnected = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .build();
+        let mut first = find_first_match(&graph, &disconnected, &config).unwrap();
+        first.sort_unstable();
+        assert_eq!(vec![0, 2], first);
+        let too_many = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .build();
+        assert_eq!(None, find_first_match(&graph, &too_many, &config));
+
+        let unmatched = GraphBuilder::new()
+            .add_node(0, "b")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+        assert_eq!(None, find_first_match(&graph, &unmatched, &config));
+    }
+
+    #[test]
+    fn match_to_row_major() {
+        assert_eq!(
+            vec![0, 1, 2, 3, 4, 5],
+            to_row_major(&[vec![0, 1, 2], vec![3, 4, 5]], 3)
+        );
+        assert!(to_row_major(&[], 3).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Match 1 has 2 nodes, but the pattern has 3.")]
+    fn match_to_row_major_with_other_size() {
+        to_row_major(&[vec![0, 1, 2], vec![3, 4]], 3);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn match_to_ndarray() {
+        let array = to_ndarray(&[vec![0, 1, 2], vec![3, 4, 5]], 3);
+        assert_eq!(&[2, 3], array.shape());
+        assert_eq!(ndarray::arr1(&[3, 4, 5]), array.row(1));
+        assert_eq!(&[0, 3], to_ndarray(&[], 3).shape());
+    }
+
+    #[test]
+    fn match_simulation_rounds() {
+        // the end of the path is missing, which the simulation propagates
+        // back one node per round
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..6 {
+            builder.add_node(node_id, "a");
+        }
+        for node_id in 0..4 {
+            builder.add_relationship(node_id, node_id + 1);
+        }
+        let graph = builder.build();
+
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..4 {
+            builder.add_node(node_id, "a");
+        }
+        for node_id in 0..3 {
+            builder.add_relationship(node_id, node_id + 1);
+        }
+        let pattern = builder.build();
+
+        let report = simulate(&graph, &pattern, &MatchConfig::default());
+        assert!(report.is_converged());
+        assert_eq!(report.rounds(), report.removed().len());
+        assert_eq!(0, *report.removed().last().unwrap());
+        let mut first = report.candidates()[0].clone();
+        first.sort_unstable();
+        assert_eq!(vec![0, 1], first);
+
+        let mut config = MatchConfig::new();
+        config.max_simulation_rounds(1);
+        let report = simulate(&graph, &pattern, &config);
+        assert!(!report.is_converged());
+        assert_eq!(1, report.rounds());
+
+        let mut expected = dual_iso(&graph, &pattern);
+        let mut matches = dual_iso_with_config(&graph, &pattern, &config);
+        expected.sort();
+        matches.sort();
+        assert_eq!(expected, matches);
+    }
+
+    #[test]
+    fn match_simulation_requires_parents() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_node(3, "a")
+            .add_relationship(0, 1)
+            .add_relationship(2, 3)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        // b node 2 has a relationship to an a node, but none from one
+        let report = simulate(&graph, &pattern, &MatchConfig::default());
+        assert_eq!(&vec![vec![0], vec![1]], report.candidates());
+        assert_eq!(&[1], report.node_candidates(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id 2 must be within range [0..2).")]
+    fn match_simulation_candidates_of_invalid_node() {
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .build();
+        simulate(&pattern, &pattern, &MatchConfig::default()).node_candidates(2);
+    }
+
+    #[test]
+    fn match_all_different() {
+        // every node has a successor, but three nodes cannot be matched
+        // injectively to two
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .add_relationship(1, 2)
+            .build();
+
+        let triangle = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+        let query = Query::new(&triangle);
+        let mut candidates = init_candidates(&graph, &query);
+        let config = MatchConfig::default();
+        assert!(simple_simulation(&graph, &query, &config, &mut candidates));
+        assert!(!has_injective_assignment(&candidates));
+
+        let mut config = MatchConfig::new();
+        config.all_different(true);
+        assert!(dual_iso_with_config(&graph, &triangle, &config).is_empty());
+
+        let path = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+        assert_eq!(
+            vec![vec![0, 1, 2]],
+            dual_iso_with_config(&graph, &path, &config)
+        );
+    }
+
+    #[test]
+    fn match_with_deferred_hubs() {
+        // node 0 is a hub connected to all other nodes
+        let mut builder = GraphBuilder::new();
+        builder.add_node(0, "hub");
+        for node_id in 1..8 {
+            builder.add_node(node_id, "leaf");
+            builder.add_relationship(0, node_id);
+        }
+        for node_id in 1..7 {
+            builder.add_relationship(node_id, node_id + 1);
+        }
+        let graph = builder.build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "hub")
+            .add_node(1, "leaf")
+            .add_node(2, "leaf")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let query = Query::new(&pattern);
+        let candidates = init_candidates(&graph, &query);
+        assert_eq!(vec![1, 2, 0], hub_order(&graph, &candidates, 5));
+
+        let mut config = MatchConfig::new();
+        config.defer_hubs(5);
+        let mut expected = dual_iso(&graph, &pattern);
+        let mut matches = dual_iso_with_config(&graph, &pattern, &config);
+        expected.sort();
+        matches.sort();
+        assert_eq!(6, matches.len());
+        assert_eq!(expected, matches);
+    }
+
+    #[test]
+    fn match_missing_label() {
+        let graph = GraphBuilder::new().add_node(0, "a").build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .build();
+
+        assert!(dual_iso(&graph, &pattern).is_empty())
+    }
+
+    #[test]
+    fn match_simulation_shares_unchanged_candidates() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_relationship(0, 2)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let query = Query::new(&pattern);
+        let mut candidates = init_candidates(&graph, &query);
+        assert!(simple_simulation(
+            &graph,
+            &query,
+            &MatchConfig::default(),
+            &mut candidates
+        ));
+
+        assert_eq!(&[0], &*candidates[0]);
+        assert!(matches!(candidates[1], Candidates::Borrowed(_)));
+    }
 }
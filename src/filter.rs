@@ -0,0 +1,671 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::Graph;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl Value {
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+// Provides node properties to filter expressions. The properties `id` and
+// `degree` are always available and take precedence.
+pub trait PropertySource {
+    fn property(&self, node_id: usize, key: &str) -> Option<Value>;
+}
+
+pub struct NoProperties;
+
+impl PropertySource for NoProperties {
+    fn property(&self, _node_id: usize, _key: &str) -> Option<Value> {
+        None
+    }
+}
+
+impl PropertySource for HashMap<usize, HashMap<String, Value>> {
+    fn property(&self, node_id: usize, key: &str) -> Option<Value> {
+        self.get(&node_id)?.get(key).cloned()
+    }
+}
+
+// The properties set in the graph builder.
+impl<T: Eq + Hash> PropertySource for Graph<T> {
+    fn property(&self, node_id: usize, key: &str) -> Option<Value> {
+        Graph::property(self, node_id, key).cloned()
+    }
+}
+
+// Everything an expression can refer to besides the bound pattern nodes.
+pub(crate) struct Scope<'a, T> {
+    pub(crate) graph: &'a Graph<T>,
+    pub(crate) properties: &'a dyn PropertySource,
+    pub(crate) parameters: &'a HashMap<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterError {
+    message: String,
+    position: usize,
+}
+
+impl FilterError {
+    pub(crate) fn new(message: impl Into<String>, position: usize) -> Self {
+        FilterError {
+            message: message.into(),
+            position,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // moves the error position by the offset of the parsed text in its source
+    pub(crate) fn shifted(mut self, offset: usize) -> Self {
+        self.position += offset;
+        self
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}.", self.message, self.position)
+    }
+}
+
+impl Error for FilterError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Parameter(String),
+    // property of the graph node bound to the pattern node
+    Property(usize, String),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate<T: Eq + Hash>(
+        &self,
+        scope: &Scope<T>,
+        binding: &dyn Fn(usize) -> Option<usize>,
+    ) -> Option<Value> {
+        match self {
+            Expr::Literal(value) => Some(value.clone()),
+            Expr::Parameter(name) => scope.parameters.get(name).cloned(),
+            Expr::Property(pattern_node, key) => {
+                let node_id = binding(*pattern_node)?;
+                match key.as_str() {
+                    "id" => Some(Value::Int(node_id as i64)),
+                    "degree" => Some(Value::Int(scope.graph.degree(node_id) as i64)),
+                    _ => scope.properties.property(node_id, key),
+                }
+            }
+            Expr::Compare(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(scope, binding)?;
+                let rhs = rhs.evaluate(scope, binding)?;
+                // values of different types are never comparable
+                Some(Value::Bool(
+                    lhs.compare(&rhs).is_some_and(|ordering| op.holds(ordering)),
+                ))
+            }
+            Expr::And(lhs, rhs) => Some(Value::Bool(
+                lhs.holds(scope, binding) && rhs.holds(scope, binding),
+            )),
+            Expr::Or(lhs, rhs) => Some(Value::Bool(
+                lhs.holds(scope, binding) || rhs.holds(scope, binding),
+            )),
+            Expr::Not(expr) => Some(Value::Bool(!expr.holds(scope, binding))),
+        }
+    }
+
+    // missing properties and non-boolean results count as false
+    fn holds<T: Eq + Hash>(
+        &self,
+        scope: &Scope<T>,
+        binding: &dyn Fn(usize) -> Option<usize>,
+    ) -> bool {
+        self.evaluate(scope, binding) == Some(Value::Bool(true))
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<usize>) {
+        match self {
+            Expr::Literal(_) | Expr::Parameter(_) => {}
+            Expr::Property(pattern_node, _) => {
+                if !variables.contains(pattern_node) {
+                    variables.push(*pattern_node);
+                }
+            }
+            Expr::Compare(_, lhs, rhs) | Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+                lhs.collect_variables(variables);
+                rhs.collect_variables(variables);
+            }
+            Expr::Not(expr) => expr.collect_variables(variables),
+        }
+    }
+
+    fn remap(&self, mapping: &dyn Fn(usize) -> usize) -> Expr {
+        let remap = |expr: &Expr| Box::new(expr.remap(mapping));
+        match self {
+            Expr::Literal(_) | Expr::Parameter(_) => self.clone(),
+            Expr::Property(pattern_node, key) => {
+                Expr::Property(mapping(*pattern_node), key.clone())
+            }
+            Expr::Compare(op, lhs, rhs) => Expr::Compare(*op, remap(lhs), remap(rhs)),
+            Expr::And(lhs, rhs) => Expr::And(remap(lhs), remap(rhs)),
+            Expr::Or(lhs, rhs) => Expr::Or(remap(lhs), remap(rhs)),
+            Expr::Not(expr) => Expr::Not(remap(expr)),
+        }
+    }
+
+    fn into_conjuncts(self, conjuncts: &mut Vec<Expr>) {
+        match self {
+            Expr::And(lhs, rhs) => {
+                lhs.into_conjuncts(conjuncts);
+                rhs.into_conjuncts(conjuncts);
+            }
+            expr => conjuncts.push(expr),
+        }
+    }
+}
+
+// A conjunct of a filter expression together with the pattern nodes it refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Conjunct {
+    expr: Expr,
+    variables: Vec<usize>,
+}
+
+impl Conjunct {
+    pub(crate) fn variables(&self) -> &[usize] {
+        &self.variables
+    }
+
+    pub(crate) fn holds<T: Eq + Hash>(
+        &self,
+        scope: &Scope<T>,
+        binding: &dyn Fn(usize) -> Option<usize>,
+    ) -> bool {
+        self.expr.holds(scope, binding)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    conjuncts: Vec<Conjunct>,
+}
+
+impl Filter {
+    // Parses expressions like `a.age > $min AND (b.name = 'x' OR NOT a.vip)`,
+    // resolving variable names to pattern nodes. Parameters such as `$min`
+    // are bound when the pattern is executed.
+    pub fn parse(
+        source: &str,
+        resolve: &dyn Fn(&str) -> Option<usize>,
+    ) -> Result<Filter, FilterError> {
+        Filter::parse_with_subject(source, resolve, None)
+    }
+
+    // Parses expressions on a single pattern node like `age > 30 AND vip`,
+    // where keys refer to the properties of the node.
+    pub fn parse_node(source: &str, node_id: usize) -> Result<Filter, FilterError> {
+        Filter::parse_with_subject(source, &|_| None, Some(node_id))
+    }
+
+    fn parse_with_subject(
+        source: &str,
+        resolve: &dyn Fn(&str) -> Option<usize>,
+        subject: Option<usize>,
+    ) -> Result<Filter, FilterError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end: source.len(),
+            resolve,
+            subject,
+        };
+        let expr = parser.parse_or()?;
+        if let Some((_, position)) = parser.tokens.get(parser.position) {
+            return Err(FilterError::new("Unexpected token", *position));
+        }
+
+        let mut conjuncts = vec![];
+        expr.into_conjuncts(&mut conjuncts);
+        Ok(Filter {
+            conjuncts: conjuncts
+                .into_iter()
+                .map(|expr| {
+                    let mut variables = vec![];
+                    expr.collect_variables(&mut variables);
+                    Conjunct { expr, variables }
+                })
+                .collect(),
+        })
+    }
+
+    pub(crate) fn conjuncts(&self) -> &[Conjunct] {
+        &self.conjuncts
+    }
+
+    // Moves the filter to other pattern node ids, e.g. after joining patterns.
+    pub(crate) fn remap(&self, mapping: &dyn Fn(usize) -> usize) -> Filter {
+        Filter {
+            conjuncts: self
+                .conjuncts
+                .iter()
+                .map(|conjunct| Conjunct {
+                    expr: conjunct.expr.remap(mapping),
+                    variables: conjunct.variables.iter().map(|v| mapping(*v)).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn holds<T: Eq + Hash>(
+        &self,
+        graph: &Graph<T>,
+        properties: &dyn PropertySource,
+        binding: &dyn Fn(usize) -> Option<usize>,
+    ) -> bool {
+        let scope = Scope {
+            graph,
+            properties,
+            parameters: &HashMap::new(),
+        };
+        self.conjuncts
+            .iter()
+            .all(|conjunct| conjunct.holds(&scope, binding))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Parameter(String),
+    Literal(Value),
+    Compare(CompareOp),
+    And,
+    Or,
+    Not,
+    Dot,
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, FilterError> {
+    let chars = source.char_indices().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let (position, c) = chars[i];
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+        let (token, width) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('.', _) => (Token::Dot, 1),
+            ('=', Some('=')) => (Token::Compare(CompareOp::Eq), 2),
+            ('=', _) => (Token::Compare(CompareOp::Eq), 1),
+            ('!', Some('=')) | ('<', Some('>')) => (Token::Compare(CompareOp::Ne), 2),
+            ('<', Some('=')) => (Token::Compare(CompareOp::Le), 2),
+            ('<', _) => (Token::Compare(CompareOp::Lt), 1),
+            ('>', Some('=')) => (Token::Compare(CompareOp::Ge), 2),
+            ('>', _) => (Token::Compare(CompareOp::Gt), 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('!', _) => (Token::Not, 1),
+            ('\'', _) | ('"', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|(_, d)| *d == c)
+                    .ok_or_else(|| FilterError::new("Unterminated string", position))?;
+                let value = chars[i + 1..i + 1 + end].iter().map(|(_, c)| c).collect();
+                (Token::Literal(Value::Str(value)), end + 2)
+            }
+            (c, _)
+                if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) =>
+            {
+                let width = 1 + chars[i + 1..]
+                    .iter()
+                    .take_while(|(_, d)| d.is_ascii_digit() || *d == '.')
+                    .count();
+                let text = chars[i..i + width]
+                    .iter()
+                    .map(|(_, c)| c)
+                    .collect::<String>();
+                let value = if text.contains('.') {
+                    text.parse().map(Value::Float).ok()
+                } else {
+                    text.parse().map(Value::Int).ok()
+                };
+                let value = value.ok_or_else(|| FilterError::new("Invalid number", position))?;
+                (Token::Literal(value), width)
+            }
+            ('$', _) => {
+                let width = chars[i + 1..]
+                    .iter()
+                    .take_while(|(_, d)| d.is_alphanumeric() || *d == '_')
+                    .count();
+                if width == 0 {
+                    return Err(FilterError::new("Expected parameter name", position));
+                }
+                let name = chars[i + 1..i + 1 + width].iter().map(|(_, c)| c).collect();
+                (Token::Parameter(name), width + 1)
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let width = chars[i..]
+                    .iter()
+                    .take_while(|(_, d)| d.is_alphanumeric() || *d == '_')
+                    .count();
+                let word = chars[i..i + width]
+                    .iter()
+                    .map(|(_, c)| c)
+                    .collect::<String>();
+                let token = match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    "TRUE" => Token::Literal(Value::Bool(true)),
+                    "FALSE" => Token::Literal(Value::Bool(false)),
+                    _ => Token::Ident(word),
+                };
+                (token, width)
+            }
+            _ => return Err(FilterError::new("Unexpected character", position)),
+        };
+        tokens.push((token, position));
+        i += width;
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    end: usize,
+    resolve: &'a dyn Fn(&str) -> Option<usize>,
+    // the pattern node whose properties are referred to by their keys alone
+    subject: Option<usize>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(_, position)| *position)
+    }
+
+    fn next(&mut self) -> Result<Token, FilterError> {
+        let offset = self.offset();
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| FilterError::new("Unexpected end of expression", offset))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.parse_not()?;
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, FilterError> {
+        if self.peek() == Some(&Token::Not) {
+            self.position += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        let lhs = self.parse_operand()?;
+        if let Some(Token::Compare(op)) = self.peek() {
+            let op = *op;
+            self.position += 1;
+            let rhs = self.parse_operand()?;
+            return Ok(Expr::Compare(op, Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_operand(&mut self) -> Result<Expr, FilterError> {
+        let offset = self.offset();
+        match self.next()? {
+            Token::Literal(value) => Ok(Expr::Literal(value)),
+            Token::Parameter(name) => Ok(Expr::Parameter(name)),
+            Token::Open => {
+                let expr = self.parse_or()?;
+                let offset = self.offset();
+                match self.next() {
+                    Ok(Token::Close) => Ok(expr),
+                    _ => Err(FilterError::new("Expected ')'", offset)),
+                }
+            }
+            Token::Ident(key) if self.subject.is_some() && self.peek() != Some(&Token::Dot) => {
+                Ok(Expr::Property(self.subject.unwrap(), key))
+            }
+            Token::Ident(variable) => {
+                let pattern_node = (self.resolve)(&variable).ok_or_else(|| {
+                    FilterError::new(format!("Unknown variable '{}'", variable), offset)
+                })?;
+                let offset = self.offset();
+                match (self.next(), self.next()) {
+                    (Ok(Token::Dot), Ok(Token::Ident(key))) => {
+                        Ok(Expr::Property(pattern_node, key))
+                    }
+                    _ => Err(FilterError::new("Expected property access", offset)),
+                }
+            }
+            _ => Err(FilterError::new("Unexpected token", offset)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    fn resolve(name: &str) -> Option<usize> {
+        match name {
+            "a" => Some(0),
+            "b" => Some(1),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_parse_conjuncts() {
+        let filter = Filter::parse("a.age > 30 AND (b.x = 'y' OR a.id = b.id)", &resolve).unwrap();
+
+        assert_eq!(2, filter.conjuncts().len());
+        assert_eq!(&[0], filter.conjuncts()[0].variables());
+        assert_eq!(&[1, 0], filter.conjuncts()[1].variables());
+    }
+
+    #[test]
+    fn test_parse_node() {
+        let filter = Filter::parse_node("age > 30 AND NOT vip", 1).unwrap();
+        assert_eq!(2, filter.conjuncts().len());
+        assert_eq!(&[1], filter.conjuncts()[1].variables());
+        assert_eq!(
+            "Unknown variable 'a' at position 0.",
+            Filter::parse_node("a.age > 30", 1).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "Unknown variable 'c' at position 0.",
+            Filter::parse("c.age > 1", &resolve)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            8,
+            Filter::parse("a.age > ", &resolve).unwrap_err().position()
+        );
+        assert_eq!(
+            "Unterminated string at position 9.",
+            Filter::parse("a.name = 'x", &resolve)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_holds() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut properties = HashMap::new();
+        properties.insert(0, HashMap::new());
+        properties
+            .get_mut(&0)
+            .unwrap()
+            .insert("age".to_string(), Value::Int(42));
+
+        let binding = |pattern_node: usize| Some(pattern_node);
+
+        let holds = |source: &str| {
+            Filter::parse(source, &resolve)
+                .unwrap()
+                .holds(&graph, &properties, &binding)
+        };
+
+        assert!(holds("a.age > 30"));
+        assert!(holds("a.age >= 41.5 && a.degree == 1"));
+        assert!(holds("NOT a.age < 42 AND b.degree = 0"));
+        assert!(holds("a.id <> b.id OR b.age = 1"));
+        // missing properties never match
+        assert!(!holds("b.age = 1"));
+        assert!(!holds("a.age = 'old'"));
+        // unbound parameters never match
+        assert!(!holds("a.age = $age"));
+    }
+
+    #[test]
+    fn test_holds_with_parameters() {
+        let graph = GraphBuilder::new().add_node(0, "foo").build();
+        let filter = Filter::parse("a.id = $id", &resolve).unwrap();
+
+        let mut parameters = HashMap::new();
+        let binding = |pattern_node: usize| Some(pattern_node);
+        let holds = |parameters: &HashMap<String, Value>| {
+            let scope = Scope {
+                graph: &graph,
+                properties: &NoProperties,
+                parameters,
+            };
+            filter
+                .conjuncts()
+                .iter()
+                .all(|conjunct| conjunct.holds(&scope, &binding))
+        };
+
+        parameters.insert("id".to_string(), Value::Int(0));
+        assert!(holds(&parameters));
+        parameters.insert("id".to_string(), Value::Int(1));
+        assert!(!holds(&parameters));
+    }
+}
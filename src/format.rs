@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, BufRead, Write};
+
+use crate::{Graph, GraphBuilder};
+
+// A line based text format for graphs, where `#` starts a comment:
+//
+//   node <id> <label>
+//   rel <start node> <end node>
+//
+// Labels extend to the end of the line. Nodes must be declared before they
+// are used in relationships.
+
+#[derive(Debug)]
+pub struct FormatError {
+    message: String,
+    line: usize,
+}
+
+impl FormatError {
+    pub(crate) fn new(message: impl Into<String>, line: usize) -> Self {
+        FormatError {
+            message: message.into(),
+            line,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in line {}.", self.message, self.line)
+    }
+}
+
+impl Error for FormatError {}
+
+pub fn parse_graph(source: &str) -> Result<Graph<String>, FormatError> {
+    read_graph(source.as_bytes())
+}
+
+pub fn read_graph<R: BufRead>(reader: R) -> Result<Graph<String>, FormatError> {
+    let mut builder = GraphBuilder::new();
+    let mut node_count = 0;
+    for (idx, line) in reader.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line.map_err(|e| FormatError::new(e.to_string(), line_number))?;
+        match parse_directive(&line, line_number, node_count)? {
+            Some(Directive::Node(id, label)) => {
+                node_count = node_count.max(id + 1);
+                builder.add_node(id, label.to_string());
+            }
+            Some(Directive::Relationship(start, end)) => {
+                builder.add_relationship(start, end);
+            }
+            None => {}
+        }
+    }
+    Ok(builder.build())
+}
+
+pub(crate) enum Directive<'a> {
+    Node(usize, &'a str),
+    Relationship(usize, usize),
+}
+
+// Parses a single line given the number of nodes declared so far, returns
+// None for empty lines and comments.
+pub(crate) fn parse_directive(
+    line: &str,
+    line_number: usize,
+    node_count: usize,
+) -> Result<Option<Directive<'_>>, FormatError> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let (directive, rest) = split_token(line);
+    match directive {
+        "node" => {
+            let (id, label) = split_token(rest);
+            let id = parse_id(id, line_number)?;
+            if label.is_empty() {
+                return Err(FormatError::new(
+                    format!("Missing label of node {}", id),
+                    line_number,
+                ));
+            }
+            if id > node_count {
+                return Err(FormatError::new(
+                    format!(
+                        "Next node id should be within range [0..{}], but was {}",
+                        node_count, id
+                    ),
+                    line_number,
+                ));
+            }
+            Ok(Some(Directive::Node(id, label)))
+        }
+        "rel" => {
+            let (start, rest) = split_token(rest);
+            let (end, rest) = split_token(rest);
+            if !rest.is_empty() {
+                return Err(FormatError::new(
+                    format!("Unexpected '{}'", rest),
+                    line_number,
+                ));
+            }
+            let start = parse_id(start, line_number)?;
+            let end = parse_id(end, line_number)?;
+            for node_id in [start, end] {
+                if node_id >= node_count {
+                    return Err(FormatError::new(
+                        format!("Node {} has not been declared", node_id),
+                        line_number,
+                    ));
+                }
+            }
+            Ok(Some(Directive::Relationship(start, end)))
+        }
+        _ => Err(FormatError::new(
+            format!("Unknown directive '{}'", directive),
+            line_number,
+        )),
+    }
+}
+
+pub fn write_graph<T, W>(graph: &Graph<T>, writer: &mut W) -> io::Result<()>
+where
+    T: Eq + Hash + fmt::Display,
+    W: Write,
+{
+    for node_id in 0..graph.node_count() {
+        writeln!(writer, "node {} {}", node_id, graph.node_label(node_id))?;
+    }
+    for (start_node, end_node) in graph.edges() {
+        writeln!(writer, "rel {} {}", start_node, end_node)?;
+    }
+    Ok(())
+}
+
+fn split_token(line: &str) -> (&str, &str) {
+    match line.find(char::is_whitespace) {
+        Some(end) => (&line[..end], line[end..].trim_start()),
+        None => (line, ""),
+    }
+}
+
+fn parse_id(token: &str, line_number: usize) -> Result<usize, FormatError> {
+    token
+        .parse()
+        .map_err(|_| FormatError::new(format!("Invalid node id '{}'", token), line_number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_graph() {
+        let graph = parse_graph(
+            "# a small graph\n\
+             node 0 person\n\
+             node 1 red car\n\
+             \n\
+             rel 0 1\n\
+             rel 1 1\n",
+        )
+        .unwrap();
+
+        assert_eq!(2, graph.node_count());
+        assert_eq!(2, graph.relationship_count());
+        assert_eq!("red car", graph.node_label(1));
+        assert_eq!(&[1], graph.neighbors(0));
+    }
+
+    #[test]
+    fn test_write_graph_round_trip() {
+        let graph = parse_graph("node 0 a\nnode 1 b\nrel 1 0\nrel 0 1\n").unwrap();
+        let mut out = vec![];
+        write_graph(&graph, &mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+
+        assert_eq!("node 0 a\nnode 1 b\nrel 0 1\nrel 1 0\n", written);
+        assert_eq!(graph, parse_graph(&written).unwrap());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source: &str| parse_graph(source).unwrap_err().to_string();
+
+        assert_eq!("Unknown directive 'edge' in line 1.", error("edge 0 1"));
+        assert_eq!("Invalid node id 'x' in line 1.", error("node x a"));
+        assert_eq!("Missing label of node 0 in line 1.", error("node 0"));
+        assert_eq!(
+            "Next node id should be within range [0..0], but was 1 in line 1.",
+            error("node 1 a")
+        );
+        assert_eq!(
+            "Node 1 has not been declared in line 2.",
+            error("node 0 a\nrel 0 1")
+        );
+        assert_eq!("Unexpected '2' in line 2.", error("node 0 a\nrel 0 0 2"));
+    }
+}
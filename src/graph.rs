@@ -1,17 +1,44 @@
 #![allow(dead_code)]
 
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::hash::Hash;
+use std::cell::OnceCell;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use std::rc::Rc;
 
+use crate::parallel::{reverse_shard_lists, shard_lists};
+use crate::partition::{distances, undirected_adjacency};
+use crate::{GraphDiff, RelationshipShard, Value};
+
 pub struct Graph<T> {
     node_count: usize,
     relationship_count: usize,
+    // relationships added to the builder, including deduped ones
+    raw_relationship_count: usize,
     node_labels: HashMap<usize, Rc<T>>,
-    label_idx: HashMap<Rc<T>, Vec<usize>>,
+    // built on the first query by label if the builder deferred it. The
+    // labels are shared as `Rc`, so a graph is never shared between threads
+    // and a `OnceCell` suffices where a `OnceLock` would only add a lock;
+    // threads get their own copy through `DetachedGraph`.
+    label_idx: OnceCell<LabelIndex>,
     offsets: Vec<usize>,
     lists: Vec<usize>,
+    // the start nodes of the relationships per end node, laid out like the
+    // adjacency lists
+    in_offsets: Vec<usize>,
+    in_lists: Vec<usize>,
+    // the adjacency lists in standard CSR form, built on the first `as_csr`
+    csr: OnceCell<(Vec<usize>, Vec<usize>)>,
+    // the sorted types of the typed relationships per (start, end) pair,
+    // untyped relationships have no entry
+    relationship_types: HashMap<(usize, usize), Vec<Rc<str>>>,
+    properties: HashMap<usize, HashMap<String, Value>>,
+    // ids of the nodes added with `add_external_node` in both directions
+    external_ids: HashMap<usize, u64>,
+    internal_ids: HashMap<u64, usize>,
 }
 
 impl<T> Graph<T>
@@ -22,17 +49,86 @@ where
         self.node_count
     }
 
+    pub fn node_ids(&self) -> Range<usize> {
+        0..self.node_count
+    }
+
+    // The (start node, end node) pairs of all relationships, ordered by start
+    // node and then by end node. Parallel relationships are repeated pairs.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.node_ids().flat_map(move |node_id| {
+            self.neighbors(node_id)
+                .iter()
+                .map(move |neighbor| (node_id, *neighbor))
+        })
+    }
+
+    // Parallel relationships count individually, unless the builder deduped them.
     pub fn relationship_count(&self) -> usize {
         self.relationship_count
     }
 
+    // The relationships added to the builder, including those it deduped.
+    pub fn raw_relationship_count(&self) -> usize {
+        self.raw_relationship_count
+    }
+
+    // The number of distinct (start, end) pairs, independent of deduping.
+    pub fn unique_relationship_count(&self) -> usize {
+        (0..self.node_count)
+            .map(|node_id| self.distinct_neighbors(node_id).count())
+            .sum()
+    }
+
     pub fn node_label(&self, node_id: usize) -> &T {
         self.validate_node_id(node_id);
         self.node_labels.get(&node_id).unwrap()
     }
 
-    pub fn nodes_by_label(&self, label: &T) -> &Vec<usize> {
-        self.label_idx.get(label).unwrap()
+    // Properties set in the builder, which filters of patterns can refer to.
+    pub fn property(&self, node_id: usize, key: &str) -> Option<&Value> {
+        self.validate_node_id(node_id);
+        self.properties.get(&node_id)?.get(key)
+    }
+
+    // The id the node was added with in `GraphBuilder::add_external_node`,
+    // e.g. to report matches in the ids of the input.
+    pub fn to_external_id(&self, node_id: usize) -> Option<u64> {
+        self.validate_node_id(node_id);
+        self.external_ids.get(&node_id).copied()
+    }
+
+    pub fn to_internal_id(&self, external_id: u64) -> Option<usize> {
+        self.internal_ids.get(&external_id).copied()
+    }
+
+    // The nodes with the label in ascending order, so that candidates and
+    // thereby the order of matches are the same for equal graphs.
+    pub fn nodes_by_label(&self, label: &T) -> &[usize] {
+        self.label_index()
+            .buckets
+            .get(&label_hash(label))
+            .and_then(|buckets| {
+                buckets
+                    .iter()
+                    .find(|nodes| *self.node_labels[&nodes[0]] == *label)
+            })
+            .map_or(&[], |nodes| nodes.as_slice())
+    }
+
+    // The number of nodes per distinct label, in no particular order.
+    pub fn label_counts(&self) -> Vec<(&T, usize)> {
+        self.label_buckets()
+            .map(|nodes| (&*self.node_labels[&nodes[0]], nodes.len()))
+            .collect()
+    }
+
+    // The fraction of nodes with the label, 0 for labels without nodes.
+    pub fn label_selectivity(&self, label: &T) -> f64 {
+        if self.node_count == 0 {
+            return 0.0;
+        }
+        self.nodes_by_label(label).len() as f64 / self.node_count as f64
     }
 
     pub fn degree(&self, node_id: usize) -> usize {
@@ -48,209 +144,2768 @@ where
         &self.lists[offset + 1..offset + 1 + degree]
     }
 
-    fn validate_node_id(&self, node_id: usize) {
-        if node_id >= self.node_count {
-            panic!(
-                "Node id {} must be within range [0..{}).",
-                node_id, self.node_count
-            )
+    pub fn in_degree(&self, node_id: usize) -> usize {
+        self.validate_node_id(node_id);
+        self.in_lists[self.in_offsets[node_id]]
+    }
+
+    // The start nodes of the relationships to the node in ascending order,
+    // parallel relationships are repeated entries.
+    pub fn in_neighbors(&self, node_id: usize) -> &[usize] {
+        self.validate_node_id(node_id);
+        let offset = self.in_offsets[node_id];
+        let degree = self.in_lists[offset];
+        &self.in_lists[offset + 1..offset + 1 + degree]
+    }
+
+    // yields each neighbor once together with the number of parallel relationships to it
+    pub fn distinct_neighbors(&self, node_id: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.neighbors(node_id)
+            .chunk_by(|a, b| a == b)
+            .map(|run| (run[0], run.len()))
+    }
+
+    pub fn multiplicity(&self, start_node: usize, end_node: usize) -> usize {
+        let neighbors = self.neighbors(start_node);
+        let lower = neighbors.partition_point(|n| *n < end_node);
+        neighbors[lower..].partition_point(|n| *n == end_node)
+    }
+
+    // The types of the relationships from the start to the end node in
+    // ascending order, parallel relationships of a type are repeated entries.
+    // Untyped relationships are not listed.
+    pub fn relationship_types(
+        &self,
+        start_node: usize,
+        end_node: usize,
+    ) -> impl Iterator<Item = &str> + '_ {
+        self.shared_relationship_types(start_node, end_node)
+            .iter()
+            .map(|rel_type| rel_type.as_ref())
+    }
+
+    // The number of relationships of the given type from the start to the end node.
+    pub fn typed_multiplicity(&self, start_node: usize, end_node: usize, rel_type: &str) -> usize {
+        let types = self.shared_relationship_types(start_node, end_node);
+        let lower = types.partition_point(|t| t.as_ref() < rel_type);
+        types[lower..].partition_point(|t| t.as_ref() == rel_type)
+    }
+
+    pub(crate) fn shared_relationship_types(
+        &self,
+        start_node: usize,
+        end_node: usize,
+    ) -> &[Rc<str>] {
+        self.validate_node_id(start_node);
+        self.validate_node_id(end_node);
+        self.relationship_types
+            .get(&(start_node, end_node))
+            .map_or(&[], |types| types.as_slice())
+    }
+
+    // Like `to_csr`, but borrows the arrays, e.g. to hand them to another
+    // library. The adjacency lists store each degree in front of its list, so
+    // the CSR arrays are built once on the first call and kept until the
+    // graph is changed.
+    pub fn as_csr(&self) -> (&[usize], &[usize]) {
+        let (indptr, indices) = self.csr.get_or_init(|| self.to_csr());
+        (indptr, indices)
+    }
+
+    // The adjacency matrix in compressed sparse row form as `(indptr, indices)`,
+    // where the end nodes of node `i` are `indices[indptr[i]..indptr[i + 1]]`
+    // in ascending order. Parallel relationships are repeated entries.
+    pub fn to_csr(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut indptr = Vec::with_capacity(self.node_count + 1);
+        let mut indices = Vec::with_capacity(self.relationship_count);
+        indptr.push(0);
+        for node_id in 0..self.node_count {
+            indices.extend_from_slice(self.neighbors(node_id));
+            indptr.push(indices.len());
         }
+        (indptr, indices)
     }
-}
 
-#[derive(Default)]
-pub struct GraphBuilder<T> {
-    node_count: usize,
-    relationship_count: usize,
-    node_labels: HashMap<usize, Rc<T>>,
-    adjacency_lists: HashMap<usize, Vec<usize>>,
-}
+    // The adjacency matrix as a sparse matrix, e.g. for PageRank, where the
+    // entry `(i, j)` counts the relationships from node `i` to node `j`.
+    #[cfg(feature = "sprs")]
+    pub fn to_sparse_matrix(&self) -> sprs::CsMat<usize> {
+        let mut indptr = Vec::with_capacity(self.node_count + 1);
+        let mut indices = Vec::with_capacity(self.relationship_count);
+        let mut data: Vec<usize> = Vec::with_capacity(self.relationship_count);
+        indptr.push(0);
+        for node_id in 0..self.node_count {
+            let start = indices.len();
+            for neighbor in self.neighbors(node_id) {
+                // parallel relationships are adjacent in the sorted lists
+                if indices.len() > start && indices.last() == Some(neighbor) {
+                    *data.last_mut().unwrap() += 1;
+                } else {
+                    indices.push(*neighbor);
+                    data.push(1);
+                }
+            }
+            indptr.push(indices.len());
+        }
+        sprs::CsMat::new((self.node_count, self.node_count), indptr, indices, data)
+    }
 
-impl<T> GraphBuilder<T>
-where
-    T: Eq + Hash,
-{
-    pub fn new() -> Self {
-        GraphBuilder {
-            node_count: 0,
-            relationship_count: 0,
-            node_labels: HashMap::new(),
-            adjacency_lists: HashMap::new(),
+    // A graph where node `i` has `labels[i]` and relationships to the nodes in
+    // `lists[i]`, which need not be sorted. Parallel relationships are
+    // repeated entries.
+    pub fn from_adjacency_lists(lists: Vec<Vec<usize>>, labels: Vec<T>) -> Graph<T> {
+        let node_count = labels.len();
+        if lists.len() != node_count {
+            panic!(
+                "Expected an adjacency list for each of the {} nodes, but got {}.",
+                node_count,
+                lists.len()
+            );
+        }
+        let mut builder = GraphBuilder::new();
+        builder.node_count = node_count;
+        builder.node_labels = labels.into_iter().map(Rc::new).enumerate().collect();
+        for (start_node, list) in lists.into_iter().enumerate() {
+            if let Some(end_node) = list.iter().find(|end_node| **end_node >= node_count) {
+                panic!(
+                    "End node {} of node {} must be within range [0..{}).",
+                    end_node, start_node, node_count
+                );
+            }
+            if list.is_empty() {
+                continue;
+            }
+            if !list.is_sorted() {
+                builder.unsorted_lists.insert(start_node);
+            }
+            builder.relationship_count += list.len();
+            builder.adjacency_lists.insert(start_node, list);
         }
+        builder.build()
     }
 
-    pub fn add_node(&mut self, node_id: usize, node_label: T) -> &mut Self {
-        if node_id > self.node_count {
+    // A graph where node `i` has `labels[i]` and a relationship to node `j` if
+    // `matrix[i][j]` is set.
+    pub fn from_adjacency_matrix(matrix: &[Vec<bool>], labels: Vec<T>) -> Graph<T> {
+        let node_count = labels.len();
+        if let Some(row) = matrix.iter().position(|row| row.len() != node_count) {
             panic!(
-                "Next node id should be within range [0..{}], but was {}.",
-                self.node_count, node_id
-            )
+                "Row {} of the adjacency matrix must have {} entries, but has {}.",
+                row,
+                node_count,
+                matrix[row].len()
+            );
         }
-        if let Entry::Vacant(o) = self.node_labels.entry(node_id) {
-            o.insert(Rc::new(node_label));
-            self.node_count += 1;
+        let lists = matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, is_related)| **is_related)
+                    .map(|(end_node, _)| end_node)
+                    .collect()
+            })
+            .collect();
+        Graph::from_adjacency_lists(lists, labels)
+    }
+
+    // A builder holding the labels and relationships of the graph, e.g. to
+    // build a slightly modified copy. Labels are shared with the graph.
+    pub fn to_builder(&self) -> GraphBuilder<T> {
+        let mut builder = GraphBuilder::new();
+        builder.node_count = self.node_count;
+        builder.relationship_count = self.relationship_count;
+        builder.node_labels = self.node_labels.clone();
+        builder.adjacency_lists = (0..self.node_count)
+            .filter(|node_id| self.degree(*node_id) > 0)
+            .map(|node_id| (node_id, self.neighbors(node_id).to_vec()))
+            .collect();
+        builder.relationship_types = self.relationship_types.clone();
+        builder.properties = self.properties.clone();
+        builder.external_ids = self.external_ids.clone();
+        builder.internal_ids = self.internal_ids.clone();
+        builder.is_label_index_lazy = self.label_idx.get().is_none();
+        builder
+    }
+
+    // The union of both graphs, see `GraphBuilder::extend_from_graph`. Nodes
+    // of this graph keep their ids and labels.
+    pub fn merge(&self, other: &Graph<T>) -> Graph<T> {
+        self.to_builder().extend_from_graph(other).build()
+    }
+
+    // Adds a node with the next free id, which is returned. The adjacency
+    // lists and the label index are updated in place, so changing a built
+    // graph does not require rebuilding it.
+    pub fn add_node(&mut self, node_label: T) -> usize {
+        let node_id = self.node_count;
+        self.node_labels.insert(node_id, Rc::new(node_label));
+        if let Some(label_idx) = self.label_idx.get_mut() {
+            label_idx.insert(&self.node_labels, node_id);
         }
-        self
+        self.offsets.push(0);
+        self.in_offsets.push(0);
+        self.csr.take();
+        self.node_count += 1;
+        node_id
     }
 
-    pub fn add_relationship(&mut self, start_node: usize, end_node: usize) -> &mut Self {
-        if !self.node_labels.contains_key(&start_node) {
-            panic!("Start node {} has not been added yet.", start_node);
+    // Replaces the label of a node, which moves to the bucket of its new label.
+    pub fn set_label(&mut self, node_id: usize, node_label: T) -> &mut Self {
+        self.validate_node_id(node_id);
+        if let Some(label_idx) = self.label_idx.get_mut() {
+            label_idx.remove(&self.node_labels, node_id);
         }
-        if !self.node_labels.contains_key(&end_node) {
-            panic!("End node {} has not been added yet.", end_node);
+        self.node_labels.insert(node_id, Rc::new(node_label));
+        if let Some(label_idx) = self.label_idx.get_mut() {
+            label_idx.insert(&self.node_labels, node_id);
         }
-        self.adjacency_lists
-            .entry(start_node)
-            .or_insert_with(Vec::new)
-            .push(end_node);
+        self
+    }
+
+    // Adds an untyped relationship. Its start node's adjacency list moves to
+    // the end of the lists unless it already is the last one, lists left
+    // behind are reclaimed once they take more space than the graph.
+    pub fn add_relationship(&mut self, start_node: usize, end_node: usize) -> &mut Self {
+        self.validate_node_id(start_node);
+        self.validate_node_id(end_node);
+        self.csr.take();
+        insert_entry(&mut self.offsets, &mut self.lists, start_node, end_node);
+        insert_entry(
+            &mut self.in_offsets,
+            &mut self.in_lists,
+            end_node,
+            start_node,
+        );
         self.relationship_count += 1;
+        self.raw_relationship_count += 1;
+        self.reclaim_lists();
         self
     }
 
-    pub fn build(&mut self) -> Graph<T> {
-        // initialize with 0
-        let mut offsets = vec![0; self.node_count];
-        // position at offset 0 stores the 0-degree
+    // Removes one relationship from start to end, returns false if there is
+    // none. Untyped relationships are removed before typed ones, which are
+    // removed in descending order of their types.
+    pub fn remove_relationship(&mut self, start_node: usize, end_node: usize) -> bool {
+        self.validate_node_id(start_node);
+        self.validate_node_id(end_node);
+        if !remove_entry(&self.offsets, &mut self.lists, start_node, end_node) {
+            return false;
+        }
+        remove_entry(&self.in_offsets, &mut self.in_lists, end_node, start_node);
+        self.csr.take();
+        self.relationship_count -= 1;
+        self.raw_relationship_count -= 1;
+        let multiplicity = self.multiplicity(start_node, end_node);
+        if let Entry::Occupied(mut types) = self.relationship_types.entry((start_node, end_node)) {
+            if types.get().len() > multiplicity {
+                types.get_mut().pop();
+            }
+            if types.get().is_empty() {
+                types.remove();
+            }
+        }
+        self.reclaim_lists();
+        true
+    }
+
+    // Removes the node and its relationships. Nodes with larger ids move down
+    // by one, which takes time linear in the size of the graph.
+    pub fn remove_node(&mut self, node_id: usize) {
+        self.validate_node_id(node_id);
+        let shift = |other: usize| if other > node_id { other - 1 } else { other };
+        let removed =
+            self.degree(node_id) + self.in_degree(node_id) - self.multiplicity(node_id, node_id);
+
+        let mut offsets = Vec::with_capacity(self.node_count - 1);
         let mut lists = vec![0];
+        for other in (0..self.node_count).filter(|other| *other != node_id) {
+            let offset = lists.len();
+            lists.push(0);
+            lists.extend(
+                self.neighbors(other)
+                    .iter()
+                    .filter(|neighbor| **neighbor != node_id)
+                    .map(|neighbor| shift(*neighbor)),
+            );
+            let degree = lists.len() - offset - 1;
+            if degree > 0 {
+                lists[offset] = degree;
+                offsets.push(offset);
+            } else {
+                lists.pop();
+                offsets.push(0);
+            }
+        }
+        let (in_offsets, in_lists) = reverse_lists(&offsets, &lists);
+        self.offsets = offsets;
+        self.lists = lists;
+        self.in_offsets = in_offsets;
+        self.in_lists = in_lists;
+        self.csr.take();
+        self.relationship_count -= removed;
+        self.raw_relationship_count -= removed;
+        self.node_count -= 1;
+
+        self.node_labels = std::mem::take(&mut self.node_labels)
+            .into_iter()
+            .filter(|(other, _)| *other != node_id)
+            .map(|(other, label)| (shift(other), label))
+            .collect();
+        self.properties = std::mem::take(&mut self.properties)
+            .into_iter()
+            .filter(|(other, _)| *other != node_id)
+            .map(|(other, properties)| (shift(other), properties))
+            .collect();
+        if let Some(external_id) = self.external_ids.remove(&node_id) {
+            self.internal_ids.remove(&external_id);
+        }
+        self.external_ids = std::mem::take(&mut self.external_ids)
+            .into_iter()
+            .map(|(other, external_id)| (shift(other), external_id))
+            .collect();
+        for other in self.internal_ids.values_mut() {
+            *other = shift(*other);
+        }
+        self.relationship_types = std::mem::take(&mut self.relationship_types)
+            .into_iter()
+            .filter(|((start, end), _)| *start != node_id && *end != node_id)
+            .map(|((start, end), types)| ((shift(start), shift(end)), types))
+            .collect();
+        if self.label_idx.get().is_some() {
+            self.label_idx = OnceCell::from(build_label_index(&self.node_labels));
+        }
+    }
+
+    // Copies the adjacency lists into place once moved and shrunk lists
+    // left more unused entries behind than there are used ones.
+    fn reclaim_lists(&mut self) {
+        let used = self.node_count + self.relationship_count + 1;
+        if self.lists.len() > 2 * used {
+            self.lists = compact_lists(&mut self.offsets, &self.lists);
+        }
+        if self.in_lists.len() > 2 * used {
+            self.in_lists = compact_lists(&mut self.in_offsets, &self.in_lists);
+        }
+    }
+
+    // Estimated heap size in bytes, not counting heap data owned by the labels
+    // or held by properties. A lazy label index only counts once it has been
+    // built.
+    pub fn memory_usage(&self) -> usize {
+        let usize_size = std::mem::size_of::<usize>();
+        let label_idx = self.label_idx.get().map_or(0, |label_idx| {
+            let vec_size = std::mem::size_of::<Vec<usize>>();
+            let buckets = label_idx
+                .buckets
+                .values()
+                .flatten()
+                .map(|nodes| vec_size + nodes.capacity() * usize_size)
+                .sum::<usize>();
+            label_idx.buckets.capacity() * (std::mem::size_of::<u64>() + vec_size) + buckets
+        });
+        // types are shared between relationships and only count per entry
+        let relationship_types = self.relationship_types.capacity()
+            * (2 * usize_size + std::mem::size_of::<Vec<Rc<str>>>())
+            + self
+                .relationship_types
+                .values()
+                .map(|types| types.capacity() * std::mem::size_of::<Rc<str>>())
+                .sum::<usize>();
+        let external_ids = (self.external_ids.capacity() + self.internal_ids.capacity())
+            * (usize_size + std::mem::size_of::<u64>());
+        let csr = self.csr.get().map_or(0, |(indptr, indices)| {
+            (indptr.capacity() + indices.capacity()) * usize_size
+        });
+        (self.offsets.capacity()
+            + self.lists.capacity()
+            + self.in_offsets.capacity()
+            + self.in_lists.capacity())
+            * usize_size
+            + self.node_labels.capacity() * (usize_size + std::mem::size_of::<Rc<T>>())
+            + label_idx
+            + relationship_types
+            + external_ids
+            + csr
+    }
+
+    // The nodes of each distinct label, in no particular order.
+    pub(crate) fn label_buckets(&self) -> impl Iterator<Item = &[usize]> + '_ {
+        self.label_index()
+            .buckets
+            .values()
+            .flatten()
+            .map(|nodes| nodes.as_slice())
+    }
+
+    fn label_index(&self) -> &LabelIndex {
+        self.label_idx
+            .get_or_init(|| build_label_index(&self.node_labels))
+    }
 
-        let adjacency_lists = std::mem::take(&mut self.adjacency_lists);
-        for (node_id, mut list) in adjacency_lists {
-            let degree = list.len();
-            list.sort_unstable();
-            offsets[node_id] = lists.len();
+    // Hints the CPU to load the adjacency list of the node into the cache
+    // ahead of its use, does nothing for unknown nodes or other platforms.
+    #[inline]
+    pub(crate) fn prefetch_neighbors(&self, node_id: usize) {
+        #[cfg(target_arch = "x86_64")]
+        if let Some(offset) = self.offsets.get(node_id) {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let address = self.lists.as_ptr().wrapping_add(*offset) as *const i8;
+            // prefetching is only a hint and never faults
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(address) };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = node_id;
+    }
 
-            // try to avoid too much resizing, but might have no effect in the end
-            lists.reserve(degree + 1);
-            lists.push(degree);
-            lists.extend(list);
+    pub(crate) fn from_parts(labels: Vec<Rc<T>>, relationships: &[(usize, usize)]) -> Graph<T> {
+        let mut builder = GraphBuilder::new();
+        builder.node_count = labels.len();
+        builder.node_labels = labels.into_iter().enumerate().collect();
+        for (start_node, end_node) in relationships {
+            builder.add_relationship(*start_node, *end_node);
         }
+        builder.build()
+    }
+
+    pub(crate) fn shared_label(&self, node_id: usize) -> Rc<T> {
+        self.validate_node_id(node_id);
+        Rc::clone(&self.node_labels[&node_id])
+    }
+
+    // (offsets, lists, in_offsets, in_lists) as laid out in memory
+    pub(crate) fn adjacency_arrays(&self) -> [&[usize]; 4] {
+        [&self.offsets, &self.lists, &self.in_offsets, &self.in_lists]
+    }
 
-        // Build label index
-        let mut label_idx = HashMap::new();
-        for (node_id, label) in self.node_labels.iter() {
-            label_idx
-                .entry(Rc::clone(label))
-                .or_insert_with(Vec::new)
-                .push(*node_id);
+    pub(crate) fn typed_relationships(
+        &self,
+    ) -> impl Iterator<Item = (&(usize, usize), &[Rc<str>])> + '_ {
+        self.relationship_types
+            .iter()
+            .map(|(pair, types)| (pair, types.as_slice()))
+    }
+
+    pub(crate) fn node_properties(&self) -> &HashMap<usize, HashMap<String, Value>> {
+        &self.properties
+    }
+
+    pub(crate) fn external_ids(&self) -> &HashMap<usize, u64> {
+        &self.external_ids
+    }
+
+    // Assembles a graph from arrays that are known to be consistent, e.g. a
+    // validated snapshot, without building any of them.
+    pub(crate) fn from_raw(raw: RawGraph<T>) -> Graph<T> {
+        let label_idx = OnceCell::new();
+        if let Some(buckets) = raw.label_buckets {
+            let mut label_idx_buckets: HashMap<u64, Vec<Vec<usize>>> = HashMap::new();
+            for nodes in buckets {
+                label_idx_buckets
+                    .entry(label_hash(&raw.node_labels[&nodes[0]]))
+                    .or_default()
+                    .push(nodes);
+            }
+            let _ = label_idx.set(LabelIndex {
+                buckets: label_idx_buckets,
+            });
+        }
+        Graph {
+            node_count: raw.node_labels.len(),
+            relationship_count: raw.relationship_count,
+            raw_relationship_count: raw.raw_relationship_count,
+            node_labels: raw.node_labels,
+            label_idx,
+            offsets: raw.offsets,
+            lists: raw.lists,
+            in_offsets: raw.in_offsets,
+            in_lists: raw.in_lists,
+            csr: OnceCell::new(),
+            relationship_types: raw.relationship_types,
+            properties: raw.properties,
+            internal_ids: raw
+                .external_ids
+                .iter()
+                .map(|(node_id, external_id)| (*external_id, *node_id))
+                .collect(),
+            external_ids: raw.external_ids,
         }
+    }
 
+    // The transposed graph, in which every relationship points from its end
+    // node to its start node, e.g. to match a pattern with inverted
+    // directions. Shares the labels with this graph and keeps the types,
+    // properties and external ids.
+    pub fn reverse(&self) -> Graph<T> {
         Graph {
             node_count: self.node_count,
             relationship_count: self.relationship_count,
-            node_labels: std::mem::take(&mut self.node_labels),
-            label_idx,
-            offsets,
-            lists,
+            raw_relationship_count: self.raw_relationship_count,
+            node_labels: self.node_labels.clone(),
+            label_idx: self.label_idx.clone(),
+            // both directions are sorted, so they only change places
+            offsets: self.in_offsets.clone(),
+            lists: self.in_lists.clone(),
+            in_offsets: self.offsets.clone(),
+            in_lists: self.lists.clone(),
+            csr: OnceCell::new(),
+            relationship_types: self
+                .relationship_types
+                .iter()
+                .map(|((start_node, end_node), types)| ((*end_node, *start_node), types.clone()))
+                .collect(),
+            properties: self.properties.clone(),
+            external_ids: self.external_ids.clone(),
+            internal_ids: self.internal_ids.clone(),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn induced_subgraph(&self, nodes: &[usize]) -> Graph<T> {
+        let mut mapping = HashMap::with_capacity(nodes.len());
+        let mut builder = GraphBuilder::new();
+        for node_id in nodes {
+            self.validate_node_id(*node_id);
+            if let Entry::Vacant(o) = mapping.entry(*node_id) {
+                let new_id = builder.node_count;
+                o.insert(new_id);
+                builder
+                    .node_labels
+                    .insert(new_id, Rc::clone(&self.node_labels[node_id]));
+                builder.node_count += 1;
+            }
+        }
+        for (node_id, new_id) in mapping.iter() {
+            for neighbor in self.neighbors(*node_id) {
+                if let Some(new_neighbor) = mapping.get(neighbor) {
+                    builder.add_relationship(*new_id, *new_neighbor);
+                }
+            }
+        }
+        for (node_id, new_id) in mapping.iter() {
+            if let Some(properties) = self.properties.get(node_id) {
+                builder.properties.insert(*new_id, properties.clone());
+            }
+            if let Some(external_id) = self.external_ids.get(node_id) {
+                builder.external_ids.insert(*new_id, *external_id);
+                builder.internal_ids.insert(*external_id, *new_id);
+            }
+        }
+        for ((start_node, end_node), types) in &self.relationship_types {
+            if let (Some(new_start), Some(new_end)) =
+                (mapping.get(start_node), mapping.get(end_node))
+            {
+                builder
+                    .relationship_types
+                    .insert((*new_start, *new_end), types.clone());
+            }
+        }
+        builder.build()
+    }
 
-    #[test]
-    fn test_node_count() {
-        let graph = GraphBuilder::new()
-            .add_node(0, "foo")
-            .add_node(1, "bar")
-            .build();
-        assert_eq!(2, graph.node_count())
+    pub fn filter_labels(&self, labels: &[T]) -> Subgraph<T> {
+        let mut nodes = labels
+            .iter()
+            .flat_map(|label| self.nodes_by_label(label))
+            .copied()
+            .collect::<Vec<_>>();
+        nodes.sort_unstable();
+        nodes.dedup();
+        Subgraph::induced(self, nodes)
     }
 
-    #[test]
-    #[should_panic(expected = "Next node id should be within range [0..1], but was 2.")]
-    fn test_add_invalid_node() {
-        let _ = GraphBuilder::new()
-            .add_node(0, "foo")
-            .add_node(2, "bar")
-            .build();
+    // The subgraph induced by the nodes within the given distance of the
+    // center, ignoring relationship directions.
+    pub fn ball(&self, center: usize, radius: usize) -> Subgraph<T> {
+        self.validate_node_id(center);
+        let nodes = distances(&undirected_adjacency(self), &[center], radius)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, distance)| distance.is_some())
+            .map(|(node_id, _)| node_id)
+            .collect();
+        Subgraph::induced(self, nodes)
     }
 
-    #[test]
-    fn test_relationship_count() {
-        let graph = GraphBuilder::new()
-            .add_node(0, "foo")
-            .add_node(1, "bar")
-            .add_relationship(0, 1)
-            .build();
-        assert_eq!(1, graph.relationship_count())
+    // The paths from the start to the end node along relationship directions
+    // with at most `max_length` relationships that visit no node twice, in
+    // ascending order of their node ids. Parallel relationships yield a path
+    // once. Meant to inspect why a path pattern does not match.
+    pub fn simple_paths(
+        &self,
+        start_node: usize,
+        end_node: usize,
+        max_length: usize,
+    ) -> Vec<Vec<usize>> {
+        fn extend<T: Eq + Hash>(
+            graph: &Graph<T>,
+            remaining: &[Option<usize>],
+            end_node: usize,
+            path: &mut Vec<usize>,
+            paths: &mut Vec<Vec<usize>>,
+            max_length: usize,
+        ) {
+            let last = path[path.len() - 1];
+            if last == end_node {
+                paths.push(path.clone());
+                return;
+            }
+            for (neighbor, _) in graph.distinct_neighbors(last) {
+                // the end node has to stay within reach
+                let is_reachable =
+                    remaining[neighbor].is_some_and(|distance| path.len() + distance <= max_length);
+                if is_reachable && !path.contains(&neighbor) {
+                    path.push(neighbor);
+                    extend(graph, remaining, end_node, path, paths, max_length);
+                    path.pop();
+                }
+            }
+        }
+
+        self.validate_node_id(start_node);
+        self.validate_node_id(end_node);
+        // distances to the end node against relationship directions
+        let mut remaining = vec![None; self.node_count];
+        remaining[end_node] = Some(0);
+        let mut frontier = vec![end_node];
+        for distance in 1..=max_length {
+            let mut next = vec![];
+            for node_id in frontier {
+                for parent in self.in_neighbors(node_id) {
+                    if remaining[*parent].is_none() {
+                        remaining[*parent] = Some(distance);
+                        next.push(*parent);
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        let mut paths = vec![];
+        if remaining[start_node].is_some() {
+            let mut path = vec![start_node];
+            extend(
+                self, &remaining, end_node, &mut path, &mut paths, max_length,
+            );
+        }
+        paths
     }
 
-    #[test]
-    #[should_panic(expected = "Start node 0 has not been added yet")]
-    fn test_add_relationship_for_invalid_start_node() {
-        let _ = GraphBuilder::<&str>::new().add_relationship(0, 1).build();
+    pub fn weakly_connected_components(&self) -> Vec<Vec<usize>> {
+        fn find(parents: &mut [usize], node_id: usize) -> usize {
+            let mut root = node_id;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            let mut current = node_id;
+            while parents[current] != root {
+                let next = parents[current];
+                parents[current] = root;
+                current = next;
+            }
+            root
+        }
+
+        // union-find over all relationships, ignoring their direction
+        let mut parents = (0..self.node_count).collect::<Vec<_>>();
+        for node_id in 0..self.node_count {
+            for neighbor in self.neighbors(node_id) {
+                let (a, b) = (find(&mut parents, node_id), find(&mut parents, *neighbor));
+                if a != b {
+                    parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        // components are ordered by their smallest node id
+        let mut component_idx = HashMap::new();
+        let mut components: Vec<Vec<usize>> = vec![];
+        for node_id in 0..self.node_count {
+            let root = find(&mut parents, node_id);
+            let idx = *component_idx.entry(root).or_insert_with(|| {
+                components.push(vec![]);
+                components.len() - 1
+            });
+            components[idx].push(node_id);
+        }
+        components
     }
 
-    #[test]
-    #[should_panic(expected = "End node 1 has not been added yet")]
-    fn test_add_relationship_for_invalid_end_node() {
-        let _ = GraphBuilder::new()
-            .add_node(0, "foo")
-            .add_relationship(0, 1)
-            .build();
+    pub fn diff(&self, other: &Graph<T>) -> GraphDiff {
+        GraphDiff::new(self, other)
     }
 
-    #[test]
-    fn test_node_label() {
-        let graph = GraphBuilder::new()
-            .add_node(0, "foo")
-            .add_node(1, "bar")
-            .build();
-        assert_eq!("foo", *graph.node_label(0));
-        assert_eq!("bar", *graph.node_label(1))
+    fn validate_node_id(&self, node_id: usize) {
+        if node_id >= self.node_count {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                node_id, self.node_count
+            )
+        }
     }
+}
 
-    #[test]
-    #[should_panic(expected = "Node id 1 must be within range [0..1).")]
-    fn test_node_label_for_invalid_node() {
-        let graph = GraphBuilder::new().add_node(0, "foo").build();
-        let _ = *graph.node_label(1);
+// The parts of a graph as stored in a snapshot, see `Graph::from_raw`.
+pub(crate) struct RawGraph<T> {
+    pub(crate) relationship_count: usize,
+    pub(crate) raw_relationship_count: usize,
+    pub(crate) node_labels: HashMap<usize, Rc<T>>,
+    // the nodes per label, or None for a lazy label index
+    pub(crate) label_buckets: Option<Vec<Vec<usize>>>,
+    pub(crate) offsets: Vec<usize>,
+    pub(crate) lists: Vec<usize>,
+    pub(crate) in_offsets: Vec<usize>,
+    pub(crate) in_lists: Vec<usize>,
+    pub(crate) relationship_types: HashMap<(usize, usize), Vec<Rc<str>>>,
+    pub(crate) properties: HashMap<usize, HashMap<String, Value>>,
+    pub(crate) external_ids: HashMap<usize, u64>,
+}
+
+// A graph derived from another graph, whose node ids can be translated back.
+pub struct Subgraph<T> {
+    graph: Graph<T>,
+    original_ids: Vec<usize>,
+}
+
+impl<T> Subgraph<T>
+where
+    T: Eq + Hash,
+{
+    // node `i` of the subgraph is `nodes[i]`, which must be distinct
+    pub(crate) fn induced(graph: &Graph<T>, nodes: Vec<usize>) -> Self {
+        Subgraph {
+            graph: graph.induced_subgraph(&nodes),
+            original_ids: nodes,
+        }
     }
 
-    #[test]
-    fn test_degree() {
-        let graph = GraphBuilder::new()
-            .add_node(0, "foo")
-            .add_node(1, "bar")
-            .add_node(2, "baz")
-            .add_relationship(0, 1)
-            .add_relationship(0, 2)
-            .add_relationship(1, 2)
-            .build();
+    pub(crate) fn detach(&self) -> (DetachedGraph<T>, Vec<usize>)
+    where
+        T: Clone,
+    {
+        (self.graph.detach(), self.original_ids.clone())
+    }
 
-        assert_eq!(2, graph.degree(0));
-        assert_eq!(1, graph.degree(1));
-        assert_eq!(0, graph.degree(2));
+    pub(crate) fn attach(graph: DetachedGraph<T>, original_ids: Vec<usize>) -> Self {
+        Subgraph {
+            graph: graph.attach(),
+            original_ids,
+        }
     }
+}
 
-    #[test]
-    #[should_panic(expected = "Node id 1 must be within range [0..1).")]
-    fn test_degree_for_invalid_node() {
-        let graph = GraphBuilder::new().add_node(0, "foo").build();
-        let _ = graph.degree(1);
+impl<T> Subgraph<T> {
+    pub fn graph(&self) -> &Graph<T> {
+        &self.graph
     }
 
-    #[test]
-    fn test_neighbors() {
-        let graph = GraphBuilder::new()
-            .add_node(0, "foo")
-            .add_node(1, "bar")
-            .add_node(2, "baz")
-            .add_node(3, "boo")
-            .add_relationship(0, 2)
-            .add_relationship(0, 1)
-            .add_relationship(0, 0)
-            .add_relationship(0, 3)
-            .add_relationship(1, 2)
-            .build();
+    pub fn into_graph(self) -> Graph<T> {
+        self.graph
+    }
 
-        let empty: &[usize; 0] = &[];
+    pub(crate) fn original_ids(&self) -> &[usize] {
+        &self.original_ids
+    }
 
-        assert_eq!(&[0, 1, 2, 3], graph.neighbors(0));
-        assert_eq!(&[2], graph.neighbors(1));
-        assert_eq!(empty, graph.neighbors(2))
+    pub fn original_id(&self, node_id: usize) -> usize {
+        self.original_ids[node_id]
+    }
+
+    // translates a match in the subgraph into a match in the original graph
+    pub fn to_original(&self, embedding: &[usize]) -> Vec<usize> {
+        embedding
+            .iter()
+            .map(|node_id| self.original_ids[*node_id])
+            .collect()
+    }
+}
+
+// A deep copy of a graph that shares no allocations with it, unlike `Graph`
+// whose labels and types are reference counted. It can be sent to another
+// thread, which turns it into its own graph with `attach`.
+pub(crate) struct DetachedGraph<T> {
+    labels: Vec<T>,
+    label_buckets: Vec<Vec<usize>>,
+    relationship_count: usize,
+    raw_relationship_count: usize,
+    adjacency: [Vec<usize>; 4],
+    relationship_types: Vec<((usize, usize), Vec<String>)>,
+    properties: HashMap<usize, HashMap<String, Value>>,
+    external_ids: HashMap<usize, u64>,
+}
+
+impl<T> Graph<T>
+where
+    T: Eq + Hash + Clone,
+{
+    pub(crate) fn detach(&self) -> DetachedGraph<T> {
+        DetachedGraph {
+            labels: self
+                .node_ids()
+                .map(|node_id| self.node_label(node_id).clone())
+                .collect(),
+            label_buckets: self.label_buckets().map(<[usize]>::to_vec).collect(),
+            relationship_count: self.relationship_count,
+            raw_relationship_count: self.raw_relationship_count,
+            adjacency: self.adjacency_arrays().map(<[usize]>::to_vec),
+            relationship_types: self
+                .typed_relationships()
+                .map(|(pair, types)| (*pair, types.iter().map(|t| t.to_string()).collect()))
+                .collect(),
+            properties: self.properties.clone(),
+            external_ids: self.external_ids.clone(),
+        }
+    }
+}
+
+impl<T> DetachedGraph<T>
+where
+    T: Eq + Hash,
+{
+    pub(crate) fn attach(self) -> Graph<T> {
+        let [offsets, lists, in_offsets, in_lists] = self.adjacency;
+        let mut interned: HashMap<String, Rc<str>> = HashMap::new();
+        let relationship_types = self
+            .relationship_types
+            .into_iter()
+            .map(|(pair, types)| {
+                let types = types
+                    .into_iter()
+                    .map(|rel_type| {
+                        let rel_type = interned
+                            .entry(rel_type)
+                            .or_insert_with_key(|rel_type| Rc::from(rel_type.as_str()));
+                        Rc::clone(rel_type)
+                    })
+                    .collect();
+                (pair, types)
+            })
+            .collect();
+        // nodes with the same label share it again, as in the builder
+        let mut node_labels = HashMap::with_capacity(self.labels.len());
+        let mut labels = self.labels.into_iter().map(Some).collect::<Vec<_>>();
+        for nodes in &self.label_buckets {
+            let label = Rc::new(labels[nodes[0]].take().unwrap());
+            for node_id in nodes {
+                node_labels.insert(*node_id, Rc::clone(&label));
+            }
+        }
+        Graph::from_raw(RawGraph {
+            relationship_count: self.relationship_count,
+            raw_relationship_count: self.raw_relationship_count,
+            node_labels,
+            label_buckets: Some(self.label_buckets),
+            offsets,
+            lists,
+            in_offsets,
+            in_lists,
+            relationship_types,
+            properties: self.properties,
+            external_ids: self.external_ids,
+        })
+    }
+}
+
+// Cloning shares the labels with the original graph.
+impl<T> Clone for Graph<T> {
+    fn clone(&self) -> Self {
+        Graph {
+            node_count: self.node_count,
+            relationship_count: self.relationship_count,
+            raw_relationship_count: self.raw_relationship_count,
+            node_labels: self.node_labels.clone(),
+            label_idx: self.label_idx.clone(),
+            csr: self.csr.clone(),
+            offsets: self.offsets.clone(),
+            lists: self.lists.clone(),
+            in_offsets: self.in_offsets.clone(),
+            in_lists: self.in_lists.clone(),
+            relationship_types: self.relationship_types.clone(),
+            properties: self.properties.clone(),
+            external_ids: self.external_ids.clone(),
+            internal_ids: self.internal_ids.clone(),
+        }
+    }
+}
+
+// Graphs are equal if they have the same labels and neighbors per node id,
+// independent of how the adjacency lists are laid out internally. Properties
+// are not compared, as float values are not reflexive.
+impl<T> PartialEq for Graph<T>
+where
+    T: Eq + Hash,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.node_count == other.node_count
+            && self.relationship_count == other.relationship_count
+            && (0..self.node_count).all(|node_id| {
+                self.node_label(node_id) == other.node_label(node_id)
+                    && self.neighbors(node_id) == other.neighbors(node_id)
+            })
+            && self.relationship_types == other.relationship_types
+    }
+}
+
+impl<T> Eq for Graph<T> where T: Eq + Hash {}
+
+impl<T> fmt::Debug for Graph<T>
+where
+    T: Eq + Hash + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Graph")
+            .field(
+                "nodes",
+                &(0..self.node_count)
+                    .map(|node_id| (node_id, self.node_label(node_id)))
+                    .collect::<Vec<_>>(),
+            )
+            .field("relationships", &self.edges().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+// Graphs built from fuzzer input, e.g. `fuzz_target!(|graph: Graph<u8>| ..)`.
+// The node count is bounded, degrees and label buckets are not, so inputs
+// reach empty buckets, hubs, self-loops and parallel relationships.
+#[cfg(feature = "fuzzing")]
+impl<T> Graph<T>
+where
+    T: Eq + Hash,
+{
+    pub fn arbitrary_from<'a>(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Graph<T>>
+    where
+        T: arbitrary::Arbitrary<'a>,
+    {
+        let node_count = u.int_in_range(0..=MAX_ARBITRARY_NODES)?;
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..node_count {
+            builder.add_node(node_id, T::arbitrary(u)?);
+        }
+        if node_count > 0 {
+            for _ in 0..u.arbitrary_len::<(u8, u8)>()? {
+                let start_node = u.choose_index(node_count)?;
+                let end_node = u.choose_index(node_count)?;
+                match u.choose(&[None, Some("A"), Some("B")])? {
+                    Some(rel_type) => {
+                        builder.add_relationship_with_type(start_node, end_node, rel_type)
+                    }
+                    None => builder.add_relationship(start_node, end_node),
+                };
+            }
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(feature = "fuzzing")]
+const MAX_ARBITRARY_NODES: usize = 64;
+
+#[cfg(feature = "fuzzing")]
+impl<'a, T> arbitrary::Arbitrary<'a> for Graph<T>
+where
+    T: Eq + Hash + arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Graph::arbitrary_from(u)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DuplicateNodes {
+    /// Adding a node twice fails: `try_add_node` and `try_extend_from_graph`
+    /// return an error, `add_node` and `extend_from_graph` panic.
+    Error,
+    /// Adding a node twice keeps the first label.
+    #[default]
+    KeepFirst,
+    /// Adding a node twice replaces its label.
+    Overwrite,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateNodeError {
+    node_id: usize,
+}
+
+impl DuplicateNodeError {
+    pub fn node_id(&self) -> usize {
+        self.node_id
+    }
+}
+
+impl fmt::Display for DuplicateNodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Node {} has already been added.", self.node_id)
+    }
+}
+
+impl Error for DuplicateNodeError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeError {
+    /// The node has already been added and duplicates are an error.
+    Duplicate(DuplicateNodeError),
+    /// The node id skips over the next dense node id.
+    OutOfRange { node_id: usize, node_count: usize },
+}
+
+impl From<DuplicateNodeError> for NodeError {
+    fn from(error: DuplicateNodeError) -> Self {
+        NodeError::Duplicate(error)
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeError::Duplicate(error) => error.fmt(f),
+            NodeError::OutOfRange {
+                node_id,
+                node_count,
+            } => write!(
+                f,
+                "Next node id should be within range [0..{}], but was {}.",
+                node_count, node_id
+            ),
+        }
+    }
+}
+
+impl Error for NodeError {}
+
+#[derive(Default)]
+pub struct GraphBuilder<T> {
+    node_count: usize,
+    relationship_count: usize,
+    node_labels: HashMap<usize, Rc<T>>,
+    adjacency_lists: HashMap<usize, Vec<usize>>,
+    relationship_types: HashMap<(usize, usize), Vec<Rc<str>>>,
+    properties: HashMap<usize, HashMap<String, Value>>,
+    external_ids: HashMap<usize, u64>,
+    internal_ids: HashMap<u64, usize>,
+    // each distinct type is allocated once
+    interned_types: HashSet<Rc<str>>,
+    // start nodes whose relationships have not been added in end node order
+    unsorted_lists: HashSet<usize>,
+    is_label_index_lazy: bool,
+    duplicate_nodes: DuplicateNodes,
+    dedupe_relationships: bool,
+    sort_neighbors: bool,
+    // relationships added with `add_shards`
+    shards: Vec<RelationshipShard>,
+    threads: Option<usize>,
+    // labels of nodes created by `add_relationship`
+    implicit_label: Option<Box<dyn Fn(usize) -> T>>,
+}
+
+impl<T> GraphBuilder<T>
+where
+    T: Eq + Hash,
+{
+    pub fn new() -> Self {
+        GraphBuilder {
+            node_count: 0,
+            relationship_count: 0,
+            node_labels: HashMap::new(),
+            adjacency_lists: HashMap::new(),
+            relationship_types: HashMap::new(),
+            interned_types: HashSet::new(),
+            properties: HashMap::new(),
+            external_ids: HashMap::new(),
+            internal_ids: HashMap::new(),
+            unsorted_lists: HashSet::new(),
+            is_label_index_lazy: false,
+            duplicate_nodes: DuplicateNodes::default(),
+            dedupe_relationships: false,
+            sort_neighbors: true,
+            shards: vec![],
+            threads: None,
+            implicit_label: None,
+        }
+    }
+
+    // Defers building the label index until the graph is first queried by
+    // label, which saves time and memory for graphs that never are. The graph
+    // is not `Sync`, so the index is built by whichever single thread queries
+    // it first.
+    pub fn lazy_label_index(&mut self) -> &mut Self {
+        self.is_label_index_lazy = true;
+        self
+    }
+
+    // Lets `add_relationship` create missing end points with the given label
+    // instead of panicking, e.g. for edge lists without a node list. Since node
+    // ids are dense, all missing nodes up to the end point are created.
+    pub fn implicit_nodes(&mut self, node_label: T) -> &mut Self
+    where
+        T: Clone + 'static,
+    {
+        self.implicit_nodes_with(move |_| node_label.clone())
+    }
+
+    // Like `implicit_nodes`, but labels each created node by its id.
+    pub fn implicit_nodes_with<F>(&mut self, node_label: F) -> &mut Self
+    where
+        F: Fn(usize) -> T + 'static,
+    {
+        self.implicit_label = Some(Box::new(node_label));
+        self
+    }
+
+    // Drops parallel relationships in `build`, which turns the multigraph into
+    // a simple graph. Disabled by default.
+    pub fn dedupe_edges(&mut self, dedupe_edges: bool) -> &mut Self {
+        self.dedupe_relationships = dedupe_edges;
+        self
+    }
+
+    // The matcher requires adjacency lists in end node order, so `build` sorts
+    // them by default. Disabling the sorting is only valid if relationships
+    // are added in order per start node, `build` panics otherwise.
+    pub fn sort_neighbors(&mut self, sort_neighbors: bool) -> &mut Self {
+        self.sort_neighbors = sort_neighbors;
+        self
+    }
+
+    // The number of threads `build` uses to build the adjacency lists, default
+    // is 1, so building in parallel is opt-in. With more threads, each
+    // direction is built in three parallel passes, counting the degrees,
+    // placing the end nodes and sorting.
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        if threads == 0 {
+            panic!("Thread count must be positive.");
+        }
+        self.threads = Some(threads);
+        self
+    }
+
+    // How adding an existing node is handled, default is to keep its label.
+    pub fn duplicate_nodes(&mut self, duplicate_nodes: DuplicateNodes) -> &mut Self {
+        self.duplicate_nodes = duplicate_nodes;
+        self
+    }
+
+    pub fn add_node(&mut self, node_id: usize, node_label: T) -> &mut Self {
+        if let Err(e) = self.try_add_node(node_id, node_label) {
+            panic!("{}", e)
+        }
+        self
+    }
+
+    // Like `add_node`, but returns an error instead of panicking if the node
+    // id is out of range, or if the node has been added before and duplicates
+    // are an error.
+    pub fn try_add_node(&mut self, node_id: usize, node_label: T) -> Result<&mut Self, NodeError> {
+        if node_id > self.node_count {
+            return Err(NodeError::OutOfRange {
+                node_id,
+                node_count: self.node_count,
+            });
+        }
+        match self.node_labels.entry(node_id) {
+            Entry::Vacant(o) => {
+                o.insert(Rc::new(node_label));
+                self.node_count += 1;
+            }
+            Entry::Occupied(mut o) => match self.duplicate_nodes {
+                DuplicateNodes::Error => return Err(DuplicateNodeError { node_id }.into()),
+                DuplicateNodes::KeepFirst => {}
+                DuplicateNodes::Overwrite => {
+                    o.insert(Rc::new(node_label));
+                }
+            },
+        }
+        Ok(self)
+    }
+
+    // Adds a node with an arbitrary id, e.g. from an export, which is mapped
+    // to the next dense node id. Adding an id twice is handled like adding
+    // its dense node id twice.
+    pub fn add_external_node(&mut self, external_id: u64, node_label: T) -> &mut Self {
+        let node_id = match self.internal_ids.get(&external_id) {
+            Some(node_id) => *node_id,
+            None => {
+                let node_id = self.node_count;
+                self.internal_ids.insert(external_id, node_id);
+                self.external_ids.insert(node_id, external_id);
+                node_id
+            }
+        };
+        self.add_node(node_id, node_label)
+    }
+
+    // Adds a relationship between nodes added with `add_external_node`.
+    pub fn add_external_relationship(&mut self, start_id: u64, end_id: u64) -> &mut Self {
+        let internal_id = |external_id: u64| match self.internal_ids.get(&external_id) {
+            Some(node_id) => *node_id,
+            None => panic!(
+                "Node with external id {} has not been added yet.",
+                external_id
+            ),
+        };
+        let (start_node, end_node) = (internal_id(start_id), internal_id(end_id));
+        self.add_relationship(start_node, end_node)
+    }
+
+    // Replaces the label of a node that has already been added.
+    pub fn set_label(&mut self, node_id: usize, node_label: T) -> &mut Self {
+        match self.node_labels.get_mut(&node_id) {
+            Some(label) => *label = Rc::new(node_label),
+            None => panic!("Node {} has not been added yet.", node_id),
+        }
+        self
+    }
+
+    // Sets a property of a node that has already been added, replacing the
+    // previous value of the key.
+    pub fn set_property<V: Into<Value>>(
+        &mut self,
+        node_id: usize,
+        key: &str,
+        value: V,
+    ) -> &mut Self {
+        if !self.node_labels.contains_key(&node_id) {
+            panic!("Node {} has not been added yet.", node_id);
+        }
+        self.properties
+            .entry(node_id)
+            .or_default()
+            .insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn add_relationship(&mut self, start_node: usize, end_node: usize) -> &mut Self {
+        if let Some(implicit_label) = &self.implicit_label {
+            while self.node_count <= start_node.max(end_node) {
+                let node_id = self.node_count;
+                self.node_labels
+                    .insert(node_id, Rc::new(implicit_label(node_id)));
+                self.node_count += 1;
+            }
+        }
+        if !self.node_labels.contains_key(&start_node) {
+            panic!("Start node {} has not been added yet.", start_node);
+        }
+        if !self.node_labels.contains_key(&end_node) {
+            panic!("End node {} has not been added yet.", end_node);
+        }
+        let list = self.adjacency_lists.entry(start_node).or_default();
+        if list.last().is_some_and(|last| *last > end_node) {
+            self.unsorted_lists.insert(start_node);
+        }
+        list.push(end_node);
+        self.relationship_count += 1;
+        self
+    }
+
+    // Adds a relationship of the given type, e.g. "KNOWS". Pattern
+    // relationships with a type only match graph relationships of the same
+    // type, pattern relationships without a type match any relationship.
+    pub fn add_relationship_with_type(
+        &mut self,
+        start_node: usize,
+        end_node: usize,
+        rel_type: &str,
+    ) -> &mut Self {
+        self.add_relationship(start_node, end_node);
+        let rel_type = match self.interned_types.get(rel_type) {
+            Some(rel_type) => Rc::clone(rel_type),
+            None => {
+                let rel_type = Rc::<str>::from(rel_type);
+                self.interned_types.insert(Rc::clone(&rel_type));
+                rel_type
+            }
+        };
+        self.relationship_types
+            .entry((start_node, end_node))
+            .or_default()
+            .push(rel_type);
+        self
+    }
+
+    // Adds the relationships collected in the shards, e.g. by one thread per
+    // part of the input. Their adjacency lists are built in parallel and are
+    // sorted regardless of `sort_neighbors`.
+    pub fn add_shards<I>(&mut self, shards: I) -> &mut Self
+    where
+        I: IntoIterator<Item = RelationshipShard>,
+    {
+        for shard in shards {
+            if let Some(implicit_label) = &self.implicit_label {
+                let max_node = shard
+                    .relationships()
+                    .iter()
+                    .map(|(start_node, end_node)| *start_node.max(end_node))
+                    .max();
+                while max_node.is_some_and(|max_node| self.node_count <= max_node) {
+                    let node_id = self.node_count;
+                    self.node_labels
+                        .insert(node_id, Rc::new(implicit_label(node_id)));
+                    self.node_count += 1;
+                }
+            }
+            for (start_node, end_node) in shard.relationships() {
+                if *start_node >= self.node_count {
+                    panic!("Start node {} has not been added yet.", start_node);
+                }
+                if *end_node >= self.node_count {
+                    panic!("End node {} has not been added yet.", end_node);
+                }
+            }
+            self.relationship_count += shard.len();
+            self.shards.push(shard);
+        }
+        self
+    }
+
+    // Adds relationships that are sorted by start node and then by end node,
+    // e.g. from a sorted export. Their adjacency lists need no sorting in
+    // `build`. Debug builds check the order.
+    pub fn add_sorted_relationships<I>(&mut self, relationships: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        let mut previous: Option<(usize, usize)> = None;
+        for relationship in relationships {
+            if cfg!(debug_assertions) {
+                if let Some(previous) = previous.filter(|previous| *previous > relationship) {
+                    panic!(
+                        "Relationships must be sorted, but ({}, {}) follows ({}, {}).",
+                        relationship.0, relationship.1, previous.0, previous.1
+                    );
+                }
+                previous = Some(relationship);
+            }
+            self.add_relationship(relationship.0, relationship.1);
+        }
+        self
+    }
+
+    // Adds the nodes and relationships of a graph, e.g. one loaded from
+    // another source. Nodes with an external id that has been added before
+    // are merged with that node like adding the id twice, so `duplicate_nodes`
+    // decides their label and properties. All other nodes get the next free
+    // ids in the order of the graph. Relationships both graphs have become
+    // parallel relationships unless `dedupe_edges` is enabled.
+    pub fn extend_from_graph(&mut self, graph: &Graph<T>) -> &mut Self {
+        if let Err(e) = self.try_extend_from_graph(graph) {
+            panic!("{}", e)
+        }
+        self
+    }
+
+    // Like `extend_from_graph`, but returns an error instead of panicking if
+    // a node has been added before and duplicates are an error. Nothing is
+    // added then.
+    pub fn try_extend_from_graph(
+        &mut self,
+        graph: &Graph<T>,
+    ) -> Result<&mut Self, DuplicateNodeError> {
+        if self.duplicate_nodes == DuplicateNodes::Error {
+            let duplicate = graph
+                .node_ids()
+                .filter_map(|node_id| graph.to_external_id(node_id))
+                .find_map(|external_id| self.internal_ids.get(&external_id).copied());
+            if let Some(node_id) = duplicate {
+                return Err(DuplicateNodeError { node_id });
+            }
+        }
+        let mut mapping = Vec::with_capacity(graph.node_count());
+        for node_id in graph.node_ids() {
+            let existing = graph
+                .to_external_id(node_id)
+                .and_then(|external_id| self.internal_ids.get(&external_id).copied());
+            let label = &graph.node_labels[&node_id];
+            let new_id = match existing {
+                Some(new_id) => {
+                    // duplicates under `DuplicateNodes::Error` have been rejected above
+                    if self.duplicate_nodes == DuplicateNodes::Overwrite {
+                        self.node_labels.insert(new_id, Rc::clone(label));
+                    }
+                    new_id
+                }
+                None => {
+                    let new_id = self.node_count;
+                    self.node_labels.insert(new_id, Rc::clone(label));
+                    self.node_count += 1;
+                    if let Some(external_id) = graph.to_external_id(node_id) {
+                        self.external_ids.insert(new_id, external_id);
+                        self.internal_ids.insert(external_id, new_id);
+                    }
+                    new_id
+                }
+            };
+            if let Some(properties) = graph.properties.get(&node_id) {
+                let new_properties = self.properties.entry(new_id).or_default();
+                for (key, value) in properties {
+                    if existing.is_none() || self.duplicate_nodes == DuplicateNodes::Overwrite {
+                        new_properties.insert(key.clone(), value.clone());
+                    } else {
+                        new_properties
+                            .entry(key.clone())
+                            .or_insert_with(|| value.clone());
+                    }
+                }
+            }
+            mapping.push(new_id);
+        }
+        for (start_node, end_node) in graph.edges() {
+            self.add_relationship(mapping[start_node], mapping[end_node]);
+        }
+        for ((start_node, end_node), types) in &graph.relationship_types {
+            for rel_type in types {
+                let rel_type = match self.interned_types.get(rel_type) {
+                    Some(rel_type) => Rc::clone(rel_type),
+                    None => {
+                        self.interned_types.insert(Rc::clone(rel_type));
+                        Rc::clone(rel_type)
+                    }
+                };
+                self.relationship_types
+                    .entry((mapping[*start_node], mapping[*end_node]))
+                    .or_default()
+                    .push(rel_type);
+            }
+        }
+        Ok(self)
+    }
+
+    // Builds the graph. The adjacency lists are only built in parallel with
+    // `threads` above 1 or with shards from `add_shards`, otherwise the build
+    // runs on the calling thread. Deduping and the label index are always
+    // built sequentially.
+    pub fn build(&mut self) -> Graph<T> {
+        let mut adjacency_lists = std::mem::take(&mut self.adjacency_lists);
+        let unsorted_lists = std::mem::take(&mut self.unsorted_lists);
+        if !self.sort_neighbors {
+            if let Some(node_id) = unsorted_lists.iter().min() {
+                panic!(
+                    "Relationships of node {} are not sorted, but sorting is disabled.",
+                    node_id
+                );
+            }
+        }
+
+        let threads = self.threads.unwrap_or(1);
+        let is_parallel = !self.shards.is_empty() || threads > 1;
+        let (mut offsets, mut lists) = if is_parallel {
+            // relationships added one by one join the shards
+            let mut shard = RelationshipShard::new();
+            for (start_node, list) in adjacency_lists {
+                for end_node in list {
+                    shard.add_relationship(start_node, end_node);
+                }
+            }
+            self.shards.push(shard);
+            let shards = std::mem::take(&mut self.shards);
+            shard_lists(self.node_count, &shards, threads)
+        } else {
+            // first pass: 0-degree nodes point to position 0, which stores the
+            // 0-degree, all other lists start with their degree
+            let mut offsets = vec![0; self.node_count];
+            let mut size = 1;
+            for (node_id, offset) in offsets.iter_mut().enumerate() {
+                if let Some(list) = adjacency_lists.get(&node_id) {
+                    *offset = size;
+                    size += list.len() + 1;
+                }
+            }
+
+            // second pass: copy the lists in node order
+            let mut lists = vec![0; size];
+            for (node_id, offset) in offsets.iter().enumerate() {
+                if let Some(list) = adjacency_lists.remove(&node_id) {
+                    lists[*offset] = list.len();
+                    lists[offset + 1..offset + 1 + list.len()].copy_from_slice(&list);
+                }
+            }
+
+            // lists built in end node order are already sorted
+            let unsorted_lists = unsorted_lists.into_iter().collect::<Vec<_>>();
+            sort_lists(&mut lists, &offsets, &unsorted_lists);
+            (offsets, lists)
+        };
+        let raw_relationship_count = self.relationship_count;
+        if self.dedupe_relationships {
+            lists = dedupe_lists(&mut offsets, &lists);
+            self.relationship_count = offsets
+                .iter()
+                .filter(|offset| **offset > 0)
+                .map(|offset| lists[*offset])
+                .sum();
+        }
+
+        let (in_offsets, in_lists) = if is_parallel {
+            reverse_shard_lists(&offsets, &lists, threads)
+        } else {
+            reverse_lists(&offsets, &lists)
+        };
+
+        // deduping keeps each type of a relationship once
+        let mut relationship_types = std::mem::take(&mut self.relationship_types);
+        for types in relationship_types.values_mut() {
+            types.sort_unstable();
+            if self.dedupe_relationships {
+                types.dedup();
+            }
+        }
+        self.interned_types.clear();
+
+        let label_idx = OnceCell::new();
+        if !self.is_label_index_lazy {
+            let _ = label_idx.set(build_label_index(&self.node_labels));
+        }
+
+        Graph {
+            node_count: self.node_count,
+            relationship_count: self.relationship_count,
+            raw_relationship_count,
+            node_labels: std::mem::take(&mut self.node_labels),
+            label_idx,
+            offsets,
+            lists,
+            in_offsets,
+            in_lists,
+            csr: OnceCell::new(),
+            relationship_types,
+            properties: std::mem::take(&mut self.properties),
+            external_ids: std::mem::take(&mut self.external_ids),
+            internal_ids: std::mem::take(&mut self.internal_ids),
+        }
+    }
+}
+
+// Sorts the lists of the given start nodes with a counting sort by end node
+// over all their relationships. Since node ids are dense, this takes time
+// linear in the number of nodes and relationships.
+fn sort_lists(lists: &mut [usize], offsets: &[usize], start_nodes: &[usize]) {
+    if start_nodes.is_empty() {
+        return;
+    }
+    let segment = |lists: &[usize], node_id: usize| {
+        let offset = offsets[node_id];
+        offset + 1..offset + 1 + lists[offset]
+    };
+
+    // bucket the start nodes by end node, `bounds[e]..bounds[e + 1]` is the
+    // bucket of end node e
+    let mut bounds = vec![0; offsets.len() + 1];
+    for start_node in start_nodes {
+        for end_node in &lists[segment(lists, *start_node)] {
+            bounds[end_node + 1] += 1;
+        }
+    }
+    for idx in 1..bounds.len() {
+        bounds[idx] += bounds[idx - 1];
+    }
+    let mut cursors = bounds.clone();
+    let mut buckets = vec![0; bounds[offsets.len()]];
+    for start_node in start_nodes {
+        for end_node in &lists[segment(lists, *start_node)] {
+            buckets[cursors[*end_node]] = *start_node;
+            cursors[*end_node] += 1;
+        }
+    }
+
+    // rewrite the lists by visiting the end nodes in ascending order
+    let mut cursors = offsets.iter().map(|offset| offset + 1).collect::<Vec<_>>();
+    for end_node in 0..offsets.len() {
+        for start_node in &buckets[bounds[end_node]..bounds[end_node + 1]] {
+            lists[cursors[*start_node]] = end_node;
+            cursors[*start_node] += 1;
+        }
+    }
+}
+
+// Inserts the entry into the sorted list of the node. The list grows in place
+// if it is the last one, otherwise it is copied to the end.
+fn insert_entry(offsets: &mut [usize], lists: &mut Vec<usize>, node_id: usize, entry: usize) {
+    let offset = offsets[node_id];
+    let degree = lists[offset];
+    let position = lists[offset + 1..offset + 1 + degree].partition_point(|e| *e <= entry);
+    if offset > 0 && offset + 1 + degree == lists.len() {
+        lists.insert(offset + 1 + position, entry);
+        lists[offset] += 1;
+        return;
+    }
+    offsets[node_id] = lists.len();
+    lists.push(degree + 1);
+    lists.extend_from_within(offset + 1..offset + 1 + position);
+    lists.push(entry);
+    lists.extend_from_within(offset + 1 + position..offset + 1 + degree);
+}
+
+// Removes one occurrence of the entry from the sorted list of the node,
+// leaving an unused entry behind the list.
+fn remove_entry(offsets: &[usize], lists: &mut [usize], node_id: usize, entry: usize) -> bool {
+    let offset = offsets[node_id];
+    let degree = lists[offset];
+    let list = &mut lists[offset + 1..offset + 1 + degree];
+    match list.binary_search(&entry) {
+        Ok(position) => {
+            list.copy_within(position + 1.., position);
+            lists[offset] -= 1;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+// Copies the lists next to each other in node order and updates the offsets.
+fn compact_lists(offsets: &mut [usize], lists: &[usize]) -> Vec<usize> {
+    let mut compacted = vec![0];
+    for offset in offsets.iter_mut() {
+        let degree = lists[*offset];
+        if degree == 0 {
+            *offset = 0;
+            continue;
+        }
+        let list = &lists[*offset..*offset + 1 + degree];
+        *offset = compacted.len();
+        compacted.extend_from_slice(list);
+    }
+    compacted
+}
+
+// Removes parallel relationships from sorted lists and updates the offsets.
+fn dedupe_lists(offsets: &mut [usize], lists: &[usize]) -> Vec<usize> {
+    let mut deduped = Vec::with_capacity(lists.len());
+    deduped.push(0);
+    for offset in offsets.iter_mut().filter(|offset| **offset > 0) {
+        let list = &lists[*offset + 1..*offset + 1 + lists[*offset]];
+        *offset = deduped.len();
+        deduped.push(0);
+        deduped.extend(list.chunk_by(|a, b| a == b).map(|run| run[0]));
+        deduped[*offset] = deduped.len() - *offset - 1;
+    }
+    deduped
+}
+
+// The lists of start nodes per end node in the layout of the adjacency lists.
+// Start nodes are visited in ascending order, so the lists come out sorted.
+pub(crate) fn reverse_lists(offsets: &[usize], lists: &[usize]) -> (Vec<usize>, Vec<usize>) {
+    let mut in_degrees = vec![0; offsets.len()];
+    for offset in offsets.iter().filter(|offset| **offset > 0) {
+        for end_node in &lists[offset + 1..offset + 1 + lists[*offset]] {
+            in_degrees[*end_node] += 1;
+        }
+    }
+    let mut in_offsets = vec![0; offsets.len()];
+    let mut size = 1;
+    for (in_offset, in_degree) in in_offsets.iter_mut().zip(&in_degrees) {
+        if *in_degree > 0 {
+            *in_offset = size;
+            size += in_degree + 1;
+        }
+    }
+    let mut in_lists = vec![0; size];
+    for (start_node, offset) in offsets.iter().enumerate() {
+        if *offset == 0 {
+            continue;
+        }
+        for end_node in &lists[offset + 1..offset + 1 + lists[*offset]] {
+            let in_offset = in_offsets[*end_node];
+            in_lists[in_offset] += 1;
+            let position = in_offset + in_lists[in_offset];
+            in_lists[position] = start_node;
+        }
+    }
+    (in_offsets, in_lists)
+}
+
+// Graphs with partially missing labels use `Option<T>` labels. Unlabeled
+// nodes only match pattern nodes without a label or wildcards.
+impl<T> GraphBuilder<Option<T>>
+where
+    T: Eq + Hash,
+{
+    pub fn add_unlabeled_node(&mut self, node_id: usize) -> &mut Self {
+        self.add_node(node_id, None)
+    }
+}
+
+// The nodes per label. Buckets are keyed by the hash of their label and told
+// apart by the label of their first node, which keeps `T` out of the index, as
+// a lazily initialized `T` would make `Graph` invariant over it.
+#[derive(Clone)]
+struct LabelIndex {
+    buckets: HashMap<u64, Vec<Vec<usize>>>,
+}
+
+impl LabelIndex {
+    // Adds the node to the bucket of its label, which stays sorted.
+    fn insert<T: Eq + Hash>(&mut self, node_labels: &HashMap<usize, Rc<T>>, node_id: usize) {
+        let label = &node_labels[&node_id];
+        let buckets = self.buckets.entry(label_hash(label)).or_default();
+        match buckets
+            .iter_mut()
+            .find(|nodes| node_labels[&nodes[0]] == *label)
+        {
+            Some(nodes) => {
+                let position = nodes.partition_point(|other| *other < node_id);
+                nodes.insert(position, node_id);
+            }
+            None => buckets.push(vec![node_id]),
+        }
+    }
+
+    // Removes the node from the bucket of its current label.
+    fn remove<T: Eq + Hash>(&mut self, node_labels: &HashMap<usize, Rc<T>>, node_id: usize) {
+        let hash = label_hash(&node_labels[&node_id]);
+        if let Some(buckets) = self.buckets.get_mut(&hash) {
+            for nodes in buckets.iter_mut() {
+                if let Ok(position) = nodes.binary_search(&node_id) {
+                    nodes.remove(position);
+                }
+            }
+            buckets.retain(|nodes| !nodes.is_empty());
+            if buckets.is_empty() {
+                self.buckets.remove(&hash);
+            }
+        }
+    }
+}
+
+fn label_hash<T: Hash>(label: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    label.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn build_label_index<T: Eq + Hash>(node_labels: &HashMap<usize, Rc<T>>) -> LabelIndex {
+    let mut label_idx = LabelIndex {
+        buckets: HashMap::new(),
+    };
+    // node ids are dense, visiting them in order keeps the buckets sorted
+    for node_id in 0..node_labels.len() {
+        label_idx.insert(node_labels, node_id);
+    }
+    label_idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_count() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .build();
+        assert_eq!(2, graph.node_count())
+    }
+
+    #[test]
+    #[should_panic(expected = "Next node id should be within range [0..1], but was 2.")]
+    fn test_add_invalid_node() {
+        let _ = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(2, "bar")
+            .build();
+    }
+
+    #[test]
+    fn test_relationship_count() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_relationship(0, 1)
+            .build();
+        assert_eq!(1, graph.relationship_count())
+    }
+
+    #[test]
+    #[should_panic(expected = "Start node 0 has not been added yet")]
+    fn test_add_relationship_for_invalid_start_node() {
+        let _ = GraphBuilder::<&str>::new().add_relationship(0, 1).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "End node 1 has not been added yet")]
+    fn test_add_relationship_for_invalid_end_node() {
+        let _ = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_relationship(0, 1)
+            .build();
+    }
+
+    #[test]
+    fn test_in_neighbors() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_relationship(2, 1)
+            .add_relationship(0, 1)
+            .add_relationship(2, 1)
+            .add_relationship(1, 1)
+            .build();
+        assert_eq!(&[0, 1, 2, 2], graph.in_neighbors(1));
+        assert_eq!(4, graph.in_degree(1));
+        assert!(graph.in_neighbors(0).is_empty());
+        assert_eq!(0, graph.in_degree(2));
+
+        let graph = graph.to_builder().dedupe_edges(true).build();
+        assert_eq!(&[0, 1, 2], graph.in_neighbors(1));
+    }
+
+    #[test]
+    fn test_from_adjacency_lists() {
+        let graph =
+            Graph::from_adjacency_lists(vec![vec![2, 1, 2], vec![], vec![0]], vec!["a", "b", "c"]);
+        let expected = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 2)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(2, 0)
+            .build();
+        assert_eq!(expected, graph);
+        assert_eq!(&[1, 2, 2], graph.neighbors(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "End node 3 of node 1 must be within range [0..2).")]
+    fn test_from_adjacency_lists_with_invalid_node() {
+        Graph::from_adjacency_lists(vec![vec![], vec![3]], vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_from_adjacency_matrix() {
+        let matrix = vec![vec![false, true], vec![true, true]];
+        let graph = Graph::from_adjacency_matrix(&matrix, vec!["a", "b"]);
+        assert_eq!(3, graph.relationship_count());
+        assert_eq!(&[1], graph.neighbors(0));
+        assert_eq!(&[0, 1], graph.neighbors(1));
+    }
+
+    #[test]
+    fn test_label_counts() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "foo")
+            .add_node(3, "foo")
+            .build();
+        let mut counts = graph.label_counts();
+        counts.sort();
+        assert_eq!(vec![(&"bar", 1), (&"foo", 3)], counts);
+        assert_eq!(0.75, graph.label_selectivity(&"foo"));
+        assert_eq!(0.0, graph.label_selectivity(&"baz"));
+        assert_eq!(
+            0.0,
+            GraphBuilder::<&str>::new()
+                .build()
+                .label_selectivity(&"foo")
+        );
+    }
+
+    #[test]
+    fn test_simple_paths() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_node(3, "c")
+            .add_relationship(0, 1)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .add_relationship(2, 1)
+            .add_relationship(1, 3)
+            .add_relationship(2, 3)
+            .add_relationship(3, 0)
+            .build();
+        assert_eq!(
+            vec![
+                vec![0, 1, 2, 3],
+                vec![0, 1, 3],
+                vec![0, 2, 1, 3],
+                vec![0, 2, 3]
+            ],
+            graph.simple_paths(0, 3, 3)
+        );
+        assert_eq!(
+            vec![vec![0, 1, 3], vec![0, 2, 3]],
+            graph.simple_paths(0, 3, 2)
+        );
+        assert!(graph.simple_paths(0, 3, 1).is_empty());
+        assert!(graph.simple_paths(3, 2, 1).is_empty());
+        assert_eq!(vec![vec![3]], graph.simple_paths(3, 3, 0));
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_relationship(0, 1)
+            .build();
+        let node_id = graph.add_node("foo");
+        assert_eq!(2, node_id);
+        graph
+            .add_relationship(2, 0)
+            .add_relationship(0, 2)
+            .add_relationship(0, 1)
+            .add_relationship(1, 2);
+        let expected = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "foo")
+            .add_relationship(0, 1)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+        assert_eq!(expected, graph);
+        assert_eq!(&[0, 1], graph.in_neighbors(2));
+        let mut foos = graph.nodes_by_label(&"foo").to_vec();
+        foos.sort();
+        assert_eq!(vec![0, 2], foos);
+
+        assert!(graph.remove_relationship(0, 1));
+        assert!(!graph.remove_relationship(2, 1));
+        assert_eq!(&[1, 2], graph.neighbors(0));
+        assert_eq!(&[0], graph.in_neighbors(1));
+        assert_eq!(4, graph.relationship_count());
+
+        graph.remove_node(1);
+        let expected = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "foo")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .build();
+        assert_eq!(expected, graph);
+        assert_eq!(&[1], graph.in_neighbors(0));
+        assert!(graph.nodes_by_label(&"bar").is_empty());
+    }
+
+    #[test]
+    fn test_mutation_reclaims_lists() {
+        let mut graph = GraphBuilder::new().add_node(0, "foo").build();
+        for _ in 0..3 {
+            graph.add_node("foo");
+        }
+        // every relationship moves the list of another node to the end
+        for round in 0..100 {
+            for node_id in 0..4 {
+                graph.add_relationship(node_id, (node_id + round) % 4);
+            }
+        }
+        assert_eq!(400, graph.relationship_count());
+        assert!(graph.lists.len() <= 2 * (graph.node_count() + graph.relationship_count() + 1));
+        assert_eq!(graph, graph.to_builder().build());
+    }
+
+    #[test]
+    fn test_external_ids() {
+        let graph = GraphBuilder::new()
+            .add_external_node(42, "foo")
+            .add_external_node(7, "bar")
+            .add_external_node(42, "baz")
+            .add_node(2, "baz")
+            .add_external_relationship(7, 42)
+            .build();
+        assert_eq!(3, graph.node_count());
+        assert_eq!(&"foo", graph.node_label(0));
+        assert_eq!(&[0], graph.neighbors(1));
+        assert_eq!(Some(42), graph.to_external_id(0));
+        assert_eq!(None, graph.to_external_id(2));
+        assert_eq!(Some(1), graph.to_internal_id(7));
+        assert_eq!(None, graph.to_internal_id(2));
+
+        let mut subgraph = graph.induced_subgraph(&[1, 0]);
+        assert_eq!(Some(7), subgraph.to_external_id(0));
+        subgraph.remove_node(0);
+        assert_eq!(Some(42), subgraph.to_external_id(0));
+        assert_eq!(Some(0), subgraph.to_internal_id(42));
+        assert_eq!(None, subgraph.to_internal_id(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "Node with external id 3 has not been added yet.")]
+    fn test_external_relationship_to_missing_node() {
+        GraphBuilder::new()
+            .add_external_node(1, "foo")
+            .add_external_relationship(1, 3);
+    }
+
+    #[test]
+    fn test_properties() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .set_property(0, "age", 42)
+            .set_property(0, "name", "alice")
+            .set_property(0, "age", 43)
+            .build();
+        assert_eq!(Some(&Value::Int(43)), graph.property(0, "age"));
+        assert_eq!(Some(&Value::from("alice")), graph.property(0, "name"));
+        assert_eq!(None, graph.property(1, "age"));
+
+        let subgraph = graph.induced_subgraph(&[1, 0]);
+        assert_eq!(Some(&Value::Int(43)), subgraph.property(1, "age"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 1 has not been added yet.")]
+    fn test_property_of_missing_node() {
+        GraphBuilder::new()
+            .add_node(0, "foo")
+            .set_property(1, "age", 42);
+    }
+
+    #[test]
+    fn test_relationship_types() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_relationship_with_type(0, 1, "WORKS_AT")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship(0, 1)
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .build();
+        assert_eq!(4, graph.multiplicity(0, 1));
+        assert_eq!(
+            vec!["KNOWS", "KNOWS", "WORKS_AT"],
+            graph.relationship_types(0, 1).collect::<Vec<_>>()
+        );
+        assert_eq!(2, graph.typed_multiplicity(0, 1, "KNOWS"));
+        assert_eq!(0, graph.typed_multiplicity(1, 0, "KNOWS"));
+        assert_eq!(graph, graph.to_builder().build());
+        assert_eq!(graph, graph.induced_subgraph(&[0, 1]));
+        assert_ne!(graph, graph.induced_subgraph(&[1, 0]));
+
+        let graph = graph.to_builder().dedupe_edges(true).build();
+        assert_eq!(
+            vec!["KNOWS", "WORKS_AT"],
+            graph.relationship_types(0, 1).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_dedupe_edges() {
+        let multigraph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .add_relationship(0, 1)
+            .add_relationship(0, 0)
+            .build();
+        assert_eq!(4, multigraph.relationship_count());
+
+        assert_eq!(4, multigraph.raw_relationship_count());
+        assert_eq!(3, multigraph.unique_relationship_count());
+
+        let graph = multigraph.to_builder().dedupe_edges(true).build();
+        assert_eq!(3, graph.relationship_count());
+        assert_eq!(4, graph.raw_relationship_count());
+        assert_eq!(3, graph.unique_relationship_count());
+        assert_eq!(&[0, 1], graph.neighbors(0));
+        assert_eq!(1, graph.multiplicity(0, 1));
+    }
+
+    #[test]
+    fn test_sort_neighbors() {
+        let graph = GraphBuilder::new()
+            .sort_neighbors(false)
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_sorted_relationships([(0, 0), (0, 1), (1, 0)])
+            .build();
+        assert_eq!(&[0, 1], graph.neighbors(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Relationships of node 0 are not sorted, but sorting is disabled.")]
+    fn test_sort_neighbors_with_unsorted_relationships() {
+        let _ = GraphBuilder::new()
+            .sort_neighbors(false)
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_relationship(0, 1)
+            .add_relationship(0, 0)
+            .build();
+    }
+
+    #[test]
+    fn test_duplicate_nodes() {
+        let mut builder = GraphBuilder::new();
+        builder.add_node(0, "foo").add_node(0, "bar");
+        assert_eq!("foo", *builder.build().node_label(0));
+
+        let mut builder = GraphBuilder::new();
+        builder
+            .duplicate_nodes(DuplicateNodes::Overwrite)
+            .add_node(0, "foo")
+            .add_node(0, "bar");
+        let graph = builder.build();
+        assert_eq!(1, graph.node_count());
+        assert_eq!("bar", *graph.node_label(0));
+
+        let mut builder = GraphBuilder::new();
+        builder.duplicate_nodes(DuplicateNodes::Error);
+        builder.try_add_node(0, "foo").unwrap();
+        let error = builder.try_add_node(0, "bar").err().unwrap();
+        assert_eq!(
+            NodeError::Duplicate(DuplicateNodeError { node_id: 0 }),
+            error
+        );
+        assert_eq!("Node 0 has already been added.", error.to_string());
+        assert_eq!("foo", *builder.build().node_label(0));
+    }
+
+    #[test]
+    fn test_try_add_node_out_of_range() {
+        let mut builder = GraphBuilder::new();
+        builder.add_node(0, "foo");
+        let error = builder.try_add_node(2, "bar").err().unwrap();
+        assert_eq!(
+            NodeError::OutOfRange {
+                node_id: 2,
+                node_count: 1
+            },
+            error
+        );
+        assert_eq!(
+            "Next node id should be within range [0..1], but was 2.",
+            error.to_string()
+        );
+        assert_eq!(1, builder.build().node_count());
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 0 has already been added.")]
+    fn test_add_duplicate_node() {
+        let _ = GraphBuilder::new()
+            .duplicate_nodes(DuplicateNodes::Error)
+            .add_node(0, "foo")
+            .add_node(0, "foo");
+    }
+
+    #[test]
+    fn test_implicit_nodes() {
+        let graph = GraphBuilder::new()
+            .implicit_nodes("unknown")
+            .add_node(0, "foo")
+            .add_relationship(0, 2)
+            .add_relationship(1, 0)
+            .build();
+        assert_eq!(3, graph.node_count());
+        assert_eq!(2, graph.relationship_count());
+        assert_eq!("foo", *graph.node_label(0));
+        let mut unknown = graph.nodes_by_label(&"unknown").to_vec();
+        unknown.sort_unstable();
+        assert_eq!(vec![1, 2], unknown);
+
+        let graph = GraphBuilder::new()
+            .implicit_nodes_with(|node_id| node_id % 2)
+            .add_relationship(2, 3)
+            .build();
+        assert_eq!(4, graph.node_count());
+        assert_eq!(1, *graph.node_label(3));
+    }
+
+    #[test]
+    fn test_to_builder() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_relationship(0, 1)
+            .build();
+        assert_eq!(graph, graph.to_builder().build());
+
+        let modified = graph
+            .to_builder()
+            .add_node(2, "baz")
+            .set_label(1, "qux")
+            .add_relationship(2, 0)
+            .add_relationship(0, 0)
+            .build();
+        assert_eq!(3, modified.node_count());
+        assert_eq!(3, modified.relationship_count());
+        assert_eq!("qux", *modified.node_label(1));
+        assert_eq!(&[0, 1], modified.neighbors(0));
+        assert_eq!(&[1], modified.nodes_by_label(&"qux"));
+        assert_eq!("bar", *graph.node_label(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 1 has not been added yet.")]
+    fn test_set_label_for_invalid_node() {
+        let _ = GraphBuilder::new().add_node(0, "foo").set_label(1, "bar");
+    }
+
+    #[test]
+    fn test_node_label() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .build();
+        assert_eq!("foo", *graph.node_label(0));
+        assert_eq!("bar", *graph.node_label(1))
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id 1 must be within range [0..1).")]
+    fn test_node_label_for_invalid_node() {
+        let graph = GraphBuilder::new().add_node(0, "foo").build();
+        let _ = *graph.node_label(1);
+    }
+
+    #[test]
+    fn test_degree() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .build();
+
+        assert_eq!(2, graph.degree(0));
+        assert_eq!(1, graph.degree(1));
+        assert_eq!(0, graph.degree(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id 1 must be within range [0..1).")]
+    fn test_degree_for_invalid_node() {
+        let graph = GraphBuilder::new().add_node(0, "foo").build();
+        let _ = graph.degree(1);
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_node(3, "boo")
+            .add_relationship(0, 2)
+            .add_relationship(0, 1)
+            .add_relationship(0, 0)
+            .add_relationship(0, 3)
+            .add_relationship(1, 2)
+            .build();
+
+        let empty: &[usize; 0] = &[];
+
+        assert_eq!(&[0, 1, 2, 3], graph.neighbors(0));
+        assert_eq!(&[2], graph.neighbors(1));
+        assert_eq!(empty, graph.neighbors(2))
+    }
+
+    #[test]
+    fn test_neighbors_of_several_unsorted_lists() {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..10 {
+            builder.add_node(node_id, "foo");
+        }
+        let mut expected = vec![vec![]; 10];
+        for idx in 0..50 {
+            let (start_node, end_node) = ((idx * 7) % 10, (idx * 13 + 5) % 10);
+            builder.add_relationship(start_node, end_node);
+            expected[start_node].push(end_node);
+        }
+        let graph = builder.build();
+
+        for (node_id, mut neighbors) in expected.into_iter().enumerate() {
+            neighbors.sort_unstable();
+            assert_eq!(neighbors, graph.neighbors(node_id));
+        }
+    }
+
+    #[test]
+    fn test_multiplicity() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_relationship(0, 2)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(0, 2)
+            .build();
+
+        assert_eq!(1, graph.multiplicity(0, 1));
+        assert_eq!(3, graph.multiplicity(0, 2));
+        assert_eq!(0, graph.multiplicity(1, 0));
+        assert_eq!(
+            vec![(1, 1), (2, 3)],
+            graph.distinct_neighbors(0).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_add_sorted_relationships() {
+        let mut builder = GraphBuilder::new();
+        builder.add_node(0, "a").add_node(1, "a").add_node(2, "a");
+        let graph = builder
+            .add_sorted_relationships(vec![(0, 1), (0, 2), (0, 2), (2, 0)])
+            .build();
+
+        assert_eq!(4, graph.relationship_count());
+        assert_eq!(&[1, 2, 2], graph.neighbors(0));
+        assert_eq!(&[0], graph.neighbors(2));
+
+        // relationships added out of order are still sorted
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 2)
+            .add_relationship(0, 1)
+            .build();
+        assert_eq!(&[1, 2], graph.neighbors(0));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Relationships must be sorted, but (0, 1) follows (1, 0).")]
+    fn test_add_unsorted_relationships() {
+        GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_sorted_relationships(vec![(1, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn test_to_csr() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(2, 0)
+            .add_relationship(0, 2)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+
+        let (indptr, indices) = graph.to_csr();
+        assert_eq!(vec![0, 3, 3, 4], indptr);
+        assert_eq!(vec![1, 2, 2, 0], indices);
+    }
+
+    #[cfg(feature = "sprs")]
+    #[test]
+    fn test_to_sparse_matrix() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(2, 0)
+            .add_relationship(0, 2)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(2, 2)
+            .build();
+
+        let matrix = graph.to_sparse_matrix();
+        assert_eq!((3, 3), matrix.shape());
+        assert_eq!(4, matrix.nnz());
+        assert_eq!(Some(&1), matrix.get(0, 1));
+        assert_eq!(Some(&2), matrix.get(0, 2));
+        assert_eq!(Some(&1), matrix.get(2, 2));
+        assert_eq!(None, matrix.get(1, 0));
+        assert_eq!(&[0, 2, 2, 4], matrix.indptr().raw_storage());
+    }
+
+    #[test]
+    fn test_node_ids_and_edges() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_relationship(2, 0)
+            .add_relationship(0, 1)
+            .add_relationship(0, 1)
+            .build();
+
+        assert_eq!(vec![0, 1, 2], graph.node_ids().collect::<Vec<_>>());
+        assert_eq!(
+            vec![(0, 1), (0, 1), (2, 0)],
+            graph.edges().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let first = GraphBuilder::new()
+            .add_external_node(10, "a")
+            .add_external_node(20, "b")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .set_property(0, "age", 42)
+            .build();
+        let second = GraphBuilder::new()
+            .add_node(0, "c")
+            .add_external_node(20, "x")
+            .add_relationship(0, 1)
+            .add_relationship_with_type(1, 0, "KNOWS")
+            .set_property(1, "age", 23)
+            .build();
+
+        let merged = first.merge(&second);
+        assert_eq!(3, merged.node_count());
+        assert_eq!(
+            vec![(0, 1), (1, 2), (2, 1)],
+            merged.edges().collect::<Vec<_>>()
+        );
+        assert_eq!(&"b", merged.node_label(1));
+        assert_eq!(&"c", merged.node_label(2));
+        assert_eq!(Some(1), merged.to_internal_id(20));
+        assert_eq!(None, merged.to_external_id(2));
+        assert_eq!(1, merged.typed_multiplicity(0, 1, "KNOWS"));
+        assert_eq!(1, merged.typed_multiplicity(1, 2, "KNOWS"));
+        assert_eq!(Some(&Value::from(23)), merged.property(1, "age"));
+
+        let overwrite = |graphs: &[&Graph<&'static str>]| {
+            let mut builder = first.to_builder();
+            builder.duplicate_nodes(DuplicateNodes::Overwrite);
+            for graph in graphs {
+                builder.extend_from_graph(graph);
+            }
+            builder
+        };
+        assert_eq!(&"x", overwrite(&[&second]).build().node_label(1));
+        let merged = overwrite(&[&second, &first]).dedupe_edges(true).build();
+        assert_eq!(3, merged.node_count());
+        assert_eq!(&"b", merged.node_label(1));
+        assert_eq!(Some(&Value::from(42)), merged.property(0, "age"));
+        assert_eq!(3, merged.relationship_count());
+    }
+
+    #[test]
+    fn test_extend_from_graph_with_duplicate_nodes() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "c")
+            .add_external_node(7, "a")
+            .add_relationship(0, 1)
+            .build();
+        let mut builder = GraphBuilder::new();
+        builder
+            .add_node(0, "b")
+            .add_external_node(7, "a")
+            .duplicate_nodes(DuplicateNodes::Error);
+
+        let error = builder.try_extend_from_graph(&graph).err().unwrap();
+        assert_eq!(1, error.node_id());
+        assert_eq!("Node 1 has already been added.", error.to_string());
+
+        // nothing has been added
+        let built = builder.build();
+        assert_eq!(2, built.node_count());
+        assert_eq!(0, built.relationship_count());
+    }
+
+    #[test]
+    fn test_set_label_on_graph() {
+        let mut graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .build();
+
+        graph.set_label(1, "a").set_label(0, "c");
+        assert_eq!(&"a", graph.node_label(1));
+        assert_eq!(&[1, 2], graph.nodes_by_label(&"a"));
+        assert_eq!(&[0], graph.nodes_by_label(&"c"));
+        assert!(graph.nodes_by_label(&"b").is_empty());
+    }
+
+    #[test]
+    fn test_reverse() {
+        let graph = GraphBuilder::new()
+            .add_external_node(7, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship(0, 1)
+            .add_relationship(2, 1)
+            .set_property(2, "age", 42)
+            .build();
+
+        let reversed = graph.reverse();
+        assert_eq!(
+            vec![(1, 0), (1, 0), (1, 2)],
+            reversed.edges().collect::<Vec<_>>()
+        );
+        assert_eq!(graph.neighbors(0), reversed.in_neighbors(0));
+        assert_eq!(1, reversed.typed_multiplicity(1, 0, "KNOWS"));
+        assert_eq!(0, reversed.typed_multiplicity(0, 1, "KNOWS"));
+        assert_eq!(&[0, 2], reversed.nodes_by_label(&"a"));
+        assert_eq!(Some(&Value::from(42)), reversed.property(2, "age"));
+        assert_eq!(Some(0), reversed.to_internal_id(7));
+        assert!(std::ptr::eq(graph.node_label(0), reversed.node_label(0)));
+        assert_eq!(graph, reversed.reverse());
+    }
+
+    #[test]
+    fn test_as_csr() {
+        let mut graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(1, 0)
+            .build();
+
+        let memory_usage = graph.memory_usage();
+        assert_eq!((&[0, 0, 1][..], &[0][..]), graph.as_csr());
+        assert!(graph.memory_usage() > memory_usage);
+        // the borrowed arrays are the cached ones
+        assert!(std::ptr::eq(graph.as_csr().0, graph.as_csr().0));
+
+        graph.add_relationship(0, 1);
+        assert_eq!((&[0, 1, 2][..], &[1, 0][..]), graph.as_csr());
+        graph.add_node("a");
+        assert_eq!((&[0, 1, 2, 2][..], &[1, 0][..]), graph.as_csr());
+    }
+
+    #[test]
+    fn test_eq_and_clone() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_relationship(2, 0)
+            .add_relationship(0, 1)
+            .build();
+        // same graph, but adjacency lists are added in a different order
+        let same = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_relationship(0, 1)
+            .add_relationship(2, 0)
+            .build();
+        let relabeled = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "boo")
+            .add_relationship(0, 1)
+            .add_relationship(2, 0)
+            .build();
+
+        assert_eq!(graph, same);
+        assert_eq!(graph, graph.clone());
+        assert_ne!(graph, relabeled);
+        assert_ne!(graph, graph.induced_subgraph(&[0, 1]));
+    }
+
+    #[test]
+    fn test_filter_labels() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_node(3, "foo")
+            .add_relationship(0, 1)
+            .add_relationship(1, 3)
+            .add_relationship(3, 0)
+            .add_relationship(2, 3)
+            .build();
+
+        let subgraph = graph.filter_labels(&["foo", "baz"]);
+
+        assert_eq!(3, subgraph.graph().node_count());
+        assert_eq!(2, subgraph.graph().relationship_count());
+        assert_eq!(vec![0, 2, 3], subgraph.to_original(&[0, 1, 2]));
+        assert_eq!("baz", *subgraph.graph().node_label(1));
+        // 2 -> 3 and 3 -> 0 in the original ids
+        assert_eq!(&[2], subgraph.graph().neighbors(1));
+        assert_eq!(&[0], subgraph.graph().neighbors(2));
+    }
+
+    #[test]
+    fn test_nodes_by_label() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "foo")
+            .build();
+
+        assert_eq!(&[0, 2], graph.nodes_by_label(&"foo"));
+        assert!(graph.nodes_by_label(&"baz").is_empty());
+    }
+
+    #[test]
+    fn test_lazy_label_index() {
+        let build = |builder: &mut GraphBuilder<&'static str>| {
+            builder
+                .add_node(0, "foo")
+                .add_node(1, "bar")
+                .add_node(2, "foo");
+            builder.build()
+        };
+        let eager = build(&mut GraphBuilder::new());
+        let lazy = build(GraphBuilder::new().lazy_label_index());
+
+        assert!(lazy.memory_usage() < eager.memory_usage());
+        let mut foo_nodes = lazy.nodes_by_label(&"foo").to_vec();
+        foo_nodes.sort_unstable();
+        assert_eq!(vec![0, 2], foo_nodes);
+        assert_eq!(eager.memory_usage(), lazy.memory_usage());
+    }
+
+    #[test]
+    fn test_induced_subgraph() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+
+        let subgraph = graph.induced_subgraph(&[2, 0]);
+
+        assert_eq!(2, subgraph.node_count());
+        assert_eq!(1, subgraph.relationship_count());
+        assert_eq!("baz", *subgraph.node_label(0));
+        assert_eq!("foo", *subgraph.node_label(1));
+        assert_eq!(&[1], subgraph.neighbors(0));
+    }
+
+    #[test]
+    fn test_ball() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_node(3, "d")
+            .add_relationship(0, 1)
+            .add_relationship(2, 1)
+            .add_relationship(2, 3)
+            .build();
+
+        let ball = graph.ball(1, 1);
+        assert_eq!(3, ball.graph().node_count());
+        assert_eq!(2, ball.graph().relationship_count());
+        assert_eq!(vec![0, 1, 2], ball.to_original(&[0, 1, 2]));
+
+        assert_eq!(1, graph.ball(3, 0).graph().node_count());
+        assert_eq!(4, graph.ball(0, 3).graph().node_count());
+    }
+
+    #[test]
+    fn test_weakly_connected_components() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "foo")
+            .add_node(1, "bar")
+            .add_node(2, "baz")
+            .add_node(3, "boo")
+            .add_node(4, "bam")
+            .add_relationship(3, 0)
+            .add_relationship(2, 4)
+            .build();
+
+        assert_eq!(
+            vec![vec![0, 3], vec![1], vec![2, 4]],
+            graph.weakly_connected_components()
+        );
+    }
+
+    #[test]
+    fn test_parallel_build() {
+        let relationships = (0..50)
+            .flat_map(|node_id| {
+                (0..node_id % 7).map(move |i| (node_id, (node_id * 13 + i * 7) % 50))
+            })
+            .collect::<Vec<_>>();
+        let nodes = || {
+            let mut builder = GraphBuilder::new();
+            for node_id in 0..50 {
+                builder.add_node(node_id, node_id % 3);
+            }
+            builder
+        };
+        let mut expected = nodes();
+        for (start_node, end_node) in &relationships {
+            expected.add_relationship(*start_node, *end_node);
+        }
+        let expected = expected.build();
+
+        let shards = relationships.chunks(17).map(|chunk| {
+            let mut shard = RelationshipShard::new();
+            for (start_node, end_node) in chunk.iter().rev() {
+                shard.add_relationship(*start_node, *end_node);
+            }
+            shard
+        });
+        let graph = nodes().threads(3).add_shards(shards).build();
+        assert_eq!(expected, graph);
+        for node_id in 0..50 {
+            assert_eq!(expected.in_neighbors(node_id), graph.in_neighbors(node_id));
+        }
+    }
+
+    #[test]
+    fn test_parallel_build_with_single_relationships() {
+        let mut shard = RelationshipShard::new();
+        shard.add_relationship(2, 0).add_relationship(2, 0);
+        let graph = GraphBuilder::new()
+            .implicit_nodes("a")
+            .dedupe_edges(true)
+            .add_relationship(2, 1)
+            .add_shards(vec![shard])
+            .build();
+
+        assert_eq!(3, graph.node_count());
+        assert_eq!(2, graph.relationship_count());
+        assert_eq!(3, graph.raw_relationship_count());
+        assert_eq!(&[0, 1], graph.neighbors(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "End node 3 has not been added yet")]
+    fn test_add_shard_for_invalid_end_node() {
+        let mut shard = RelationshipShard::new();
+        shard.add_relationship(0, 3);
+        let _ = GraphBuilder::new().add_node(0, "a").add_shards(vec![shard]);
+    }
+
+    #[cfg(feature = "fuzzing")]
+    #[test]
+    fn test_arbitrary_graphs() {
+        use arbitrary::Unstructured;
+        use rand::rngs::SmallRng;
+        use rand::{RngCore, SeedableRng};
+
+        use crate::{try_dual_iso_with_config, verify_embedding, MatchConfig};
+
+        let mut rng = SmallRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let mut graph_bytes = [0; 512];
+            let mut pattern_bytes = [0; 16];
+            rng.fill_bytes(&mut graph_bytes);
+            rng.fill_bytes(&mut pattern_bytes);
+            let graph =
+                Graph::<bool>::arbitrary_from(&mut Unstructured::new(&graph_bytes)).unwrap();
+            let pattern =
+                Graph::<bool>::arbitrary_from(&mut Unstructured::new(&pattern_bytes)).unwrap();
+            assert!(graph.node_count() <= MAX_ARBITRARY_NODES);
+
+            let matches = try_dual_iso_with_config(
+                &graph,
+                &pattern,
+                MatchConfig::new().limit(100).max_branches(10_000),
+            );
+            for embedding in matches.unwrap() {
+                assert_eq!(Ok(()), verify_embedding(&graph, &pattern, &embedding));
+            }
+        }
+        assert_eq!(
+            0,
+            Graph::<bool>::arbitrary_from(&mut Unstructured::new(&[]))
+                .unwrap()
+                .node_count()
+        );
     }
 }
@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{dual_iso_with_candidates, NestedVec};
+use crate::query::Query;
+use crate::{Graph, MatchConfig};
+
+// Subtype relations between labels, e.g. `Car` is a `Vehicle`. A label
+// matches itself and all of its direct and indirect subtypes.
+pub struct LabelHierarchy<T> {
+    subtypes: HashMap<T, Vec<T>>,
+}
+
+impl<T> Default for LabelHierarchy<T> {
+    fn default() -> Self {
+        LabelHierarchy {
+            subtypes: HashMap::new(),
+        }
+    }
+}
+
+impl<T> LabelHierarchy<T>
+where
+    T: Eq + Hash,
+{
+    pub fn new() -> Self {
+        LabelHierarchy::default()
+    }
+
+    // A label may have several supertypes, but no label may be its own subtype.
+    pub fn add_subtype(&mut self, subtype: T, supertype: T) -> &mut Self {
+        if subtype == supertype || self.descendants(&subtype).contains(&&supertype) {
+            panic!("Adding the subtype would create a cycle in the label hierarchy.");
+        }
+        self.subtypes.entry(supertype).or_default().push(subtype);
+        self
+    }
+
+    // The label itself followed by all of its subtypes, each once.
+    pub fn descendants<'a>(&'a self, label: &'a T) -> Vec<&'a T> {
+        let mut descendants = vec![label];
+        let mut idx = 0;
+        while idx < descendants.len() {
+            if let Some(subtypes) = self.subtypes.get(descendants[idx]) {
+                for subtype in subtypes {
+                    if !descendants.contains(&subtype) {
+                        descendants.push(subtype);
+                    }
+                }
+            }
+            idx += 1;
+        }
+        descendants
+    }
+
+    // The nodes whose label is the given label or one of its subtypes.
+    pub fn nodes_by_label<'graph>(
+        &self,
+        graph: &'graph Graph<T>,
+        label: &T,
+    ) -> Cow<'graph, [usize]> {
+        let descendants = self.descendants(label);
+        if descendants.len() == 1 {
+            return Cow::Borrowed(graph.nodes_by_label(label));
+        }
+        Cow::Owned(
+            descendants
+                .into_iter()
+                .flat_map(|label| graph.nodes_by_label(label))
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+// Matches pattern labels against their subtypes in the graph as well.
+pub fn dual_iso_with_hierarchy<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    hierarchy: &LabelHierarchy<T>,
+) -> NestedVec {
+    let query = Query::new(pattern);
+    let candidates = (0..query.node_count())
+        .map(|u_p| Candidates::from(hierarchy.nodes_by_label(graph, query.label(u_p))))
+        .collect();
+    dual_iso_with_candidates(graph, &query, config, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    fn hierarchy() -> LabelHierarchy<&'static str> {
+        let mut hierarchy = LabelHierarchy::new();
+        hierarchy
+            .add_subtype("car", "vehicle")
+            .add_subtype("bike", "vehicle")
+            .add_subtype("e-bike", "bike")
+            .add_subtype("e-bike", "electric");
+        hierarchy
+    }
+
+    #[test]
+    fn test_descendants() {
+        let hierarchy = hierarchy();
+        assert_eq!(
+            vec![&"vehicle", &"car", &"bike", &"e-bike"],
+            hierarchy.descendants(&"vehicle")
+        );
+        assert_eq!(
+            vec![&"electric", &"e-bike"],
+            hierarchy.descendants(&"electric")
+        );
+        assert_eq!(vec![&"person"], hierarchy.descendants(&"person"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Adding the subtype would create a cycle in the label hierarchy.")]
+    fn test_cycle() {
+        hierarchy().add_subtype("vehicle", "e-bike");
+    }
+
+    #[test]
+    fn test_dual_iso_with_hierarchy() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "car")
+            .add_node(2, "e-bike")
+            .add_node(3, "vehicle")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "vehicle")
+            .add_relationship(0, 1)
+            .build();
+        let config = MatchConfig::default();
+
+        let mut matches = dual_iso_with_hierarchy(&graph, &pattern, &config, &hierarchy());
+        matches.sort();
+        assert_eq!(vec![vec![0, 1], vec![0, 2], vec![0, 3]], matches);
+
+        assert_eq!(
+            vec![vec![0, 3]],
+            dual_iso_with_hierarchy(&graph, &pattern, &config, &LabelHierarchy::new())
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+// Label wrappers that compare and hash a normalized form of the wrapped string
+// while keeping the original value. Since they implement `Eq + Hash`, they can
+// be used as is for graph and pattern labels, e.g. `GraphBuilder<CaseInsensitive<String>>`.
+// Labels equal under the normalization share a single entry in the label index.
+
+#[derive(Clone, Copy, Default)]
+pub struct CaseInsensitive<T>(pub T);
+
+#[derive(Clone, Copy, Default)]
+pub struct Trimmed<T>(pub T);
+
+impl<T> CaseInsensitive<T>
+where
+    T: AsRef<str>,
+{
+    pub fn new(value: T) -> Self {
+        CaseInsensitive(value)
+    }
+
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+
+    fn normalized(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.as_ref().chars().flat_map(char::to_lowercase)
+    }
+}
+
+impl<T> PartialEq for CaseInsensitive<T>
+where
+    T: AsRef<str>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized().eq(other.normalized())
+    }
+}
+
+impl<T> Eq for CaseInsensitive<T> where T: AsRef<str> {}
+
+impl<T> Hash for CaseInsensitive<T>
+where
+    T: AsRef<str>,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for c in self.normalized() {
+            c.hash(state);
+        }
+        // terminate like `str` does to avoid prefix collisions
+        state.write_u8(0xff);
+    }
+}
+
+impl<T> From<T> for CaseInsensitive<T>
+where
+    T: AsRef<str>,
+{
+    fn from(value: T) -> Self {
+        CaseInsensitive(value)
+    }
+}
+
+impl<T> fmt::Debug for CaseInsensitive<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T> fmt::Display for CaseInsensitive<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T> Trimmed<T>
+where
+    T: AsRef<str>,
+{
+    pub fn new(value: T) -> Self {
+        Trimmed(value)
+    }
+
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+
+    fn normalized(&self) -> &str {
+        self.0.as_ref().trim()
+    }
+}
+
+impl<T> PartialEq for Trimmed<T>
+where
+    T: AsRef<str>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl<T> Eq for Trimmed<T> where T: AsRef<str> {}
+
+impl<T> Hash for Trimmed<T>
+where
+    T: AsRef<str>,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state)
+    }
+}
+
+impl<T> From<T> for Trimmed<T>
+where
+    T: AsRef<str>,
+{
+    fn from(value: T) -> Self {
+        Trimmed(value)
+    }
+}
+
+impl<T> fmt::Debug for Trimmed<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T> fmt::Display for Trimmed<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Trimmed is applied first, so `CaseInsensitive<Trimmed<String>>` ignores both.
+impl<T> AsRef<str> for Trimmed<T>
+where
+    T: AsRef<str>,
+{
+    fn as_ref(&self) -> &str {
+        self.normalized()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use crate::{dual_iso, GraphBuilder};
+
+    use super::*;
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let a = CaseInsensitive("Person");
+        let b = CaseInsensitive("PERSON");
+
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_ne!(a, CaseInsensitive("persons"));
+        assert_eq!("Person", a.to_string());
+    }
+
+    #[test]
+    fn test_trimmed() {
+        let a = Trimmed(String::from(" city\t"));
+        let b = Trimmed(String::from("city"));
+
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_eq!(
+            CaseInsensitive(Trimmed(" City ")),
+            CaseInsensitive(Trimmed("city"))
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_labels() {
+        let graph = GraphBuilder::new()
+            .add_node(0, CaseInsensitive("Person"))
+            .add_node(1, CaseInsensitive("person"))
+            .add_relationship(0, 1)
+            .build();
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, CaseInsensitive("PERSON"))
+            .add_node(1, CaseInsensitive("PERSON"))
+            .add_relationship(0, 1)
+            .build();
+
+        assert_eq!(vec![vec![0, 1]], dual_iso(&graph, &pattern));
+        assert_eq!(2, graph.nodes_by_label(&CaseInsensitive("pErSoN")).len());
+    }
+}
@@ -1,6 +1,150 @@
+mod automorphism;
+mod candidates;
+mod canonical;
+mod catalog;
+mod checkpoint;
+mod config;
+mod cypher;
+mod diff;
+mod distributed;
 mod dual_iso;
+mod filter;
+mod format;
 mod graph;
+mod hierarchy;
+mod labels;
+mod live;
+mod minimize;
+mod parallel;
+mod parameters;
+mod partition;
+mod pattern;
+mod plan;
+mod property_graph;
+mod query;
+mod ranges;
+mod registry;
+mod repl;
+mod round_trip;
+mod sampling;
+mod scoring;
+mod server;
+mod shapes;
+mod snapshot;
+mod stats;
+mod stream;
+mod streams;
+mod summary;
+mod table;
+mod verify;
 
+pub use self::automorphism::automorphisms;
+pub use self::automorphism::group_by_automorphism;
+pub use self::automorphism::MatchGroup;
+pub use self::canonical::CanonicalForm;
+pub use self::canonical::PatternSet;
+pub use self::catalog::CatalogError;
+pub use self::catalog::GraphCatalog;
+pub use self::checkpoint::Checkpoint;
+pub use self::checkpoint::CheckpointMismatch;
+pub use self::checkpoint::ResumableSearch;
+pub use self::config::EmptyCandidates;
+pub use self::config::MatchConfig;
+pub use self::config::SelfLoops;
+pub use self::config::Semantics;
+pub use self::cypher::parse_pattern;
+pub use self::diff::GraphDiff;
+pub use self::diff::MatchDiff;
+pub use self::distributed::run_coordinator;
+pub use self::distributed::run_worker;
+pub use self::distributed::DistributedRuntime;
+pub use self::distributed::LocalTransport;
+pub use self::distributed::Message;
+pub use self::distributed::Transport;
+pub use self::dual_iso::count_matches;
 pub use self::dual_iso::dual_iso;
+pub use self::dual_iso::dual_iso_anytime;
+pub use self::dual_iso::dual_iso_checked;
+pub use self::dual_iso::dual_iso_for_anchors;
+pub use self::dual_iso::dual_iso_for_anchors_with_config;
+pub use self::dual_iso::dual_iso_to_channel;
+pub use self::dual_iso::dual_iso_with_config;
+pub use self::dual_iso::dual_iso_with_participation;
+pub use self::dual_iso::dual_iso_with_repetitions;
+pub use self::dual_iso::dual_iso_with_stats;
+pub use self::dual_iso::find_first_match;
+pub use self::dual_iso::simulate;
+#[cfg(feature = "ndarray")]
+pub use self::dual_iso::to_ndarray;
+pub use self::dual_iso::to_row_major;
+pub use self::dual_iso::try_dual_iso_with_config;
+pub use self::dual_iso::EmptyCandidatesError;
+pub use self::dual_iso::MatchPanic;
+pub use self::dual_iso::ParticipationIndex;
+pub use self::dual_iso::SimulationReport;
+pub use self::filter::Filter;
+pub use self::filter::FilterError;
+pub use self::filter::NoProperties;
+pub use self::filter::PropertySource;
+pub use self::filter::Value;
+pub use self::format::parse_graph;
+pub use self::format::read_graph;
+pub use self::format::write_graph;
+pub use self::format::FormatError;
+pub use self::graph::DuplicateNodeError;
+pub use self::graph::DuplicateNodes;
 pub use self::graph::Graph;
 pub use self::graph::GraphBuilder;
+pub use self::graph::NodeError;
+pub use self::graph::Subgraph;
+pub use self::hierarchy::dual_iso_with_hierarchy;
+pub use self::hierarchy::LabelHierarchy;
+pub use self::labels::CaseInsensitive;
+pub use self::labels::Trimmed;
+pub use self::live::LiveGraph;
+pub use self::live::MatchEvent;
+pub use self::live::SubscriptionId;
+pub use self::minimize::minimize_pattern;
+pub use self::parallel::RelationshipShard;
+pub use self::parameters::Parameters;
+pub use self::partition::Partition;
+pub use self::partition::Partitioning;
+pub use self::pattern::match_pattern;
+pub use self::pattern::match_pattern_with_parameters;
+pub use self::pattern::match_pattern_with_properties;
+pub use self::pattern::Pattern;
+pub use self::pattern::PatternMatch;
+pub use self::plan::plan;
+pub use self::plan::Pruning;
+pub use self::plan::QueryPlan;
+pub use self::property_graph::dual_iso_with_label_sets;
+pub use self::property_graph::LabelSet;
+pub use self::property_graph::PropertyGraph;
+pub use self::query::Direction;
+pub use self::ranges::dual_iso_with_ranges;
+pub use self::ranges::SortedLabelIndex;
+pub use self::registry::PatternRegistry;
+pub use self::registry::RegisteredPattern;
+pub use self::repl::Repl;
+pub use self::round_trip::round_trip_check;
+pub use self::round_trip::GraphFormat;
+pub use self::round_trip::RoundTripReport;
+pub use self::sampling::estimate_count;
+pub use self::sampling::sample_matches;
+pub use self::sampling::CountEstimate;
+pub use self::scoring::CandidateScorer;
+pub use self::server::QueryServer;
+pub use self::server::Request;
+#[cfg(feature = "memmap2")]
+pub use self::snapshot::map_snapshot;
+pub use self::snapshot::read_snapshot;
+pub use self::snapshot::write_snapshot;
+pub use self::stats::MatchStats;
+pub use self::stats::Quota;
+pub use self::stream::GraphStreamBuilder;
+pub use self::streams::Input;
+pub use self::streams::Output;
+pub use self::summary::GraphSummary;
+pub use self::table::MatchTable;
+pub use self::verify::verify_embedding;
+pub use self::verify::Violation;
@@ -0,0 +1,406 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::dual_iso::{NestedVec, Resolved};
+use crate::{Graph, MatchConfig};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchEvent<'a> {
+    Appeared(&'a [usize]),
+    Broken(&'a [usize]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(usize);
+
+struct Subscription<'a, T> {
+    id: SubscriptionId,
+    pattern: Graph<T>,
+    // the pattern as searched in the committed graph, see `EmptyCandidates::Relax`
+    // and `MatchConfig::minimize`
+    resolved: Resolved<T>,
+    matches: HashSet<Vec<usize>>,
+    callback: Box<dyn FnMut(MatchEvent) + 'a>,
+}
+
+enum Change<T> {
+    AddNode(T),
+    SetLabel(usize, T),
+    AddRelationship(usize, usize),
+    RemoveRelationship(usize, usize),
+}
+
+// A graph that can be changed while registered patterns are kept up to date.
+// Changes are collected until `commit`, which applies them to the graph in
+// place and notifies each subscription about matches that appeared or broke.
+// Only matches touching a changed node are recomputed, since all other
+// matches cannot be affected. Relationship types, properties and external
+// ids of the graph are kept.
+pub struct LiveGraph<'a, T> {
+    graph: Graph<T>,
+    changes: Vec<Change<T>>,
+    // the node count including the nodes added since the last commit
+    node_count: usize,
+    config: MatchConfig,
+    touched: HashSet<usize>,
+    subscriptions: Vec<Subscription<'a, T>>,
+    next_id: usize,
+}
+
+impl<'a, T> LiveGraph<'a, T>
+where
+    T: Eq + Hash,
+{
+    pub fn new(graph: Graph<T>) -> Self {
+        LiveGraph {
+            changes: vec![],
+            node_count: graph.node_count(),
+            graph,
+            config: MatchConfig::default(),
+            touched: HashSet::new(),
+            subscriptions: vec![],
+            next_id: 0,
+        }
+    }
+
+    // The config used for all subscriptions, its limit is ignored.
+    pub fn config(&mut self, config: MatchConfig) -> &mut Self {
+        self.config = config;
+        self.config.limit = None;
+        self
+    }
+
+    // The committed graph.
+    pub fn graph(&self) -> &Graph<T> {
+        &self.graph
+    }
+
+    // Registers the pattern, its current matches do not cause notifications.
+    pub fn subscribe(
+        &mut self,
+        pattern: Graph<T>,
+        callback: impl FnMut(MatchEvent) + 'a,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        let resolved = Resolved::new(&self.graph, &pattern, &self.config);
+        let matches = resolved
+            .matches(&self.graph, &self.config)
+            .into_iter()
+            .collect();
+        self.subscriptions.push(Subscription {
+            id,
+            pattern,
+            resolved,
+            matches,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let count = self.subscriptions.len();
+        self.subscriptions
+            .retain(|subscription| subscription.id != id);
+        self.subscriptions.len() < count
+    }
+
+    // The current matches of the subscription in the committed graph.
+    pub fn matches(&self, id: SubscriptionId) -> NestedVec {
+        let subscription = self
+            .subscriptions
+            .iter()
+            .find(|subscription| subscription.id == id)
+            .unwrap_or_else(|| panic!("Unknown subscription {}.", id.0));
+        let mut matches = subscription.matches.iter().cloned().collect::<Vec<_>>();
+        matches.sort();
+        matches
+    }
+
+    pub fn add_node(&mut self, label: T) -> usize {
+        self.changes.push(Change::AddNode(label));
+        self.touched.insert(self.node_count);
+        self.node_count += 1;
+        self.node_count - 1
+    }
+
+    pub fn set_label(&mut self, node_id: usize, label: T) -> &mut Self {
+        self.validate_node_id(node_id);
+        self.changes.push(Change::SetLabel(node_id, label));
+        self.touched.insert(node_id);
+        self
+    }
+
+    pub fn add_relationship(&mut self, start_node: usize, end_node: usize) -> &mut Self {
+        self.validate_node_id(start_node);
+        self.validate_node_id(end_node);
+        self.changes
+            .push(Change::AddRelationship(start_node, end_node));
+        self.touched.extend([start_node, end_node]);
+        self
+    }
+
+    // Removes one relationship from start to end, returns false if there is none.
+    pub fn remove_relationship(&mut self, start_node: usize, end_node: usize) -> bool {
+        let committed = if start_node.max(end_node) < self.graph.node_count() {
+            self.graph.multiplicity(start_node, end_node)
+        } else {
+            0
+        };
+        let pending = self
+            .changes
+            .iter()
+            .fold(committed as isize, |count, change| match change {
+                Change::AddRelationship(s, e) if (*s, *e) == (start_node, end_node) => count + 1,
+                Change::RemoveRelationship(s, e) if (*s, *e) == (start_node, end_node) => count - 1,
+                _ => count,
+            });
+        if pending <= 0 {
+            return false;
+        }
+        self.changes
+            .push(Change::RemoveRelationship(start_node, end_node));
+        self.touched.extend([start_node, end_node]);
+        true
+    }
+
+    // Applies all changes since the last commit and notifies the subscriptions.
+    pub fn commit(&mut self) {
+        for change in std::mem::take(&mut self.changes) {
+            match change {
+                Change::AddNode(label) => {
+                    self.graph.add_node(label);
+                }
+                Change::SetLabel(node_id, label) => {
+                    self.graph.set_label(node_id, label);
+                }
+                Change::AddRelationship(start_node, end_node) => {
+                    self.graph.add_relationship(start_node, end_node);
+                }
+                Change::RemoveRelationship(start_node, end_node) => {
+                    self.graph.remove_relationship(start_node, end_node);
+                }
+            }
+        }
+        let mut touched = std::mem::take(&mut self.touched)
+            .into_iter()
+            .collect::<Vec<_>>();
+        touched.sort_unstable();
+
+        for subscription in self.subscriptions.iter_mut() {
+            // a pattern relaxed or minimized differently changes matches of
+            // untouched nodes as well, so they are all recomputed
+            let resolved = Resolved::new(&self.graph, &subscription.pattern, &self.config);
+            let is_changed = resolved != subscription.resolved;
+            subscription.resolved = resolved;
+            let before = subscription
+                .matches
+                .iter()
+                .filter(|embedding| {
+                    is_changed
+                        || embedding
+                            .iter()
+                            .any(|v_g| touched.binary_search(v_g).is_ok())
+                })
+                .cloned()
+                .collect::<HashSet<_>>();
+
+            let mut after = HashSet::new();
+            if is_changed {
+                after.extend(subscription.resolved.matches(&self.graph, &self.config));
+            } else {
+                for v_g in &touched {
+                    for u_p in 0..subscription.pattern.node_count() {
+                        subscription.resolved.visit_anchored(
+                            &self.graph,
+                            &self.config,
+                            &[(u_p, *v_g)],
+                            &mut |embedding| {
+                                after.insert(embedding);
+                                true
+                            },
+                        );
+                    }
+                }
+            }
+
+            let mut broken = before.difference(&after).cloned().collect::<Vec<_>>();
+            let mut appeared = after.difference(&before).cloned().collect::<Vec<_>>();
+            broken.sort();
+            appeared.sort();
+            for embedding in &broken {
+                subscription.matches.remove(embedding);
+                (subscription.callback)(MatchEvent::Broken(embedding));
+            }
+            for embedding in appeared {
+                (subscription.callback)(MatchEvent::Appeared(&embedding));
+                subscription.matches.insert(embedding);
+            }
+        }
+    }
+
+    fn validate_node_id(&self, node_id: usize) {
+        if node_id >= self.node_count {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                node_id, self.node_count
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::{EmptyCandidates, GraphBuilder, Semantics};
+
+    use super::*;
+
+    fn events(log: &RefCell<Vec<String>>) -> impl FnMut(MatchEvent) + '_ {
+        move |event| {
+            log.borrow_mut().push(match event {
+                MatchEvent::Appeared(embedding) => format!("+{:?}", embedding),
+                MatchEvent::Broken(embedding) => format!("-{:?}", embedding),
+            })
+        }
+    }
+
+    #[test]
+    fn test_subscription() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let log = RefCell::new(vec![]);
+        let mut live = LiveGraph::new(graph);
+        let id = live.subscribe(pattern, events(&log));
+        assert_eq!(vec![vec![0, 1]], live.matches(id));
+
+        live.add_relationship(0, 2);
+        live.commit();
+        assert!(live.remove_relationship(0, 1));
+        live.commit();
+        let node = live.add_node("b");
+        live.add_relationship(0, node).set_label(2, "a");
+        live.commit();
+
+        assert_eq!(
+            vec!["+[0, 2]", "-[0, 1]", "-[0, 2]", "+[0, 3]"],
+            *log.borrow()
+        );
+        assert_eq!(vec![vec![0, 3]], live.matches(id));
+        assert_eq!(4, live.graph().node_count());
+    }
+
+    #[test]
+    fn test_relaxed_and_minimized_subscriptions() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let log = RefCell::new(vec![]);
+        let folded_log = RefCell::new(vec![]);
+        let mut live = LiveGraph::new(graph);
+        let mut config = MatchConfig::new();
+        config.empty_candidates(EmptyCandidates::Relax);
+        live.config(config);
+        let id = live.subscribe(pattern, events(&log));
+        assert_eq!(vec![vec![0, 1], vec![0, 2]], live.matches(id));
+
+        // the pattern is still relaxed, so its matches are kept
+        live.add_relationship(1, 2);
+        live.commit();
+        assert!(log.borrow().is_empty());
+        // the relationship no longer needs to be relaxed
+        live.add_relationship(0, 1);
+        live.commit();
+        assert_eq!(vec!["-[0, 2]"], *log.borrow());
+        assert_eq!(vec![vec![0, 1]], live.matches(id));
+
+        // both `a` nodes of the pattern are folded onto the same graph node
+        let folded = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .build();
+        let mut config = MatchConfig::new();
+        config.semantics(Semantics::Homomorphism).minimize(true);
+        live.config(config);
+        let id = live.subscribe(folded, events(&folded_log));
+        assert_eq!(vec![vec![0, 0, 1]], live.matches(id));
+        let node = live.add_node("a");
+        live.add_relationship(node, 2);
+        live.commit();
+        assert_eq!(vec!["+[3, 3, 2]"], *folded_log.borrow());
+    }
+
+    #[test]
+    fn test_typed_subscription() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .set_property(0, "age", 42)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .build();
+
+        let log = RefCell::new(vec![]);
+        let mut live = LiveGraph::new(graph);
+        let id = live.subscribe(pattern.clone(), events(&log));
+        live.add_relationship(2, 2);
+        live.commit();
+        assert_eq!(vec![vec![0, 1]], crate::dual_iso(live.graph(), &pattern));
+        assert_eq!(vec![vec![0, 1]], live.matches(id));
+        assert_eq!(1, live.graph().typed_multiplicity(0, 1, "KNOWS"));
+        assert!(live.graph().property(0, "age").is_some());
+
+        // the untyped relationship is removed first, the typed one breaks the match
+        live.add_relationship(0, 1);
+        live.commit();
+        assert!(live.remove_relationship(0, 1));
+        live.commit();
+        assert!(log.borrow().is_empty());
+        assert!(live.remove_relationship(0, 1));
+        assert!(!live.remove_relationship(0, 1));
+        live.commit();
+        assert_eq!(vec!["-[0, 1]"], *log.borrow());
+        assert!(live.matches(id).is_empty());
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let log = RefCell::new(vec![]);
+        let mut live = LiveGraph::new(GraphBuilder::new().add_node(0, "a").build());
+        let pattern = GraphBuilder::new().add_node(0, "a").build();
+        let id = live.subscribe(pattern, events(&log));
+
+        assert!(live.unsubscribe(id));
+        assert!(!live.unsubscribe(id));
+        live.add_node("a");
+        live.commit();
+        assert!(log.borrow().is_empty());
+    }
+}
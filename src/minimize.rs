@@ -0,0 +1,137 @@
+use std::hash::Hash;
+
+use crate::{find_first_match, Graph, MatchConfig, Semantics, Subgraph};
+
+// The smallest subgraph of the pattern that a graph matches if and only if it
+// matches the pattern.
+//
+// Under homomorphism semantics, this is the core of the pattern: nodes that
+// can be folded onto other nodes without breaking a relationship are removed,
+// e.g. one of two `a` nodes with a relationship to the same `b` node. Under
+// isomorphism semantics, every pattern node needs a graph node of its own, so
+// no node is redundant and the whole pattern is kept.
+pub fn minimize_pattern<T: Eq + Hash>(pattern: &Graph<T>, config: &MatchConfig) -> Subgraph<T> {
+    retract(pattern, config).0
+}
+
+// The minimized pattern and, for each pattern node, the node of the minimized
+// pattern it is folded onto. Matches of the minimized pattern are mapped back
+// onto all pattern nodes through the latter.
+pub(crate) fn retract<T: Eq + Hash>(
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+) -> (Subgraph<T>, Vec<usize>) {
+    let mut nodes = (0..pattern.node_count()).collect::<Vec<_>>();
+    let mut retraction = nodes.clone();
+    if config.is_injective() {
+        return (Subgraph::induced(pattern, nodes), retraction);
+    }
+
+    let mut homomorphism = MatchConfig::new();
+    homomorphism
+        .semantics(Semantics::Homomorphism)
+        .self_loops(config.self_loops);
+    let mut current = pattern.induced_subgraph(&nodes);
+    // a node that cannot be removed stays in the core, since it could not be
+    // removed from any smaller pattern either
+    let mut idx = 0;
+    while idx < nodes.len() {
+        let mut remaining = nodes.clone();
+        remaining.remove(idx);
+        let candidate = pattern.induced_subgraph(&remaining);
+        match find_first_match(&candidate, &current, &homomorphism) {
+            Some(folding) => {
+                for u_c in retraction.iter_mut() {
+                    *u_c = folding[*u_c];
+                }
+                nodes = remaining;
+                current = candidate;
+            }
+            None => idx += 1,
+        }
+    }
+    (Subgraph::induced(pattern, nodes), retraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dual_iso_with_config, GraphBuilder};
+
+    use super::*;
+
+    fn homomorphism() -> MatchConfig {
+        let mut config = MatchConfig::new();
+        config.semantics(Semantics::Homomorphism);
+        config
+    }
+
+    #[test]
+    fn test_minimize_pattern() {
+        // a path a -> b -> a -> b folds onto a single a -> b
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_relationship(0, 1)
+            .add_relationship(2, 1)
+            .add_relationship(2, 3)
+            .build();
+
+        let core = minimize_pattern(&pattern, &homomorphism());
+        assert_eq!(2, core.graph().node_count());
+        assert_eq!(1, core.graph().relationship_count());
+        assert_eq!(&vec![2, 3], core.original_ids());
+
+        let core = minimize_pattern(&pattern, &MatchConfig::default());
+        assert_eq!(4, core.graph().node_count());
+    }
+
+    #[test]
+    fn test_minimize_pattern_keeps_cycles() {
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+
+        assert_eq!(
+            3,
+            minimize_pattern(&pattern, &homomorphism())
+                .graph()
+                .node_count()
+        );
+    }
+
+    #[test]
+    fn test_dual_iso_minimized() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(2, 1)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .build();
+
+        let mut all = dual_iso_with_config(&graph, &pattern, &homomorphism());
+        all.sort();
+        assert_eq!(
+            vec![vec![0, 0, 1], vec![0, 2, 1], vec![2, 0, 1], vec![2, 2, 1]],
+            all
+        );
+
+        let mut matches = dual_iso_with_config(&graph, &pattern, homomorphism().minimize(true));
+        matches.sort();
+        assert_eq!(vec![vec![0, 0, 1], vec![2, 2, 1]], matches);
+    }
+}
@@ -0,0 +1,199 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// Collects relationships on one thread, e.g. while parsing a part of an edge
+// list. Unlike the builder, shards can be sent between threads. Filled shards
+// are added with `GraphBuilder::add_shards`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RelationshipShard {
+    relationships: Vec<(usize, usize)>,
+}
+
+impl RelationshipShard {
+    pub fn new() -> Self {
+        RelationshipShard::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        RelationshipShard {
+            relationships: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn add_relationship(&mut self, start_node: usize, end_node: usize) -> &mut Self {
+        self.relationships.push((start_node, end_node));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.relationships.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.relationships.is_empty()
+    }
+
+    pub(crate) fn relationships(&self) -> &[(usize, usize)] {
+        &self.relationships
+    }
+}
+
+// Builds sorted adjacency lists in the layout of `GraphBuilder::build` from
+// the relationships of the shards, with one thread per part.
+pub(crate) fn shard_lists(
+    node_count: usize,
+    shards: &[RelationshipShard],
+    threads: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let relationships = shards
+        .iter()
+        .flat_map(|shard| shard.relationships().chunks(4096))
+        .collect::<Vec<_>>();
+    let parts = relationships
+        .chunks(relationships.len().div_ceil(threads).max(1))
+        .collect::<Vec<_>>();
+    build_lists(node_count, threads, &parts, |part, emit| {
+        for (start_node, end_node) in part.iter().copied().flatten() {
+            emit(*start_node, *end_node);
+        }
+    })
+}
+
+// Like `reverse_lists`, but visits the start nodes concurrently.
+pub(crate) fn reverse_shard_lists(
+    offsets: &[usize],
+    lists: &[usize],
+    threads: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let parts = balanced_ranges(offsets, lists, threads);
+    build_lists(offsets.len(), threads, &parts, |nodes, emit| {
+        for start_node in nodes.clone() {
+            let offset = offsets[start_node];
+            if offset == 0 {
+                continue;
+            }
+            for end_node in &lists[offset + 1..offset + 1 + lists[offset]] {
+                emit(*end_node, start_node);
+            }
+        }
+    })
+}
+
+// Each part emits its (start node, end node) pairs into the `emit` callback,
+// once for counting the degrees and once for placing the end nodes.
+fn build_lists<P, F>(
+    node_count: usize,
+    threads: usize,
+    parts: &[P],
+    for_each: F,
+) -> (Vec<usize>, Vec<usize>)
+where
+    P: Sync,
+    F: Fn(&P, &mut dyn FnMut(usize, usize)) + Sync,
+{
+    // first pass: count the degrees concurrently
+    let degrees = (0..node_count)
+        .map(|_| AtomicUsize::new(0))
+        .collect::<Vec<_>>();
+    let (for_each, degrees) = (&for_each, &degrees);
+    thread::scope(|scope| {
+        for part in parts {
+            scope.spawn(move || {
+                for_each(part, &mut |start_node, _| {
+                    degrees[start_node].fetch_add(1, Ordering::Relaxed);
+                })
+            });
+        }
+    });
+
+    // 0-degree nodes point to position 0, which stores the 0-degree, all
+    // other lists start with their degree
+    let mut offsets = vec![0; node_count];
+    let mut size = 1;
+    for (offset, degree) in offsets.iter_mut().zip(degrees) {
+        let degree = degree.load(Ordering::Relaxed);
+        if degree > 0 {
+            *offset = size;
+            size += degree + 1;
+        }
+    }
+    let lists = (0..size).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+    // the degrees become the next free position per list
+    for (offset, degree) in offsets.iter().zip(degrees) {
+        if *offset > 0 {
+            lists[*offset].store(degree.load(Ordering::Relaxed), Ordering::Relaxed);
+            degree.store(offset + 1, Ordering::Relaxed);
+        }
+    }
+
+    // second pass: each relationship claims the next free position of its
+    // list, so the lists are filled in no particular order
+    let positions = &lists;
+    thread::scope(|scope| {
+        for part in parts {
+            scope.spawn(move || {
+                for_each(part, &mut |start_node, end_node| {
+                    let position = degrees[start_node].fetch_add(1, Ordering::Relaxed);
+                    positions[position].store(end_node, Ordering::Relaxed);
+                })
+            });
+        }
+    });
+    let mut lists = lists
+        .into_iter()
+        .map(AtomicUsize::into_inner)
+        .collect::<Vec<_>>();
+
+    // third pass: sort the lists of node ranges concurrently, the lists of
+    // consecutive nodes are stored consecutively
+    let ranges = balanced_ranges(&offsets, &lists, threads);
+    thread::scope(|scope| {
+        let mut rest = &mut lists[..];
+        let mut base = 0;
+        for nodes in ranges {
+            let end = nodes
+                .clone()
+                .rev()
+                .map(|node_id| offsets[node_id])
+                .find(|offset| *offset > 0)
+                .map_or(base, |offset| offset + 1 + rest[offset - base]);
+            let (head, tail) = std::mem::take(&mut rest).split_at_mut(end - base);
+            let offsets = &offsets;
+            scope.spawn(move || {
+                for node_id in nodes {
+                    let offset = offsets[node_id];
+                    if offset > 0 {
+                        let offset = offset - base;
+                        let degree = head[offset];
+                        head[offset + 1..offset + 1 + degree].sort_unstable();
+                    }
+                }
+            });
+            rest = tail;
+            base = end;
+        }
+    });
+    (offsets, lists)
+}
+
+// Splits the nodes into at most `threads` consecutive ranges with about the
+// same number of relationships.
+fn balanced_ranges(offsets: &[usize], lists: &[usize], threads: usize) -> Vec<Range<usize>> {
+    let degree = |node_id: usize| match offsets[node_id] {
+        0 => 0,
+        offset => lists[offset],
+    };
+    let total = (0..offsets.len()).map(degree).sum::<usize>();
+    let mut ranges = vec![];
+    let (mut start, mut seen) = (0, 0);
+    for node_id in 0..offsets.len() {
+        seen += degree(node_id);
+        if seen * threads >= total * (ranges.len() + 1) && ranges.len() + 1 < threads {
+            ranges.push(start..node_id + 1);
+            start = node_id + 1;
+        }
+    }
+    ranges.push(start..offsets.len());
+    ranges
+}
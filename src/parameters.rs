@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::Value;
+
+// Values for the parameters of a pattern, bound when the pattern is executed.
+pub struct Parameters<T> {
+    pub(crate) values: HashMap<String, Value>,
+    pub(crate) labels: HashMap<String, T>,
+    pub(crate) anchors: HashMap<String, usize>,
+}
+
+impl<T> Default for Parameters<T> {
+    fn default() -> Self {
+        Parameters {
+            values: HashMap::new(),
+            labels: HashMap::new(),
+            anchors: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Parameters<T>
+where
+    T: Eq + Hash,
+{
+    pub fn new() -> Self {
+        Parameters::default()
+    }
+
+    pub fn value(&mut self, name: &str, value: impl Into<Value>) -> &mut Self {
+        self.values.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn label(&mut self, name: &str, label: T) -> &mut Self {
+        self.labels.insert(name.to_string(), label);
+        self
+    }
+
+    pub fn anchor(&mut self, name: &str, node_id: usize) -> &mut Self {
+        self.anchors.insert(name.to_string(), node_id);
+        self
+    }
+
+    pub(crate) fn get_label(&self, name: &str) -> &T {
+        self.labels
+            .get(name)
+            .unwrap_or_else(|| panic!("Label parameter '{}' has not been bound.", name))
+    }
+
+    pub(crate) fn get_anchor(&self, name: &str) -> usize {
+        *self
+            .anchors
+            .get(name)
+            .unwrap_or_else(|| panic!("Anchor parameter '{}' has not been bound.", name))
+    }
+}
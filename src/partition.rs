@@ -0,0 +1,392 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{combine_components, dual_iso_with_candidates, NestedVec};
+use crate::graph::DetachedGraph;
+use crate::query::Query;
+use crate::{Graph, MatchConfig, Subgraph};
+
+// A part of a partitioned graph. It contains the nodes owned by the part and
+// all nodes within `halo` hops of them, ignoring relationship directions.
+pub struct Partition<T> {
+    subgraph: Subgraph<T>,
+    owned: Vec<bool>,
+    boundary: Vec<usize>,
+    // the other parts holding a copy of an owned node, by original id
+    copies: HashMap<usize, Vec<usize>>,
+}
+
+impl<T> Partition<T> {
+    pub fn graph(&self) -> &Graph<T> {
+        self.subgraph.graph()
+    }
+
+    pub fn subgraph(&self) -> &Subgraph<T> {
+        &self.subgraph
+    }
+
+    pub fn is_owned(&self, node_id: usize) -> bool {
+        self.owned[node_id]
+    }
+
+    // original ids of the nodes owned by this part
+    pub fn owned_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.owned.len())
+            .filter(move |node_id| self.owned[*node_id])
+            .map(move |node_id| self.subgraph.original_id(node_id))
+    }
+
+    // original ids of the nodes only copied into this part
+    pub fn halo_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.owned.len())
+            .filter(move |node_id| !self.owned[*node_id])
+            .map(move |node_id| self.subgraph.original_id(node_id))
+    }
+
+    // original ids of the owned nodes with a relationship to another part
+    pub fn boundary_nodes(&self) -> &[usize] {
+        &self.boundary
+    }
+
+    // the other parts holding a copy of the owned node with the original id
+    pub fn copies(&self, node_id: usize) -> &[usize] {
+        self.copies
+            .get(&node_id)
+            .map_or(&[], |parts| parts.as_slice())
+    }
+
+    pub(crate) fn detach(&self) -> DetachedPartition<T>
+    where
+        T: Eq + Hash + Clone,
+    {
+        let (graph, original_ids) = self.subgraph.detach();
+        DetachedPartition {
+            graph,
+            original_ids,
+            owned: self.owned.clone(),
+            boundary: self.boundary.clone(),
+            copies: self.copies.clone(),
+        }
+    }
+}
+
+// A deep copy of a part, which can be sent to the thread of its worker.
+pub(crate) struct DetachedPartition<T> {
+    graph: DetachedGraph<T>,
+    original_ids: Vec<usize>,
+    owned: Vec<bool>,
+    boundary: Vec<usize>,
+    copies: HashMap<usize, Vec<usize>>,
+}
+
+impl<T> DetachedPartition<T>
+where
+    T: Eq + Hash,
+{
+    pub(crate) fn attach(self) -> Partition<T> {
+        Partition {
+            subgraph: Subgraph::attach(self.graph, self.original_ids),
+            owned: self.owned,
+            boundary: self.boundary,
+            copies: self.copies,
+        }
+    }
+}
+
+pub struct Partitioning<T> {
+    parts: Vec<Partition<T>>,
+    owners: Vec<usize>,
+    halo: usize,
+    edge_cut: usize,
+}
+
+impl<T> Partitioning<T>
+where
+    T: Eq + Hash,
+{
+    // Assigns node `n` to part `n % part_count`.
+    pub fn hash(graph: &Graph<T>, part_count: usize, halo: usize) -> Self {
+        if part_count == 0 {
+            panic!("Partition count must be greater than 0.");
+        }
+        let owners = (0..graph.node_count())
+            .map(|node_id| node_id % part_count)
+            .collect::<Vec<_>>();
+        let adjacency = undirected_adjacency(graph);
+
+        let mut parts = (0..part_count)
+            .map(|part| {
+                let owned = (0..graph.node_count())
+                    .filter(|node_id| owners[*node_id] == part)
+                    .collect::<Vec<_>>();
+                let boundary = owned
+                    .iter()
+                    .copied()
+                    .filter(|node_id| adjacency[*node_id].iter().any(|n| owners[*n] != part))
+                    .collect();
+
+                let distances = distances(&adjacency, &owned, halo);
+                let nodes = (0..graph.node_count())
+                    .filter(|node_id| distances[*node_id].is_some())
+                    .collect::<Vec<_>>();
+                let owned = nodes
+                    .iter()
+                    .map(|node_id| owners[*node_id] == part)
+                    .collect();
+                Partition {
+                    subgraph: Subgraph::induced(graph, nodes),
+                    owned,
+                    boundary,
+                    copies: HashMap::new(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut copies = vec![HashMap::<usize, Vec<usize>>::new(); part_count];
+        for (part_id, part) in parts.iter().enumerate() {
+            for node_id in part.halo_nodes() {
+                copies[owners[node_id]]
+                    .entry(node_id)
+                    .or_default()
+                    .push(part_id);
+            }
+        }
+        for (part, copies) in parts.iter_mut().zip(copies) {
+            part.copies = copies;
+        }
+
+        let edge_cut = graph
+            .edges()
+            .filter(|(start_node, end_node)| owners[*start_node] != owners[*end_node])
+            .count();
+
+        Partitioning {
+            parts,
+            owners,
+            halo,
+            edge_cut,
+        }
+    }
+
+    pub fn parts(&self) -> &[Partition<T>] {
+        &self.parts
+    }
+
+    pub fn owner(&self, node_id: usize) -> usize {
+        self.owners[node_id]
+    }
+
+    pub fn halo(&self) -> usize {
+        self.halo
+    }
+
+    // number of relationships between nodes owned by different parts
+    pub fn edge_cut(&self) -> usize {
+        self.edge_cut
+    }
+
+    // Matches each pattern component in every part, where the first node of
+    // the component is bound to a node owned by the part. The halo must cover
+    // the distance from that node to all other nodes in its component, so each
+    // match is found in exactly one part. Component matches are then stitched
+    // together across parts.
+    pub fn dual_iso(&self, pattern: &Graph<T>, config: &MatchConfig) -> NestedVec {
+        let query = Query::new(pattern);
+        let components = pattern.weakly_connected_components();
+
+        let mut component_config = config.clone();
+        if components.len() > 1 {
+            component_config.limit = None;
+        }
+        let component_matches = components
+            .iter()
+            .map(|component| {
+                self.validate_halo(&pattern.induced_subgraph(component));
+                let component_query = query.restrict(component);
+                let mut matches: NestedVec = vec![];
+                for part in &self.parts {
+                    if component_config.is_limit_reached(matches.len()) {
+                        break;
+                    }
+                    let graph = part.graph();
+                    let candidates = (0..component_query.node_count())
+                        .map(|u_p| {
+                            let candidates = graph.nodes_by_label(component_query.label(u_p));
+                            if u_p == 0 {
+                                Candidates::Owned(
+                                    candidates
+                                        .iter()
+                                        .copied()
+                                        .filter(|v_g| part.is_owned(*v_g))
+                                        .collect(),
+                                )
+                            } else {
+                                Candidates::Borrowed(candidates)
+                            }
+                        })
+                        .collect();
+                    let mut part_config = component_config.clone();
+                    part_config.limit = component_config.limit.map(|limit| limit - matches.len());
+                    matches.extend(
+                        dual_iso_with_candidates(graph, &component_query, &part_config, candidates)
+                            .iter()
+                            .map(|embedding| part.subgraph.to_original(embedding)),
+                    );
+                }
+                matches
+            })
+            .collect::<Vec<_>>();
+
+        if components.len() == 1 {
+            return component_matches.into_iter().next().unwrap();
+        }
+        combine_components(
+            config,
+            pattern.node_count(),
+            &components,
+            &component_matches,
+        )
+    }
+
+    // the halo must reach all nodes of a connected pattern from its first node
+    pub(crate) fn validate_halo(&self, component_pattern: &Graph<T>) {
+        let radius = distances(&undirected_adjacency(component_pattern), &[0], usize::MAX)
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or(0);
+        if radius > self.halo {
+            panic!(
+                "Pattern component requires a halo of {} but the partitioning has a halo of {}.",
+                radius, self.halo
+            );
+        }
+    }
+}
+
+pub(crate) fn undirected_adjacency<T: Eq + Hash>(graph: &Graph<T>) -> Vec<Vec<usize>> {
+    let mut adjacency = vec![vec![]; graph.node_count()];
+    for (start_node, end_node) in graph.edges() {
+        adjacency[start_node].push(end_node);
+        adjacency[end_node].push(start_node);
+    }
+    adjacency
+}
+
+// breadth-first distances from the given sources, up to `max_distance`
+pub(crate) fn distances(
+    adjacency: &[Vec<usize>],
+    sources: &[usize],
+    max_distance: usize,
+) -> Vec<Option<usize>> {
+    let mut distances = vec![None; adjacency.len()];
+    let mut queue = VecDeque::new();
+    for source in sources {
+        distances[*source] = Some(0);
+        queue.push_back(*source);
+    }
+    while let Some(node_id) = queue.pop_front() {
+        let distance = distances[node_id].unwrap();
+        if distance == max_distance {
+            continue;
+        }
+        for neighbor in &adjacency[node_id] {
+            if distances[*neighbor].is_none() {
+                distances[*neighbor] = Some(distance + 1);
+                queue.push_back(*neighbor);
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dual_iso, GraphBuilder};
+
+    use super::*;
+
+    fn cycle(n: usize) -> Graph<&'static str> {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..n {
+            builder.add_node(node_id, "x");
+        }
+        for node_id in 0..n {
+            builder.add_relationship(node_id, (node_id + 1) % n);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_hash_partition() {
+        let graph = cycle(6);
+        let partitioning = Partitioning::hash(&graph, 2, 1);
+
+        assert_eq!(2, partitioning.parts().len());
+        assert_eq!(1, partitioning.owner(3));
+        assert_eq!(6, partitioning.edge_cut());
+
+        let part = &partitioning.parts()[0];
+        assert_eq!(vec![0, 2, 4], part.owned_nodes().collect::<Vec<_>>());
+        assert_eq!(vec![1, 3, 5], part.halo_nodes().collect::<Vec<_>>());
+        assert_eq!(&[0, 2, 4], part.boundary_nodes());
+    }
+
+    #[test]
+    fn test_partitioned_dual_iso() {
+        let graph = cycle(7);
+        let pattern = GraphBuilder::new()
+            .add_node(0, "x")
+            .add_node(1, "x")
+            .add_node(2, "x")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let partitioning = Partitioning::hash(&graph, 3, 2);
+        let mut matches = partitioning.dual_iso(&pattern, &MatchConfig::default());
+        matches.sort();
+        let mut expected = dual_iso(&graph, &pattern);
+        expected.sort();
+
+        assert_eq!(7, matches.len());
+        assert_eq!(expected, matches);
+    }
+
+    #[test]
+    fn test_partitioned_dual_iso_disconnected() {
+        let graph = cycle(4);
+        let pattern = GraphBuilder::new()
+            .add_node(0, "x")
+            .add_node(1, "x")
+            .add_relationship(0, 1)
+            .add_node(2, "x")
+            .build();
+
+        let partitioning = Partitioning::hash(&graph, 2, 1);
+        let mut matches = partitioning.dual_iso(&pattern, &MatchConfig::default());
+        matches.sort();
+        let mut expected = dual_iso(&graph, &pattern);
+        expected.sort();
+
+        assert_eq!(8, matches.len());
+        assert_eq!(expected, matches);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Pattern component requires a halo of 2 but the partitioning has a halo of 1."
+    )]
+    fn test_partitioned_dual_iso_small_halo() {
+        let graph = cycle(4);
+        let pattern = GraphBuilder::new()
+            .add_node(0, "x")
+            .add_node(1, "x")
+            .add_node(2, "x")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        Partitioning::hash(&graph, 2, 1).dual_iso(&pattern, &MatchConfig::default());
+    }
+}
@@ -0,0 +1,1192 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{dual_iso_anchored, dual_iso_with_candidates, NestedVec};
+use crate::filter::{Conjunct, Scope};
+use crate::query::Query;
+use crate::{Direction, Filter, FilterError, Graph, MatchConfig, Parameters, PropertySource};
+
+pub struct Pattern<T> {
+    graph: Graph<T>,
+    optional_blocks: Vec<Vec<usize>>,
+    negatives: Vec<NegativePattern<T>>,
+    names: HashMap<String, usize>,
+    filters: Vec<Filter>,
+    label_parameters: HashMap<usize, String>,
+    wildcards: HashSet<usize>,
+    anchor_parameters: HashMap<usize, String>,
+    directions: HashMap<(usize, usize), Direction>,
+}
+
+struct NegativePattern<T> {
+    graph: Graph<T>,
+    // (negative pattern node, pattern node) pairs that share a graph node
+    shared: Vec<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternMatch {
+    nodes: Vec<Option<usize>>,
+    bound_blocks: Vec<bool>,
+}
+
+impl PatternMatch {
+    pub fn nodes(&self) -> &[Option<usize>] {
+        &self.nodes
+    }
+
+    pub fn node(&self, pattern_node: usize) -> Option<usize> {
+        self.nodes[pattern_node]
+    }
+
+    pub fn is_block_bound(&self, block: usize) -> bool {
+        self.bound_blocks[block]
+    }
+}
+
+impl<T> From<Graph<T>> for Pattern<T>
+where
+    T: Eq + Hash,
+{
+    fn from(graph: Graph<T>) -> Self {
+        Pattern::new(graph)
+    }
+}
+
+impl<T> Pattern<T>
+where
+    T: Eq + Hash,
+{
+    pub fn new(graph: Graph<T>) -> Self {
+        Pattern {
+            graph,
+            optional_blocks: vec![],
+            negatives: vec![],
+            names: HashMap::new(),
+            filters: vec![],
+            label_parameters: HashMap::new(),
+            wildcards: HashSet::new(),
+            anchor_parameters: HashMap::new(),
+            directions: HashMap::new(),
+        }
+    }
+
+    pub fn graph(&self) -> &Graph<T> {
+        &self.graph
+    }
+
+    pub fn name_node(&mut self, node_id: usize, name: &str) -> &mut Self {
+        self.validate_node_id(node_id);
+        match self.names.get(name) {
+            Some(other) if *other != node_id => {
+                panic!("Name '{}' is already used by node {}.", name, other)
+            }
+            _ => {
+                self.names.retain(|_, named| *named != node_id);
+                self.names.insert(name.to_string(), node_id);
+            }
+        }
+        self
+    }
+
+    pub fn node_by_name(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub fn node_name(&self, node_id: usize) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|(_, named)| **named == node_id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    // Filters refer to pattern nodes by their names.
+    pub fn add_filter(&mut self, expression: &str) -> Result<&mut Self, FilterError> {
+        let filter = Filter::parse(expression, &|name| self.node_by_name(name))?;
+        self.filters.push(filter);
+        Ok(self)
+    }
+
+    // Filters on a single pattern node refer to its properties by their keys,
+    // e.g. `age > 30`. They restrict the candidates of the node before the
+    // search unless the node is in an optional block.
+    pub fn add_node_filter(
+        &mut self,
+        node_id: usize,
+        expression: &str,
+    ) -> Result<&mut Self, FilterError> {
+        self.validate_node_id(node_id);
+        let filter = Filter::parse_node(expression, node_id)?;
+        self.filters.push(filter);
+        Ok(self)
+    }
+
+    // The label of the pattern node is replaced by the bound label parameter.
+    pub fn label_parameter(&mut self, node_id: usize, name: &str) -> &mut Self {
+        self.validate_node_id(node_id);
+        self.label_parameters.insert(node_id, name.to_string());
+        self
+    }
+
+    // The pattern node matches graph nodes regardless of their label, e.g. both
+    // labeled and unlabeled nodes of a graph with `Option<T>` labels.
+    pub fn wildcard(&mut self, node_id: usize) -> &mut Self {
+        self.validate_node_id(node_id);
+        self.wildcards.insert(node_id);
+        self
+    }
+
+    pub fn is_wildcard(&self, node_id: usize) -> bool {
+        self.wildcards.contains(&node_id)
+    }
+
+    // The pattern node is fixed to the graph node bound to the anchor parameter.
+    pub fn anchor_parameter(&mut self, node_id: usize, name: &str) -> &mut Self {
+        self.validate_node_id(node_id);
+        self.anchor_parameters.insert(node_id, name.to_string());
+        self
+    }
+
+    // Sets how the pattern relationships from start to end are matched,
+    // parallel relationships share their direction. Default is outgoing.
+    pub fn set_direction(&mut self, start: usize, end: usize, direction: Direction) -> &mut Self {
+        self.validate_node_id(start);
+        self.validate_node_id(end);
+        if self.graph.multiplicity(start, end) == 0 {
+            panic!(
+                "Relationship ({}, {}) does not exist in the pattern.",
+                start, end
+            );
+        }
+        self.directions.insert((start, end), direction);
+        self
+    }
+
+    pub fn direction(&self, start: usize, end: usize) -> Direction {
+        self.directions
+            .get(&(start, end))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn optional_blocks(&self) -> &[Vec<usize>] {
+        &self.optional_blocks
+    }
+
+    // A block consists of the given pattern nodes and all relationships
+    // touching them. It is either matched as a whole or left unbound.
+    pub fn add_optional_block(&mut self, nodes: &[usize]) -> &mut Self {
+        let block_of = self.block_index();
+        for node_id in nodes {
+            self.validate_node_id(*node_id);
+            if let Some(block) = block_of.get(node_id) {
+                panic!(
+                    "Node {} is already part of optional block {}.",
+                    node_id, block
+                );
+            }
+        }
+        for node_id in 0..self.graph.node_count() {
+            for neighbor in self.graph.neighbors(node_id) {
+                let other = match (nodes.contains(&node_id), nodes.contains(neighbor)) {
+                    (true, false) => block_of.get(neighbor),
+                    (false, true) => block_of.get(&node_id),
+                    _ => None,
+                };
+                if let Some(other) = other {
+                    panic!(
+                        "Optional block {} must not be connected to optional block {}.",
+                        self.optional_blocks.len(),
+                        other
+                    );
+                }
+            }
+        }
+        self.optional_blocks.push(nodes.to_vec());
+        self
+    }
+
+    // Matches are dropped if the negative pattern can be matched with its
+    // shared nodes fixed to the graph nodes bound by the match. Unshared
+    // negative nodes may reuse graph nodes of the match.
+    pub fn add_negative(&mut self, negative: Graph<T>, shared: &[(usize, usize)]) -> &mut Self {
+        for (negative_node, pattern_node) in shared {
+            if *negative_node >= negative.node_count() {
+                panic!(
+                    "Negative node id {} must be within range [0..{}).",
+                    negative_node,
+                    negative.node_count()
+                );
+            }
+            self.validate_node_id(*pattern_node);
+        }
+        self.negatives.push(NegativePattern {
+            graph: negative,
+            shared: shared.to_vec(),
+        });
+        self
+    }
+
+    // Combines both patterns into one, where nodes with the same name are
+    // merged into a single node. Unnamed nodes are never merged.
+    pub fn join(&self, other: &Pattern<T>) -> Pattern<T> {
+        let mut labels = (0..self.graph.node_count())
+            .map(|node_id| self.graph.shared_label(node_id))
+            .collect::<Vec<_>>();
+        let mut names = self.names.clone();
+        let mapping = (0..other.graph.node_count())
+            .map(|node_id| {
+                let name = other.node_name(node_id);
+                match name.and_then(|name| self.node_by_name(name)) {
+                    Some(shared) => {
+                        if self.graph.node_label(shared) != other.graph.node_label(node_id) {
+                            panic!(
+                                "Variable '{}' has different labels in the joined patterns.",
+                                name.unwrap()
+                            );
+                        }
+                        shared
+                    }
+                    None => {
+                        labels.push(other.graph.shared_label(node_id));
+                        if let Some(name) = name {
+                            names.insert(name.to_string(), labels.len() - 1);
+                        }
+                        labels.len() - 1
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut relationships = self.graph.edges().collect::<Vec<_>>();
+        relationships.extend(
+            other
+                .graph
+                .edges()
+                .map(|(start_node, end_node)| (mapping[start_node], mapping[end_node])),
+        );
+
+        let mut joined = Pattern::new(Graph::from_parts(labels, &relationships));
+        joined.names = names;
+        let identity = (0..self.graph.node_count()).collect::<Vec<_>>();
+        joined.copy_constraints(self, &identity);
+        joined.copy_constraints(other, &mapping);
+        joined
+    }
+
+    pub fn with_relationship(&self, start: &str, end: &str) -> Pattern<T> {
+        let resolve = |name: &str| {
+            self.node_by_name(name)
+                .unwrap_or_else(|| panic!("Unknown variable '{}'.", name))
+        };
+        let (start_node, end_node) = (resolve(start), resolve(end));
+
+        let labels = (0..self.graph.node_count())
+            .map(|node_id| self.graph.shared_label(node_id))
+            .collect::<Vec<_>>();
+        let mut relationships = self.graph.edges().collect::<Vec<_>>();
+        relationships.push((start_node, end_node));
+
+        let mut extended = Pattern::new(Graph::from_parts(labels, &relationships));
+        extended.names = self.names.clone();
+        let identity = (0..self.graph.node_count()).collect::<Vec<_>>();
+        extended.copy_constraints(self, &identity);
+        extended
+    }
+
+    pub fn rename(&mut self, old_name: &str, new_name: &str) -> &mut Self {
+        if self.names.contains_key(new_name) {
+            panic!(
+                "Name '{}' is already used by node {}.",
+                new_name, self.names[new_name]
+            );
+        }
+        let node_id = self
+            .names
+            .remove(old_name)
+            .unwrap_or_else(|| panic!("Unknown variable '{}'.", old_name));
+        self.names.insert(new_name.to_string(), node_id);
+        self
+    }
+
+    // copies blocks, negative patterns, filters and parameters of `other`,
+    // where `mapping` translates node ids of `other` into node ids of `self`
+    fn copy_constraints(&mut self, other: &Pattern<T>, mapping: &[usize]) {
+        for block in &other.optional_blocks {
+            let block = block
+                .iter()
+                .map(|node_id| mapping[*node_id])
+                .collect::<Vec<_>>();
+            self.add_optional_block(&block);
+        }
+        for negative in &other.negatives {
+            self.negatives.push(NegativePattern {
+                graph: negative.graph.clone(),
+                shared: negative
+                    .shared
+                    .iter()
+                    .map(|(negative_node, pattern_node)| (*negative_node, mapping[*pattern_node]))
+                    .collect(),
+            });
+        }
+        for filter in &other.filters {
+            self.filters.push(filter.remap(&|node_id| mapping[node_id]));
+        }
+        for (node_id, name) in &other.label_parameters {
+            self.label_parameters
+                .insert(mapping[*node_id], name.clone());
+        }
+        for node_id in &other.wildcards {
+            self.wildcards.insert(mapping[*node_id]);
+        }
+        for (node_id, name) in &other.anchor_parameters {
+            self.anchor_parameters
+                .insert(mapping[*node_id], name.clone());
+        }
+        for ((start_node, end_node), direction) in &other.directions {
+            self.directions
+                .insert((mapping[*start_node], mapping[*end_node]), *direction);
+        }
+    }
+
+    fn is_excluded(
+        &self,
+        graph: &Graph<T>,
+        config: &MatchConfig,
+        pattern_match: &PatternMatch,
+    ) -> bool {
+        let mut existence_config = config.clone();
+        existence_config.limit(1);
+        self.negatives.iter().any(|negative| {
+            // negative patterns sharing an unbound node cannot exist
+            let anchors = negative
+                .shared
+                .iter()
+                .map(|(negative_node, pattern_node)| {
+                    pattern_match
+                        .node(*pattern_node)
+                        .map(|v_g| (*negative_node, v_g))
+                })
+                .collect::<Option<Vec<_>>>();
+            // negative nodes shared with wildcards match any label
+            let wildcards = negative
+                .shared
+                .iter()
+                .filter(|(_, pattern_node)| self.is_wildcard(*pattern_node))
+                .map(|(negative_node, _)| *negative_node)
+                .collect::<Vec<_>>();
+            anchors.is_some_and(|anchors| {
+                !dual_iso_anchored(
+                    graph,
+                    &negative.graph,
+                    &existence_config,
+                    &anchors,
+                    &wildcards,
+                )
+                .is_empty()
+            })
+        })
+    }
+
+    fn validate_node_id(&self, node_id: usize) {
+        if node_id >= self.graph.node_count() {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                node_id,
+                self.graph.node_count()
+            );
+        }
+    }
+
+    fn initial_candidates<'graph>(
+        &self,
+        graph: &'graph Graph<T>,
+        parameters: &Parameters<T>,
+        node_id: usize,
+    ) -> Candidates<'graph> {
+        let label = if self.wildcards.contains(&node_id) {
+            None
+        } else {
+            match self.label_parameters.get(&node_id) {
+                Some(name) => Some(parameters.get_label(name)),
+                None => Some(self.graph.node_label(node_id)),
+            }
+        };
+        match (self.anchor_parameters.get(&node_id), label) {
+            (Some(name), _) => {
+                let anchor = parameters.get_anchor(name);
+                if label.is_none_or(|label| graph.node_label(anchor) == label) {
+                    Candidates::single(anchor)
+                } else {
+                    Candidates::from(vec![])
+                }
+            }
+            (None, Some(label)) => Candidates::Borrowed(graph.nodes_by_label(label)),
+            (None, None) => Candidates::Owned((0..graph.node_count()).collect()),
+        }
+    }
+
+    fn block_index(&self) -> HashMap<usize, usize> {
+        self.optional_blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(block, nodes)| nodes.iter().map(move |node_id| (*node_id, block)))
+            .collect()
+    }
+
+    fn required_nodes(&self) -> Vec<usize> {
+        let block_of = self.block_index();
+        (0..self.graph.node_count())
+            .filter(|node_id| !block_of.contains_key(node_id))
+            .collect()
+    }
+}
+
+// Filters refer to the properties set in the graph builder.
+pub fn match_pattern<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Pattern<T>,
+    config: &MatchConfig,
+) -> Vec<PatternMatch> {
+    match_pattern_with_properties(graph, pattern, config, graph)
+}
+
+pub fn match_pattern_with_properties<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Pattern<T>,
+    config: &MatchConfig,
+    properties: &dyn PropertySource,
+) -> Vec<PatternMatch> {
+    match_pattern_with_parameters(graph, pattern, config, properties, &Parameters::new())
+}
+
+pub fn match_pattern_with_parameters<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Pattern<T>,
+    config: &MatchConfig,
+    properties: &dyn PropertySource,
+    parameters: &Parameters<T>,
+) -> Vec<PatternMatch> {
+    // optional blocks, negative patterns and filters change the number of
+    // matches, so the limit is applied to the final matches only
+    let limit = config.limit;
+    let mut unlimited_config = config.clone();
+    unlimited_config.limit = None;
+    let config = &unlimited_config;
+    let scope = Scope {
+        graph,
+        properties,
+        parameters: &parameters.values,
+    };
+
+    // filter conjuncts on a single required node are applied to its candidates,
+    // everything else is checked once a match is complete
+    let required = pattern.required_nodes();
+    let mut pushed_down: HashMap<usize, Vec<&Conjunct>> = HashMap::new();
+    let mut post_filters = vec![];
+    for conjunct in pattern.filters.iter().flat_map(|filter| filter.conjuncts()) {
+        match conjunct.variables() {
+            [u_p] if required.contains(u_p) => pushed_down.entry(*u_p).or_default().push(conjunct),
+            _ => post_filters.push(conjunct),
+        }
+    }
+    let query = Query::with_directions(&pattern.graph, &pattern.directions);
+    let match_subpattern = |nodes: &[usize]| {
+        let candidates = nodes
+            .iter()
+            .map(|u_p| {
+                let candidates = pattern.initial_candidates(graph, parameters, *u_p);
+                match pushed_down.get(u_p) {
+                    Some(conjuncts) => Candidates::Owned(
+                        candidates
+                            .iter()
+                            .copied()
+                            .filter(|v_g| {
+                                let binding = |x: usize| if x == *u_p { Some(*v_g) } else { None };
+                                conjuncts
+                                    .iter()
+                                    .all(|conjunct| conjunct.holds(&scope, &binding))
+                            })
+                            .collect(),
+                    ),
+                    None => candidates,
+                }
+            })
+            .collect();
+        dual_iso_with_candidates(graph, &query.restrict(nodes), config, candidates)
+    };
+
+    let required_matches = match_subpattern(&required);
+
+    // for each block, group the matches of the required part plus the block
+    // by their required part to know which extensions are available
+    let block_extensions = pattern
+        .optional_blocks
+        .iter()
+        .map(|block| {
+            let mut nodes = required.clone();
+            nodes.extend(block);
+            let mut extensions: HashMap<Vec<usize>, NestedVec> = HashMap::new();
+            for mut extended_match in match_subpattern(&nodes) {
+                let extension = extended_match.split_off(required.len());
+                extensions
+                    .entry(extended_match)
+                    .or_default()
+                    .push(extension);
+            }
+            extensions
+        })
+        .collect::<Vec<_>>();
+
+    let extender = OptionalExtender {
+        graph,
+        pattern,
+        config,
+        scope: &scope,
+        post_filters,
+        block_extensions,
+    };
+    let mut matches = vec![];
+    for required_match in required_matches {
+        if limit.is_some_and(|limit| matches.len() >= limit) {
+            break;
+        }
+        let mut nodes = vec![None; pattern.graph.node_count()];
+        for (u_p, v_g) in required.iter().zip(&required_match) {
+            nodes[*u_p] = Some(*v_g);
+        }
+        let mut state = ExtensionState {
+            nodes,
+            bound_blocks: vec![false; pattern.optional_blocks.len()],
+            used: required_match.iter().copied().collect(),
+        };
+        extender.extend(&required_match, &mut state, &mut matches, 0);
+    }
+    if let Some(limit) = limit {
+        matches.truncate(limit);
+    }
+    matches
+}
+
+struct OptionalExtender<'a, T> {
+    graph: &'a Graph<T>,
+    pattern: &'a Pattern<T>,
+    config: &'a MatchConfig,
+    scope: &'a Scope<'a, T>,
+    post_filters: Vec<&'a Conjunct>,
+    block_extensions: Vec<HashMap<Vec<usize>, NestedVec>>,
+}
+
+struct ExtensionState {
+    nodes: Vec<Option<usize>>,
+    bound_blocks: Vec<bool>,
+    used: HashSet<usize>,
+}
+
+impl<T: Eq + Hash> OptionalExtender<'_, T> {
+    fn extend(
+        &self,
+        required_match: &[usize],
+        state: &mut ExtensionState,
+        matches: &mut Vec<PatternMatch>,
+        block: usize,
+    ) {
+        if block == self.pattern.optional_blocks.len() {
+            let pattern_match = PatternMatch {
+                nodes: state.nodes.clone(),
+                bound_blocks: state.bound_blocks.clone(),
+            };
+            let binding = |u_p: usize| pattern_match.node(u_p);
+            if self
+                .post_filters
+                .iter()
+                .all(|conjunct| conjunct.holds(self.scope, &binding))
+                && !self
+                    .pattern
+                    .is_excluded(self.graph, self.config, &pattern_match)
+            {
+                matches.push(pattern_match);
+            }
+            return;
+        }
+        let block_nodes = &self.pattern.optional_blocks[block];
+        let extensions = self.block_extensions[block]
+            .get(required_match)
+            .map_or(&[][..], |extensions| extensions.as_slice());
+
+        let mut is_bound = false;
+        for extension in extensions {
+            // graph nodes must not be shared with other blocks
+            if self.config.is_injective() && extension.iter().any(|v_g| state.used.contains(v_g)) {
+                continue;
+            }
+            is_bound = true;
+            for (u_p, v_g) in block_nodes.iter().zip(extension) {
+                state.nodes[*u_p] = Some(*v_g);
+                state.used.insert(*v_g);
+            }
+            state.bound_blocks[block] = true;
+            self.extend(required_match, state, matches, block + 1);
+            for (u_p, v_g) in block_nodes.iter().zip(extension) {
+                state.nodes[*u_p] = None;
+                state.used.remove(v_g);
+            }
+            state.bound_blocks[block] = false;
+        }
+
+        if !is_bound {
+            self.extend(required_match, state, matches, block + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GraphBuilder, NoProperties, Value};
+
+    use super::*;
+
+    #[test]
+    fn test_optional_block() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "car")
+            .add_node(3, "city")
+            .add_relationship(0, 2)
+            .add_relationship(2, 3)
+            .build();
+
+        // (person)-->(car)-->(city), where car and city are optional
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "car")
+                .add_node(2, "city")
+                .add_relationship(0, 1)
+                .add_relationship(1, 2)
+                .build(),
+        );
+        pattern.add_optional_block(&[1, 2]);
+
+        let mut matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+        matches.sort_by_key(|m| m.nodes().to_vec());
+
+        assert_eq!(2, matches.len());
+        assert_eq!(&[Some(0), Some(2), Some(3)], matches[0].nodes());
+        assert!(matches[0].is_block_bound(0));
+        assert_eq!(&[Some(1), None, None], matches[1].nodes());
+        assert!(!matches[1].is_block_bound(0));
+    }
+
+    #[test]
+    fn test_optional_block_is_all_or_nothing() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "car")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "car")
+                .add_node(2, "city")
+                .add_relationship(0, 1)
+                .add_relationship(1, 2)
+                .build(),
+        );
+        pattern.add_optional_block(&[1, 2]);
+
+        let matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(0), None, None], matches[0].nodes());
+    }
+
+    #[test]
+    fn test_negative_relationship() {
+        // a pays b, but there is no payment back
+        let graph = GraphBuilder::new()
+            .add_node(0, "account")
+            .add_node(1, "account")
+            .add_node(2, "account")
+            .add_node(3, "account")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .add_relationship(2, 3)
+            .build();
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "account")
+                .add_node(1, "account")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        pattern.add_negative(
+            GraphBuilder::new()
+                .add_node(0, "account")
+                .add_node(1, "account")
+                .add_relationship(1, 0)
+                .build(),
+            &[(0, 0), (1, 1)],
+        );
+
+        let matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(2), Some(3)], matches[0].nodes());
+    }
+
+    #[test]
+    fn test_limit_applies_after_negative_patterns() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "person")
+            .add_node(3, "car")
+            .add_relationship(0, 3)
+            .add_relationship(1, 3)
+            .build();
+
+        let mut pattern = Pattern::new(GraphBuilder::new().add_node(0, "person").build());
+        pattern.add_negative(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "car")
+                .add_relationship(0, 1)
+                .build(),
+            &[(0, 0)],
+        );
+
+        let matches = match_pattern(&graph, &pattern, MatchConfig::new().limit(1));
+
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(2)], matches[0].nodes());
+    }
+
+    #[test]
+    fn test_negative_node() {
+        // persons without a car
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "car")
+            .add_relationship(0, 2)
+            .build();
+
+        let mut pattern = Pattern::new(GraphBuilder::new().add_node(0, "person").build());
+        pattern.add_negative(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "car")
+                .add_relationship(0, 1)
+                .build(),
+            &[(0, 0)],
+        );
+
+        let matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(1)], matches[0].nodes());
+    }
+
+    #[test]
+    fn test_filter() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "person")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+
+        let mut properties = HashMap::new();
+        for (node_id, age) in [(0, 20), (1, 40), (2, 60)] {
+            let mut node_properties = HashMap::new();
+            node_properties.insert("age".to_string(), Value::Int(age));
+            properties.insert(node_id, node_properties);
+        }
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "person")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        pattern
+            .name_node(0, "a")
+            .name_node(1, "b")
+            .add_filter("a.age >= 40 AND b.age < a.age")
+            .unwrap();
+
+        let matches =
+            match_pattern_with_properties(&graph, &pattern, &MatchConfig::default(), &properties);
+
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(2), Some(0)], matches[0].nodes());
+    }
+
+    #[test]
+    fn test_node_filter() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "person")
+            .set_property(0, "age", 20)
+            .set_property(1, "age", 40)
+            .set_property(1, "vip", true)
+            .set_property(2, "age", 60)
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "person")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        pattern.add_node_filter(0, "age > 30").unwrap();
+        let matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+        let mut nodes = matches
+            .iter()
+            .map(|m| m.nodes().to_vec())
+            .collect::<Vec<_>>();
+        nodes.sort();
+        assert_eq!(vec![vec![Some(1), Some(2)], vec![Some(2), Some(0)]], nodes);
+
+        pattern.add_node_filter(0, "vip").unwrap();
+        let matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(1), Some(2)], matches[0].nodes());
+    }
+
+    #[test]
+    fn test_parameters() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "car")
+            .add_node(3, "bike")
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .add_relationship(1, 3)
+            .build();
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "vehicle")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        pattern
+            .name_node(0, "owner")
+            .label_parameter(1, "kind")
+            .add_filter("owner.id >= $min_id")
+            .unwrap();
+
+        let run = |parameters: &Parameters<&str>| {
+            let mut matches = match_pattern_with_parameters(
+                &graph,
+                &pattern,
+                &MatchConfig::default(),
+                &NoProperties,
+                parameters,
+            );
+            matches.sort_by_key(|m| m.nodes().to_vec());
+            matches
+                .iter()
+                .map(|m| m.nodes().iter().map(|n| n.unwrap()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![vec![0, 2]],
+            run(Parameters::new().label("kind", "car").value("min_id", 0))
+        );
+        assert_eq!(
+            vec![vec![0, 3], vec![1, 3]],
+            run(Parameters::new().label("kind", "bike").value("min_id", 0))
+        );
+        assert_eq!(
+            vec![vec![1, 3]],
+            run(Parameters::new().label("kind", "bike").value("min_id", 1))
+        );
+    }
+
+    #[test]
+    fn test_wildcard() {
+        // the car has no label
+        let graph = GraphBuilder::new()
+            .add_node(0, Some("person"))
+            .add_unlabeled_node(1)
+            .add_node(2, Some("house"))
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, Some("person"))
+                .add_node(1, None)
+                .add_relationship(0, 1)
+                .build(),
+        );
+        let config = MatchConfig::default();
+        let nodes = |matches: Vec<PatternMatch>| {
+            let mut nodes = matches
+                .iter()
+                .map(|m| m.nodes().to_vec())
+                .collect::<Vec<_>>();
+            nodes.sort();
+            nodes
+        };
+
+        assert_eq!(
+            vec![vec![Some(0), Some(1)]],
+            nodes(match_pattern(&graph, &pattern, &config))
+        );
+
+        pattern.wildcard(1);
+        assert!(pattern.is_wildcard(1));
+        assert_eq!(
+            vec![vec![Some(0), Some(1)], vec![Some(0), Some(2)]],
+            nodes(match_pattern(&graph, &pattern, &config))
+        );
+
+        // a negative node shared with the wildcard matches the house as well,
+        // so the negative pattern excludes both matches
+        pattern.add_negative(
+            GraphBuilder::new()
+                .add_node(0, Some("person"))
+                .add_node(1, None)
+                .add_relationship(0, 1)
+                .build(),
+            &[(0, 0), (1, 1)],
+        );
+        assert!(match_pattern(&graph, &pattern, &config).is_empty());
+    }
+
+    #[test]
+    fn test_anchor_parameter() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "car")
+            .add_relationship(0, 2)
+            .add_relationship(1, 2)
+            .build();
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "car")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        pattern.anchor_parameter(0, "start");
+
+        for start in 0..2 {
+            let matches = match_pattern_with_parameters(
+                &graph,
+                &pattern,
+                &MatchConfig::default(),
+                &NoProperties,
+                Parameters::new().anchor("start", start),
+            );
+            assert_eq!(1, matches.len());
+            assert_eq!(&[Some(start), Some(2)], matches[0].nodes());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Anchor parameter 'start' has not been bound.")]
+    fn test_unbound_anchor_parameter() {
+        let graph = GraphBuilder::new().add_node(0, "person").build();
+        let mut pattern = Pattern::new(GraphBuilder::new().add_node(0, "person").build());
+        pattern.anchor_parameter(0, "start");
+
+        let _ = match_pattern(&graph, &pattern, &MatchConfig::default());
+    }
+
+    #[test]
+    fn test_filter_unknown_variable() {
+        let mut pattern = Pattern::new(GraphBuilder::new().add_node(0, "person").build());
+        pattern.name_node(0, "a");
+
+        assert!(pattern.add_filter("b.age > 1").is_err());
+    }
+
+    fn named_path(names: &[&'static str], labels: &[&'static str]) -> Pattern<&'static str> {
+        let mut builder = GraphBuilder::new();
+        for (node_id, label) in labels.iter().enumerate() {
+            builder.add_node(node_id, *label);
+        }
+        for node_id in 1..labels.len() {
+            builder.add_relationship(node_id - 1, node_id);
+        }
+        let mut pattern = Pattern::new(builder.build());
+        for (node_id, name) in names.iter().enumerate() {
+            pattern.name_node(node_id, name);
+        }
+        pattern
+    }
+
+    #[test]
+    fn test_join() {
+        let owns = named_path(&["p", "c"], &["person", "car"]);
+        let mut lives = named_path(&["p", "x"], &["person", "city"]);
+        lives.add_filter("x.id > 0").unwrap();
+
+        let joined = owns.join(&lives);
+
+        assert_eq!(3, joined.graph().node_count());
+        assert_eq!(2, joined.graph().relationship_count());
+        assert_eq!(Some(2), joined.node_by_name("x"));
+        assert_eq!(&[1, 2], joined.graph().neighbors(0));
+
+        let graph = GraphBuilder::new()
+            .add_node(0, "city")
+            .add_node(1, "person")
+            .add_node(2, "car")
+            .add_node(3, "city")
+            .add_relationship(1, 2)
+            .add_relationship(1, 0)
+            .add_relationship(1, 3)
+            .build();
+
+        let matches = match_pattern(&graph, &joined, &MatchConfig::default());
+
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(1), Some(2), Some(3)], matches[0].nodes());
+    }
+
+    #[test]
+    fn test_join_keeps_unnamed_nodes_apart() {
+        let knows = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "person")
+                .add_relationship(0, 1)
+                .build(),
+        );
+
+        let joined = knows.join(&knows);
+
+        assert_eq!(4, joined.graph().node_count());
+        assert_eq!(2, joined.graph().weakly_connected_components().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable 'p' has different labels in the joined patterns.")]
+    fn test_join_label_mismatch() {
+        let owns = named_path(&["p", "c"], &["person", "car"]);
+        let broken = named_path(&["p"], &["car"]);
+
+        let _ = owns.join(&broken);
+    }
+
+    #[test]
+    fn test_with_relationship_and_rename() {
+        let mut path = named_path(&["a", "b", "c"], &["x", "x", "x"]);
+        let triangle = path.with_relationship("c", "a");
+
+        assert_eq!(3, triangle.graph().relationship_count());
+        assert_eq!(&[0], triangle.graph().neighbors(2));
+
+        path.rename("c", "z");
+        assert_eq!(Some(2), path.node_by_name("z"));
+        assert_eq!(None, path.node_by_name("c"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Optional block 1 must not be connected to optional block 0.")]
+    fn test_connected_optional_blocks() {
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "a")
+                .add_node(1, "b")
+                .add_node(2, "c")
+                .add_relationship(1, 2)
+                .build(),
+        );
+        pattern.add_optional_block(&[1]).add_optional_block(&[2]);
+    }
+
+    #[test]
+    fn test_direction() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "city")
+            .add_relationship(0, 2)
+            .add_relationship(2, 1)
+            .build();
+
+        // (city)-->(person) matched against both relationship directions
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "city")
+                .add_node(1, "person")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        let config = MatchConfig::default();
+
+        let matches = match_pattern(&graph, &pattern, &config);
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(2), Some(1)], matches[0].nodes());
+
+        pattern.set_direction(0, 1, Direction::Incoming);
+        let matches = match_pattern(&graph, &pattern, &config);
+        assert_eq!(1, matches.len());
+        assert_eq!(&[Some(2), Some(0)], matches[0].nodes());
+
+        pattern.set_direction(0, 1, Direction::Either);
+        let mut matches = match_pattern(&graph, &pattern, &config);
+        matches.sort_by_key(|m| m.nodes().to_vec());
+        assert_eq!(2, matches.len());
+        assert_eq!(&[Some(2), Some(0)], matches[0].nodes());
+        assert_eq!(&[Some(2), Some(1)], matches[1].nodes());
+    }
+
+    #[test]
+    fn test_direction_in_optional_block() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "car")
+            .add_relationship(1, 0)
+            .build();
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "car")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        pattern.add_optional_block(&[1]);
+        let config = MatchConfig::default();
+
+        let matches = match_pattern(&graph, &pattern, &config);
+        assert_eq!(&[Some(0), None], matches[0].nodes());
+
+        pattern.set_direction(0, 1, Direction::Either);
+        let matches = match_pattern(&graph, &pattern, &config);
+        assert_eq!(&[Some(0), Some(1)], matches[0].nodes());
+    }
+
+    #[test]
+    #[should_panic(expected = "Relationship (1, 0) does not exist in the pattern.")]
+    fn test_direction_unknown_relationship() {
+        let mut pattern = named_path(&["a", "b"], &["x", "x"]);
+        pattern.set_direction(1, 0, Direction::Incoming);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::dual_iso::{
+    has_injective_assignment, hub_order, init_candidates, simulation_rounds, Fixpoint,
+};
+use crate::query::{Direction, Query};
+use crate::{Graph, MatchConfig};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pruning {
+    /// Each branch runs the simulation on the candidates of its pattern nodes.
+    Simulation,
+    /// Distinct pattern nodes match distinct graph nodes.
+    DistinctNodes,
+    /// Branches whose candidates cannot be assigned to distinct graph nodes are dropped.
+    AllDifferent,
+}
+
+// How a search would proceed, computed from the simulation that precedes the
+// search without running the search itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryPlan {
+    components: Vec<Vec<usize>>,
+    pruning: Vec<Pruning>,
+    candidates: Vec<usize>,
+    branches: Vec<f64>,
+    matches: f64,
+}
+
+impl QueryPlan {
+    // The weakly connected components of the pattern, each in the order in
+    // which its pattern nodes are matched. Components are matched on their
+    // own and their matches are combined afterwards.
+    pub fn components(&self) -> &[Vec<usize>] {
+        &self.components
+    }
+
+    // The pattern nodes in the order in which they are matched.
+    pub fn order(&self) -> Vec<usize> {
+        self.components.concat()
+    }
+
+    pub fn pruning(&self) -> &[Pruning] {
+        &self.pruning
+    }
+
+    // number of candidates per pattern node after the simulation, 0 for all
+    // pattern nodes if the simulation rules out any match
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+
+    // estimated number of branches per pattern node, i.e. of partial matches
+    // up to and including the pattern node in its component
+    pub fn estimated_branches(&self) -> &[f64] {
+        &self.branches
+    }
+
+    pub fn estimated_matches(&self) -> f64 {
+        self.matches
+    }
+
+    // The estimated number of branches of all components plus the number of
+    // combinations of their matches. Limits and quotas are not taken into
+    // account.
+    pub fn estimated_cost(&self) -> f64 {
+        let branches = self.branches.iter().sum::<f64>();
+        if self.components.len() > 1 {
+            branches + self.matches
+        } else {
+            branches
+        }
+    }
+}
+
+// Plans the search of the pattern as `dual_iso_with_config` would run it.
+// The estimates assume that a partial match extends to as many candidates of
+// the next pattern node as the candidates of its most selective matched
+// neighbor have on average. A minimized pattern is not planned, but the
+// pattern as given.
+pub fn plan<T: Eq + Hash>(graph: &Graph<T>, pattern: &Graph<T>, config: &MatchConfig) -> QueryPlan {
+    let query = Query::new(pattern);
+    let mut candidates = init_candidates(graph, &query);
+    let fixpoint = simulation_rounds(
+        graph,
+        &query,
+        config,
+        &mut candidates,
+        config.max_simulation_rounds,
+        &mut |_| {},
+    );
+
+    let mut pruning = vec![Pruning::Simulation];
+    if config.is_injective() {
+        pruning.push(Pruning::DistinctNodes);
+        if config.all_different {
+            pruning.push(Pruning::AllDifferent);
+        }
+    }
+
+    let is_empty = fixpoint == Fixpoint::Empty
+        || (pruning.contains(&Pruning::AllDifferent) && !has_injective_assignment(&candidates));
+    let components = query
+        .weakly_connected_components()
+        .into_iter()
+        .map(|component| match config.hub_degree {
+            Some(hub_degree) => {
+                let component_candidates = component
+                    .iter()
+                    .map(|u_p| candidates[*u_p].clone())
+                    .collect::<Vec<_>>();
+                hub_order(graph, &component_candidates, hub_degree)
+                    .into_iter()
+                    .map(|idx| component[idx])
+                    .collect()
+            }
+            None => component,
+        })
+        .collect::<Vec<Vec<usize>>>();
+    if is_empty {
+        return QueryPlan {
+            components,
+            pruning,
+            candidates: vec![0; query.node_count()],
+            branches: vec![0.0; query.node_count()],
+            matches: 0.0,
+        };
+    }
+
+    let sets = candidates
+        .iter()
+        .map(|c| c.iter().copied().collect::<HashSet<_>>())
+        .collect::<Vec<_>>();
+    let mut branches = vec![0.0; query.node_count()];
+    let mut matches = 1.0;
+    for component in &components {
+        let mut partial_matches = 1.0;
+        for (idx, v_p) in component.iter().enumerate() {
+            let extensions = component[..idx]
+                .iter()
+                .filter_map(|u_p| fanout(graph, &query, &sets, *u_p, *v_p))
+                .fold(candidates[*v_p].len() as f64, f64::min);
+            partial_matches *= extensions;
+            branches[*v_p] = partial_matches;
+        }
+        matches *= partial_matches;
+    }
+
+    QueryPlan {
+        components,
+        pruning,
+        candidates: candidates.iter().map(|c| c.len()).collect(),
+        branches,
+        matches,
+    }
+}
+
+// The average number of candidates of v_P related to a candidate of u_P, or
+// None if the pattern nodes are not related.
+fn fanout<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    sets: &[HashSet<usize>],
+    u_p: usize,
+    v_p: usize,
+) -> Option<f64> {
+    // relationships from a candidate of `start` to a candidate of `end`
+    let count = |start: usize, end: usize| {
+        sets[start]
+            .iter()
+            .map(|s_g| {
+                graph
+                    .distinct_neighbors(*s_g)
+                    .filter(|(e_g, _)| *e_g != *s_g && sets[end].contains(e_g))
+                    .count()
+            })
+            .sum::<usize>()
+    };
+    let mut pairs = None;
+    let constraints = query
+        .constraints(u_p)
+        .iter()
+        .filter(|c| c.target == v_p)
+        .map(|c| (u_p, v_p, c.direction))
+        .chain(
+            query
+                .constraints(v_p)
+                .iter()
+                .filter(|c| c.target == u_p)
+                .map(|c| (v_p, u_p, c.direction)),
+        );
+    for (start, end, direction) in constraints {
+        let supported = match direction {
+            Direction::Outgoing => count(start, end),
+            Direction::Incoming => count(end, start),
+            Direction::Either => count(start, end) + count(end, start),
+        };
+        pairs = Some(pairs.map_or(supported, |pairs: usize| pairs.min(supported)));
+    }
+    pairs.map(|pairs| pairs as f64 / sets[u_p].len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        // a star with one b center and three a leaves
+        let graph = GraphBuilder::new()
+            .add_node(0, "b")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_node(3, "a")
+            .add_node(4, "c")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "b")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+
+        let plan = plan(&graph, &pattern, &MatchConfig::default());
+        assert_eq!(vec![0, 1, 2], plan.order());
+        assert_eq!(&[1, 3, 3], plan.candidates());
+        assert_eq!(
+            &[Pruning::Simulation, Pruning::DistinctNodes],
+            plan.pruning()
+        );
+        assert_eq!(&[1.0, 3.0, 9.0], plan.estimated_branches());
+        assert_eq!(9.0, plan.estimated_matches());
+        assert_eq!(13.0, plan.estimated_cost());
+    }
+
+    #[test]
+    fn test_plan_without_matches() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let plan = plan(&graph, &pattern, MatchConfig::new().all_different(true));
+        assert_eq!(&[0, 0], plan.candidates());
+        assert_eq!(0.0, plan.estimated_cost());
+        assert_eq!(
+            &[
+                Pruning::Simulation,
+                Pruning::DistinctNodes,
+                Pruning::AllDifferent
+            ],
+            plan.pruning()
+        );
+    }
+
+    #[test]
+    fn test_plan_disconnected() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "b")
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .build();
+
+        let plan = plan(&graph, &pattern, &MatchConfig::default());
+        assert_eq!(&[vec![0], vec![1]], plan.components());
+        assert_eq!(2.0, plan.estimated_matches());
+        assert_eq!(5.0, plan.estimated_cost());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{dual_iso_with_candidates, NestedVec};
+use crate::query::Query;
+use crate::{Graph, GraphBuilder, MatchConfig, Value};
+
+// The labels of a node in a lowered property graph. Each distinct set is one
+// label of the matcher, so nodes with the same labels share a label bucket.
+pub type LabelSet = BTreeSet<String>;
+
+// Nodes with any number of labels and properties, connected by relationships
+// with an optional type. The model is only meant for assembling graphs and
+// patterns, `lower` turns it into the graph the matcher runs on.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PropertyGraph {
+    labels: Vec<LabelSet>,
+    properties: Vec<HashMap<String, Value>>,
+    relationships: Vec<(usize, usize, Option<String>)>,
+}
+
+impl PropertyGraph {
+    pub fn new() -> Self {
+        PropertyGraph::default()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn relationship_count(&self) -> usize {
+        self.relationships.len()
+    }
+
+    // Adds a node with the given labels and returns its id, ids are assigned
+    // in the order nodes are added.
+    pub fn add_node<I, S>(&mut self, labels: I) -> usize
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.labels
+            .push(labels.into_iter().map(Into::into).collect());
+        self.properties.push(HashMap::new());
+        self.labels.len() - 1
+    }
+
+    pub fn add_label(&mut self, node_id: usize, label: &str) -> &mut Self {
+        self.validate_node_id(node_id);
+        self.labels[node_id].insert(label.to_string());
+        self
+    }
+
+    pub fn set_property<V: Into<Value>>(
+        &mut self,
+        node_id: usize,
+        key: &str,
+        value: V,
+    ) -> &mut Self {
+        self.validate_node_id(node_id);
+        self.properties[node_id].insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn add_relationship(&mut self, start_node: usize, end_node: usize) -> &mut Self {
+        self.validate_node_id(start_node);
+        self.validate_node_id(end_node);
+        self.relationships.push((start_node, end_node, None));
+        self
+    }
+
+    pub fn add_relationship_with_type(
+        &mut self,
+        start_node: usize,
+        end_node: usize,
+        rel_type: &str,
+    ) -> &mut Self {
+        self.validate_node_id(start_node);
+        self.validate_node_id(end_node);
+        self.relationships
+            .push((start_node, end_node, Some(rel_type.to_string())));
+        self
+    }
+
+    pub fn labels(&self, node_id: usize) -> &LabelSet {
+        self.validate_node_id(node_id);
+        &self.labels[node_id]
+    }
+
+    pub fn property(&self, node_id: usize, key: &str) -> Option<&Value> {
+        self.validate_node_id(node_id);
+        self.properties[node_id].get(key)
+    }
+
+    // Builds the graph the matcher runs on, which is labeled by label sets
+    // and keeps the relationship types and properties. Match it with
+    // `dual_iso_with_label_sets`.
+    pub fn lower(&self) -> Graph<LabelSet> {
+        let mut builder = GraphBuilder::new();
+        for (node_id, labels) in self.labels.iter().enumerate() {
+            builder.add_node(node_id, labels.clone());
+        }
+        for (node_id, properties) in self.properties.iter().enumerate() {
+            for (key, value) in properties {
+                builder.set_property(node_id, key, value.clone());
+            }
+        }
+        for (start_node, end_node, rel_type) in &self.relationships {
+            match rel_type {
+                Some(rel_type) => {
+                    builder.add_relationship_with_type(*start_node, *end_node, rel_type)
+                }
+                None => builder.add_relationship(*start_node, *end_node),
+            };
+        }
+        builder.build()
+    }
+
+    fn validate_node_id(&self, node_id: usize) {
+        if node_id >= self.labels.len() {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                node_id,
+                self.labels.len()
+            );
+        }
+    }
+}
+
+// Matches lowered property graphs. A pattern node matches the graph nodes that
+// have all of its labels and all of its properties with equal values.
+pub fn dual_iso_with_label_sets(
+    graph: &Graph<LabelSet>,
+    pattern: &Graph<LabelSet>,
+    config: &MatchConfig,
+) -> NestedVec {
+    let query = Query::new(pattern);
+    let buckets = graph.label_buckets().collect::<Vec<_>>();
+    let candidates = (0..query.node_count())
+        .map(|u_p| {
+            let labels = query.label(u_p);
+            // the buckets of all label sets that include the pattern labels
+            let mut candidates = buckets
+                .iter()
+                .filter(|nodes| labels.is_subset(graph.node_label(nodes[0])))
+                .flat_map(|nodes| nodes.iter().copied())
+                .filter(|v_g| has_properties(graph, pattern, u_p, *v_g))
+                .collect::<Vec<_>>();
+            candidates.sort_unstable();
+            Candidates::from(candidates)
+        })
+        .collect();
+    dual_iso_with_candidates(graph, &query, config, candidates)
+}
+
+fn has_properties(
+    graph: &Graph<LabelSet>,
+    pattern: &Graph<LabelSet>,
+    u_p: usize,
+    v_g: usize,
+) -> bool {
+    pattern
+        .node_properties()
+        .get(&u_p)
+        .is_none_or(|properties| {
+            properties
+                .iter()
+                .all(|(key, value)| graph.property(v_g, key) == Some(value))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower() {
+        let mut graph = PropertyGraph::new();
+        let alice = graph.add_node(["Person", "Employee"]);
+        let acme = graph.add_node(["Company"]);
+        graph
+            .set_property(alice, "name", "Alice")
+            .add_relationship_with_type(alice, acme, "WORKS_AT");
+
+        let lowered = graph.lower();
+        assert_eq!(2, lowered.node_count());
+        assert_eq!(graph.labels(alice), lowered.node_label(alice));
+        assert_eq!(1, lowered.typed_multiplicity(alice, acme, "WORKS_AT"));
+        assert_eq!(Some(&Value::from("Alice")), lowered.property(alice, "name"));
+    }
+
+    #[test]
+    fn test_dual_iso_with_label_sets() {
+        let mut graph = PropertyGraph::new();
+        let alice = graph.add_node(["Person", "Employee"]);
+        let bob = graph.add_node(["Person"]);
+        let acme = graph.add_node(["Company"]);
+        graph
+            .set_property(alice, "age", 42)
+            .set_property(bob, "age", 42)
+            .add_relationship_with_type(alice, acme, "WORKS_AT")
+            .add_relationship_with_type(bob, acme, "KNOWS");
+        let graph = graph.lower();
+
+        let mut pattern = PropertyGraph::new();
+        let person = pattern.add_node(["Person"]);
+        let company = pattern.add_node(["Company"]);
+        pattern.add_relationship(person, company);
+        let config = MatchConfig::default();
+        assert_eq!(
+            vec![vec![alice, acme], vec![bob, acme]],
+            dual_iso_with_label_sets(&graph, &pattern.lower(), &config)
+        );
+
+        pattern
+            .add_label(person, "Employee")
+            .set_property(person, "age", 42);
+        assert_eq!(
+            vec![vec![alice, acme]],
+            dual_iso_with_label_sets(&graph, &pattern.lower(), &config)
+        );
+
+        let mut pattern = PropertyGraph::new();
+        let person = pattern.add_node(["Person"]);
+        let company = pattern.add_node(Vec::<String>::new());
+        pattern.add_relationship_with_type(person, company, "KNOWS");
+        assert_eq!(
+            vec![vec![bob, acme]],
+            dual_iso_with_label_sets(&graph, &pattern.lower(), &config)
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use crate::Graph;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Direction {
+    /// The pattern relationship (u, v) matches a graph relationship (u, v).
+    #[default]
+    Outgoing,
+    /// The pattern relationship (u, v) matches a graph relationship (v, u).
+    Incoming,
+    /// The pattern relationship (u, v) matches graph relationships in both directions.
+    Either,
+}
+
+// A relationship from u_P to `target` that every candidate of u_P must have.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Constraint {
+    pub(crate) target: usize,
+    pub(crate) multiplicity: usize,
+    pub(crate) direction: Direction,
+    // only relationships of the type count if set
+    pub(crate) rel_type: Option<Rc<str>>,
+}
+
+impl Constraint {
+    // number of graph relationships between u_G and v_G satisfying the direction
+    pub(crate) fn count<T: Eq + Hash>(&self, graph: &Graph<T>, u_g: usize, v_g: usize) -> usize {
+        match self.direction {
+            Direction::Outgoing => self.relationships(graph, u_g, v_g),
+            Direction::Incoming => self.relationships(graph, v_g, u_g),
+            Direction::Either => {
+                self.relationships(graph, u_g, v_g) + self.relationships(graph, v_g, u_g)
+            }
+        }
+    }
+
+    // number of graph relationships from the start to the end node of the type
+    pub(crate) fn relationships<T: Eq + Hash>(
+        &self,
+        graph: &Graph<T>,
+        start_node: usize,
+        end_node: usize,
+    ) -> usize {
+        match &self.rel_type {
+            Some(rel_type) => graph.typed_multiplicity(start_node, end_node, rel_type),
+            None => graph.multiplicity(start_node, end_node),
+        }
+    }
+
+    pub(crate) fn is_satisfied_by<T: Eq + Hash>(
+        &self,
+        graph: &Graph<T>,
+        u_g: usize,
+        v_g: usize,
+    ) -> bool {
+        self.count(graph, u_g, v_g) >= self.multiplicity
+    }
+}
+
+// The pattern as seen by the matcher: node labels and the constraints per node.
+#[derive(PartialEq, Eq)]
+pub(crate) struct Query<T> {
+    labels: Vec<Rc<T>>,
+    constraints: Vec<Vec<Constraint>>,
+    // (u_P, v_P) pairs where u_P must match a smaller graph node than v_P
+    order: Vec<(usize, usize)>,
+    // the pattern node each node of a restricted query stems from
+    original_ids: Vec<usize>,
+}
+
+impl<T> Query<T>
+where
+    T: Eq + Hash,
+{
+    pub(crate) fn new(pattern: &Graph<T>) -> Self {
+        Query::with_directions(pattern, &HashMap::new())
+    }
+
+    pub(crate) fn with_directions(
+        pattern: &Graph<T>,
+        directions: &HashMap<(usize, usize), Direction>,
+    ) -> Self {
+        let labels = (0..pattern.node_count())
+            .map(|node_id| pattern.shared_label(node_id))
+            .collect();
+        let constraints = (0..pattern.node_count())
+            .map(|u_p| {
+                // parallel pattern relationships require at least as many
+                // parallel relationships in the graph
+                let mut constraints = vec![];
+                for (v_p, multiplicity) in pattern.distinct_neighbors(u_p) {
+                    let direction = directions.get(&(u_p, v_p)).copied().unwrap_or_default();
+                    // one constraint per type, the untyped relationships can
+                    // be matched by any of the remaining graph relationships
+                    let types = pattern.shared_relationship_types(u_p, v_p);
+                    for run in types.chunk_by(|a, b| a == b) {
+                        constraints.push(Constraint {
+                            target: v_p,
+                            multiplicity: run.len(),
+                            direction,
+                            rel_type: Some(Rc::clone(&run[0])),
+                        });
+                    }
+                    if types.len() < multiplicity {
+                        constraints.push(Constraint {
+                            target: v_p,
+                            multiplicity,
+                            direction,
+                            rel_type: None,
+                        });
+                    }
+                }
+                constraints
+            })
+            .collect();
+        Query {
+            original_ids: (0..pattern.node_count()).collect(),
+            labels,
+            constraints,
+            order: vec![],
+        }
+    }
+
+    pub(crate) fn node_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub(crate) fn label(&self, u_p: usize) -> &T {
+        &self.labels[u_p]
+    }
+
+    pub(crate) fn constraints(&self, u_p: usize) -> &[Constraint] {
+        &self.constraints[u_p]
+    }
+
+    pub(crate) fn order(&self) -> &[(usize, usize)] {
+        &self.order
+    }
+
+    pub(crate) fn original_id(&self, u_p: usize) -> usize {
+        self.original_ids[u_p]
+    }
+
+    // Adds `count - 1` copies of the pattern node with the same label and
+    // relationships. Copies are ordered by the graph nodes they match, so the
+    // same set of graph nodes is not matched in every permutation.
+    pub(crate) fn repeat(&mut self, u_p: usize, count: usize) {
+        let mut previous = u_p;
+        for _ in 1..count {
+            let copy = self.labels.len();
+            self.labels.push(Rc::clone(&self.labels[u_p]));
+            let constraints = self.constraints[u_p]
+                .iter()
+                .map(|constraint| Constraint {
+                    target: if constraint.target == u_p {
+                        copy
+                    } else {
+                        constraint.target
+                    },
+                    ..constraint.clone()
+                })
+                .collect::<Vec<_>>();
+            // relationships from other pattern nodes to u_P, the self-loop of
+            // u_P became one of the copy above
+            for (v_p, constraints) in self.constraints.iter_mut().enumerate() {
+                if v_p == u_p {
+                    continue;
+                }
+                let incoming = constraints
+                    .iter()
+                    .filter(|constraint| constraint.target == u_p)
+                    .map(|constraint| Constraint {
+                        target: copy,
+                        ..constraint.clone()
+                    })
+                    .collect::<Vec<_>>();
+                constraints.extend(incoming);
+            }
+            self.constraints.push(constraints);
+            self.original_ids.push(copy);
+            self.order.push((previous, copy));
+            previous = copy;
+        }
+    }
+
+    // The query induced by the given pattern nodes, node `i` of the result is `nodes[i]`.
+    pub(crate) fn restrict(&self, nodes: &[usize]) -> Query<T> {
+        let mapping = nodes
+            .iter()
+            .enumerate()
+            .map(|(new_id, u_p)| (*u_p, new_id))
+            .collect::<HashMap<_, _>>();
+        Query {
+            labels: nodes
+                .iter()
+                .map(|u_p| Rc::clone(&self.labels[*u_p]))
+                .collect(),
+            constraints: nodes
+                .iter()
+                .map(|u_p| {
+                    self.constraints[*u_p]
+                        .iter()
+                        .filter_map(|constraint| {
+                            mapping.get(&constraint.target).map(|target| Constraint {
+                                target: *target,
+                                ..constraint.clone()
+                            })
+                        })
+                        .collect()
+                })
+                .collect(),
+            order: self
+                .order
+                .iter()
+                .filter_map(|(u_p, v_p)| Some((*mapping.get(u_p)?, *mapping.get(v_p)?)))
+                .collect(),
+            original_ids: nodes.iter().map(|u_p| self.original_ids[*u_p]).collect(),
+        }
+    }
+
+    pub(crate) fn weakly_connected_components(&self) -> Vec<Vec<usize>> {
+        let relationships = self
+            .constraints
+            .iter()
+            .enumerate()
+            .flat_map(|(u_p, constraints)| constraints.iter().map(move |c| (u_p, c.target)))
+            .collect::<Vec<_>>();
+        Graph::from_parts(self.labels.clone(), &relationships).weakly_connected_components()
+    }
+}
@@ -0,0 +1,133 @@
+use std::hash::Hash;
+use std::ops::{Bound, RangeBounds};
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{dual_iso_with_candidates, init_candidates, NestedVec};
+use crate::query::Query;
+use crate::{Graph, MatchConfig};
+
+// The label buckets of a graph in label order, e.g. for numeric labels such
+// as bucketized scores. A range of labels is a contiguous run of buckets.
+pub struct SortedLabelIndex<'graph, T> {
+    buckets: Vec<(&'graph T, &'graph [usize])>,
+}
+
+impl<'graph, T> SortedLabelIndex<'graph, T>
+where
+    T: Ord + Hash,
+{
+    pub fn new(graph: &'graph Graph<T>) -> Self {
+        let mut buckets = graph
+            .label_buckets()
+            .map(|nodes| (graph.node_label(nodes[0]), nodes))
+            .collect::<Vec<_>>();
+        buckets.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        SortedLabelIndex { buckets }
+    }
+
+    // The distinct labels in ascending order.
+    pub fn labels(&self) -> impl Iterator<Item = &'graph T> + '_ {
+        self.buckets.iter().map(|(label, _)| *label)
+    }
+
+    // The nodes whose label is within the range, ordered by label.
+    pub fn nodes_in_range<R: RangeBounds<T>>(&self, range: &R) -> Vec<usize> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => self.buckets.partition_point(|(label, _)| *label < start),
+            Bound::Excluded(start) => self.buckets.partition_point(|(label, _)| *label <= start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => self.buckets.partition_point(|(label, _)| *label <= end),
+            Bound::Excluded(end) => self.buckets.partition_point(|(label, _)| *label < end),
+            Bound::Unbounded => self.buckets.len(),
+        };
+        self.buckets[start..end.max(start)]
+            .iter()
+            .flat_map(|(_, nodes)| nodes.iter().copied())
+            .collect()
+    }
+}
+
+// Matches the given pattern nodes against all graph nodes with a label in
+// their range instead of their own label.
+pub fn dual_iso_with_ranges<T, R>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    ranges: &[(usize, R)],
+) -> NestedVec
+where
+    T: Ord + Hash,
+    R: RangeBounds<T>,
+{
+    let query = Query::new(pattern);
+    let mut candidates = init_candidates(graph, &query);
+    let index = SortedLabelIndex::new(graph);
+    for (u_p, range) in ranges {
+        if *u_p >= query.node_count() {
+            panic!(
+                "Node id {} must be within range [0..{}).",
+                u_p,
+                query.node_count()
+            );
+        }
+        candidates[*u_p] = Candidates::from(index.nodes_in_range(range));
+    }
+    dual_iso_with_candidates(graph, &query, config, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    fn graph() -> Graph<u32> {
+        GraphBuilder::new()
+            .add_node(0, 0)
+            .add_node(1, 3)
+            .add_node(2, 5)
+            .add_node(3, 8)
+            .add_node(4, 5)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .add_relationship(0, 4)
+            .build()
+    }
+
+    #[test]
+    fn test_nodes_in_range() {
+        let graph = graph();
+        let index = SortedLabelIndex::new(&graph);
+        let sorted = |mut nodes: Vec<usize>| {
+            nodes.sort_unstable();
+            nodes
+        };
+
+        assert_eq!(vec![&0, &3, &5, &8], index.labels().collect::<Vec<_>>());
+        assert_eq!(vec![1, 2, 4], sorted(index.nodes_in_range(&(3..=5))));
+        assert_eq!(vec![1], sorted(index.nodes_in_range(&(1..5))));
+        assert_eq!(vec![2, 3, 4], sorted(index.nodes_in_range(&(4..))));
+        assert!(index.nodes_in_range(&(6..8)).is_empty());
+        assert!(index
+            .nodes_in_range(&(Bound::Excluded(5), Bound::Included(3)))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_dual_iso_with_ranges() {
+        let graph = graph();
+        let pattern = GraphBuilder::new()
+            .add_node(0, 0)
+            .add_node(1, 0)
+            .add_relationship(0, 1)
+            .build();
+
+        let mut matches =
+            dual_iso_with_ranges(&graph, &pattern, &MatchConfig::default(), &[(1, 5..)]);
+        matches.sort();
+        assert_eq!(vec![vec![0, 2], vec![0, 3], vec![0, 4]], matches);
+    }
+}
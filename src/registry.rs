@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+use crate::format::FormatError;
+use crate::{
+    match_pattern, parse_pattern, CanonicalForm, FilterError, Graph, MatchConfig, Pattern,
+    PatternMatch,
+};
+
+// A version of a registered pattern together with the source it was parsed
+// from, which is what the registry stores on disk.
+pub struct RegisteredPattern {
+    version: usize,
+    source: String,
+    pattern: Pattern<String>,
+    canonical_form: CanonicalForm<String>,
+}
+
+impl RegisteredPattern {
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn pattern(&self) -> &Pattern<String> {
+        &self.pattern
+    }
+
+    pub fn canonical_form(&self) -> &CanonicalForm<String> {
+        &self.canonical_form
+    }
+}
+
+// Named patterns in the Cypher subset of `parse_pattern`, e.g. the motifs a
+// deployment monitors. Each name has versions 1, 2, ... and queries use the
+// latest version unless they ask for another one. The registry is written as
+// text with one line per version:
+//
+//   pattern <name> <version> <source>
+//
+// where `#` starts a comment.
+#[derive(Default)]
+pub struct PatternRegistry {
+    // versions per name in ascending order
+    patterns: BTreeMap<String, Vec<RegisteredPattern>>,
+}
+
+impl PatternRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Parses the source and adds it as the next version of the name, which is
+    // returned. Registering the source of the latest version again returns
+    // the latest version instead.
+    pub fn register(&mut self, name: &str, source: &str) -> Result<usize, FilterError> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            panic!("Pattern name '{}' must be a single word.", name);
+        }
+        if let Some(position) = source.find(['\n', '\r']) {
+            return Err(FilterError::new("Line break in pattern source", position));
+        }
+        let pattern = parse_pattern(source)?;
+        let versions = self.patterns.entry(name.to_string()).or_default();
+        if let Some(latest) = versions.last().filter(|latest| latest.source == source) {
+            return Ok(latest.version);
+        }
+        let version = versions.len() + 1;
+        versions.push(RegisteredPattern {
+            version,
+            source: source.to_string(),
+            canonical_form: pattern.canonical_form(),
+            pattern,
+        });
+        Ok(version)
+    }
+
+    // The latest version of the name.
+    pub fn get(&self, name: &str) -> Option<&RegisteredPattern> {
+        self.patterns.get(name)?.last()
+    }
+
+    pub fn get_version(&self, name: &str, version: usize) -> Option<&RegisteredPattern> {
+        self.versions(name).get(version.checked_sub(1)?)
+    }
+
+    pub fn versions(&self, name: &str) -> &[RegisteredPattern] {
+        self.patterns
+            .get(name)
+            .map_or(&[], |versions| versions.as_slice())
+    }
+
+    // The names in ascending order.
+    pub fn names(&self) -> Vec<&str> {
+        self.patterns.keys().map(String::as_str).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    // The (name, version) pairs whose pattern equals the given pattern up to
+    // renumbering its nodes, e.g. to find out whether a motif is monitored
+    // already.
+    pub fn find(&self, pattern: &Pattern<String>) -> Vec<(&str, usize)> {
+        let canonical_form = pattern.canonical_form();
+        self.patterns
+            .iter()
+            .flat_map(|(name, versions)| versions.iter().map(move |p| (name.as_str(), p)))
+            .filter(|(_, registered)| registered.canonical_form == canonical_form)
+            .map(|(name, registered)| (name, registered.version))
+            .collect()
+    }
+
+    // Matches the latest version of each name and returns the matches per
+    // name in the order of `names`.
+    pub fn match_all(
+        &self,
+        graph: &Graph<String>,
+        config: &MatchConfig,
+    ) -> Vec<(&str, Vec<PatternMatch>)> {
+        self.patterns
+            .iter()
+            .filter_map(|(name, versions)| {
+                let latest = versions.last()?;
+                Some((name.as_str(), match_pattern(graph, &latest.pattern, config)))
+            })
+            .collect()
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (name, versions) in &self.patterns {
+            for registered in versions {
+                writeln!(
+                    writer,
+                    "pattern {} {} {}",
+                    name, registered.version, registered.source
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads a registry written by `write`. The versions of each name must be
+    // listed in ascending order without gaps.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, FormatError> {
+        let mut registry = PatternRegistry::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.map_err(|e| FormatError::new(e.to_string(), line_number))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.splitn(4, char::is_whitespace);
+            let (directive, name, version, source) =
+                match (tokens.next(), tokens.next(), tokens.next(), tokens.next()) {
+                    (Some(directive), Some(name), Some(version), Some(source)) => {
+                        (directive, name, version, source)
+                    }
+                    _ => return Err(FormatError::new("Incomplete pattern", line_number)),
+                };
+            if directive != "pattern" {
+                return Err(FormatError::new(
+                    format!("Unknown directive '{}'", directive),
+                    line_number,
+                ));
+            }
+            let expected = registry.versions(name).len() + 1;
+            if version.parse() != Ok(expected) {
+                return Err(FormatError::new(
+                    format!("Expected version {} of '{}'", expected, name),
+                    line_number,
+                ));
+            }
+            let pattern =
+                parse_pattern(source).map_err(|e| FormatError::new(e.to_string(), line_number))?;
+            registry
+                .patterns
+                .entry(name.to_string())
+                .or_default()
+                .push(RegisteredPattern {
+                    version: expected,
+                    source: source.to_string(),
+                    canonical_form: pattern.canonical_form(),
+                    pattern,
+                });
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GraphBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_register_versions() {
+        let mut registry = PatternRegistry::new();
+        assert_eq!(Ok(1), registry.register("owns", "(p:person)-->(c:car)"));
+        assert_eq!(Ok(1), registry.register("owns", "(p:person)-->(c:car)"));
+        assert_eq!(Ok(2), registry.register("owns", "(p:person)--(c:car)"));
+        assert_eq!(Ok(1), registry.register("knows", "(a:person)-->(b:person)"));
+
+        assert_eq!(vec!["knows", "owns"], registry.names());
+        assert_eq!(2, registry.get("owns").unwrap().version());
+        assert_eq!(
+            "(p:person)-->(c:car)",
+            registry.get_version("owns", 1).unwrap().source()
+        );
+        assert!(registry.get_version("owns", 0).is_none());
+        assert_eq!(2, registry.versions("owns").len());
+
+        let pattern = parse_pattern("(car:car)<--(owner:person)").unwrap();
+        assert_eq!(vec![("owns", 1)], registry.find(&pattern));
+
+        let error = registry
+            .register("owns", "(p:person)\n-->(c:car)")
+            .unwrap_err();
+        assert_eq!(10, error.position());
+        assert!(registry.register("owns", "(p:person)-->").is_err());
+    }
+
+    #[test]
+    fn test_match_all() {
+        let mut registry = PatternRegistry::new();
+        registry.register("owns", "(p:person)-->(c:car)").unwrap();
+        registry
+            .register("knows", "(a:person)-->(b:person)")
+            .unwrap();
+        let graph = GraphBuilder::new()
+            .add_node(0, "person".to_string())
+            .add_node(1, "car".to_string())
+            .add_relationship(0, 1)
+            .build();
+
+        let matches = registry.match_all(&graph, &MatchConfig::default());
+        assert_eq!("knows", matches[0].0);
+        assert!(matches[0].1.is_empty());
+        assert_eq!("owns", matches[1].0);
+        assert_eq!(&[Some(0), Some(1)], matches[1].1[0].nodes());
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let mut registry = PatternRegistry::new();
+        registry.register("owns", "(p:person)-->(c:car)").unwrap();
+        registry
+            .register("owns", "(p:person)-->(c:car) WHERE p.age > 18")
+            .unwrap();
+
+        let mut bytes = vec![];
+        registry.write(&mut bytes).unwrap();
+        assert_eq!(
+            "pattern owns 1 (p:person)-->(c:car)\n\
+             pattern owns 2 (p:person)-->(c:car) WHERE p.age > 18\n",
+            String::from_utf8(bytes.clone()).unwrap()
+        );
+        let read = PatternRegistry::read(bytes.as_slice()).unwrap();
+        assert_eq!(registry.names(), read.names());
+        assert_eq!(
+            registry.get("owns").unwrap().canonical_form(),
+            read.get("owns").unwrap().canonical_form()
+        );
+
+        let error = PatternRegistry::read("pattern owns 2 (p:person)".as_bytes());
+        assert_eq!(1, error.err().unwrap().line());
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+
+use crate::{
+    match_pattern, parse_pattern, read_graph, Graph, Input, MatchConfig, MatchTable, Output,
+    Pattern,
+};
+
+const HELP: &str = "\
+load <file>                 loads a graph in the text format of `read_graph`, - is stdin
+pattern <name> = <pattern>  defines a pattern, e.g. (a:x)-->(b:y) WHERE b.degree > 1
+patterns                    lists the defined patterns
+match <name> [<limit>]      matches a pattern against the loaded graph
+show [<rows>]               prints the last matches as CSV
+export <file>               writes the last matches as JSON (*.json) or CSV, - is stdout
+stats                       prints statistics of the graph and the last match
+quit                        leaves the REPL";
+
+// Line based interactive session for exploring patterns on a single graph.
+#[derive(Default)]
+pub struct Repl {
+    graph: Option<Graph<String>>,
+    patterns: HashMap<String, Pattern<String>>,
+    last: Option<LastMatch>,
+}
+
+struct LastMatch {
+    pattern: String,
+    table: MatchTable,
+    millis: u128,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            if !self.execute(&line?, output)? {
+                return Ok(());
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    // Executes a single command and returns false if the session should end.
+    pub fn execute<W: Write>(&mut self, line: &str, output: &mut W) -> io::Result<bool> {
+        let line = line.trim();
+        let (command, args) = match line.split_once(char::is_whitespace) {
+            Some((command, args)) => (command, args.trim()),
+            None => (line, ""),
+        };
+        let result = match command {
+            "" => return Ok(true),
+            "quit" | "exit" => return Ok(false),
+            "help" => Ok(HELP.to_string()),
+            "load" => self.load(args),
+            "pattern" => self.define(args),
+            "patterns" => {
+                let mut names = self.patterns.keys().cloned().collect::<Vec<_>>();
+                names.sort();
+                Ok(names.join("\n"))
+            }
+            "match" => self.run_match(args),
+            "show" => self.show(args),
+            "export" => self.export(args),
+            "stats" => self.stats(),
+            _ => Err(format!("Unknown command '{}', try 'help'.", command)),
+        };
+        match result {
+            Ok(message) if message.is_empty() => {}
+            Ok(message) => writeln!(output, "{}", message)?,
+            Err(error) => writeln!(output, "Error: {}", error)?,
+        }
+        Ok(true)
+    }
+
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let input = Input::open(path).map_err(|e| format!("Cannot open '{}': {}", path, e))?;
+        let graph = read_graph(input).map_err(|e| e.to_string())?;
+        let message = format!(
+            "Loaded {} nodes and {} relationships.",
+            graph.node_count(),
+            graph.relationship_count()
+        );
+        self.graph = Some(graph);
+        self.last = None;
+        Ok(message)
+    }
+
+    fn define(&mut self, args: &str) -> Result<String, String> {
+        let (name, source) = args
+            .split_once('=')
+            .ok_or("Expected 'pattern <name> = <pattern>'.")?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Missing pattern name.".to_string());
+        }
+        let pattern = parse_pattern(source.trim()).map_err(|e| e.to_string())?;
+        let message = format!(
+            "Defined '{}' with {} nodes.",
+            name,
+            pattern.graph().node_count()
+        );
+        self.patterns.insert(name.to_string(), pattern);
+        Ok(message)
+    }
+
+    fn run_match(&mut self, args: &str) -> Result<String, String> {
+        let graph = self.graph.as_ref().ok_or("No graph loaded.")?;
+        let mut args = args.split_whitespace();
+        let name = args.next().ok_or("Expected 'match <name> [<limit>]'.")?;
+        let pattern = self
+            .patterns
+            .get(name)
+            .ok_or_else(|| format!("Unknown pattern '{}'.", name))?;
+        let mut config = MatchConfig::new();
+        if let Some(limit) = args.next() {
+            let limit = limit
+                .parse()
+                .map_err(|_| format!("Invalid limit '{}'.", limit))?;
+            config.limit(limit);
+        }
+
+        let start = Instant::now();
+        let matches = match_pattern(graph, pattern, &config);
+        let millis = start.elapsed().as_millis();
+        self.last = Some(LastMatch {
+            pattern: name.to_string(),
+            table: MatchTable::from_matches(pattern, &matches),
+            millis,
+        });
+        Ok(format!("{} matches in {} ms.", matches.len(), millis))
+    }
+
+    fn show(&self, args: &str) -> Result<String, String> {
+        let last = self.last.as_ref().ok_or("Nothing matched yet.")?;
+        let rows = if args.is_empty() {
+            10
+        } else {
+            args.parse()
+                .map_err(|_| format!("Invalid row count '{}'.", args))?
+        };
+        let mut csv = vec![];
+        last.table.write_csv(&mut csv).map_err(|e| e.to_string())?;
+        // header plus the requested rows
+        let mut lines = String::from_utf8_lossy(&csv)
+            .lines()
+            .take(rows + 1)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if last.table.row_count() > rows {
+            lines.push(format!("... {} more", last.table.row_count() - rows));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn export(&self, path: &str) -> Result<String, String> {
+        let last = self.last.as_ref().ok_or("Nothing matched yet.")?;
+        if path.is_empty() {
+            return Err("Expected 'export <file>'.".to_string());
+        }
+        let mut output =
+            Output::create(path).map_err(|e| format!("Cannot create '{}': {}", path, e))?;
+        if path.ends_with(".json") {
+            last.table.write_json(&mut output)
+        } else {
+            last.table.write_csv(&mut output)
+        }
+        .and_then(|_| output.finish())
+        .map_err(|e| e.to_string())?;
+        Ok(format!(
+            "Exported {} rows to '{}'.",
+            last.table.row_count(),
+            path
+        ))
+    }
+
+    fn stats(&self) -> Result<String, String> {
+        let graph = self.graph.as_ref().ok_or("No graph loaded.")?;
+        let labels = (0..graph.node_count())
+            .map(|node_id| graph.node_label(node_id))
+            .collect::<HashSet<_>>();
+        let max_degree = (0..graph.node_count())
+            .map(|node_id| graph.degree(node_id))
+            .max()
+            .unwrap_or(0);
+        let mut stats = format!(
+            "nodes: {}\nrelationships: {}\nlabels: {}\nmax degree: {}",
+            graph.node_count(),
+            graph.relationship_count(),
+            labels.len(),
+            max_degree
+        );
+        if let Some(last) = &self.last {
+            stats.push_str(&format!(
+                "\nlast match: '{}' with {} matches in {} ms",
+                last.pattern,
+                last.table.row_count(),
+                last.millis
+            ));
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(commands: &[&str]) -> String {
+        let mut repl = Repl::new();
+        let mut output = vec![];
+        for command in commands {
+            repl.execute(command, &mut output).unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_session() {
+        let path = std::env::temp_dir().join(format!("dual_iso_repl_{}.txt", std::process::id()));
+        std::fs::write(&path, "node 0 a\nnode 1 b\nnode 2 b\nrel 0 1\nrel 0 2\n").unwrap();
+
+        let output = session(&[
+            &format!("load {}", path.display()),
+            "pattern ab = (x:a)-->(y:b)",
+            "match ab",
+            "show 1",
+            "stats",
+        ]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(output.contains("Loaded 3 nodes and 2 relationships.\n"));
+        assert!(output.contains("Defined 'ab' with 2 nodes.\n"));
+        assert!(output.contains("2 matches in "));
+        assert!(output.contains("x,y\n0,"));
+        assert!(output.contains("... 1 more\n"));
+        assert!(output.contains("labels: 2\nmax degree: 2\nlast match: 'ab' with 2 matches"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let output = session(&["match ab", "pattern ab = (x:a)-->(y)", "frobnicate"]);
+
+        assert_eq!(
+            "Error: No graph loaded.\n\
+             Error: Node without label at position 8.\n\
+             Error: Unknown command 'frobnicate', try 'help'.\n",
+            output
+        );
+    }
+
+    #[test]
+    fn test_quit() {
+        let mut repl = Repl::new();
+        let mut output = vec![];
+        repl.run("help\nquit\nstats\n".as_bytes(), &mut output)
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("leaves the REPL"));
+        assert!(!output.contains("No graph loaded"));
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::hash::Hash;
+
+use crate::{
+    read_graph, read_snapshot, write_graph, write_snapshot, Graph, GraphBuilder, GraphDiff,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// The line based text format of `write_graph`, which keeps labels and relationships only.
+    Text,
+    /// The binary format of `write_snapshot`.
+    Snapshot,
+}
+
+const FORMATS: [GraphFormat; 2] = [GraphFormat::Text, GraphFormat::Snapshot];
+
+// What a graph lost or changed by writing it in a format and reading it back.
+// Labels are compared by their `Display` form, as both formats store labels
+// as strings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundTripReport {
+    format: GraphFormat,
+    error: Option<String>,
+    diff: GraphDiff,
+    changed_types: Vec<(usize, usize)>,
+    changed_properties: Vec<usize>,
+    changed_external_ids: Vec<usize>,
+}
+
+impl RoundTripReport {
+    pub fn format(&self) -> GraphFormat {
+        self.format
+    }
+
+    // The error that writing or reading the graph failed with, the report is
+    // empty otherwise.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    // differences of the nodes, labels and relationships
+    pub fn diff(&self) -> &GraphDiff {
+        &self.diff
+    }
+
+    // (start, end) pairs of relationships whose types differ
+    pub fn changed_types(&self) -> &[(usize, usize)] {
+        &self.changed_types
+    }
+
+    pub fn changed_properties(&self) -> &[usize] {
+        &self.changed_properties
+    }
+
+    pub fn changed_external_ids(&self) -> &[usize] {
+        &self.changed_external_ids
+    }
+
+    pub fn is_faithful(&self) -> bool {
+        self.error.is_none()
+            && self.diff.is_empty()
+            && self.changed_types.is_empty()
+            && self.changed_properties.is_empty()
+            && self.changed_external_ids.is_empty()
+    }
+}
+
+// Writes the graph in every supported format, reads it back and compares the
+// result with the graph, e.g. to check that an export of the data survives a
+// format. Returns one report per format.
+pub fn round_trip_check<T>(graph: &Graph<T>) -> Vec<RoundTripReport>
+where
+    T: Eq + Hash + fmt::Display,
+{
+    let expected = with_string_labels(graph);
+    FORMATS
+        .iter()
+        .map(|format| {
+            let mut bytes = vec![];
+            let reloaded = match format {
+                GraphFormat::Text => write_graph(graph, &mut bytes)
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| read_graph(bytes.as_slice()).map_err(|e| e.to_string())),
+                GraphFormat::Snapshot => write_snapshot(graph, &mut bytes)
+                    .and_then(|_| read_snapshot(bytes.as_slice()))
+                    .map_err(|e| e.to_string()),
+            };
+            match reloaded {
+                Ok(reloaded) => compare(*format, &expected, &reloaded),
+                Err(error) => RoundTripReport {
+                    format: *format,
+                    error: Some(error),
+                    diff: GraphDiff::default(),
+                    changed_types: vec![],
+                    changed_properties: vec![],
+                    changed_external_ids: vec![],
+                },
+            }
+        })
+        .collect()
+}
+
+fn compare(
+    format: GraphFormat,
+    expected: &Graph<String>,
+    actual: &Graph<String>,
+) -> RoundTripReport {
+    let common = expected.node_count().min(actual.node_count());
+    let mut changed_types = expected
+        .typed_relationships()
+        .map(|(pair, _)| *pair)
+        .chain(actual.typed_relationships().map(|(pair, _)| *pair))
+        .filter(|(start_node, end_node)| {
+            *start_node >= common
+                || *end_node >= common
+                || expected.shared_relationship_types(*start_node, *end_node)
+                    != actual.shared_relationship_types(*start_node, *end_node)
+        })
+        .collect::<Vec<_>>();
+    changed_types.sort_unstable();
+    changed_types.dedup();
+    let changed = |is_changed: &dyn Fn(usize) -> bool| {
+        (0..expected.node_count().max(actual.node_count()))
+            .filter(|node_id| *node_id >= common || is_changed(*node_id))
+            .collect::<Vec<_>>()
+    };
+    let changed_properties = changed(&|node_id| {
+        expected.node_properties().get(&node_id) != actual.node_properties().get(&node_id)
+    });
+    let changed_external_ids =
+        changed(&|node_id| expected.to_external_id(node_id) != actual.to_external_id(node_id));
+    RoundTripReport {
+        format,
+        error: None,
+        diff: GraphDiff::new(expected, actual),
+        changed_types,
+        changed_properties,
+        changed_external_ids,
+    }
+}
+
+// The graph with the `Display` form of its labels, which both formats store.
+fn with_string_labels<T>(graph: &Graph<T>) -> Graph<String>
+where
+    T: Eq + Hash + fmt::Display,
+{
+    let mut builder = GraphBuilder::new();
+    for node_id in 0..graph.node_count() {
+        match graph.to_external_id(node_id) {
+            Some(external_id) => {
+                builder.add_external_node(external_id, graph.node_label(node_id).to_string())
+            }
+            None => builder.add_node(node_id, graph.node_label(node_id).to_string()),
+        };
+        if let Some(properties) = graph.node_properties().get(&node_id) {
+            for (key, value) in properties {
+                builder.set_property(node_id, key, value.clone());
+            }
+        }
+    }
+    for start_node in 0..graph.node_count() {
+        for (end_node, multiplicity) in graph.distinct_neighbors(start_node) {
+            let types = graph.shared_relationship_types(start_node, end_node);
+            for rel_type in types {
+                builder.add_relationship_with_type(start_node, end_node, rel_type);
+            }
+            for _ in types.len()..multiplicity {
+                builder.add_relationship(start_node, end_node);
+            }
+        }
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_check() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .add_relationship(1, 1)
+            .build();
+        for report in round_trip_check(&graph) {
+            assert!(report.is_faithful(), "{:?}", report);
+        }
+
+        let graph = GraphBuilder::new()
+            .add_external_node(7, " a")
+            .add_node(1, "b")
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship(0, 1)
+            .set_property(1, "age", 42)
+            .build();
+        let reports = round_trip_check(&graph);
+        assert_eq!(GraphFormat::Text, reports[0].format());
+        assert!(!reports[0].is_faithful());
+        assert_eq!(None, reports[0].error());
+        // the text format trims labels and drops everything but relationships
+        assert_eq!(&[0], reports[0].diff().changed_labels());
+        assert!(reports[0].diff().added_relationships().is_empty());
+        assert_eq!(&[(0, 1)], reports[0].changed_types());
+        assert_eq!(&[1], reports[0].changed_properties());
+        assert_eq!(&[0], reports[0].changed_external_ids());
+        assert_eq!(GraphFormat::Snapshot, reports[1].format());
+        assert!(reports[1].is_faithful(), "{:?}", reports[1]);
+    }
+}
@@ -0,0 +1,299 @@
+use std::hash::Hash;
+
+use rand::Rng;
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{init_candidates, simple_simulation, NestedVec};
+use crate::query::{Direction, Query};
+use crate::{Graph, MatchConfig};
+
+// Attempted walks per requested sample before giving up, e.g. because the
+// pattern has no matches.
+const ATTEMPTS_PER_SAMPLE: usize = 10_000;
+
+// Draws up to `count` matches uniformly at random, with replacement, without
+// enumerating all matches. Each sample is a random walk through the search
+// tree, where every branch picks a random candidate. As walks reach matches
+// with different probabilities, a match is accepted with a probability
+// inversely proportional to the probability of its walk.
+pub fn sample_matches<T: Eq + Hash, R: Rng>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    count: usize,
+    rng: &mut R,
+) -> NestedVec {
+    let query = Query::new(pattern);
+    let mut candidates = init_candidates(graph, &query);
+    let mut samples = vec![];
+    if !simple_simulation(graph, &query, config, &mut candidates) {
+        return samples;
+    }
+    let bound = walk_bound(graph, &query, &candidates);
+
+    for _ in 0..count.saturating_mul(ATTEMPTS_PER_SAMPLE) {
+        if samples.len() == count {
+            break;
+        }
+        if let Some((embedding, weight)) = walk(graph, &query, config, &candidates, rng) {
+            if rng.gen_bool((weight / bound).min(1.0)) {
+                samples.push(embedding);
+            }
+        }
+    }
+    samples
+}
+
+// Bounds the inverse probability of every walk, which multiplies the number of
+// candidates at each depth. The candidate sets only shrink during a walk, and
+// once a related pattern node is assigned, the simulation leaves only
+// neighbors of its graph node, so the largest degree among its candidates
+// bounds the choices as well. A bound close to the actual weights keeps the
+// rejection rate low, e.g. a path in a sparse graph is bounded by the number
+// of start candidates times the degrees instead of all candidate sets.
+fn walk_bound<T: Eq + Hash>(graph: &Graph<T>, query: &Query<T>, candidates: &[Candidates]) -> f64 {
+    let max_degree = |u_p: usize, direction: Direction| {
+        candidates[u_p]
+            .iter()
+            .map(|v_g| match direction {
+                Direction::Outgoing => graph.degree(*v_g),
+                Direction::Incoming => graph.in_degree(*v_g),
+                Direction::Either => graph.degree(*v_g) + graph.in_degree(*v_g),
+            })
+            .max()
+            .unwrap_or(0)
+    };
+    let reversed = |direction| match direction {
+        Direction::Outgoing => Direction::Incoming,
+        Direction::Incoming => Direction::Outgoing,
+        Direction::Either => Direction::Either,
+    };
+    let mut bounds = candidates.iter().map(|c| c.len()).collect::<Vec<_>>();
+    for u_p in 0..query.node_count() {
+        for constraint in query.constraints(u_p) {
+            let v_p = constraint.target;
+            // the later of the two pattern nodes is bounded by the earlier
+            if u_p < v_p {
+                bounds[v_p] = bounds[v_p].min(max_degree(u_p, constraint.direction));
+            } else if v_p < u_p {
+                let direction = reversed(constraint.direction);
+                bounds[u_p] = bounds[u_p].min(max_degree(v_p, direction));
+            }
+        }
+    }
+    bounds.iter().map(|bound| *bound as f64).product()
+}
+
+// An approximate number of matches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CountEstimate {
+    estimate: f64,
+    standard_error: f64,
+    walks: usize,
+}
+
+impl CountEstimate {
+    pub fn estimate(&self) -> f64 {
+        self.estimate
+    }
+
+    pub fn standard_error(&self) -> f64 {
+        self.standard_error
+    }
+
+    pub fn walks(&self) -> usize {
+        self.walks
+    }
+
+    // The approximate 95% confidence interval.
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        let margin = 1.96 * self.standard_error;
+        ((self.estimate - margin).max(0.0), self.estimate + margin)
+    }
+}
+
+// Estimates the number of matches from the given number of random walks
+// through the search tree. A walk that reaches a match contributes the inverse
+// of its probability, a stuck walk contributes 0, so the mean over all walks
+// is an unbiased estimate of the match count.
+pub fn estimate_count<T: Eq + Hash, R: Rng>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    config: &MatchConfig,
+    walks: usize,
+    rng: &mut R,
+) -> CountEstimate {
+    let query = Query::new(pattern);
+    let mut candidates = init_candidates(graph, &query);
+    if walks == 0 || !simple_simulation(graph, &query, config, &mut candidates) {
+        return CountEstimate {
+            estimate: 0.0,
+            standard_error: 0.0,
+            walks,
+        };
+    }
+
+    let (mut sum, mut sum_of_squares) = (0.0, 0.0);
+    for _ in 0..walks {
+        if let Some((_, weight)) = walk(graph, &query, config, &candidates, rng) {
+            sum += weight;
+            sum_of_squares += weight * weight;
+        }
+    }
+    let n = walks as f64;
+    let mean = sum / n;
+    let variance = if walks > 1 {
+        ((sum_of_squares - n * mean * mean) / (n - 1.0)).max(0.0)
+    } else {
+        0.0
+    };
+    CountEstimate {
+        estimate: mean,
+        standard_error: (variance / n).sqrt(),
+        walks,
+    }
+}
+
+// A random walk from the given candidates down to a match, returns the match
+// and the inverse of the probability of the walk, or None if it got stuck.
+pub(crate) fn walk<T: Eq + Hash, R: Rng>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    initial_candidates: &[Candidates],
+    rng: &mut R,
+) -> Option<(Vec<usize>, f64)> {
+    let mut candidates = initial_candidates.to_vec();
+    let mut weight = 1.0;
+    for depth in 0..query.node_count() {
+        let v_g = candidates[depth][rng.gen_range(0, candidates[depth].len())];
+        weight *= candidates[depth].len() as f64;
+        // check if v_G has matched a previous candidate
+        if config.is_injective() && candidates[..depth].iter().any(|x| x[0] == v_g) {
+            return None;
+        }
+        candidates[depth] = Candidates::single(v_g);
+        if !simple_simulation(graph, query, config, &mut candidates) {
+            return None;
+        }
+    }
+    Some((candidates.iter().map(|c| c[0]).collect(), weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use crate::{dual_iso, GraphBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_sample_matches() {
+        // node 0 has many more neighbors than node 1, so walks starting at
+        // node 1 are more likely to reach a particular match
+        let mut builder = GraphBuilder::new();
+        builder.add_node(0, "a").add_node(1, "a");
+        for node_id in 2..12 {
+            builder.add_node(node_id, "b");
+        }
+        for node_id in 2..11 {
+            builder.add_relationship(0, node_id);
+        }
+        builder.add_relationship(1, 11);
+        let graph = builder.build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+
+        let mut rng = SmallRng::seed_from_u64(42);
+        let samples = sample_matches(&graph, &pattern, &MatchConfig::default(), 5000, &mut rng);
+        assert_eq!(5000, samples.len());
+
+        let matches = dual_iso(&graph, &pattern);
+        let mut counts: HashMap<Vec<usize>, usize> = HashMap::new();
+        for sample in samples {
+            assert!(matches.contains(&sample));
+            *counts.entry(sample).or_default() += 1;
+        }
+        // each of the 10 matches is expected 500 times
+        assert_eq!(10, counts.len());
+        assert!(counts.values().all(|count| (400..600).contains(count)));
+    }
+
+    #[test]
+    fn test_sample_matches_of_sparse_graph() {
+        // the candidate sets multiply to 10^9, but there are 1000 matches
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..1000 {
+            builder.add_node(node_id, "a");
+        }
+        for node_id in 0..1000 {
+            builder.add_relationship(node_id, (node_id + 1) % 1000);
+        }
+        let graph = builder.build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let mut rng = SmallRng::seed_from_u64(42);
+        let samples = sample_matches(&graph, &pattern, &MatchConfig::default(), 5, &mut rng);
+        assert_eq!(5, samples.len());
+        for sample in samples {
+            assert_eq!((sample[0] + 1) % 1000, sample[1]);
+            assert_eq!((sample[1] + 1) % 1000, sample[2]);
+        }
+    }
+
+    #[test]
+    fn test_estimate_count() {
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..30 {
+            builder.add_node(node_id, "a");
+        }
+        for start_node in 0..30 {
+            for end_node in [(start_node + 1) % 30, (start_node * 7 + 3) % 30] {
+                builder.add_relationship(start_node, end_node);
+            }
+        }
+        let graph = builder.build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+
+        let count = dual_iso(&graph, &pattern).len() as f64;
+        let mut rng = SmallRng::seed_from_u64(42);
+        let estimate = estimate_count(&graph, &pattern, &MatchConfig::default(), 20000, &mut rng);
+
+        assert_eq!(20000, estimate.walks());
+        assert!(estimate.standard_error() > 0.0);
+        assert!((estimate.estimate() - count).abs() < 4.0 * estimate.standard_error());
+        let (lower, upper) = estimate.confidence_interval();
+        assert!(lower < estimate.estimate() && estimate.estimate() < upper);
+    }
+
+    #[test]
+    fn test_sample_without_matches() {
+        let graph = GraphBuilder::new().add_node(0, "a").build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .build();
+
+        let mut rng = SmallRng::seed_from_u64(42);
+        assert!(sample_matches(&graph, &pattern, &MatchConfig::default(), 3, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use crate::candidates::Candidates;
+use crate::query::Query;
+
+// Orders the candidates of a pattern node within a search step, e.g. by a
+// learned model. Candidates with higher scores are tried first, candidates
+// with equal scores keep their order. Scoring only changes the order in
+// which matches are found, which matters with limits and `find_first_match`.
+pub trait CandidateScorer: Send + Sync {
+    // `assigned` holds the (pattern node, graph node) pairs of the branch.
+    fn score(&self, pattern_node: usize, graph_node: usize, assigned: &[(usize, usize)]) -> f64;
+}
+
+impl<F> CandidateScorer for F
+where
+    F: Fn(usize, usize, &[(usize, usize)]) -> f64 + Send + Sync,
+{
+    fn score(&self, pattern_node: usize, graph_node: usize, assigned: &[(usize, usize)]) -> f64 {
+        self(pattern_node, graph_node, assigned)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Scorer(pub(crate) Arc<dyn CandidateScorer>);
+
+impl fmt::Debug for Scorer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Scorer")
+    }
+}
+
+impl Scorer {
+    // The candidates of the pattern node at `depth` by descending score.
+    pub(crate) fn order<T: Eq + Hash>(
+        &self,
+        query: &Query<T>,
+        candidates: &[Candidates],
+        depth: usize,
+    ) -> Vec<usize> {
+        let assigned = (0..depth)
+            .map(|u_p| (query.original_id(u_p), candidates[u_p][0]))
+            .collect::<Vec<_>>();
+        let pattern_node = query.original_id(depth);
+        let mut scored = candidates[depth]
+            .iter()
+            .map(|v_g| (self.0.score(pattern_node, *v_g, &assigned), *v_g))
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().map(|(_, v_g)| v_g).collect()
+    }
+}
@@ -0,0 +1,722 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::dual_iso::panic_message;
+use crate::table::json_string;
+use crate::{
+    dual_iso_to_channel, parse_graph, CatalogError, Graph, GraphCatalog, MatchConfig, MatchPanic,
+};
+
+// A minimal HTTP/1.1 interface to the matcher. Graphs and patterns are sent
+// in the text format of `read_graph`, matches are returned as one JSON array
+// per line.
+//
+//   GET    /graphs                            names of the loaded graphs
+//   PUT    /graphs/<name>                     loads or replaces a graph
+//   DELETE /graphs/<name>                     unloads a graph
+//   PUT    /patterns/<name>                   registers or replaces a pattern
+//   POST   /graphs/<graph>/match/<pattern>    matches, optionally `?limit=<n>`
+
+pub struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    body: String,
+}
+
+impl Request {
+    pub fn new(method: &str, target: &str, body: &str) -> Self {
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, query),
+            None => (target, ""),
+        };
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (pair.to_string(), String::new()),
+            })
+            .collect();
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+            body: body.to_string(),
+        }
+    }
+
+    // Bodies larger than `max_body_size` bytes are rejected with a
+    // `BodyTooLarge` error before they are read. The request line and headers
+    // are limited to `MAX_HEADER_SIZE` bytes and `MAX_HEADERS` headers, beyond
+    // that they are rejected with a `HeadersTooLarge` error.
+    pub fn read<R: BufRead>(reader: &mut R, max_body_size: usize) -> io::Result<Request> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let too_large = || io::Error::new(io::ErrorKind::InvalidData, HeadersTooLarge);
+        let mut remaining = MAX_HEADER_SIZE;
+        let mut read_line = |reader: &mut R, line: &mut String| {
+            let read = reader.by_ref().take(remaining as u64).read_line(line)?;
+            remaining -= read;
+            if remaining == 0 && !line.ends_with('\n') {
+                return Err(too_large());
+            }
+            Ok(read)
+        };
+
+        let mut request_line = String::new();
+        read_line(reader, &mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+            _ => return Err(invalid("Invalid request line.")),
+        };
+
+        let mut content_length = 0;
+        for count in 0.. {
+            let mut header = String::new();
+            if read_line(reader, &mut header)? == 0 {
+                break;
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if count == MAX_HEADERS {
+                return Err(too_large());
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_length = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid("Invalid content length."))?;
+                }
+            }
+        }
+
+        if content_length > max_body_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                BodyTooLarge {
+                    content_length,
+                    max_body_size,
+                },
+            ));
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        let body = String::from_utf8(body).map_err(|_| invalid("Body is not valid UTF-8."))?;
+        Ok(Request::new(&method, &target, &body))
+    }
+}
+
+// The request announced a body larger than the server accepts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BodyTooLarge {
+    content_length: usize,
+    max_body_size: usize,
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Body of {} bytes exceeds the limit of {} bytes.",
+            self.content_length, self.max_body_size
+        )
+    }
+}
+
+impl Error for BodyTooLarge {}
+
+// The request line and headers exceed `MAX_HEADER_SIZE` bytes or there are
+// more than `MAX_HEADERS` headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeadersTooLarge;
+
+impl fmt::Display for HeadersTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Headers exceed the limit of {} bytes or {} headers.",
+            MAX_HEADER_SIZE, MAX_HEADERS
+        )
+    }
+}
+
+impl Error for HeadersTooLarge {}
+
+const MAX_HEADER_SIZE: usize = 16 << 10;
+
+const MAX_HEADERS: usize = 100;
+
+const DEFAULT_MAX_BODY_SIZE: usize = 64 << 20;
+
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+const DEFAULT_MATCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Matches found but not yet written, the search pauses while it is full.
+const MATCH_BUFFER_SIZE: usize = 1024;
+
+pub struct QueryServer {
+    catalog: GraphCatalog<String>,
+    patterns: HashMap<String, Graph<String>>,
+    max_body_size: usize,
+    read_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    match_timeout: Option<Duration>,
+}
+
+impl Default for QueryServer {
+    fn default() -> Self {
+        QueryServer {
+            catalog: GraphCatalog::new(),
+            patterns: HashMap::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            read_timeout: Some(DEFAULT_READ_TIMEOUT),
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            match_timeout: Some(DEFAULT_MATCH_TIMEOUT),
+        }
+    }
+}
+
+impl QueryServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn catalog(&self) -> &GraphCatalog<String> {
+        &self.catalog
+    }
+
+    // Requests with larger bodies are answered with 413, 64 MiB by default.
+    pub fn max_body_size(&mut self, max_body_size: usize) -> &mut Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    // How long reading a request may stall before the connection is dropped,
+    // 30 seconds by default. `None` waits forever.
+    pub fn read_timeout(&mut self, read_timeout: Option<Duration>) -> &mut Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    // How long reading a whole request may take before the connection is
+    // dropped, 60 seconds by default. Unlike the read timeout, it also stops
+    // clients that keep sending a few bytes at a time. `None` waits forever.
+    pub fn request_timeout(&mut self, request_timeout: Option<Duration>) -> &mut Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    // How long matching may take, 10 seconds by default, as requests are
+    // handled one at a time and a long search delays all other clients.
+    // Matching that takes longer is answered with 503, or if matches have
+    // been sent already, the response is cut off before its last chunk.
+    // `None` matches until all matches are found.
+    pub fn match_timeout(&mut self, match_timeout: Option<Duration>) -> &mut Self {
+        self.match_timeout = match_timeout;
+        self
+    }
+
+    // Limits the estimated memory usage of the loaded graphs, loading a graph
+    // beyond it is answered with 507.
+    pub fn memory_limit(&mut self, bytes: usize) -> &mut Self {
+        self.catalog.memory_limit(bytes);
+        self
+    }
+
+    // Handles one request per connection until accepting fails. Failing
+    // connections, e.g. clients that disconnect or time out, do not stop the
+    // server, see `serve_with_errors` to observe them.
+    pub fn serve(&mut self, listener: &TcpListener) -> io::Result<()> {
+        self.serve_with_errors(listener, &mut |_, _| {})
+    }
+
+    // Like `serve`, but passes the error of each failing connection to
+    // `on_error` together with the address of the client.
+    pub fn serve_with_errors(
+        &mut self,
+        listener: &TcpListener,
+        on_error: &mut dyn FnMut(SocketAddr, io::Error),
+    ) -> io::Result<()> {
+        loop {
+            let (stream, address) = listener.accept()?;
+            if let Err(error) = self.serve_connection(stream) {
+                on_error(address, error);
+            }
+        }
+    }
+
+    fn serve_connection(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(DeadlineReader {
+            stream: &stream,
+            deadline: self
+                .request_timeout
+                .map(|request_timeout| Instant::now() + request_timeout),
+            read_timeout: self.read_timeout,
+        });
+        let request = match Request::read(&mut reader, self.max_body_size) {
+            Ok(request) => request,
+            Err(error) if is_body_too_large(&error) => {
+                return respond(&mut stream, 413, &error.to_string())
+            }
+            Err(error) if is_headers_too_large(&error) => {
+                return respond(&mut stream, 431, &error.to_string())
+            }
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                return respond(&mut stream, 400, &error.to_string())
+            }
+            Err(error) => return Err(error),
+        };
+        self.handle(&request, &mut stream)
+    }
+
+    pub fn handle<W: Write + Send>(&mut self, request: &Request, writer: &mut W) -> io::Result<()> {
+        let segments = request
+            .path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["graphs"]) => {
+                let names = self
+                    .catalog
+                    .names()
+                    .into_iter()
+                    .map(json_string)
+                    .collect::<Vec<_>>();
+                respond(writer, 200, &format!("[{}]", names.join(",")))
+            }
+            ("PUT", ["graphs", name]) => match parse_graph(&request.body) {
+                Ok(graph) => {
+                    let result = if self.catalog.contains(name) {
+                        self.catalog.replace(name, graph).map(|_| ())
+                    } else {
+                        self.catalog.register(name, graph).map(|_| ())
+                    };
+                    match result {
+                        Ok(()) => respond(writer, 201, ""),
+                        Err(error @ CatalogError::MemoryLimit { .. }) => {
+                            respond(writer, 507, &error.to_string())
+                        }
+                        Err(error) => respond(writer, 500, &error.to_string()),
+                    }
+                }
+                Err(error) => respond(writer, 400, &error.to_string()),
+            },
+            ("DELETE", ["graphs", name]) => match self.catalog.remove(name) {
+                Some(_) => respond(writer, 204, ""),
+                None => respond(writer, 404, &format!("Unknown graph '{}'.", name)),
+            },
+            ("PUT", ["patterns", name]) => match parse_graph(&request.body) {
+                Ok(pattern) => {
+                    self.patterns.insert(name.to_string(), pattern);
+                    respond(writer, 201, "")
+                }
+                Err(error) => respond(writer, 400, &error.to_string()),
+            },
+            ("POST", ["graphs", graph_name, "match", pattern]) => {
+                let graph = match self.catalog.get(graph_name) {
+                    Some(graph) => graph,
+                    None => {
+                        return respond(writer, 404, &format!("Unknown graph '{}'.", graph_name))
+                    }
+                };
+                let pattern = match self.patterns.get(*pattern) {
+                    Some(pattern) => pattern,
+                    None => {
+                        return respond(writer, 404, &format!("Unknown pattern '{}'.", pattern))
+                    }
+                };
+                let mut config = MatchConfig::new();
+                if let Some(match_timeout) = self.match_timeout {
+                    config.max_duration(match_timeout);
+                }
+                if let Some(limit) = request.query.get("limit") {
+                    match limit.parse() {
+                        Ok(limit) => config.limit(limit),
+                        Err(_) => {
+                            return respond(writer, 400, &format!("Invalid limit '{}'.", limit))
+                        }
+                    };
+                }
+                stream_matches(writer, graph, pattern, &config)
+            }
+            (_, ["graphs"])
+            | (_, ["graphs", _])
+            | (_, ["patterns", _])
+            | (_, ["graphs", _, "match", _]) => respond(writer, 405, "Method not allowed."),
+            _ => respond(writer, 404, "Not found."),
+        }
+    }
+}
+
+// Reads from the stream until the deadline, where each read waits at most the
+// read timeout.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Option<Instant>,
+    read_timeout: Option<Duration>,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(request_timed_out());
+                }
+                Some(
+                    self.read_timeout
+                        .map_or(remaining, |timeout| timeout.min(remaining)),
+                )
+            }
+            None => self.read_timeout,
+        };
+        self.stream.set_read_timeout(timeout)?;
+        match self.stream.read(buf) {
+            // the socket reports an expired read timeout as `WouldBlock` on
+            // unix, which also covers the read cut short by the deadline
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) && self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline) =>
+            {
+                Err(request_timed_out())
+            }
+            result => result,
+        }
+    }
+}
+
+fn request_timed_out() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        "Reading the request took too long.",
+    )
+}
+
+fn is_body_too_large(error: &io::Error) -> bool {
+    error
+        .get_ref()
+        .is_some_and(|error| error.is::<BodyTooLarge>())
+}
+
+fn is_headers_too_large(error: &io::Error) -> bool {
+    error
+        .get_ref()
+        .is_some_and(|error| error.is::<HeadersTooLarge>())
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        507 => "Insufficient Storage",
+        _ => "Internal Server Error",
+    }
+}
+
+fn respond<W: Write>(writer: &mut W, status: u16, body: &str) -> io::Result<()> {
+    let content_type = if status == 200 {
+        "application/json"
+    } else {
+        "text/plain"
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        status_text(status),
+        content_type,
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+// Sends each match as its own chunk as soon as it is found. The search runs
+// on this thread, since graphs cannot be shared between threads, and another
+// thread writes the matches it receives through a bounded channel, so a slow
+// client pauses the search. A search that panics or exceeds a quota after
+// matches have been sent cuts the response off before its last chunk.
+fn stream_matches<W: Write + Send>(
+    writer: &mut W,
+    graph: &Graph<String>,
+    pattern: &Graph<String>,
+    config: &MatchConfig,
+) -> io::Result<()> {
+    // accounted here to tell whether the quota stopped the search
+    let config = config.accounted();
+    let (sender, receiver) = mpsc::sync_channel(MATCH_BUFFER_SIZE);
+    let chunks = &mut *writer;
+    let (searched, written) = thread::scope(|scope| {
+        let writing = scope.spawn(move || write_matches(chunks, receiver));
+        let searched = panic::catch_unwind(AssertUnwindSafe(|| {
+            dual_iso_to_channel(graph, pattern, &config, &sender)
+        }));
+        // the writer stops once the channel is closed
+        drop(sender);
+        let written = writing
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload));
+        (searched, written)
+    });
+    let is_started = written?;
+
+    let error = match searched {
+        Err(payload) => Some((
+            500,
+            MatchPanic {
+                message: panic_message(payload),
+            }
+            .to_string(),
+        )),
+        Ok(_) if config.is_quota_exceeded() => Some((503, "Matching took too long.".to_string())),
+        Ok(_) => None,
+    };
+    match error {
+        Some((_, message)) if is_started => Err(io::Error::other(message)),
+        Some((status, message)) => respond(writer, status, &message),
+        None => {
+            if !is_started {
+                write_head(writer)?;
+            }
+            write!(writer, "0\r\n\r\n")?;
+            writer.flush()
+        }
+    }
+}
+
+fn write_head<W: Write>(writer: &mut W) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n"
+    )
+}
+
+// Writes the received matches as chunks, the head before the first one.
+// Returns whether the response has been started.
+fn write_matches<W: Write>(writer: &mut W, receiver: Receiver<Vec<usize>>) -> io::Result<bool> {
+    let mut is_started = false;
+    for embedding in receiver {
+        if !is_started {
+            write_head(writer)?;
+            is_started = true;
+        }
+        let line = format!(
+            "[{}]\n",
+            embedding
+                .iter()
+                .map(|node_id| node_id.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        write!(writer, "{:x}\r\n{}\r\n", line.len(), line)?;
+    }
+    Ok(is_started)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn call(server: &mut QueryServer, method: &str, target: &str, body: &str) -> String {
+        let mut out = vec![];
+        server
+            .handle(&Request::new(method, target, body), &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = "PUT /graphs/g?limit=2 HTTP/1.1\r\nHost: x\r\nContent-Length: 8\r\n\r\nnode 0 a";
+        let request = Request::read(&mut raw.as_bytes(), 8).unwrap();
+
+        assert_eq!("PUT", request.method);
+        assert_eq!("/graphs/g", request.path);
+        assert_eq!(Some(&String::from("2")), request.query.get("limit"));
+        assert_eq!("node 0 a", request.body);
+    }
+
+    #[test]
+    fn test_read_large_request() {
+        let raw = "PUT /graphs/g HTTP/1.1\r\nContent-Length: 9999999999\r\n\r\nnode 0 a";
+        let error = Request::read(&mut raw.as_bytes(), 1024).err().unwrap();
+
+        assert!(is_body_too_large(&error));
+        assert_eq!(
+            "Body of 9999999999 bytes exceeds the limit of 1024 bytes.",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_read_large_headers() {
+        let long = format!(
+            "GET /graphs HTTP/1.1\r\nCookie: {}\r\n\r\n",
+            "x".repeat(MAX_HEADER_SIZE)
+        );
+        let error = Request::read(&mut long.as_bytes(), 1024).err().unwrap();
+        assert!(is_headers_too_large(&error));
+
+        let many = format!(
+            "GET /graphs HTTP/1.1\r\n{}\r\n",
+            "Host: x\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let error = Request::read(&mut many.as_bytes(), 1024).err().unwrap();
+        assert!(is_headers_too_large(&error));
+        assert_eq!(
+            "Headers exceed the limit of 16384 bytes or 100 headers.",
+            error.to_string()
+        );
+
+        let enough = format!(
+            "GET /graphs HTTP/1.1\r\n{}\r\n",
+            "Host: x\r\n".repeat(MAX_HEADERS)
+        );
+        assert!(Request::read(&mut enough.as_bytes(), 1024).is_ok());
+    }
+
+    #[test]
+    fn test_match_timeout() {
+        let mut server = QueryServer::new();
+        let graph = "node 0 a\nnode 1 b\nrel 0 1\n";
+        call(&mut server, "PUT", "/graphs/g", graph);
+        call(
+            &mut server,
+            "PUT",
+            "/patterns/p",
+            "node 0 a\nnode 1 b\nrel 0 1",
+        );
+
+        server.match_timeout(Some(Duration::from_secs(0)));
+        let response = call(&mut server, "POST", "/graphs/g/match/p", "");
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(response.ends_with("Matching took too long."));
+
+        server.match_timeout(None);
+        let response = call(&mut server, "POST", "/graphs/g/match/p", "");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("6\r\n[0,1]\n\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_load_and_match() {
+        let mut server = QueryServer::new();
+        let graph = "node 0 a\nnode 1 b\nnode 2 b\nrel 0 1\nrel 0 2\n";
+
+        assert!(call(&mut server, "PUT", "/graphs/g", graph).starts_with("HTTP/1.1 201"));
+        assert!(call(
+            &mut server,
+            "PUT",
+            "/patterns/p",
+            "node 0 a\nnode 1 b\nrel 0 1"
+        )
+        .starts_with("HTTP/1.1 201"));
+        assert!(call(&mut server, "GET", "/graphs", "").ends_with("[\"g\"]"));
+
+        let response = call(&mut server, "POST", "/graphs/g/match/p?limit=1", "");
+        assert!(response.contains("Transfer-Encoding: chunked"));
+        assert!(response.contains("6\r\n[0,1]\n\r\n") || response.contains("6\r\n[0,2]\n\r\n"));
+        assert!(response.ends_with("0\r\n\r\n"));
+        assert_eq!(1, response.matches("[0,").count());
+
+        assert!(call(&mut server, "DELETE", "/graphs/g", "").starts_with("HTTP/1.1 204"));
+        assert!(server.catalog().is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut server = QueryServer::new();
+
+        let response = call(&mut server, "PUT", "/graphs/g", "edge 0 1");
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.ends_with("Unknown directive 'edge' in line 1."));
+        assert!(call(&mut server, "POST", "/graphs/g/match/p", "").ends_with("Unknown graph 'g'."));
+        assert!(call(&mut server, "GET", "/graphs/g", "").starts_with("HTTP/1.1 405"));
+        assert!(call(&mut server, "GET", "/unknown", "").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (errors, failures) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            QueryServer::new()
+                .max_body_size(64)
+                .read_timeout(Some(Duration::from_millis(50)))
+                .request_timeout(Some(Duration::from_millis(200)))
+                .memory_limit(1)
+                .serve_with_errors(&listener, &mut |_, error| {
+                    let _ = errors.send(error.kind());
+                })
+        });
+        let send = |request: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        // a client that disconnects and one that stalls until the timeout
+        drop(TcpStream::connect(address).unwrap());
+        let mut stalled = TcpStream::connect(address).unwrap();
+        stalled.write_all(b"PUT /graphs/g HTTP/1.1\r\n").unwrap();
+
+        let response = send("PUT /graphs/g HTTP/1.1\r\nContent-Length: 65\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 413 Content Too Large"));
+        let response = send("PUT /graphs/g HTTP/1.1\r\nContent-Length: 8\r\n\r\nnode 0 a");
+        assert!(response.starts_with("HTTP/1.1 507 Insufficient Storage"));
+        let response = send("GET /graphs HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("[]"));
+
+        // a client that sends a byte within each read timeout is stopped by
+        // the request timeout
+        let mut trickling = TcpStream::connect(address).unwrap();
+        let started = Instant::now();
+        for byte in b"PUT /graphs/g HTTP/1.1\r\n".iter().cycle() {
+            if trickling.write_all(&[*byte]).is_err() || started.elapsed() > Duration::from_secs(5)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let response = send("GET /graphs HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"));
+
+        drop(stalled);
+        let failures = failures.try_iter().collect::<Vec<_>>();
+        assert!(failures.contains(&io::ErrorKind::TimedOut));
+    }
+}
@@ -0,0 +1,796 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::candidates::Candidates;
+use crate::query::{Direction, Query};
+use crate::{Graph, MatchConfig};
+
+// Patterns of a common shape are matched by dedicated routines instead of the
+// simulation and backtracking of the general search. Shapes are only
+// detected within a connected pattern and only if the search would not order
+// candidates differently, i.e. without a scorer and order constraints.
+//
+// Returns false if the pattern has none of the shapes.
+pub(crate) fn visit_shape<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    candidates: &[Candidates],
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
+) -> bool {
+    if config.disable_fast_paths
+        || config.scorer.is_some()
+        || !query.order().is_empty()
+        || query.node_count() < 2
+    {
+        return false;
+    }
+    if let Some(path) = directed_path(query) {
+        config.account_candidates(candidates.iter().map(|c| c.len()).sum());
+        let members = Members::new(graph, query, candidates);
+        let mut embedding = vec![0; query.node_count()];
+        visit_path(
+            graph,
+            config,
+            &path,
+            candidates,
+            &members,
+            0,
+            &mut embedding,
+            emit,
+        );
+        return true;
+    }
+    if let Some(star) = Star::new(query) {
+        config.account_candidates(candidates.iter().map(|c| c.len()).sum());
+        let members = Members::new(graph, query, candidates);
+        star.visit(graph, query, config, candidates, &members, emit);
+        return true;
+    }
+    // clique nodes are distinct, so repeated graph nodes need the general search
+    if config.is_injective() && is_clique(query, candidates) {
+        config.account_candidates(candidates.iter().map(|c| c.len()).sum());
+        visit_cliques(graph, query, config, candidates, emit);
+        return true;
+    }
+    false
+}
+
+// Tells whether graph nodes are candidates of a pattern node.
+struct Members<'a, T> {
+    graph: &'a Graph<T>,
+    labels: Vec<&'a T>,
+    // None if the candidates are all graph nodes with the label of the pattern node
+    sets: Vec<Option<HashSet<usize>>>,
+}
+
+impl<'a, T: Eq + Hash> Members<'a, T> {
+    fn new(graph: &'a Graph<T>, query: &'a Query<T>, candidates: &[Candidates]) -> Self {
+        let labels = (0..query.node_count())
+            .map(|u_p| query.label(u_p))
+            .collect::<Vec<_>>();
+        let sets = candidates
+            .iter()
+            .zip(&labels)
+            .map(|(candidates, label)| {
+                let bucket = graph.nodes_by_label(label);
+                if std::ptr::eq(&**candidates, bucket) {
+                    None
+                } else {
+                    Some(candidates.iter().copied().collect())
+                }
+            })
+            .collect();
+        Members {
+            graph,
+            labels,
+            sets,
+        }
+    }
+
+    fn contains(&self, u_p: usize, v_g: usize) -> bool {
+        match &self.sets[u_p] {
+            Some(set) => set.contains(&v_g),
+            None => self.graph.node_label(v_g) == self.labels[u_p],
+        }
+    }
+}
+
+// The pattern nodes of a path whose relationships all point from the start
+// towards the end in the graph, each with the multiplicity of the
+// relationship from its predecessor. Such a path is matched by following
+// the adjacency lists of the graph from the start, so it has no typed
+// relationships.
+fn directed_path<T: Eq + Hash>(query: &Query<T>) -> Option<Vec<(usize, usize)>> {
+    let node_count = query.node_count();
+    let mut next = vec![None; node_count];
+    let mut has_predecessor = vec![false; node_count];
+    for u_p in 0..node_count {
+        for constraint in query.constraints(u_p) {
+            let (start, end) = match constraint.direction {
+                Direction::Outgoing => (u_p, constraint.target),
+                Direction::Incoming => (constraint.target, u_p),
+                Direction::Either => return None,
+            };
+            if start == end
+                || constraint.rel_type.is_some()
+                || next[start].is_some()
+                || has_predecessor[end]
+            {
+                return None;
+            }
+            next[start] = Some((end, constraint.multiplicity));
+            has_predecessor[end] = true;
+        }
+    }
+    let start = (0..node_count).find(|u_p| !has_predecessor[*u_p])?;
+    let mut path = vec![(start, 0)];
+    while let Some(step) = next[path[path.len() - 1].0] {
+        path.push(step);
+    }
+    // a path covers all pattern nodes, otherwise there is a cycle
+    if path.len() == node_count {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+// Returns false once `emit` asked to stop.
+#[allow(clippy::too_many_arguments)]
+fn visit_path<T: Eq + Hash>(
+    graph: &Graph<T>,
+    config: &MatchConfig,
+    path: &[(usize, usize)],
+    candidates: &[Candidates],
+    members: &Members<T>,
+    depth: usize,
+    embedding: &mut Vec<usize>,
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
+) -> bool {
+    if depth == path.len() {
+        return emit(embedding.clone());
+    }
+    let (u_p, multiplicity) = path[depth];
+    let mut visit = |v_g: usize, embedding: &mut Vec<usize>| {
+        if config.is_injective() && path[..depth].iter().any(|(w_p, _)| embedding[*w_p] == v_g) {
+            return true;
+        }
+        if !config.enter_branch() {
+            return false;
+        }
+        embedding[u_p] = v_g;
+        visit_path(
+            graph,
+            config,
+            path,
+            candidates,
+            members,
+            depth + 1,
+            embedding,
+            emit,
+        )
+    };
+    if depth == 0 {
+        for v_g in &*candidates[u_p] {
+            if !visit(*v_g, embedding) {
+                return false;
+            }
+        }
+    } else {
+        let previous = embedding[path[depth - 1].0];
+        for (v_g, count) in graph.distinct_neighbors(previous) {
+            if (v_g != previous || !config.is_injective())
+                && count >= multiplicity
+                && members.contains(u_p, v_g)
+                && !visit(v_g, embedding)
+            {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Counts the matches without enumerating them if the pattern is a star, see
+// `Star::count`.
+pub(crate) fn count_shape<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    candidates: &[Candidates],
+) -> Option<usize> {
+    if config.disable_fast_paths || !query.order().is_empty() {
+        return None;
+    }
+    let star = Star::new(query)?;
+    config.account_candidates(candidates.iter().map(|c| c.len()).sum());
+    let members = Members::new(graph, query, candidates);
+    star.count(graph, query, config, candidates, &members)
+}
+
+// A center with relationships to at least two leaves, which are related to no
+// other pattern node. All relationships point from the center to the leaves
+// in the graph and are untyped.
+struct Star {
+    center: usize,
+    // (leaf, multiplicity)
+    leaves: Vec<(usize, usize)>,
+}
+
+impl Star {
+    fn new<T: Eq + Hash>(query: &Query<T>) -> Option<Self> {
+        let node_count = query.node_count();
+        let mut center = None;
+        let mut leaves = vec![];
+        for u_p in 0..node_count {
+            for constraint in query.constraints(u_p) {
+                let (start, end) = match constraint.direction {
+                    Direction::Outgoing => (u_p, constraint.target),
+                    Direction::Incoming => (constraint.target, u_p),
+                    Direction::Either => return None,
+                };
+                if start == end
+                    || constraint.rel_type.is_some()
+                    || *center.get_or_insert(start) != start
+                {
+                    return None;
+                }
+                leaves.push((end, constraint.multiplicity));
+            }
+        }
+        let center = center?;
+        leaves.sort_unstable();
+        // each other pattern node is a leaf with a single relationship
+        if node_count < 3
+            || leaves.len() != node_count - 1
+            || leaves.windows(2).any(|pair| pair[0].0 == pair[1].0)
+        {
+            return None;
+        }
+        Some(Star { center, leaves })
+    }
+
+    // The candidates of each leaf that the center candidate has the
+    // relationships to.
+    fn neighbors<T: Eq + Hash>(
+        &self,
+        graph: &Graph<T>,
+        config: &MatchConfig,
+        members: &Members<T>,
+        v_c: usize,
+    ) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![vec![]; self.leaves.len()];
+        for (v_g, count) in graph.distinct_neighbors(v_c) {
+            if v_g == v_c && config.is_injective() {
+                continue;
+            }
+            for (idx, (leaf, multiplicity)) in self.leaves.iter().enumerate() {
+                if count >= *multiplicity && members.contains(*leaf, v_g) {
+                    neighbors[idx].push(v_g);
+                }
+            }
+        }
+        neighbors
+    }
+
+    fn visit<T: Eq + Hash>(
+        &self,
+        graph: &Graph<T>,
+        query: &Query<T>,
+        config: &MatchConfig,
+        candidates: &[Candidates],
+        members: &Members<T>,
+        emit: &mut dyn FnMut(Vec<usize>) -> bool,
+    ) {
+        let mut embedding = vec![0; query.node_count()];
+        for v_c in &*candidates[self.center] {
+            if !config.enter_branch() {
+                return;
+            }
+            let neighbors = self.neighbors(graph, config, members, *v_c);
+            if neighbors.iter().any(Vec::is_empty) {
+                continue;
+            }
+            embedding[self.center] = *v_c;
+            if !self.expand(config, &neighbors, 0, &mut embedding, emit) {
+                return;
+            }
+        }
+    }
+
+    // Assigns the leaves from `depth` on to their neighbors. Returns false
+    // once `emit` asked to stop.
+    fn expand(
+        &self,
+        config: &MatchConfig,
+        neighbors: &[Vec<usize>],
+        depth: usize,
+        embedding: &mut Vec<usize>,
+        emit: &mut dyn FnMut(Vec<usize>) -> bool,
+    ) -> bool {
+        if depth == self.leaves.len() {
+            return emit(embedding.clone());
+        }
+        for v_g in &neighbors[depth] {
+            if config.is_injective()
+                && self.leaves[..depth]
+                    .iter()
+                    .any(|(leaf, _)| embedding[*leaf] == *v_g)
+            {
+                continue;
+            }
+            embedding[self.leaves[depth].0] = *v_g;
+            if !self.expand(config, neighbors, depth + 1, embedding, emit) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Leaves with the same label buckets as candidates have the same
+    // neighbors, leaves with different label buckets disjoint ones, so the
+    // matches per center candidate are a product of the ways to assign each
+    // group of leaves. Returns None if the candidates of the leaves are not
+    // label buckets or the multiplicities differ within a group.
+    fn count<T: Eq + Hash>(
+        &self,
+        graph: &Graph<T>,
+        query: &Query<T>,
+        config: &MatchConfig,
+        candidates: &[Candidates],
+        members: &Members<T>,
+    ) -> Option<usize> {
+        // (label, multiplicity, leaf index, number of leaves) per group
+        let mut groups: Vec<(&T, usize, usize, usize)> = vec![];
+        for (idx, (leaf, multiplicity)) in self.leaves.iter().enumerate() {
+            if members.sets[*leaf].is_some() {
+                return None;
+            }
+            let label = query.label(*leaf);
+            match groups.iter_mut().find(|group| group.0 == label) {
+                Some(group) if group.1 == *multiplicity => group.3 += 1,
+                Some(_) => return None,
+                None => groups.push((label, *multiplicity, idx, 1)),
+            }
+        }
+
+        let mut count = 0usize;
+        for v_c in &*candidates[self.center] {
+            if !config.enter_branch() {
+                break;
+            }
+            let neighbors = self.neighbors(graph, config, members, *v_c);
+            let center_count = groups
+                .iter()
+                .map(|(_, _, idx, leaves)| {
+                    let available = neighbors[*idx].len();
+                    if config.is_injective() {
+                        // ordered selections of distinct neighbors
+                        (0..*leaves)
+                            .map(|taken| available.saturating_sub(taken))
+                            .product::<usize>()
+                    } else {
+                        available.pow(*leaves as u32)
+                    }
+                })
+                .product::<usize>();
+            count += center_count;
+            if config.is_limit_reached(count) {
+                break;
+            }
+        }
+        Some(count)
+    }
+}
+
+// Whether every two pattern nodes are related and all pattern nodes have the
+// same candidates, e.g. a k-clique with a single label.
+fn is_clique<T: Eq + Hash>(query: &Query<T>, candidates: &[Candidates]) -> bool {
+    let node_count = query.node_count();
+    if node_count < 3 || candidates[1..].iter().any(|c| **c != *candidates[0]) {
+        return false;
+    }
+    let mut pairs = HashSet::new();
+    for u_p in 0..node_count {
+        for constraint in query.constraints(u_p) {
+            if constraint.target == u_p {
+                return false;
+            }
+            pairs.insert((u_p.min(constraint.target), u_p.max(constraint.target)));
+        }
+    }
+    pairs.len() == node_count * (node_count - 1) / 2
+}
+
+// Enumerates the cliques of the graph induced by the candidates once each,
+// as ascending node sets grown by intersecting sorted adjacency lists, and
+// then matches the pattern nodes to every order of the clique nodes that
+// satisfies the directions and multiplicities of the pattern.
+fn visit_cliques<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    candidates: &[Candidates],
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
+) {
+    let mut nodes = candidates[0].to_vec();
+    nodes.sort_unstable();
+    // relationships in either direction between candidates, by index into
+    // `nodes`, pointing from the smaller to the larger index
+    let mut successors = vec![vec![]; nodes.len()];
+    for (idx, u_g) in nodes.iter().enumerate() {
+        for (v_g, _) in graph.distinct_neighbors(*u_g) {
+            if let Ok(other) = nodes.binary_search(&v_g) {
+                if other != idx {
+                    successors[idx.min(other)].push(idx.max(other));
+                }
+            }
+        }
+    }
+    for successors in successors.iter_mut() {
+        successors.sort_unstable();
+        successors.dedup();
+    }
+
+    let mut clique = Vec::with_capacity(query.node_count());
+    let mut visit_clique = |clique: &[usize]| {
+        let clique = clique.iter().map(|idx| nodes[*idx]).collect::<Vec<_>>();
+        let mut embedding = vec![0; clique.len()];
+        assign_clique(graph, query, config, &clique, 0, &mut embedding, emit)
+    };
+    for idx in 0..nodes.len() {
+        clique.push(idx);
+        let is_continued = extend_clique(
+            config,
+            &successors,
+            query.node_count(),
+            &mut clique,
+            &successors[idx],
+            &mut visit_clique,
+        );
+        clique.pop();
+        if !is_continued {
+            return;
+        }
+    }
+}
+
+// Returns false once `visit` asked to stop.
+fn extend_clique(
+    config: &MatchConfig,
+    successors: &[Vec<usize>],
+    size: usize,
+    clique: &mut Vec<usize>,
+    common: &[usize],
+    visit: &mut dyn FnMut(&[usize]) -> bool,
+) -> bool {
+    if clique.len() == size {
+        return visit(clique);
+    }
+    // not enough common successors left to complete the clique
+    if common.len() < size - clique.len() {
+        return true;
+    }
+    for (pos, idx) in common.iter().enumerate() {
+        let next = intersect_sorted(config, &common[pos + 1..], &successors[*idx]);
+        clique.push(*idx);
+        let is_continued = extend_clique(config, successors, size, clique, &next, visit);
+        clique.pop();
+        if !is_continued {
+            return false;
+        }
+    }
+    true
+}
+
+fn intersect_sorted(config: &MatchConfig, a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut common = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    config.account_intersection(i + j, 0);
+    common
+}
+
+// Assigns the pattern nodes from `depth` on to the unassigned clique nodes.
+// Returns false once `emit` asked to stop.
+fn assign_clique<T: Eq + Hash>(
+    graph: &Graph<T>,
+    query: &Query<T>,
+    config: &MatchConfig,
+    clique: &[usize],
+    depth: usize,
+    embedding: &mut Vec<usize>,
+    emit: &mut dyn FnMut(Vec<usize>) -> bool,
+) -> bool {
+    if depth == clique.len() {
+        return emit(embedding.clone());
+    }
+    for v_g in clique {
+        if embedding[..depth].contains(v_g) {
+            continue;
+        }
+        embedding[depth] = *v_g;
+        // constraints between the pattern node and the previously assigned ones
+        let is_satisfied = (0..=depth).all(|u_p| {
+            query.constraints(u_p).iter().all(|constraint| {
+                let target = constraint.target;
+                (u_p != depth && target != depth)
+                    || target > depth
+                    || constraint.is_satisfied_by(graph, embedding[u_p], embedding[target])
+            })
+        });
+        if !is_satisfied {
+            continue;
+        }
+        if !config.enter_branch() {
+            return false;
+        }
+        if !assign_clique(graph, query, config, clique, depth + 1, embedding, emit) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{count_matches, dual_iso_with_config, GraphBuilder, Semantics};
+
+    use super::*;
+
+    fn sorted(mut matches: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        matches.sort();
+        matches
+    }
+
+    #[test]
+    fn test_directed_path() {
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(1, 0)
+            .add_relationship(2, 1)
+            .build();
+        assert_eq!(
+            Some(vec![(2, 0), (1, 1), (0, 1)]),
+            directed_path(&Query::new(&pattern))
+        );
+
+        let star = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+        assert_eq!(None, directed_path(&Query::new(&star)));
+
+        let cycle = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .build();
+        assert_eq!(None, directed_path(&Query::new(&cycle)));
+    }
+
+    #[test]
+    fn test_path_matches_general_search() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_node(4, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 3)
+            .add_relationship(3, 0)
+            .add_relationship(1, 4)
+            .add_relationship(1, 4)
+            .add_relationship(4, 4)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 3)
+            .build();
+        let parallel = GraphBuilder::new()
+            .add_node(0, "b")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .add_relationship(0, 1)
+            .build();
+
+        for semantics in [Semantics::Isomorphism, Semantics::Homomorphism] {
+            for pattern in [&pattern, &parallel] {
+                let mut config = MatchConfig::new();
+                config.semantics(semantics);
+                let fast = sorted(dual_iso_with_config(&graph, pattern, &config));
+                let general = sorted(dual_iso_with_config(
+                    &graph,
+                    pattern,
+                    config.fast_paths(false),
+                ));
+                assert!(!general.is_empty());
+                assert_eq!(general, fast);
+            }
+        }
+    }
+
+    #[test]
+    fn test_clique_matches_general_search() {
+        // two triangles sharing the relationship between 1 and 2 and a
+        // 4-clique on 3, 4, 5 and 6
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..8 {
+            builder.add_node(node_id, "a");
+        }
+        builder
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .add_relationship(2, 3)
+            .add_relationship(3, 1)
+            .add_relationship(1, 2);
+        for start_node in 3..7 {
+            for end_node in start_node + 1..7 {
+                builder.add_relationship(start_node, end_node);
+            }
+        }
+        builder.add_relationship(7, 6);
+        let graph = builder.build();
+
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..3 {
+            builder.add_node(node_id, "a");
+        }
+        let cycle = builder
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(2, 0)
+            .build();
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..3 {
+            builder.add_node(node_id, "a");
+        }
+        let transitive = builder
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .add_relationship(0, 2)
+            .build();
+        let mut builder = GraphBuilder::new();
+        for node_id in 0..4 {
+            builder.add_node(node_id, "a");
+        }
+        for start_node in 0..4 {
+            for end_node in start_node + 1..4 {
+                builder.add_relationship(start_node, end_node);
+            }
+        }
+        let four = builder.build();
+
+        let self_loop = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_relationship(0, 0)
+            .build();
+
+        for semantics in [Semantics::Isomorphism, Semantics::Homomorphism] {
+            for pattern in [&cycle, &transitive, &four] {
+                assert!(is_clique(
+                    &Query::new(pattern),
+                    &crate::dual_iso::init_candidates(&graph, &Query::new(pattern))
+                ));
+                let mut config = MatchConfig::new();
+                config.semantics(semantics);
+                let fast = sorted(dual_iso_with_config(&graph, pattern, &config));
+                let general = sorted(dual_iso_with_config(
+                    &graph,
+                    pattern,
+                    config.fast_paths(false),
+                ));
+                assert!(!general.is_empty());
+                assert_eq!(general, fast);
+            }
+        }
+        assert_eq!(
+            vec![vec![3, 4, 5, 6]],
+            dual_iso_with_config(&graph, &four, &MatchConfig::default())
+        );
+
+        // a homomorphism maps every pattern node onto the self-loop
+        let mut config = MatchConfig::new();
+        config.semantics(Semantics::Homomorphism);
+        assert_eq!(
+            vec![vec![0, 0, 0]],
+            dual_iso_with_config(&self_loop, &cycle, &config)
+        );
+        assert_eq!(
+            vec![vec![0, 0, 0]],
+            dual_iso_with_config(&self_loop, &cycle, config.fast_paths(false))
+        );
+    }
+
+    #[test]
+    fn test_star_matches_general_search() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "c")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_node(4, "c")
+            .add_node(5, "a")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(0, 3)
+            .add_relationship(0, 5)
+            .add_relationship(0, 5)
+            .add_relationship(4, 1)
+            .add_relationship(4, 2)
+            .add_relationship(4, 3)
+            .add_relationship(3, 0)
+            .build();
+        let star = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "c")
+            .add_node(2, "a")
+            .add_node(3, "b")
+            .add_relationship(1, 0)
+            .add_relationship(1, 2)
+            .add_relationship(1, 3)
+            .build();
+        let parallel = GraphBuilder::new()
+            .add_node(0, "c")
+            .add_node(1, "a")
+            .add_node(2, "a")
+            .add_relationship(0, 1)
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+
+        for semantics in [Semantics::Isomorphism, Semantics::Homomorphism] {
+            let mut config = MatchConfig::new();
+            config.semantics(semantics);
+            let fast = sorted(dual_iso_with_config(&graph, &star, &config));
+            let general = sorted(dual_iso_with_config(
+                &graph,
+                &star,
+                config.fast_paths(false),
+            ));
+            assert!(!general.is_empty());
+            assert_eq!(general, fast);
+            assert_eq!(
+                general.len(),
+                count_matches(&graph, &star, config.fast_paths(true))
+            );
+
+            let fast = sorted(dual_iso_with_config(&graph, &parallel, &config));
+            let general = sorted(dual_iso_with_config(
+                &graph,
+                &parallel,
+                config.fast_paths(false),
+            ));
+            assert_eq!(general, fast);
+            assert_eq!(
+                general.len(),
+                count_matches(&graph, &parallel, config.fast_paths(true))
+            );
+        }
+    }
+}
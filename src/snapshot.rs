@@ -0,0 +1,598 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+#[cfg(feature = "memmap2")]
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+#[cfg(feature = "memmap2")]
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::graph::RawGraph;
+use crate::{Graph, Value};
+
+// A binary format that stores a graph together with its indexes, i.e. the
+// adjacency lists of both directions and the nodes per label, so that
+// loading it only validates the arrays instead of building them. All
+// numbers are little endian u64 values.
+
+const MAGIC: &[u8; 8] = b"DISNAP01";
+
+// Writes the graph, including relationship types, properties and external
+// ids. A lazy label index is built first, as it is part of the snapshot.
+pub fn write_snapshot<T, W>(graph: &Graph<T>, writer: &mut W) -> io::Result<()>
+where
+    T: Eq + Hash + fmt::Display,
+    W: Write,
+{
+    let mut writer = BufWriter::new(writer);
+    writer.write_all(MAGIC)?;
+    let writer = &mut writer;
+    write_number(writer, graph.node_count())?;
+    write_number(writer, graph.relationship_count())?;
+    write_number(writer, graph.raw_relationship_count())?;
+
+    // each distinct label is stored once and referred to by its index, labels
+    // that display the same are loaded as the same label, so their buckets
+    // are merged
+    let mut buckets: Vec<(String, Vec<usize>)> = vec![];
+    let mut bucket_of = HashMap::new();
+    for nodes in graph.label_buckets() {
+        let label = graph.node_label(nodes[0]).to_string();
+        let idx = *bucket_of.entry(label.clone()).or_insert_with(|| {
+            buckets.push((label, vec![]));
+            buckets.len() - 1
+        });
+        buckets[idx].1.extend_from_slice(nodes);
+    }
+    let mut label_of = vec![0; graph.node_count()];
+    write_number(writer, buckets.len())?;
+    for (idx, (label, nodes)) in buckets.iter_mut().enumerate() {
+        nodes.sort_unstable();
+        write_string(writer, label)?;
+        write_numbers(writer, nodes)?;
+        for node_id in nodes.iter() {
+            label_of[*node_id] = idx;
+        }
+    }
+    write_numbers(writer, &label_of)?;
+    for array in graph.adjacency_arrays() {
+        write_numbers(writer, array)?;
+    }
+
+    let typed = graph.typed_relationships().collect::<Vec<_>>();
+    write_number(writer, typed.len())?;
+    for ((start_node, end_node), types) in typed {
+        write_number(writer, *start_node)?;
+        write_number(writer, *end_node)?;
+        write_number(writer, types.len())?;
+        for rel_type in types {
+            write_string(writer, rel_type)?;
+        }
+    }
+
+    write_number(writer, graph.node_properties().len())?;
+    for (node_id, properties) in graph.node_properties() {
+        write_number(writer, *node_id)?;
+        write_number(writer, properties.len())?;
+        for (key, value) in properties {
+            write_string(writer, key)?;
+            write_value(writer, value)?;
+        }
+    }
+
+    write_number(writer, graph.external_ids().len())?;
+    for (node_id, external_id) in graph.external_ids() {
+        write_number(writer, *node_id)?;
+        writer.write_all(&external_id.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+// Reads a snapshot written by `write_snapshot`. Snapshots that are truncated
+// or whose arrays are inconsistent are rejected with `InvalidData`.
+pub fn read_snapshot<R: Read>(reader: R) -> io::Result<Graph<String>> {
+    decode_snapshot(&mut BufReader::new(reader))
+}
+
+// Reads a snapshot file by mapping it into memory instead of reading it
+// through a buffer, so the arrays are decoded in bulk from the page cache,
+// which processes loading the same snapshot share. The arrays are still
+// copied into the graph, and candidate sets are computed when matching as
+// for any other graph. The file must not be modified while it is loaded.
+#[cfg(feature = "memmap2")]
+pub fn map_snapshot<P: AsRef<Path>>(path: P) -> io::Result<Graph<String>> {
+    let file = File::open(path)?;
+    // the mapping is only read and dropped before returning
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    decode_snapshot(&mut &map[..])
+}
+
+fn decode_snapshot<S: Source>(reader: &mut S) -> io::Result<Graph<String>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != *MAGIC {
+        return Err(invalid("unknown format"));
+    }
+    let node_count = read_number(reader)?;
+    let relationship_count = read_number(reader)?;
+    let raw_relationship_count = read_number(reader)?;
+    // deduping only removes relationships
+    if raw_relationship_count < relationship_count {
+        return Err(invalid("inconsistent relationship count"));
+    }
+    let check_node = |node_id: usize| {
+        if node_id < node_count {
+            Ok(node_id)
+        } else {
+            Err(invalid("node id out of range"))
+        }
+    };
+
+    let bucket_count = read_number(reader)?;
+    let mut labels = Vec::with_capacity(bucket_count.min(1 << 16));
+    let mut buckets = Vec::with_capacity(bucket_count.min(1 << 16));
+    let mut is_seen = HashSet::with_capacity(bucket_count.min(1 << 16));
+    for _ in 0..bucket_count {
+        let label = read_string(reader)?;
+        if !is_seen.insert(label.clone()) {
+            return Err(invalid("duplicate label"));
+        }
+        labels.push(Rc::new(label));
+        let nodes = reader.read_numbers()?;
+        if nodes.is_empty() {
+            return Err(invalid("empty label bucket"));
+        }
+        if nodes.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(invalid("unsorted label bucket"));
+        }
+        buckets.push(nodes);
+    }
+    let label_of = reader.read_numbers()?;
+    if label_of.len() != node_count {
+        return Err(invalid("missing labels"));
+    }
+    let mut node_labels = HashMap::with_capacity(node_count);
+    for (node_id, idx) in label_of.iter().enumerate() {
+        let label = labels.get(*idx).ok_or_else(|| invalid("unknown label"))?;
+        node_labels.insert(node_id, Rc::clone(label));
+    }
+    // every node is in the bucket of its label exactly once
+    let mut is_bucketed = vec![false; node_count];
+    for (idx, nodes) in buckets.iter().enumerate() {
+        for node_id in nodes {
+            let node_id = check_node(*node_id)?;
+            if label_of[node_id] != idx || is_bucketed[node_id] {
+                return Err(invalid("inconsistent label index"));
+            }
+            is_bucketed[node_id] = true;
+        }
+    }
+    if is_bucketed.contains(&false) {
+        return Err(invalid("inconsistent label index"));
+    }
+
+    let offsets = reader.read_numbers()?;
+    let lists = reader.read_numbers()?;
+    let in_offsets = reader.read_numbers()?;
+    let in_lists = reader.read_numbers()?;
+    for (offsets, lists) in [(&offsets, &lists), (&in_offsets, &in_lists)] {
+        if offsets.len() != node_count || lists.first() != Some(&0) {
+            return Err(invalid("inconsistent adjacency lists"));
+        }
+        let mut entries = 0;
+        for offset in offsets {
+            let degree = *lists
+                .get(*offset)
+                .ok_or_else(|| invalid("inconsistent adjacency lists"))?;
+            let list = lists
+                .get(offset + 1..offset + 1 + degree)
+                .ok_or_else(|| invalid("inconsistent adjacency lists"))?;
+            if list.iter().any(|node_id| *node_id >= node_count)
+                || list.windows(2).any(|pair| pair[0] > pair[1])
+            {
+                return Err(invalid("inconsistent adjacency lists"));
+            }
+            entries += degree;
+        }
+        if entries != relationship_count {
+            return Err(invalid("inconsistent relationship count"));
+        }
+    }
+    // the incoming lists are the outgoing lists reversed: visiting the start
+    // nodes in ascending order meets the entries of each sorted incoming list
+    // in order, and both have the same number of entries
+    let mut next_in = in_offsets
+        .iter()
+        .map(|offset| offset + 1)
+        .collect::<Vec<_>>();
+    for (start_node, offset) in offsets.iter().enumerate() {
+        for end_node in &lists[offset + 1..offset + 1 + lists[*offset]] {
+            let idx = next_in[*end_node];
+            if idx > in_offsets[*end_node] + in_lists[in_offsets[*end_node]]
+                || in_lists[idx] != start_node
+            {
+                return Err(invalid("incoming lists do not match outgoing lists"));
+            }
+            next_in[*end_node] += 1;
+        }
+    }
+
+    let mut interned: HashMap<String, Rc<str>> = HashMap::new();
+    let mut relationship_types = HashMap::new();
+    for _ in 0..read_number(reader)? {
+        let start_node = check_node(read_number(reader)?)?;
+        let end_node = check_node(read_number(reader)?)?;
+        let mut types = vec![];
+        for _ in 0..read_number(reader)? {
+            let rel_type = read_string(reader)?;
+            let rel_type = interned
+                .entry(rel_type)
+                .or_insert_with_key(|rel_type| Rc::from(rel_type.as_str()));
+            types.push(Rc::clone(rel_type));
+        }
+        relationship_types.insert((start_node, end_node), types);
+    }
+
+    let mut properties = HashMap::new();
+    for _ in 0..read_number(reader)? {
+        let node_id = check_node(read_number(reader)?)?;
+        let mut node_properties = HashMap::new();
+        for _ in 0..read_number(reader)? {
+            let key = read_string(reader)?;
+            node_properties.insert(key, read_value(reader)?);
+        }
+        properties.insert(node_id, node_properties);
+    }
+
+    let mut external_ids = HashMap::new();
+    for _ in 0..read_number(reader)? {
+        let node_id = check_node(read_number(reader)?)?;
+        let mut external_id = [0; 8];
+        reader.read_exact(&mut external_id)?;
+        external_ids.insert(node_id, u64::from_le_bytes(external_id));
+    }
+
+    Ok(Graph::from_raw(RawGraph {
+        relationship_count,
+        raw_relationship_count,
+        node_labels,
+        label_buckets: Some(buckets),
+        offsets,
+        lists,
+        in_offsets,
+        in_lists,
+        relationship_types,
+        properties,
+        external_ids,
+    }))
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid snapshot, {}.", reason),
+    )
+}
+
+fn write_number<W: Write>(writer: &mut W, number: usize) -> io::Result<()> {
+    writer.write_all(&(number as u64).to_le_bytes())
+}
+
+fn write_numbers<W: Write>(writer: &mut W, numbers: &[usize]) -> io::Result<()> {
+    write_number(writer, numbers.len())?;
+    for number in numbers {
+        write_number(writer, *number)?;
+    }
+    Ok(())
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    write_number(writer, string.len())?;
+    writer.write_all(string.as_bytes())
+}
+
+fn write_value<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+    match value {
+        Value::Int(value) => {
+            write_number(writer, 0)?;
+            writer.write_all(&value.to_le_bytes())
+        }
+        Value::Float(value) => {
+            write_number(writer, 1)?;
+            writer.write_all(&value.to_le_bytes())
+        }
+        Value::Str(value) => {
+            write_number(writer, 2)?;
+            write_string(writer, value)
+        }
+        Value::Bool(value) => {
+            write_number(writer, 3)?;
+            write_number(writer, *value as usize)
+        }
+    }
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<[u8; 8]> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_number<R: Read>(reader: &mut R) -> io::Result<usize> {
+    usize::try_from(u64::from_le_bytes(read_bytes(reader)?))
+        .map_err(|_| invalid("number out of range"))
+}
+
+// What snapshots are decoded from, buffered readers or mapped files.
+trait Source: Read + Sized {
+    fn read_numbers(&mut self) -> io::Result<Vec<usize>> {
+        let len = read_number(self)?;
+        // the length is not trusted to allocate up front
+        let mut numbers = Vec::with_capacity(len.min(1 << 16));
+        for _ in 0..len {
+            numbers.push(read_number(self)?);
+        }
+        Ok(numbers)
+    }
+}
+
+impl<R: Read> Source for BufReader<R> {}
+
+impl Source for &[u8] {
+    // the remaining bytes bound the length, so it can be allocated up front
+    fn read_numbers(&mut self) -> io::Result<Vec<usize>> {
+        let len = read_number(self)?;
+        let size = len
+            .checked_mul(8)
+            .filter(|size| *size <= self.len())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let (bytes, rest) = self.split_at(size);
+        let numbers = bytes
+            .chunks_exact(8)
+            .map(|number| {
+                usize::try_from(u64::from_le_bytes(<[u8; 8]>::try_from(number).unwrap()))
+                    .map_err(|_| invalid("number out of range"))
+            })
+            .collect::<io::Result<_>>()?;
+        *self = rest;
+        Ok(numbers)
+    }
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_number(reader)?;
+    let mut bytes = vec![];
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid("string is not UTF-8"))
+}
+
+fn read_value<R: Read>(reader: &mut R) -> io::Result<Value> {
+    match read_number(reader)? {
+        0 => Ok(Value::Int(i64::from_le_bytes(read_bytes(reader)?))),
+        1 => Ok(Value::Float(f64::from_le_bytes(read_bytes(reader)?))),
+        2 => Ok(Value::Str(read_string(reader)?)),
+        3 => Ok(Value::Bool(read_number(reader)? != 0)),
+        _ => Err(invalid("unknown value type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dual_iso, GraphBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let graph = GraphBuilder::new()
+            .lazy_label_index()
+            .add_external_node(10, "a".to_string())
+            .add_external_node(20, "b".to_string())
+            .add_node(2, "a".to_string())
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .add_relationship(2, 1)
+            .add_relationship(1, 1)
+            .set_property(0, "age", 42)
+            .set_property(2, "name", "x")
+            .build();
+
+        let mut bytes = vec![];
+        write_snapshot(&graph, &mut bytes).unwrap();
+        let loaded = read_snapshot(bytes.as_slice()).unwrap();
+
+        assert_eq!(graph, loaded);
+        assert_eq!(&[0, 1, 2], loaded.in_neighbors(1));
+        assert_eq!(&[0, 2], loaded.nodes_by_label(&"a".to_string()));
+        assert_eq!(1, loaded.typed_multiplicity(0, 1, "KNOWS"));
+        assert_eq!(Some(&Value::Int(42)), loaded.property(0, "age"));
+        assert_eq!(Some(1), loaded.to_internal_id(20));
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a".to_string())
+            .add_node(1, "b".to_string())
+            .add_relationship(0, 1)
+            .build();
+        assert_eq!(dual_iso(&graph, &pattern), dual_iso(&loaded, &pattern));
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a".to_string())
+            .add_node(1, "b".to_string())
+            .add_relationship(0, 1)
+            .build();
+        let mut bytes = vec![];
+        write_snapshot(&graph, &mut bytes).unwrap();
+
+        let error = read_snapshot(&bytes[..bytes.len() - 4]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+        let error = read_snapshot(&b"DISNAP00"[..]).unwrap_err();
+        assert_eq!("Invalid snapshot, unknown format.", error.to_string());
+
+        // the node count claims a third node
+        bytes[8] = 3;
+        let error = read_snapshot(bytes.as_slice()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn test_decode_from_slice() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a".to_string())
+            .add_node(1, "b".to_string())
+            .add_relationship(0, 1)
+            .add_relationship(1, 1)
+            .build();
+        let mut bytes = vec![];
+        write_snapshot(&graph, &mut bytes).unwrap();
+
+        assert_eq!(graph, decode_snapshot(&mut bytes.as_slice()).unwrap());
+        let error = decode_snapshot(&mut &bytes[..bytes.len() - 4]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+        // the length of the label bucket of "a" exceeds the snapshot
+        let mut invalid_length = bytes.clone();
+        invalid_length[55] = 0xff;
+        let error = decode_snapshot(&mut invalid_length.as_slice()).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+    }
+
+    #[test]
+    fn test_inconsistent_counts_and_buckets() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a".to_string())
+            .add_node(1, "a".to_string())
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .build();
+        let mut bytes = vec![];
+        write_snapshot(&graph, &mut bytes).unwrap();
+
+        // the raw relationship count is below the relationship count
+        let mut raw_count = bytes.clone();
+        raw_count[24] = 1;
+        let error = read_snapshot(raw_count.as_slice()).unwrap_err();
+        assert_eq!(
+            "Invalid snapshot, inconsistent relationship count.",
+            error.to_string()
+        );
+
+        // the bucket of "a" lists its nodes as [1, 0]
+        let mut unsorted = bytes.clone();
+        unsorted[57] = 1;
+        unsorted[65] = 0;
+        let error = read_snapshot(unsorted.as_slice()).unwrap_err();
+        assert_eq!(
+            "Invalid snapshot, unsorted label bucket.",
+            error.to_string()
+        );
+        let error = decode_snapshot(&mut unsorted.as_slice()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    struct Version(u8);
+
+    impl fmt::Display for Version {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "x")
+        }
+    }
+
+    #[test]
+    fn test_labels_with_the_same_string() {
+        let graph = GraphBuilder::new()
+            .add_node(0, Version(0))
+            .add_node(1, Version(1))
+            .add_relationship(0, 1)
+            .build();
+        let mut bytes = vec![];
+        write_snapshot(&graph, &mut bytes).unwrap();
+        let loaded = read_snapshot(bytes.as_slice()).unwrap();
+        assert_eq!(&[0, 1], loaded.nodes_by_label(&"x".to_string()));
+    }
+
+    // A snapshot of two nodes without relationship types, properties or
+    // external ids.
+    fn forge(
+        buckets: &[(&str, &[usize])],
+        relationship_count: usize,
+        adjacency_arrays: [&[usize]; 4],
+    ) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for number in [2, relationship_count, relationship_count, buckets.len()] {
+            write_number(&mut bytes, number).unwrap();
+        }
+        let mut label_of = vec![0; 2];
+        for (idx, (label, nodes)) in buckets.iter().enumerate() {
+            write_string(&mut bytes, label).unwrap();
+            write_numbers(&mut bytes, nodes).unwrap();
+            for node_id in *nodes {
+                label_of[*node_id] = idx;
+            }
+        }
+        write_numbers(&mut bytes, &label_of).unwrap();
+        for array in adjacency_arrays {
+            write_numbers(&mut bytes, array).unwrap();
+        }
+        bytes.extend_from_slice(&[0; 24]);
+        bytes
+    }
+
+    #[test]
+    fn test_forged_snapshots() {
+        let empty: [&[usize]; 4] = [&[0, 0], &[0], &[0, 0], &[0]];
+        let valid = forge(&[("x", &[0, 1])], 0, empty);
+        assert_eq!(2, read_snapshot(valid.as_slice()).unwrap().node_count());
+
+        let duplicate_labels = forge(&[("x", &[0]), ("x", &[1])], 0, empty);
+        let error = read_snapshot(duplicate_labels.as_slice()).unwrap_err();
+        assert_eq!("Invalid snapshot, duplicate label.", error.to_string());
+
+        // the relationship from 0 to 1 is listed as incoming at 0 from 1
+        let reversed = forge(
+            &[("x", &[0, 1])],
+            1,
+            [&[1, 0], &[0, 1, 1], &[1, 0], &[0, 1, 1]],
+        );
+        let error = read_snapshot(reversed.as_slice()).unwrap_err();
+        assert_eq!(
+            "Invalid snapshot, incoming lists do not match outgoing lists.",
+            error.to_string()
+        );
+        let consistent = forge(
+            &[("x", &[0, 1])],
+            1,
+            [&[1, 0], &[0, 1, 1], &[0, 1], &[0, 1, 0]],
+        );
+        let graph = read_snapshot(consistent.as_slice()).unwrap();
+        assert_eq!(&[1], graph.neighbors(0));
+        assert_eq!(&[0], graph.in_neighbors(1));
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn test_map_snapshot() {
+        let graph = GraphBuilder::new()
+            .add_external_node(10, "a".to_string())
+            .add_node(1, "b".to_string())
+            .add_relationship_with_type(0, 1, "KNOWS")
+            .set_property(1, "age", 42)
+            .build();
+        let path =
+            std::env::temp_dir().join(format!("dual_iso_snapshot_{}.bin", std::process::id()));
+        write_snapshot(&graph, &mut File::create(&path).unwrap()).unwrap();
+
+        let mapped = map_snapshot(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mapped = mapped.unwrap();
+        assert_eq!(graph, mapped);
+        assert_eq!(Some(0), mapped.to_internal_id(10));
+        assert_eq!(Some(&Value::Int(42)), mapped.property(1, "age"));
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::MatchConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quota {
+    /// The search ran longer than the maximum duration.
+    Duration,
+    /// The search entered more branches than allowed.
+    Branches,
+    /// Branches copied more candidates than allowed.
+    AllocatedCandidates,
+}
+
+// Resources used by a single search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchStats {
+    elapsed: Duration,
+    branches: usize,
+    candidates: usize,
+    allocated_candidates: usize,
+    exceeded: Option<Quota>,
+    // (candidate index, candidate count) per depth where the search stopped
+    frontier: Vec<(usize, usize)>,
+    #[cfg(feature = "perf-counters")]
+    intersections: usize,
+    #[cfg(feature = "perf-counters")]
+    scanned_elements: usize,
+    #[cfg(feature = "perf-counters")]
+    binary_searches: usize,
+}
+
+impl MatchStats {
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    // number of candidate assignments the search tried
+    pub fn branches(&self) -> usize {
+        self.branches
+    }
+
+    // number of candidates after the simulation that precedes the search
+    pub fn candidates(&self) -> usize {
+        self.candidates
+    }
+
+    // number of candidates copied into the candidate sets of branches, which
+    // dominates the memory allocated by the search
+    pub fn allocated_candidates(&self) -> usize {
+        self.allocated_candidates
+    }
+
+    // The quota that stopped the search, whose matches are then incomplete.
+    pub fn exceeded(&self) -> Option<Quota> {
+        self.exceeded
+    }
+
+    // Estimated fraction of the search that was explored, 1.0 unless a quota
+    // stopped it. The estimate assumes that the branches of a search step are
+    // equally large, i.e. each branch weighs 1 / the product of the candidate
+    // counts along its path. For disconnected patterns, it covers the search
+    // of the component that was stopped.
+    pub fn explored(&self) -> f64 {
+        if self.exceeded.is_none() {
+            return 1.0;
+        }
+        let (mut explored, mut weight) = (0.0, 1.0);
+        for (idx, count) in &self.frontier {
+            weight /= *count as f64;
+            explored += weight * *idx as f64;
+        }
+        explored
+    }
+
+    // number of times candidates were intersected with an adjacency list,
+    // e.g. to check whether a candidate still has the required relationships
+    #[cfg(feature = "perf-counters")]
+    pub fn intersections(&self) -> usize {
+        self.intersections
+    }
+
+    // number of candidates and adjacency list entries the intersections visited
+    #[cfg(feature = "perf-counters")]
+    pub fn scanned_elements(&self) -> usize {
+        self.scanned_elements
+    }
+
+    // number of lookups in sorted adjacency lists or candidates
+    #[cfg(feature = "perf-counters")]
+    pub fn binary_searches(&self) -> usize {
+        self.binary_searches
+    }
+}
+
+// Counters shared by all branches of a search, see `MatchConfig::accounted`.
+#[derive(Debug)]
+pub(crate) struct Accounting {
+    started: Instant,
+    branches: AtomicUsize,
+    candidates: AtomicUsize,
+    allocated_candidates: AtomicUsize,
+    // 0 if no quota has been exceeded, otherwise 1 + the index of the quota
+    exceeded: AtomicUsize,
+    // depth -> (candidate index, candidate count) of the first stopped search
+    frontier: Mutex<BTreeMap<usize, (usize, usize)>>,
+    #[cfg(feature = "perf-counters")]
+    intersections: AtomicUsize,
+    #[cfg(feature = "perf-counters")]
+    scanned_elements: AtomicUsize,
+    #[cfg(feature = "perf-counters")]
+    binary_searches: AtomicUsize,
+}
+
+const QUOTAS: [Quota; 3] = [Quota::Duration, Quota::Branches, Quota::AllocatedCandidates];
+
+impl Accounting {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Accounting {
+            started: Instant::now(),
+            branches: AtomicUsize::new(0),
+            candidates: AtomicUsize::new(0),
+            allocated_candidates: AtomicUsize::new(0),
+            exceeded: AtomicUsize::new(0),
+            frontier: Mutex::new(BTreeMap::new()),
+            #[cfg(feature = "perf-counters")]
+            intersections: AtomicUsize::new(0),
+            #[cfg(feature = "perf-counters")]
+            scanned_elements: AtomicUsize::new(0),
+            #[cfg(feature = "perf-counters")]
+            binary_searches: AtomicUsize::new(0),
+        })
+    }
+
+    pub(crate) fn add_candidates(&self, count: usize) {
+        self.candidates.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_allocated_candidates(&self, count: usize) {
+        self.allocated_candidates
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "perf-counters")]
+    pub(crate) fn add_intersection(&self, scanned_elements: usize, binary_searches: usize) {
+        self.intersections.fetch_add(1, Ordering::Relaxed);
+        self.scanned_elements
+            .fetch_add(scanned_elements, Ordering::Relaxed);
+        self.binary_searches
+            .fetch_add(binary_searches, Ordering::Relaxed);
+    }
+
+    // Records where a search step stopped because of a quota. Searches that
+    // start after the quota is exceeded stop at depth 0, which is recorded
+    // already, so the frontier is the one of the first stopped search.
+    pub(crate) fn stop_branch(&self, depth: usize, idx: usize, count: usize) {
+        if self.exceeded.load(Ordering::Relaxed) > 0 {
+            self.frontier
+                .lock()
+                .unwrap()
+                .entry(depth)
+                .or_insert((idx, count));
+        }
+    }
+
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed) > 0
+    }
+
+    // Counts a branch and returns false if the search has to stop instead.
+    pub(crate) fn enter_branch(&self, config: &MatchConfig) -> bool {
+        if self.exceeded.load(Ordering::Relaxed) > 0 {
+            return false;
+        }
+        let exceeded = if config
+            .max_duration
+            .is_some_and(|max_duration| self.started.elapsed() > max_duration)
+        {
+            Quota::Duration
+        } else if config
+            .max_branches
+            .is_some_and(|max_branches| self.branches.load(Ordering::Relaxed) >= max_branches)
+        {
+            Quota::Branches
+        } else if config
+            .max_allocated_candidates
+            .is_some_and(|max_allocated| {
+                self.allocated_candidates.load(Ordering::Relaxed) > max_allocated
+            })
+        {
+            Quota::AllocatedCandidates
+        } else {
+            self.branches.fetch_add(1, Ordering::Relaxed);
+            return true;
+        };
+        let idx = QUOTAS.iter().position(|quota| *quota == exceeded).unwrap();
+        self.exceeded.store(idx + 1, Ordering::Relaxed);
+        false
+    }
+
+    pub(crate) fn stats(&self) -> MatchStats {
+        MatchStats {
+            elapsed: self.started.elapsed(),
+            branches: self.branches.load(Ordering::Relaxed),
+            candidates: self.candidates.load(Ordering::Relaxed),
+            allocated_candidates: self.allocated_candidates.load(Ordering::Relaxed),
+            exceeded: match self.exceeded.load(Ordering::Relaxed) {
+                0 => None,
+                idx => Some(QUOTAS[idx - 1]),
+            },
+            frontier: self.frontier.lock().unwrap().values().copied().collect(),
+            #[cfg(feature = "perf-counters")]
+            intersections: self.intersections.load(Ordering::Relaxed),
+            #[cfg(feature = "perf-counters")]
+            scanned_elements: self.scanned_elements.load(Ordering::Relaxed),
+            #[cfg(feature = "perf-counters")]
+            binary_searches: self.binary_searches.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::format::{parse_directive, Directive};
+use crate::graph::{reverse_lists, RawGraph};
+use crate::{FormatError, Graph};
+
+const DEFAULT_BUFFER_SIZE: usize = 1 << 20;
+
+// the most runs merged at once, which bounds the open spill files
+const MERGE_FAN_IN: usize = 64;
+
+// distinguishes the spill files of builders within the same process
+static NEXT_BUILDER_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Builds a graph from input in the text format of `read_graph`, which is read
+// line by line. At most `buffer_size` relationships are kept in memory, larger
+// inputs are spilled to disk in sorted runs. Every 64 runs of the same level
+// are merged into one run of the next level, and `build` merges at most 64
+// runs at once, so merging holds one relationship and one open file per run.
+// `build` writes the merged runs directly into the adjacency lists of the
+// graph.
+pub struct GraphStreamBuilder {
+    labels: Vec<String>,
+    buffer: Vec<(usize, usize)>,
+    buffer_size: usize,
+    spill_directory: PathBuf,
+    // (level, path), where a run of level `l` merges `MERGE_FAN_IN^l` spills
+    runs: Vec<(usize, PathBuf)>,
+    spills: usize,
+    next_run: usize,
+    // the most relationships held in memory at once
+    peak_buffered: usize,
+    id: usize,
+}
+
+impl Default for GraphStreamBuilder {
+    fn default() -> Self {
+        GraphStreamBuilder {
+            labels: vec![],
+            buffer: vec![],
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            spill_directory: std::env::temp_dir(),
+            runs: vec![],
+            spills: 0,
+            next_run: 0,
+            peak_buffered: 0,
+            id: NEXT_BUILDER_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl GraphStreamBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The number of relationships kept in memory before spilling to disk, at
+    // least 2 so that merging runs makes progress.
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut Self {
+        self.buffer_size = buffer_size.max(2);
+        self
+    }
+
+    pub fn spill_directory<P: Into<PathBuf>>(&mut self, directory: P) -> &mut Self {
+        self.spill_directory = directory.into();
+        self
+    }
+
+    // Number of times the buffer has been spilled to disk so far.
+    pub fn spilled_runs(&self) -> usize {
+        self.spills
+    }
+
+    // Reads the input, may be called repeatedly to read graphs split across
+    // several inputs. Line numbers in errors refer to the given input.
+    pub fn ingest<R: BufRead>(&mut self, mut reader: R) -> Result<&mut Self, FormatError> {
+        let mut line = String::new();
+        let mut line_number = 0;
+        loop {
+            line.clear();
+            line_number += 1;
+            let error = |e: io::Error| FormatError::new(e.to_string(), line_number);
+            if reader.read_line(&mut line).map_err(error)? == 0 {
+                return Ok(self);
+            }
+            match parse_directive(&line, line_number, self.labels.len())? {
+                Some(Directive::Node(id, label)) if id == self.labels.len() => {
+                    self.labels.push(label.to_string())
+                }
+                Some(Directive::Node(_, _)) => {}
+                Some(Directive::Relationship(start, end)) => {
+                    self.buffer.push((start, end));
+                    self.peak_buffered = self.peak_buffered.max(self.buffer.len());
+                    if self.buffer.len() >= self.buffer_size {
+                        self.spill().map_err(error)?;
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    // Merges the spilled runs and the buffer into the graph and removes the
+    // spill files. The merged relationships are read twice, once to count
+    // the degrees and once to fill the adjacency lists, so only the lists of
+    // the graph itself grow with the input.
+    pub fn build(&mut self) -> io::Result<Graph<String>> {
+        if !self.runs.is_empty() && !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        while self.runs.len() > MERGE_FAN_IN {
+            self.compact(MERGE_FAN_IN)?;
+        }
+        self.buffer.sort_unstable();
+        let node_count = self.labels.len();
+
+        // first pass: count the degrees
+        let mut degrees = vec![0; node_count];
+        self.merge(0, |(start_node, _)| {
+            degrees[start_node] += 1;
+            Ok(())
+        })?;
+
+        // 0-degree nodes point to position 0, which stores the 0-degree, all
+        // other lists start with their degree
+        let mut offsets = vec![0; node_count];
+        let mut size = 1;
+        for (offset, degree) in offsets.iter_mut().zip(&degrees) {
+            if *degree > 0 {
+                *offset = size;
+                size += degree + 1;
+            }
+        }
+        let relationship_count = degrees.iter().sum();
+        drop(degrees);
+
+        // second pass: the relationships arrive in end node order per start
+        // node, so each one is appended to its list
+        let mut lists = vec![0; size];
+        self.merge(0, |(start_node, end_node)| {
+            let offset = offsets[start_node];
+            lists[offset] += 1;
+            let position = offset + lists[offset];
+            lists[position] = end_node;
+            Ok(())
+        })?;
+        self.buffer = vec![];
+        self.remove_runs(0)?;
+
+        let (in_offsets, in_lists) = reverse_lists(&offsets, &lists);
+        let node_labels = std::mem::take(&mut self.labels)
+            .into_iter()
+            .map(Rc::new)
+            .enumerate()
+            .collect::<HashMap<_, _>>();
+        Ok(Graph::from_raw(RawGraph {
+            relationship_count,
+            raw_relationship_count: relationship_count,
+            node_labels,
+            label_buckets: None,
+            offsets,
+            lists,
+            in_offsets,
+            in_lists,
+            relationship_types: HashMap::new(),
+            properties: HashMap::new(),
+            external_ids: HashMap::new(),
+        }))
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.buffer.sort_unstable();
+        let path = self.next_run_path();
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.runs.push((0, path));
+        for relationship in self.buffer.drain(..) {
+            write_relationship(&mut writer, relationship)?;
+        }
+        writer.flush()?;
+        self.spills += 1;
+        // runs are ordered by descending level, so the runs of the lowest
+        // level are the last ones
+        loop {
+            let level = self.runs.last().map_or(0, |(level, _)| *level);
+            let count = self
+                .runs
+                .iter()
+                .rev()
+                .take_while(|(other, _)| *other == level)
+                .count();
+            if count < MERGE_FAN_IN {
+                return Ok(());
+            }
+            self.compact(count)?;
+        }
+    }
+
+    // Merges the last `count` runs into a single one of the next level.
+    fn compact(&mut self, count: usize) -> io::Result<()> {
+        let from = self.runs.len() - count;
+        let level = self.runs[from].0 + 1;
+        let path = self.next_run_path();
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.merge(from, |relationship| {
+            write_relationship(&mut writer, relationship)
+        })?;
+        writer.flush()?;
+        self.remove_runs(from)?;
+        self.runs.push((level, path));
+        Ok(())
+    }
+
+    // k-way merge of the runs from `from` on and the sorted buffer, which
+    // holds one relationship per run in memory
+    fn merge<F>(&mut self, from: usize, mut emit: F) -> io::Result<()>
+    where
+        F: FnMut((usize, usize)) -> io::Result<()>,
+    {
+        let mut sources = self.runs[from..]
+            .iter()
+            .map(|(_, path)| Ok(Run::File(BufReader::new(File::open(path)?))))
+            .collect::<io::Result<Vec<_>>>()?;
+        sources.push(Run::Memory(self.buffer.iter()));
+
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        for (idx, source) in sources.iter_mut().enumerate() {
+            if let Some(relationship) = source.next()? {
+                heap.push(Reverse((relationship, idx)));
+            }
+        }
+        self.peak_buffered = self.peak_buffered.max(self.buffer.len() + heap.len());
+        while let Some(Reverse((relationship, idx))) = heap.pop() {
+            emit(relationship)?;
+            if let Some(relationship) = sources[idx].next()? {
+                heap.push(Reverse((relationship, idx)));
+            }
+        }
+        Ok(())
+    }
+
+    fn next_run_path(&mut self) -> PathBuf {
+        self.next_run += 1;
+        self.spill_directory.join(format!(
+            "dual_iso_spill_{}_{}_{}",
+            std::process::id(),
+            self.id,
+            self.next_run
+        ))
+    }
+
+    fn remove_runs(&mut self, from: usize) -> io::Result<()> {
+        for (_, path) in self.runs.drain(from..) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for GraphStreamBuilder {
+    fn drop(&mut self) {
+        let _ = self.remove_runs(0);
+    }
+}
+
+fn write_relationship<W: Write>(writer: &mut W, (start, end): (usize, usize)) -> io::Result<()> {
+    writer.write_all(&(start as u64).to_le_bytes())?;
+    writer.write_all(&(end as u64).to_le_bytes())
+}
+
+enum Run<'a> {
+    File(BufReader<File>),
+    Memory(std::slice::Iter<'a, (usize, usize)>),
+}
+
+impl Run<'_> {
+    fn next(&mut self) -> io::Result<Option<(usize, usize)>> {
+        match self {
+            Run::File(reader) => {
+                let (mut start, mut end) = ([0; 8], [0; 8]);
+                match reader
+                    .read_exact(&mut start)
+                    .and_then(|_| reader.read_exact(&mut end))
+                {
+                    Ok(()) => Ok(Some((
+                        u64::from_le_bytes(start) as usize,
+                        u64::from_le_bytes(end) as usize,
+                    ))),
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            Run::Memory(relationships) => Ok(relationships.next().copied()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_graph;
+
+    use super::*;
+
+    const SOURCE: &str = "node 0 a\nnode 1 b\nnode 2 a\n\
+                          rel 2 1\nrel 0 1\nrel 1 2\nrel 0 2\nrel 0 1\n";
+
+    #[test]
+    fn test_ingest() {
+        let graph = GraphStreamBuilder::new()
+            .ingest(SOURCE.as_bytes())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(parse_graph(SOURCE).unwrap(), graph);
+    }
+
+    #[test]
+    fn test_ingest_with_spilling() {
+        let directory =
+            std::env::temp_dir().join(format!("dual_iso_stream_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let mut builder = GraphStreamBuilder::new();
+        builder.buffer_size(2).spill_directory(&directory);
+        let (nodes, relationships) = SOURCE.split_at(SOURCE.find("rel").unwrap());
+        builder.ingest(nodes.as_bytes()).unwrap();
+        builder.ingest(relationships.as_bytes()).unwrap();
+        assert_eq!(2, builder.spilled_runs());
+        let graph = builder.build().unwrap();
+
+        assert_eq!(parse_graph(SOURCE).unwrap(), graph);
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_bounded_memory() {
+        let directory =
+            std::env::temp_dir().join(format!("dual_iso_stream_bounded_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut source = (0..50)
+            .map(|node_id| format!("node {} a\n", node_id))
+            .collect::<String>();
+        for idx in 0..500 {
+            source.push_str(&format!("rel {} {}\n", (idx * 7) % 50, (idx * 13) % 50));
+        }
+
+        let mut builder = GraphStreamBuilder::new();
+        builder.buffer_size(8).spill_directory(&directory);
+        let graph = builder.ingest(source.as_bytes()).unwrap().build().unwrap();
+
+        assert_eq!(parse_graph(&source).unwrap(), graph);
+        assert_eq!(500, graph.relationship_count());
+        assert!(builder.spilled_runs() > 8);
+        assert!(
+            builder.peak_buffered <= MERGE_FAN_IN,
+            "{}",
+            builder.peak_buffered
+        );
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_merge_fan_in() {
+        let directory =
+            std::env::temp_dir().join(format!("dual_iso_stream_fan_in_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut source = (0..100)
+            .map(|node_id| format!("node {} a\n", node_id))
+            .collect::<String>();
+        // one run less than the fan-in on each of the first two levels
+        let relationship_count = 2 * (MERGE_FAN_IN - 1) * (MERGE_FAN_IN + 1);
+        for idx in 0..relationship_count {
+            source.push_str(&format!("rel {} {}\n", (idx * 7) % 100, (idx * 13) % 100));
+        }
+
+        let mut builder = GraphStreamBuilder::new();
+        builder.buffer_size(2).spill_directory(&directory);
+        builder.ingest(source.as_bytes()).unwrap();
+        assert_eq!(
+            (MERGE_FAN_IN - 1) * (MERGE_FAN_IN + 1),
+            builder.spilled_runs()
+        );
+        assert_eq!(2 * (MERGE_FAN_IN - 1), builder.runs.len());
+        assert_eq!(
+            builder.runs.len(),
+            fs::read_dir(&directory).unwrap().count()
+        );
+        let graph = builder.build().unwrap();
+
+        assert_eq!(parse_graph(&source).unwrap(), graph);
+        assert!(
+            builder.peak_buffered <= MERGE_FAN_IN,
+            "{}",
+            builder.peak_buffered
+        );
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_ingest_error() {
+        let mut builder = GraphStreamBuilder::new();
+        builder.ingest("node 0 a\n".as_bytes()).unwrap();
+        let error = builder
+            .ingest("rel 0 0\nrel 0 1\n".as_bytes())
+            .err()
+            .unwrap();
+
+        assert_eq!("Node 1 has not been declared in line 2.", error.to_string());
+    }
+}
@@ -0,0 +1,273 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// the path that stands for stdin or stdout
+const STANDARD_STREAM: &str = "-";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+// magic bytes of compressed inputs, the ones without an enabled feature are
+// rejected with a clear error instead of a parse error
+const COMPRESSIONS: [(Compression, &str, &[u8]); 3] = [
+    (Compression::Gzip, "gzip", &[0x1f, 0x8b]),
+    (Compression::Zstd, "zstd", &[0x28, 0xb5, 0x2f, 0xfd]),
+    (Compression::Bzip2, "bzip2", b"BZh"),
+];
+
+// What the file based loaders read from, e.g. `read_graph(Input::open(path)?)`.
+// The path "-" reads stdin, so graphs can be piped in without temporary files.
+// Compressed inputs are decompressed with the `gzip` and `zstd` features.
+pub struct Input {
+    reader: Box<dyn BufRead>,
+}
+
+impl Input {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Input> {
+        let path = path.as_ref();
+        if path == Path::new(STANDARD_STREAM) {
+            Input::from_reader(io::stdin())
+        } else {
+            Input::from_reader(File::open(path)?)
+        }
+    }
+
+    // Buffers the reader and detects compressed input by its first bytes.
+    pub fn from_reader<R: Read + 'static>(reader: R) -> io::Result<Input> {
+        let mut reader = BufReader::new(reader);
+        let head = reader.fill_buf()?;
+        let compression = COMPRESSIONS
+            .iter()
+            .find(|(_, _, magic)| head.starts_with(magic));
+        let reader: Box<dyn BufRead> = match compression {
+            None => Box::new(reader),
+            #[cfg(feature = "gzip")]
+            Some((Compression::Gzip, _, _)) => {
+                Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))
+            }
+            #[cfg(feature = "zstd")]
+            Some((Compression::Zstd, _, _)) => {
+                Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?))
+            }
+            Some((_, name, _)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Input is {} compressed, decompress it first.", name),
+                ))
+            }
+        };
+        Ok(Input { reader })
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.reader.consume(amount)
+    }
+}
+
+enum Writer {
+    Plain(BufWriter<Box<dyn Write>>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<BufWriter<Box<dyn Write>>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, BufWriter<Box<dyn Write>>>),
+}
+
+// What the writers write to, e.g. `write_snapshot(&graph, &mut Output::create(path)?)`.
+// The path "-" writes to stdout. With the `gzip` and `zstd` features, paths
+// ending in `.gz` and `.zst` are compressed. Output is buffered, call `finish`
+// to see write errors that dropping the output would ignore and to complete
+// compressed output.
+pub struct Output {
+    writer: Writer,
+}
+
+impl Output {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Output> {
+        let path = path.as_ref();
+        let writer: Box<dyn Write> = if path == Path::new(STANDARD_STREAM) {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        let writer = BufWriter::new(writer);
+        let writer = match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "gzip")]
+            Some("gz") => Writer::Gzip(gzip_encoder(writer)),
+            #[cfg(feature = "zstd")]
+            Some("zst") => Writer::Zstd(zstd::Encoder::new(writer, 0)?),
+            _ => Writer::Plain(writer),
+        };
+        Ok(Output { writer })
+    }
+
+    pub fn from_writer<W: Write + 'static>(writer: W) -> Output {
+        Output {
+            writer: Writer::Plain(buffered(writer)),
+        }
+    }
+
+    // Gzip compressed output, e.g. to stdout.
+    #[cfg(feature = "gzip")]
+    pub fn gzip<W: Write + 'static>(writer: W) -> Output {
+        Output {
+            writer: Writer::Gzip(gzip_encoder(buffered(writer))),
+        }
+    }
+
+    // Zstd compressed output, e.g. to stdout.
+    #[cfg(feature = "zstd")]
+    pub fn zstd<W: Write + 'static>(writer: W) -> io::Result<Output> {
+        Ok(Output {
+            writer: Writer::Zstd(zstd::Encoder::new(buffered(writer), 0)?),
+        })
+    }
+
+    // Flushes the output and writes the end of compressed streams.
+    pub fn finish(self) -> io::Result<()> {
+        match self.writer {
+            Writer::Plain(mut writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            Writer::Gzip(writer) => writer.finish()?.flush(),
+            #[cfg(feature = "zstd")]
+            Writer::Zstd(writer) => writer.finish()?.flush(),
+        }
+    }
+}
+
+fn buffered<W: Write + 'static>(writer: W) -> BufWriter<Box<dyn Write>> {
+    BufWriter::new(Box::new(writer))
+}
+
+#[cfg(feature = "gzip")]
+fn gzip_encoder(
+    writer: BufWriter<Box<dyn Write>>,
+) -> flate2::write::GzEncoder<BufWriter<Box<dyn Write>>> {
+    flate2::write::GzEncoder::new(writer, flate2::Compression::default())
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.writer {
+            Writer::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "gzip")]
+            Writer::Gzip(writer) => writer.write(buf),
+            #[cfg(feature = "zstd")]
+            Writer::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Writer::Plain(writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            Writer::Gzip(writer) => writer.flush(),
+            #[cfg(feature = "zstd")]
+            Writer::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{read_graph, read_snapshot, write_graph, write_snapshot, GraphBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_files() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a".to_string())
+            .add_node(1, "b".to_string())
+            .add_relationship(0, 1)
+            .build();
+        let directory = std::env::temp_dir();
+        let text = directory.join(format!("dual_iso_streams_{}.txt", std::process::id()));
+        let snapshot = directory.join(format!("dual_iso_streams_{}.bin", std::process::id()));
+
+        let mut output = Output::create(&text).unwrap();
+        write_graph(&graph, &mut output).unwrap();
+        output.finish().unwrap();
+        let mut output = Output::create(&snapshot).unwrap();
+        write_snapshot(&graph, &mut output).unwrap();
+        output.finish().unwrap();
+
+        let from_text = read_graph(Input::open(&text).unwrap()).unwrap();
+        let from_snapshot = read_snapshot(Input::open(&snapshot).unwrap()).unwrap();
+        std::fs::remove_file(text).unwrap();
+        std::fs::remove_file(snapshot).unwrap();
+        assert!(graph.edges().eq(from_text.edges()));
+        assert!(graph.edges().eq(from_snapshot.edges()));
+    }
+
+    #[test]
+    fn test_compressed_input() {
+        let error = Input::from_reader(&b"BZh91AY"[..]).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!(
+            "Input is bzip2 compressed, decompress it first.",
+            error.to_string()
+        );
+        assert!(Input::from_reader(&b""[..]).is_ok());
+        if cfg!(not(feature = "gzip")) {
+            assert!(Input::from_reader(&[0x1f, 0x8b, 0x08][..]).is_err());
+        }
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    fn compressed_round_trip(extension: &str) {
+        let graph = crate::parse_graph("node 0 a\nnode 1 b\nrel 0 1\nrel 1 1\n").unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "dual_iso_streams_{}.bin.{}",
+            std::process::id(),
+            extension
+        ));
+        let mut output = Output::create(&path).unwrap();
+        write_snapshot(&graph, &mut output).unwrap();
+        output.finish().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(!bytes.starts_with(b"DISNAP01"));
+        let reloaded = read_snapshot(Input::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(graph, reloaded);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        compressed_round_trip("gz");
+
+        // concatenated members as written by `cat a.gz b.gz`
+        let mut bytes = vec![];
+        for line in ["node 0 a\n", "node 1 b\n"] {
+            let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+            encoder.write_all(line.as_bytes()).unwrap();
+            bytes.extend(encoder.finish().unwrap());
+        }
+        let input = Input::from_reader(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(2, read_graph(input).unwrap().node_count());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        compressed_round_trip("zst");
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::candidates::Candidates;
+use crate::dual_iso::{dual_iso_with_candidates, NestedVec};
+use crate::query::{Constraint, Query};
+use crate::{Graph, MatchConfig, SelfLoops};
+
+// A coarsened view of a graph that merges structurally redundant nodes. Nodes
+// share a supernode if they have the same label and their neighbors lie in
+// the same supernodes, which is refined until it is stable. Supernodes are
+// connected if any of their members are.
+//
+// Matching first simulates the pattern on the summary, which is small for
+// graphs with many interchangeable nodes, and then only searches the members
+// of the supernodes that survived.
+pub struct GraphSummary<T> {
+    summary: Graph<T>,
+    members: Vec<Vec<usize>>,
+}
+
+impl<T> GraphSummary<T>
+where
+    T: Eq + Hash,
+{
+    pub fn new(graph: &Graph<T>) -> Self {
+        // start with one supernode per label
+        let mut label_ids: HashMap<&T, usize> = HashMap::new();
+        let mut supernodes = graph
+            .node_ids()
+            .map(|node_id| {
+                let next_id = label_ids.len();
+                *label_ids
+                    .entry(graph.node_label(node_id))
+                    .or_insert(next_id)
+            })
+            .collect::<Vec<_>>();
+        let mut count = label_ids.len();
+        // split supernodes by the supernodes of their out and in neighbors
+        // until no supernode splits anymore
+        loop {
+            let mut signatures = HashMap::new();
+            let refined = graph
+                .node_ids()
+                .map(|node_id| {
+                    let neighbor_supernodes = |neighbors: &[usize]| {
+                        let mut neighbors = neighbors
+                            .iter()
+                            .map(|neighbor| supernodes[*neighbor])
+                            .collect::<Vec<_>>();
+                        neighbors.sort_unstable();
+                        neighbors.dedup();
+                        neighbors
+                    };
+                    let signature = (
+                        supernodes[node_id],
+                        neighbor_supernodes(graph.neighbors(node_id)),
+                        neighbor_supernodes(graph.in_neighbors(node_id)),
+                    );
+                    let next_id = signatures.len();
+                    *signatures.entry(signature).or_insert(next_id)
+                })
+                .collect::<Vec<_>>();
+            supernodes = refined;
+            if signatures.len() == count {
+                break;
+            }
+            count = signatures.len();
+        }
+
+        let mut members = vec![vec![]; count];
+        for (node_id, supernode) in supernodes.iter().enumerate() {
+            members[*supernode].push(node_id);
+        }
+        let labels = members
+            .iter()
+            .map(|nodes| graph.shared_label(nodes[0]))
+            .collect();
+        let mut relationships = graph
+            .edges()
+            .map(|(start_node, end_node)| (supernodes[start_node], supernodes[end_node]))
+            .collect::<Vec<_>>();
+        relationships.sort_unstable();
+        relationships.dedup();
+
+        GraphSummary {
+            summary: Graph::from_parts(labels, &relationships),
+            members,
+        }
+    }
+
+    // The summary graph, where node `i` is supernode `i`.
+    pub fn graph(&self) -> &Graph<T> {
+        &self.summary
+    }
+
+    pub fn members(&self, supernode: usize) -> &[usize] {
+        &self.members[supernode]
+    }
+
+    // The graph must be the one the summary was built from.
+    pub fn dual_iso(
+        &self,
+        graph: &Graph<T>,
+        pattern: &Graph<T>,
+        config: &MatchConfig,
+    ) -> NestedVec {
+        let query = Query::new(pattern);
+        let supernodes = self.simulate(&query, config);
+        let candidates = supernodes
+            .iter()
+            .map(|supernodes| {
+                let mut candidates = supernodes
+                    .iter()
+                    .flat_map(|supernode| self.members[*supernode].iter().copied())
+                    .collect::<Vec<_>>();
+                candidates.sort_unstable();
+                Candidates::from(candidates)
+            })
+            .collect();
+        dual_iso_with_candidates(graph, &query, config, candidates)
+    }
+
+    // Candidate supernodes per pattern node. Unlike the simulation on the
+    // graph, adjacent pattern nodes may share a supernode, since supernodes
+    // relate to themselves if their members do. Self-loops are skipped like in
+    // the simulation on the graph if the config ignores them.
+    fn simulate(&self, query: &Query<T>, config: &MatchConfig) -> NestedVec {
+        let mut candidates = (0..query.node_count())
+            .map(|u_p| self.summary.nodes_by_label(query.label(u_p)).to_vec())
+            .collect::<Vec<_>>();
+        let mut is_updated = true;
+        while is_updated {
+            is_updated = false;
+            for u_p in 0..query.node_count() {
+                for constraint in query.constraints(u_p) {
+                    if constraint.target == u_p && config.self_loops == SelfLoops::Ignore {
+                        continue;
+                    }
+                    // the summary has no relationship types, so typed
+                    // constraints are checked against all relationships
+                    let constraint = Constraint {
+                        rel_type: None,
+                        ..constraint.clone()
+                    };
+                    let v_p_candidates = candidates[constraint.target].clone();
+                    let count = candidates[u_p].len();
+                    candidates[u_p].retain(|s_u| {
+                        v_p_candidates
+                            .iter()
+                            .any(|s_v| constraint.count(&self.summary, *s_u, *s_v) > 0)
+                    });
+                    is_updated |= candidates[u_p].len() < count;
+                }
+            }
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dual_iso, dual_iso_with_config, GraphBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        // two components with a path a -> b -> c and one with only a -> c
+        let mut builder = GraphBuilder::new();
+        for (offset, labels) in [
+            (0, ["a", "b", "c"]),
+            (3, ["a", "b", "c"]),
+            (6, ["a", "c", "c"]),
+        ] {
+            for (idx, label) in labels.iter().enumerate() {
+                builder.add_node(offset + idx, *label);
+            }
+            builder.add_relationship(offset, offset + 1);
+            builder.add_relationship(offset + 1, offset + 2);
+        }
+        let graph = builder.build();
+        let summary = GraphSummary::new(&graph);
+
+        // the first two components share their supernodes, the last one has
+        // an a without b neighbor and two kinds of c
+        assert_eq!(6, summary.graph().node_count());
+        assert_eq!(4, summary.graph().relationship_count());
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "c")
+            .add_relationship(0, 1)
+            .add_relationship(1, 2)
+            .build();
+        let query = Query::new(&pattern);
+        let supernodes = summary.simulate(&query, &MatchConfig::default());
+        // a and b only keep the supernodes of the first two components, c has
+        // no outgoing relationship to check
+        let members = |u_p: usize| {
+            let mut members = supernodes[u_p]
+                .iter()
+                .flat_map(|supernode| summary.members(*supernode).to_vec())
+                .collect::<Vec<_>>();
+            members.sort_unstable();
+            members
+        };
+        assert_eq!(vec![0, 3], members(0));
+        assert_eq!(vec![1, 4], members(1));
+        assert_eq!(vec![2, 5, 7, 8], members(2));
+
+        let mut expected = dual_iso(&graph, &pattern);
+        let mut matches = summary.dual_iso(&graph, &pattern, &MatchConfig::default());
+        expected.sort();
+        matches.sort();
+        assert_eq!(vec![vec![0, 1, 2], vec![3, 4, 5]], matches);
+        assert_eq!(expected, matches);
+    }
+
+    #[test]
+    fn test_summary_with_shared_supernode() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .add_relationship(1, 0)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "a")
+            .add_relationship(0, 1)
+            .build();
+
+        let summary = GraphSummary::new(&graph);
+        assert_eq!(1, summary.graph().node_count());
+        assert_eq!(
+            vec![vec![0, 1], vec![1, 0]],
+            summary.dual_iso(&graph, &pattern, &MatchConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_summary_of_connected_graph() {
+        // a star whose leaves are interchangeable and one leaf with a c
+        let mut builder = GraphBuilder::new();
+        builder.add_node(0, "a");
+        for leaf in 1..=5 {
+            builder.add_node(leaf, "b").add_relationship(0, leaf);
+        }
+        builder.add_node(6, "c").add_relationship(5, 6);
+        let graph = builder.build();
+        let summary = GraphSummary::new(&graph);
+        assert_eq!(4, summary.graph().node_count());
+
+        let pattern = GraphBuilder::new()
+            .add_node(0, "b")
+            .add_node(1, "c")
+            .add_relationship(0, 1)
+            .build();
+        let query = Query::new(&pattern);
+        let supernodes = summary.simulate(&query, &MatchConfig::default());
+        assert_eq!(&[5], summary.members(supernodes[0][0]));
+        assert_eq!(
+            vec![vec![5, 6]],
+            summary.dual_iso(&graph, &pattern, &MatchConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_summary_with_ignored_self_loops() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 1)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_relationship(0, 0)
+            .add_relationship(0, 1)
+            .build();
+
+        let summary = GraphSummary::new(&graph);
+        let mut config = MatchConfig::default();
+        config.self_loops(SelfLoops::Ignore);
+        assert_eq!(
+            dual_iso_with_config(&graph, &pattern, &config),
+            summary.dual_iso(&graph, &pattern, &config)
+        );
+        assert_eq!(
+            vec![vec![0, 1]],
+            summary.dual_iso(&graph, &pattern, &config)
+        );
+        assert!(summary
+            .dual_iso(&graph, &pattern, &MatchConfig::default())
+            .is_empty());
+    }
+}
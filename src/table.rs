@@ -0,0 +1,321 @@
+use std::hash::Hash;
+use std::io::{self, Write};
+
+#[cfg(feature = "polars")]
+use polars::prelude::{AnyValue, Column, DataFrame, NamedFrom, PolarsResult, Series};
+
+#[cfg(feature = "polars")]
+use crate::Value;
+use crate::{Graph, Pattern, PatternMatch};
+
+// Matches as rows with one column per named pattern node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchTable {
+    columns: Vec<String>,
+    pattern_nodes: Vec<usize>,
+    rows: Vec<Vec<Option<usize>>>,
+}
+
+impl MatchTable {
+    pub fn from_matches<T: Eq + Hash>(pattern: &Pattern<T>, matches: &[PatternMatch]) -> Self {
+        let (pattern_nodes, columns): (Vec<_>, Vec<_>) = (0..pattern.graph().node_count())
+            .filter_map(|node_id| {
+                pattern
+                    .node_name(node_id)
+                    .map(|name| (node_id, name.to_string()))
+            })
+            .unzip();
+        let rows = matches
+            .iter()
+            .map(|m| {
+                pattern_nodes
+                    .iter()
+                    .map(|node_id| m.node(*node_id))
+                    .collect()
+            })
+            .collect();
+        MatchTable {
+            columns,
+            pattern_nodes,
+            rows,
+        }
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    // the pattern node of each column
+    pub fn pattern_nodes(&self) -> &[usize] {
+        &self.pattern_nodes
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn row(&self, row: usize) -> &[Option<usize>] {
+        &self.rows[row]
+    }
+
+    pub fn get(&self, row: usize, column: &str) -> Option<usize> {
+        self.rows[row][self.column_index(column)]
+    }
+
+    pub fn column(&self, column: &str) -> impl Iterator<Item = Option<usize>> + '_ {
+        let idx = self.column_index(column);
+        self.rows.iter().map(move |row| row[idx])
+    }
+
+    pub fn label<'graph, T: Eq + Hash>(
+        &self,
+        graph: &'graph Graph<T>,
+        row: usize,
+        column: &str,
+    ) -> Option<&'graph T> {
+        self.get(row, column)
+            .map(|node_id| graph.node_label(node_id))
+    }
+
+    // Unbound nodes are written as empty fields.
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let header = self
+            .columns
+            .iter()
+            .map(|column| csv_field(column))
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", header.join(","))?;
+        for row in &self.rows {
+            let fields = row
+                .iter()
+                .map(|node_id| node_id.map_or_else(String::new, |node_id| node_id.to_string()))
+                .collect::<Vec<_>>();
+            writeln!(writer, "{}", fields.join(","))?;
+        }
+        Ok(())
+    }
+
+    // Writes an array with one object per row, unbound nodes are `null`.
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "[")?;
+        for (i, row) in self.rows.iter().enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{{")?;
+            for (j, (column, node_id)) in self.columns.iter().zip(row).enumerate() {
+                if j > 0 {
+                    write!(writer, ",")?;
+                }
+                match node_id {
+                    Some(node_id) => write!(writer, "{}:{}", json_string(column), node_id)?,
+                    None => write!(writer, "{}:null", json_string(column))?,
+                }
+            }
+            write!(writer, "}}")?;
+        }
+        writeln!(writer, "]")
+    }
+
+    fn column_index(&self, column: &str) -> usize {
+        self.columns
+            .iter()
+            .position(|c| c == column)
+            .unwrap_or_else(|| panic!("Unknown column '{}'.", column))
+    }
+}
+
+// Data frames for analyses of the matches, e.g. grouping the owners by car.
+#[cfg(feature = "polars")]
+impl MatchTable {
+    // One nullable `UInt64` column of node ids per column of the table.
+    pub fn to_data_frame(&self) -> PolarsResult<DataFrame> {
+        DataFrame::new_infer_height(self.node_id_columns())
+    }
+
+    // Appends a column named `column.key` per `(column, key)` with the property
+    // of the node bound to the column. Unbound nodes and nodes without the
+    // property are null, mixed value types are cast to a common type.
+    pub fn to_data_frame_with_properties<T: Eq + Hash>(
+        &self,
+        graph: &Graph<T>,
+        properties: &[(&str, &str)],
+    ) -> PolarsResult<DataFrame> {
+        let mut columns = self.node_id_columns();
+        for (column, key) in properties {
+            let values = self
+                .column(column)
+                .map(
+                    |node_id| match node_id.and_then(|node_id| graph.property(node_id, key)) {
+                        Some(Value::Int(value)) => AnyValue::Int64(*value),
+                        Some(Value::Float(value)) => AnyValue::Float64(*value),
+                        Some(Value::Str(value)) => AnyValue::StringOwned(value.as_str().into()),
+                        Some(Value::Bool(value)) => AnyValue::Boolean(*value),
+                        None => AnyValue::Null,
+                    },
+                )
+                .collect::<Vec<_>>();
+            let name = format!("{}.{}", column, key);
+            columns.push(Series::from_any_values(name.into(), &values, false)?.into());
+        }
+        DataFrame::new_infer_height(columns)
+    }
+
+    fn node_id_columns(&self) -> Vec<Column> {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(idx, column)| {
+                let node_ids = self
+                    .rows
+                    .iter()
+                    .map(|row| row[idx].map(|node_id| node_id as u64))
+                    .collect::<Vec<_>>();
+                Column::from(Series::new(column.as_str().into(), node_ids))
+            })
+            .collect()
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{match_pattern, GraphBuilder, MatchConfig};
+
+    use super::*;
+
+    fn table() -> (Graph<&'static str>, MatchTable) {
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "car")
+            .add_relationship(0, 2)
+            .build();
+
+        let mut pattern = Pattern::new(
+            GraphBuilder::new()
+                .add_node(0, "person")
+                .add_node(1, "car")
+                .add_relationship(0, 1)
+                .build(),
+        );
+        pattern
+            .name_node(0, "owner")
+            .name_node(1, "car")
+            .add_optional_block(&[1]);
+
+        let mut matches = match_pattern(&graph, &pattern, &MatchConfig::default());
+        matches.sort_by_key(|m| m.nodes().to_vec());
+
+        let table = MatchTable::from_matches(&pattern, &matches);
+        (graph, table)
+    }
+
+    #[test]
+    fn test_accessors() {
+        let (graph, table) = table();
+
+        assert_eq!(&["owner".to_string(), "car".to_string()], table.columns());
+        assert_eq!(2, table.row_count());
+        assert_eq!(Some(2), table.get(0, "car"));
+        assert_eq!(None, table.get(1, "car"));
+        assert_eq!(Some(&"car"), table.label(&graph, 0, "car"));
+        assert_eq!(
+            vec![Some(0), Some(1)],
+            table.column("owner").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown column 'city'.")]
+    fn test_unknown_column() {
+        let (_, table) = table();
+        let _ = table.get(0, "city");
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let (_, table) = table();
+        let mut out = vec![];
+        table.write_csv(&mut out).unwrap();
+
+        assert_eq!("owner,car\n0,2\n1,\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_write_json() {
+        let (_, table) = table();
+        let mut out = vec![];
+        table.write_json(&mut out).unwrap();
+
+        assert_eq!(
+            "[{\"owner\":0,\"car\":2},{\"owner\":1,\"car\":null}]\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[cfg(feature = "polars")]
+    #[test]
+    fn test_to_data_frame() {
+        use polars::prelude::DataType;
+
+        let (_, table) = table();
+        let graph = GraphBuilder::new()
+            .add_node(0, "person")
+            .add_node(1, "person")
+            .add_node(2, "car")
+            .set_property(0, "age", 42)
+            .set_property(1, "age", 4.5)
+            .set_property(2, "brand", "vw")
+            .build();
+        let frame = table.to_data_frame().unwrap();
+        assert_eq!((2, 2), frame.shape());
+        let car = frame.column("car").unwrap();
+        assert_eq!(&DataType::UInt64, car.dtype());
+        assert_eq!(1, car.null_count());
+
+        let frame = table
+            .to_data_frame_with_properties(&graph, &[("owner", "age"), ("car", "brand")])
+            .unwrap();
+        assert_eq!(
+            vec!["owner", "car", "owner.age", "car.brand"],
+            frame
+                .get_column_names()
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+        );
+        let ages = frame.column("owner.age").unwrap();
+        assert_eq!(&DataType::Float64, ages.dtype());
+        let ages = ages.f64().unwrap();
+        assert_eq!((Some(42.0), Some(4.5)), (ages.get(0), ages.get(1)));
+        let brands = frame.column("car.brand").unwrap();
+        let brands = brands.str().unwrap();
+        assert_eq!((Some("vw"), None), (brands.get(0), brands.get(1)));
+    }
+}
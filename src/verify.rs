@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::Graph;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The embedding does not assign one graph node to each pattern node.
+    NodeCount { expected: usize, actual: usize },
+    /// The pattern node is assigned a graph node that does not exist.
+    MissingNode { node_id: usize, graph_node: usize },
+    /// The pattern node is assigned a graph node with another label.
+    Label { node_id: usize },
+    /// Both pattern nodes are assigned the same graph node.
+    SharedNode { node_id: usize, other_node: usize },
+    /// The graph has fewer relationships between the assigned graph nodes than the pattern, in total or of a type.
+    Relationship { start_node: usize, end_node: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::NodeCount { expected, actual } => write!(
+                f,
+                "Embedding assigns {} nodes, but the pattern has {}.",
+                actual, expected
+            ),
+            Violation::MissingNode {
+                node_id,
+                graph_node,
+            } => write!(
+                f,
+                "Node {} is assigned graph node {}, which does not exist.",
+                node_id, graph_node
+            ),
+            Violation::Label { node_id } => {
+                write!(
+                    f,
+                    "Node {} is assigned a graph node with another label.",
+                    node_id
+                )
+            }
+            Violation::SharedNode {
+                node_id,
+                other_node,
+            } => write!(
+                f,
+                "Nodes {} and {} are assigned the same graph node.",
+                other_node, node_id
+            ),
+            Violation::Relationship {
+                start_node,
+                end_node,
+            } => write!(
+                f,
+                "Relationship ({}, {}) is missing between the assigned graph nodes.",
+                start_node, end_node
+            ),
+        }
+    }
+}
+
+impl Error for Violation {}
+
+// Checks that the embedding, which assigns `embedding[u_P]` to each pattern
+// node u_P, is a match as returned by `dual_iso`, e.g. to validate matches
+// computed elsewhere. Reports the first violation in the order of the variants
+// of `Violation` and then of the pattern nodes.
+pub fn verify_embedding<T: Eq + Hash>(
+    graph: &Graph<T>,
+    pattern: &Graph<T>,
+    embedding: &[usize],
+) -> Result<(), Violation> {
+    if embedding.len() != pattern.node_count() {
+        return Err(Violation::NodeCount {
+            expected: pattern.node_count(),
+            actual: embedding.len(),
+        });
+    }
+    if let Some((node_id, graph_node)) = embedding
+        .iter()
+        .enumerate()
+        .find(|(_, v_g)| **v_g >= graph.node_count())
+    {
+        return Err(Violation::MissingNode {
+            node_id,
+            graph_node: *graph_node,
+        });
+    }
+    if let Some(node_id) = (0..pattern.node_count())
+        .find(|u_p| graph.node_label(embedding[*u_p]) != pattern.node_label(*u_p))
+    {
+        return Err(Violation::Label { node_id });
+    }
+    let mut assigned = HashMap::with_capacity(embedding.len());
+    for (node_id, v_g) in embedding.iter().enumerate() {
+        if let Some(other_node) = assigned.insert(*v_g, node_id) {
+            return Err(Violation::SharedNode {
+                node_id,
+                other_node,
+            });
+        }
+    }
+    for start_node in 0..pattern.node_count() {
+        for (end_node, multiplicity) in pattern.distinct_neighbors(start_node) {
+            let (u_g, v_g) = (embedding[start_node], embedding[end_node]);
+            let types = pattern.shared_relationship_types(start_node, end_node);
+            if graph.multiplicity(u_g, v_g) < multiplicity
+                || types
+                    .chunk_by(|a, b| a == b)
+                    .any(|run| graph.typed_multiplicity(u_g, v_g, &run[0]) < run.len())
+            {
+                return Err(Violation::Relationship {
+                    start_node,
+                    end_node,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dual_iso, GraphBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_verify_embedding() {
+        let graph = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_node(3, "a")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .add_relationship(3, 3)
+            .build();
+        let pattern = GraphBuilder::new()
+            .add_node(0, "a")
+            .add_node(1, "b")
+            .add_node(2, "b")
+            .add_relationship(0, 1)
+            .add_relationship(0, 2)
+            .build();
+
+        for embedding in dual_iso(&graph, &pattern) {
+            assert_eq!(Ok(()), verify_embedding(&graph, &pattern, &embedding));
+        }
+        assert_eq!(
+            Err(Violation::NodeCount {
+                expected: 3,
+                actual: 2
+            }),
+            verify_embedding(&graph, &pattern, &[0, 1])
+        );
+        assert_eq!(
+            Err(Violation::MissingNode {
+                node_id: 2,
+                graph_node: 4
+            }),
+            verify_embedding(&graph, &pattern, &[0, 1, 4])
+        );
+        assert_eq!(
+            Err(Violation::Label { node_id: 1 }),
+            verify_embedding(&graph, &pattern, &[0, 3, 2])
+        );
+        assert_eq!(
+            Err(Violation::SharedNode {
+                node_id: 2,
+                other_node: 1
+            }),
+            verify_embedding(&graph, &pattern, &[0, 1, 1])
+        );
+        assert_eq!(
+            Err(Violation::Relationship {
+                start_node: 0,
+                end_node: 1
+            }),
+            verify_embedding(&graph, &pattern, &[3, 1, 2])
+        );
+    }
+
+    #[test]
+    fn test_violation_display() {
+        assert_eq!(
+            "Relationship (0, 1) is missing between the assigned graph nodes.",
+            Violation::Relationship {
+                start_node: 0,
+                end_node: 1
+            }
+            .to_string()
+        );
+    }
+}